@@ -1,13 +1,25 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{DepsMut, Env, MessageInfo, Response};
+use cosmwasm_std::{
+    to_json_binary, Binary, Deps, DepsMut, Env, IbcMsg, MessageInfo, Order, Response, StdResult,
+};
+use cw_storage_plus::Bound;
 
 use crate::{
-    msg::{ExecuteMsg, InitMsg},
-    state::CONFIG,
+    msg::{
+        ExecuteMsg, InitMsg, LatencyHistogramResponse, ListStreamsResponse, QueryMsg, StreamPacket,
+        UCS00PingPong,
+    },
+    state::{Config, Stream, CONFIG, LATENCY_BUCKETS, LATENCY_HISTOGRAM, NEXT_STREAM_ID, STREAMS},
     ContractError,
 };
 
+pub const MAX_STREAMS: u32 = 100;
+pub const MAX_PAYLOAD_SIZE: u32 = 64 * 1024;
+
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -32,5 +44,164 @@ pub fn execute(
             let ibc_packet = packet.reverse(&config, env.block.height, channel_id);
             Ok(Response::default().add_message(ibc_packet))
         }
+        ExecuteMsg::StartStreams {
+            channel_id,
+            counterparty_timeout_revision_number,
+            counterparty_timeout_revision_height,
+            streams,
+            rounds,
+            payload_size,
+        } => {
+            if streams == 0 || streams > MAX_STREAMS {
+                return Err(ContractError::InvalidStreamCount {
+                    streams,
+                    max: MAX_STREAMS,
+                });
+            }
+            if rounds == 0 {
+                return Err(ContractError::InvalidRoundCount);
+            }
+            if payload_size > MAX_PAYLOAD_SIZE {
+                return Err(ContractError::PayloadTooLarge {
+                    payload_size,
+                    max: MAX_PAYLOAD_SIZE,
+                });
+            }
+            let config = CONFIG.load(deps.storage)?;
+            let first_stream_id = NEXT_STREAM_ID.may_load(deps.storage)?.unwrap_or_default();
+            let stream_ids = first_stream_id..first_stream_id + u64::from(streams);
+            NEXT_STREAM_ID.save(deps.storage, &stream_ids.end)?;
+            let messages = stream_ids
+                .clone()
+                .map(|stream_id| {
+                    STREAMS.save(
+                        deps.storage,
+                        stream_id,
+                        &Stream {
+                            channel_id: channel_id.clone(),
+                            rounds,
+                            payload_size,
+                            started_height: env.block.height,
+                            started_time: env.block.time.nanos(),
+                            completed_rounds: 0,
+                            timed_out: false,
+                            pong_timeout_height: pong_timeout_height(&config, &env),
+                            total_blocks: 0,
+                            total_time: 0,
+                            min_time: None,
+                            max_time: None,
+                        },
+                    )?;
+                    // The packet is reversed, hence the initial pong.
+                    let packet = UCS00PingPong {
+                        ping: false,
+                        counterparty_timeout_revision_number,
+                        counterparty_timeout_revision_height,
+                        stream: None,
+                    };
+                    Ok(stream_ping(
+                        &packet,
+                        &config,
+                        &env,
+                        channel_id.clone(),
+                        stream_id,
+                        0,
+                        payload_size,
+                    ))
+                })
+                .collect::<StdResult<Vec<_>>>()?;
+            Ok(Response::default()
+                .add_messages(messages)
+                .add_attribute("action", "start_streams")
+                .add_attribute("first_stream_id", stream_ids.start.to_string())
+                .add_attribute("streams", streams.to_string()))
+        }
+        ExecuteMsg::RetryStream {
+            stream_id,
+            counterparty_timeout_revision_number,
+            counterparty_timeout_revision_height,
+        } => {
+            let mut stream = STREAMS.load(deps.storage, stream_id)?;
+            if !stream.pong_timed_out(env.block.height) {
+                return Err(ContractError::StreamNotRetriable { stream_id });
+            }
+            let config = CONFIG.load(deps.storage)?;
+            stream.pong_timeout_height = pong_timeout_height(&config, &env);
+            STREAMS.save(deps.storage, stream_id, &stream)?;
+            // The packet is reversed, hence the pong.
+            let packet = UCS00PingPong {
+                ping: false,
+                counterparty_timeout_revision_number,
+                counterparty_timeout_revision_height,
+                stream: None,
+            };
+            Ok(Response::default()
+                .add_message(stream_ping(
+                    &packet,
+                    &config,
+                    &env,
+                    stream.channel_id.clone(),
+                    stream_id,
+                    stream.completed_rounds,
+                    stream.payload_size,
+                ))
+                .add_attribute("action", "retry_stream")
+                .add_attribute("stream_id", stream_id.to_string())
+                .add_attribute("round", stream.completed_rounds.to_string()))
+        }
+    }
+}
+
+/// Height from which the pong of a ping sent now can no longer be received, see [`UCS00PingPong::reverse`].
+pub(crate) fn pong_timeout_height(config: &Config, env: &Env) -> u64 {
+    env.block.height + config.number_of_block_before_pong_timeout
+}
+
+/// Reverse `packet` into the ping of the given stream round, stamped with the current block.
+pub(crate) fn stream_ping(
+    packet: &UCS00PingPong,
+    config: &Config,
+    env: &Env,
+    channel_id: String,
+    stream_id: u64,
+    round: u64,
+    payload_size: u32,
+) -> IbcMsg {
+    UCS00PingPong {
+        stream: Some(StreamPacket {
+            stream_id,
+            round,
+            sent_height: env.block.height,
+            sent_time: env.block.time.nanos(),
+            payload: vec![0; payload_size as usize].into(),
+        }),
+        ..packet.clone()
+    }
+    .reverse(config, env.block.height, channel_id)
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Stream { stream_id } => to_json_binary(&STREAMS.load(deps.storage, stream_id)?),
+        QueryMsg::ListStreams { start_after, limit } => {
+            let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+            let streams = STREAMS
+                .range(
+                    deps.storage,
+                    start_after.map(Bound::exclusive),
+                    None,
+                    Order::Ascending,
+                )
+                .take(limit)
+                .collect::<StdResult<_>>()?;
+            to_json_binary(&ListStreamsResponse { streams })
+        }
+        QueryMsg::LatencyHistogram {} => to_json_binary(&LatencyHistogramResponse {
+            buckets: LATENCY_BUCKETS.to_vec(),
+            histogram: LATENCY_HISTOGRAM
+                .may_load(deps.storage)?
+                .unwrap_or_default(),
+        }),
     }
 }
@@ -4,7 +4,12 @@ use cosmwasm_std::{
     IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcReceiveResponse, Reply, Response, StdError,
 };
 
-use crate::{msg::UCS00PingPong, state::CONFIG, ContractError};
+use crate::{
+    contract::{pong_timeout_height, stream_ping},
+    msg::UCS00PingPong,
+    state::{CONFIG, LATENCY_HISTOGRAM, STREAMS},
+    ContractError,
+};
 
 pub const PROTOCOL_VERSION: &str = "ucs00-pingpong-1";
 pub const PROTOCOL_ORDERING: IbcOrder = IbcOrder::Unordered;
@@ -98,13 +103,55 @@ fn do_ibc_packet_receive(
     packet: UCS00PingPong,
 ) -> Result<IbcReceiveResponse, ContractError> {
     let config = CONFIG.load(deps.storage)?;
-    let ibc_packet = packet.reverse(&config, env.block.height, dest_channel_id);
     let res = IbcReceiveResponse::new()
         .set_ack(ack_success())
-        .add_message(ibc_packet)
         .add_attribute("action", if packet.ping { "ping" } else { "pong" })
         .add_attribute("success", "true");
-    Ok(res)
+    match &packet.stream {
+        // The pong of a stream we started, record the round trip and keep going.
+        Some(stream_packet) if !packet.ping => {
+            let Some(mut stream) = STREAMS.may_load(deps.storage, stream_packet.stream_id)? else {
+                return Ok(res);
+            };
+            // Nothing is recorded once the stream is over, nor for a round that isn't in flight.
+            if stream.is_finished() || stream_packet.round != stream.completed_rounds {
+                return Ok(res.add_attribute("ignored", "true"));
+            }
+            let blocks = env.block.height.saturating_sub(stream_packet.sent_height);
+            let time = env
+                .block
+                .time
+                .nanos()
+                .saturating_sub(stream_packet.sent_time);
+            stream.record(blocks, time);
+            stream.pong_timeout_height = pong_timeout_height(&config, &env);
+            STREAMS.save(deps.storage, stream_packet.stream_id, &stream)?;
+            let mut histogram = LATENCY_HISTOGRAM
+                .may_load(deps.storage)?
+                .unwrap_or_default();
+            histogram.record(blocks, time);
+            LATENCY_HISTOGRAM.save(deps.storage, &histogram)?;
+            let res = res
+                .add_attribute("stream_id", stream_packet.stream_id.to_string())
+                .add_attribute("round", stream_packet.round.to_string())
+                .add_attribute("latency_blocks", blocks.to_string())
+                .add_attribute("latency_nanos", time.to_string());
+            if stream.is_finished() {
+                Ok(res)
+            } else {
+                Ok(res.add_message(stream_ping(
+                    &packet,
+                    &config,
+                    &env,
+                    dest_channel_id,
+                    stream_packet.stream_id,
+                    stream_packet.round + 1,
+                    stream.payload_size,
+                )))
+            }
+        }
+        _ => Ok(res.add_message(packet.reverse(&config, env.block.height, dest_channel_id))),
+    }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -127,13 +174,310 @@ pub fn ibc_packet_timeout(
 }
 
 fn on_packet_failure(
-    _deps: DepsMut,
-    _packet: IbcPacket,
+    deps: DepsMut,
+    packet: IbcPacket,
     err: String,
 ) -> Result<IbcBasicResponse, ContractError> {
+    // A stream can't resume once a ping is lost as the pong carries the counterparty timeout.
+    // A lost pong is only reported here, on the counterparty, and is retried by the originator
+    // with `ExecuteMsg::RetryStream`.
+    if let Ok(UCS00PingPong {
+        ping: true,
+        stream: Some(stream_packet),
+        ..
+    }) = UCS00PingPong::decode(&packet.data)
+    {
+        if let Some(mut stream) = STREAMS.may_load(deps.storage, stream_packet.stream_id)? {
+            stream.timed_out = true;
+            STREAMS.save(deps.storage, stream_packet.stream_id, &stream)?;
+        }
+    }
     let res = IbcBasicResponse::new()
         .add_attribute("action", "acknowledge")
         .add_attribute("success", "false")
         .add_attribute("error", err);
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{
+        testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage},
+        Addr, CosmosMsg, Env, IbcEndpoint, IbcMsg, IbcPacket, IbcPacketReceiveMsg,
+        IbcPacketTimeoutMsg, OwnedDeps, Timestamp,
+    };
+
+    use super::{ibc_packet_receive, ibc_packet_timeout};
+    use crate::{
+        contract::{execute, instantiate},
+        msg::{ExecuteMsg, InitMsg, UCS00PingPong},
+        state::{Config, LATENCY_HISTOGRAM, STREAMS},
+        ContractError,
+    };
+
+    type Deps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+    fn setup() -> Deps {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("admin", &[]),
+            InitMsg {
+                config: Config {
+                    number_of_block_before_pong_timeout: 100,
+                    revision_number: 1,
+                },
+            },
+        )
+        .unwrap();
+        deps
+    }
+
+    fn env_at(height: u64, seconds: u64) -> Env {
+        let mut env = mock_env();
+        env.block.height = height;
+        env.block.time = Timestamp::from_seconds(seconds);
+        env
+    }
+
+    fn into_packet(msg: IbcMsg) -> IbcPacket {
+        let IbcMsg::SendPacket { data, timeout, .. } = msg else {
+            panic!("expected a packet, got {msg:?}");
+        };
+        IbcPacket::new(
+            data,
+            IbcEndpoint {
+                port_id: "wasm.pingpong".into(),
+                channel_id: "channel-0".into(),
+            },
+            IbcEndpoint {
+                port_id: "wasm.pingpong".into(),
+                channel_id: "channel-0".into(),
+            },
+            1,
+            timeout,
+        )
+    }
+
+    fn deliver(deps: &mut Deps, env: Env, packet: IbcPacket) -> Vec<IbcMsg> {
+        ibc_packet_receive(
+            deps.as_mut(),
+            env,
+            IbcPacketReceiveMsg::new(packet, Addr::unchecked("relayer")),
+        )
+        .unwrap()
+        .messages
+        .into_iter()
+        .map(|msg| match msg.msg {
+            CosmosMsg::Ibc(msg) => msg,
+            msg => panic!("unexpected message {msg:?}"),
+        })
+        .collect()
+    }
+
+    fn start_streams(deps: &mut Deps, streams: u32, rounds: u64) -> Vec<IbcMsg> {
+        execute(
+            deps.as_mut(),
+            env_at(10, 100),
+            mock_info("user", &[]),
+            ExecuteMsg::StartStreams {
+                channel_id: "channel-0".into(),
+                counterparty_timeout_revision_number: 1,
+                counterparty_timeout_revision_height: 1000,
+                streams,
+                rounds,
+                payload_size: 128,
+            },
+        )
+        .unwrap()
+        .messages
+        .into_iter()
+        .map(|msg| match msg.msg {
+            CosmosMsg::Ibc(msg) => msg,
+            msg => panic!("unexpected message {msg:?}"),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn streams_record_round_trips_until_completion() {
+        let mut originator = setup();
+        let mut counterparty = setup();
+
+        let pings = start_streams(&mut originator, 2, 2);
+        assert_eq!(pings.len(), 2);
+
+        for ping in pings {
+            let pongs = deliver(&mut counterparty, env_at(20, 110), into_packet(ping));
+            assert_eq!(pongs.len(), 1);
+            // first round trip, 5 blocks and 3 seconds
+            let pings = deliver(
+                &mut originator,
+                env_at(15, 103),
+                into_packet(pongs[0].clone()),
+            );
+            assert_eq!(pings.len(), 1);
+            let pongs = deliver(
+                &mut counterparty,
+                env_at(21, 111),
+                into_packet(pings[0].clone()),
+            );
+            // second round trip, 100 blocks and 600 seconds
+            let pings = deliver(
+                &mut originator,
+                env_at(115, 703),
+                into_packet(pongs[0].clone()),
+            );
+            // stream is done
+            assert_eq!(pings, vec![]);
+        }
+
+        for stream_id in 0..2 {
+            let stream = STREAMS.load(&originator.storage, stream_id).unwrap();
+            assert!(stream.is_finished());
+            assert_eq!(stream.completed_rounds, 2);
+            assert_eq!(stream.total_blocks, 105);
+            assert_eq!(stream.min_time, Some(3_000_000_000));
+            assert_eq!(stream.max_time, Some(600_000_000_000));
+        }
+        assert!(STREAMS.is_empty(&counterparty.storage));
+
+        let histogram = LATENCY_HISTOGRAM.load(&originator.storage).unwrap();
+        // 3s and 5 blocks
+        assert_eq!(histogram.time[2], 2);
+        assert_eq!(histogram.blocks[3], 2);
+        // 600s and 100 blocks
+        assert_eq!(histogram.time[10], 2);
+        assert_eq!(histogram.blocks[7], 2);
+        assert_eq!(histogram.time.iter().sum::<u64>(), 4);
+    }
+
+    #[test]
+    fn ping_timeout_stops_stream() {
+        let mut deps = setup();
+        let pings = start_streams(&mut deps, 1, 10);
+        ibc_packet_timeout(
+            deps.as_mut(),
+            mock_env(),
+            IbcPacketTimeoutMsg::new(into_packet(pings[0].clone()), Addr::unchecked("relayer")),
+        )
+        .unwrap();
+        let stream = STREAMS.load(&deps.storage, 0).unwrap();
+        assert!(stream.timed_out);
+        assert!(stream.is_finished());
+    }
+
+    fn retry_stream(deps: &mut Deps, height: u64) -> Result<Vec<IbcMsg>, ContractError> {
+        Ok(execute(
+            deps.as_mut(),
+            env_at(height, 1000),
+            mock_info("anyone", &[]),
+            ExecuteMsg::RetryStream {
+                stream_id: 0,
+                counterparty_timeout_revision_number: 1,
+                counterparty_timeout_revision_height: 2000,
+            },
+        )?
+        .messages
+        .into_iter()
+        .map(|msg| match msg.msg {
+            CosmosMsg::Ibc(msg) => msg,
+            msg => panic!("unexpected message {msg:?}"),
+        })
+        .collect())
+    }
+
+    #[test]
+    fn timed_out_pong_is_retried() {
+        let mut originator = setup();
+        let mut counterparty = setup();
+        start_streams(&mut originator, 1, 2);
+
+        // the pong of the first round can be received until height 110
+        assert_eq!(
+            retry_stream(&mut originator, 109),
+            Err(ContractError::StreamNotRetriable { stream_id: 0 })
+        );
+        let pings = retry_stream(&mut originator, 110).unwrap();
+        assert_eq!(pings.len(), 1);
+        let ping = into_packet(pings[0].clone());
+        let stream_packet = UCS00PingPong::decode(&ping.data).unwrap().stream.unwrap();
+        assert_eq!(stream_packet.round, 0);
+        assert_eq!(stream_packet.sent_height, 110);
+
+        let pongs = deliver(&mut counterparty, env_at(20, 1010), ping);
+        let pings = deliver(
+            &mut originator,
+            env_at(115, 1003),
+            into_packet(pongs[0].clone()),
+        );
+        assert_eq!(pings.len(), 1);
+        let stream = STREAMS.load(&originator.storage, 0).unwrap();
+        assert_eq!(stream.completed_rounds, 1);
+        assert_eq!(stream.pong_timeout_height, 215);
+    }
+
+    #[test]
+    fn finished_stream_ignores_pongs() {
+        let mut originator = setup();
+        let mut counterparty = setup();
+        let pings = start_streams(&mut originator, 1, 1);
+        let pongs = deliver(
+            &mut counterparty,
+            env_at(20, 110),
+            into_packet(pings[0].clone()),
+        );
+        let pong = into_packet(pongs[0].clone());
+        assert_eq!(
+            deliver(&mut originator, env_at(15, 103), pong.clone()),
+            vec![]
+        );
+        // a duplicate pong is neither recorded nor answered
+        assert_eq!(deliver(&mut originator, env_at(16, 104), pong), vec![]);
+        let stream = STREAMS.load(&originator.storage, 0).unwrap();
+        assert_eq!(stream.completed_rounds, 1);
+        assert_eq!(
+            LATENCY_HISTOGRAM
+                .load(&originator.storage)
+                .unwrap()
+                .time
+                .iter()
+                .sum::<u64>(),
+            1
+        );
+        assert_eq!(
+            retry_stream(&mut originator, 1000),
+            Err(ContractError::StreamNotRetriable { stream_id: 0 })
+        );
+    }
+
+    #[test]
+    fn legacy_ping_pong_is_infinite() {
+        let mut deps = setup();
+        let msgs = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("user", &[]),
+            ExecuteMsg::Initiate {
+                channel_id: "channel-0".into(),
+                packet: UCS00PingPong {
+                    ping: false,
+                    counterparty_timeout_revision_number: 1,
+                    counterparty_timeout_revision_height: 1000,
+                    stream: None,
+                },
+            },
+        )
+        .unwrap()
+        .messages;
+        let CosmosMsg::Ibc(ping) = msgs[0].msg.clone() else {
+            panic!("expected a packet");
+        };
+        let pongs = deliver(&mut deps, mock_env(), into_packet(ping));
+        assert_eq!(pongs.len(), 1);
+        let pong = UCS00PingPong::decode(into_packet(pongs[0].clone()).data).unwrap();
+        assert!(!pong.ping);
+        assert_eq!(pong.stream, None);
+    }
+}
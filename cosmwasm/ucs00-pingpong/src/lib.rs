@@ -18,4 +18,14 @@ pub enum ContractError {
     OnlyOrderedChannel {},
     #[error("The packet has not been serialized using ETH ABI")]
     EthAbiDecoding,
+    #[error("Invalid number of streams {streams}, must be between 1 and {max}")]
+    InvalidStreamCount { streams: u32, max: u32 },
+    #[error("Invalid number of rounds, must be at least 1")]
+    InvalidRoundCount,
+    #[error("Payload size {payload_size} exceeds the maximum of {max} bytes")]
+    PayloadTooLarge { payload_size: u32, max: u32 },
+    #[error(
+        "Stream {stream_id} can't be retried, it is finished or its pong may still be received"
+    )]
+    StreamNotRetriable { stream_id: u64 },
 }
@@ -1,21 +1,51 @@
-use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{IbcMsg, IbcTimeout, IbcTimeoutBlock};
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Binary, IbcMsg, IbcTimeout, IbcTimeoutBlock};
 use ethabi::{ParamType, Token};
 
-use crate::{state::Config, ContractError};
+use crate::{
+    state::{Config, LatencyHistogram, Stream},
+    ContractError,
+};
+
+/// Size of the ETH ABI encoding of a packet not belonging to a stream.
+const UCS00_PACKET_SIZE: usize = 3 * 32;
 
 #[cw_serde]
 pub struct UCS00PingPong {
     pub ping: bool,
     pub counterparty_timeout_revision_number: u64,
     pub counterparty_timeout_revision_height: u64,
+    /// Set when the packet belongs to a load generating stream.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream: Option<StreamPacket>,
+}
+
+/// Stream metadata appended to the packet, echoed back as is by the counterparty.
+#[cw_serde]
+pub struct StreamPacket {
+    pub stream_id: u64,
+    pub round: u64,
+    /// Height at which the ping was sent by the stream originator.
+    pub sent_height: u64,
+    /// Block time at which the ping was sent by the stream originator, in nanoseconds.
+    pub sent_time: u64,
+    pub payload: Binary,
 }
 
 impl UCS00PingPong {
     pub fn decode(bz: impl AsRef<[u8]>) -> Result<Self, ContractError> {
+        let bz = bz.as_ref();
+        if bz.len() == UCS00_PACKET_SIZE {
+            Self::decode_ping_pong(bz)
+        } else {
+            Self::decode_stream(bz)
+        }
+    }
+
+    fn decode_ping_pong(bz: &[u8]) -> Result<Self, ContractError> {
         let values = ethabi::decode(
             &[ParamType::Bool, ParamType::Int(64), ParamType::Int(64)],
-            bz.as_ref(),
+            bz,
         )
         .map_err(|_| ContractError::EthAbiDecoding)?;
         match &values[..] {
@@ -24,6 +54,41 @@ impl UCS00PingPong {
                     ping,
                     counterparty_timeout_revision_number: timeout_revision_number.as_u64(),
                     counterparty_timeout_revision_height: timeout_revision_height.as_u64(),
+                    stream: None,
+                })
+            }
+            _ => Err(ContractError::EthAbiDecoding),
+        }
+    }
+
+    fn decode_stream(bz: &[u8]) -> Result<Self, ContractError> {
+        let values = ethabi::decode(
+            &[
+                ParamType::Bool,
+                ParamType::Int(64),
+                ParamType::Int(64),
+                ParamType::Uint(64),
+                ParamType::Uint(64),
+                ParamType::Uint(64),
+                ParamType::Uint(64),
+                ParamType::Bytes,
+            ],
+            bz,
+        )
+        .map_err(|_| ContractError::EthAbiDecoding)?;
+        match &values[..] {
+            [Token::Bool(ping), Token::Int(timeout_revision_number), Token::Int(timeout_revision_height), Token::Uint(stream_id), Token::Uint(round), Token::Uint(sent_height), Token::Uint(sent_time), Token::Bytes(payload)] => {
+                Ok(UCS00PingPong {
+                    ping: *ping,
+                    counterparty_timeout_revision_number: timeout_revision_number.as_u64(),
+                    counterparty_timeout_revision_height: timeout_revision_height.as_u64(),
+                    stream: Some(StreamPacket {
+                        stream_id: stream_id.as_u64(),
+                        round: round.as_u64(),
+                        sent_height: sent_height.as_u64(),
+                        sent_time: sent_time.as_u64(),
+                        payload: payload.clone().into(),
+                    }),
                 })
             }
             _ => Err(ContractError::EthAbiDecoding),
@@ -31,11 +96,21 @@ impl UCS00PingPong {
     }
 
     pub fn encode(&self) -> Vec<u8> {
-        ethabi::encode(&[
+        let mut tokens = vec![
             Token::Bool(self.ping),
             Token::Int(self.counterparty_timeout_revision_number.into()),
             Token::Int(self.counterparty_timeout_revision_height.into()),
-        ])
+        ];
+        if let Some(stream) = &self.stream {
+            tokens.extend([
+                Token::Uint(stream.stream_id.into()),
+                Token::Uint(stream.round.into()),
+                Token::Uint(stream.sent_height.into()),
+                Token::Uint(stream.sent_time.into()),
+                Token::Bytes(stream.payload.to_vec()),
+            ]);
+        }
+        ethabi::encode(&tokens)
     }
 }
 
@@ -46,6 +121,7 @@ impl UCS00PingPong {
             counterparty_timeout_revision_number: config.revision_number,
             counterparty_timeout_revision_height: config.number_of_block_before_pong_timeout
                 + current_block,
+            stream: self.stream.clone(),
         };
         IbcMsg::SendPacket {
             channel_id,
@@ -69,4 +145,83 @@ pub enum ExecuteMsg {
         channel_id: String,
         packet: UCS00PingPong,
     },
+    /// Start `streams` concurrent ping streams, each executing `rounds` round trips carrying
+    /// `payload_size` bytes. The counterparty must be running this contract as it has to echo
+    /// the stream metadata back.
+    StartStreams {
+        channel_id: String,
+        counterparty_timeout_revision_number: u64,
+        counterparty_timeout_revision_height: u64,
+        streams: u32,
+        rounds: u64,
+        payload_size: u32,
+    },
+    /// Send the ping of the round in flight again, once its pong timed out. The pong timeout is
+    /// reported to the counterparty only, which is why anyone can retry an expired round.
+    RetryStream {
+        stream_id: u64,
+        counterparty_timeout_revision_number: u64,
+        counterparty_timeout_revision_height: u64,
+    },
+}
+
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum QueryMsg {
+    #[returns(Stream)]
+    Stream { stream_id: u64 },
+    #[returns(ListStreamsResponse)]
+    ListStreams {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    #[returns(LatencyHistogramResponse)]
+    LatencyHistogram {},
+}
+
+#[cw_serde]
+pub struct ListStreamsResponse {
+    pub streams: Vec<(u64, Stream)>,
+}
+
+#[cw_serde]
+pub struct LatencyHistogramResponse {
+    /// Inclusive upper bounds of the buckets, the last bucket being unbounded.
+    pub buckets: Vec<u64>,
+    pub histogram: LatencyHistogram,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StreamPacket, UCS00PingPong};
+
+    #[test]
+    fn ping_pong_iso() {
+        let packet = UCS00PingPong {
+            ping: true,
+            counterparty_timeout_revision_number: 1,
+            counterparty_timeout_revision_height: 1000,
+            stream: None,
+        };
+        let encoded = packet.encode();
+        assert_eq!(encoded.len(), 96);
+        assert_eq!(UCS00PingPong::decode(encoded).unwrap(), packet);
+    }
+
+    #[test]
+    fn stream_iso() {
+        let packet = UCS00PingPong {
+            ping: false,
+            counterparty_timeout_revision_number: 1,
+            counterparty_timeout_revision_height: 1000,
+            stream: Some(StreamPacket {
+                stream_id: 3,
+                round: 7,
+                sent_height: 42,
+                sent_time: 1_700_000_000_000_000_000,
+                payload: vec![0; 96].into(),
+            }),
+        };
+        assert_eq!(UCS00PingPong::decode(packet.encode()).unwrap(), packet);
+    }
 }
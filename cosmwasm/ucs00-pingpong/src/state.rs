@@ -1,5 +1,5 @@
 use cosmwasm_schema::cw_serde;
-use cw_storage_plus::Item;
+use cw_storage_plus::{Item, Map};
 
 #[cw_serde]
 pub struct Config {
@@ -8,3 +8,82 @@ pub struct Config {
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
+
+/// Id of the next stream started by this contract.
+pub const NEXT_STREAM_ID: Item<u64> = Item::new("next_stream_id");
+
+/// Streams started by this contract, indexed by stream id.
+pub const STREAMS: Map<u64, Stream> = Map::new("streams");
+
+/// Round trip latencies of every stream started by this contract.
+pub const LATENCY_HISTOGRAM: Item<LatencyHistogram> = Item::new("latency_histogram");
+
+/// Upper bounds (inclusive) of the histogram buckets, the last bucket catches everything above.
+/// Time buckets are in seconds, block buckets in number of blocks.
+pub const LATENCY_BUCKETS: [u64; 10] = [1, 2, 4, 8, 16, 32, 64, 128, 256, 512];
+
+#[cw_serde]
+pub struct Stream {
+    pub channel_id: String,
+    /// Number of round trips to execute before the stream stops.
+    pub rounds: u64,
+    pub payload_size: u32,
+    pub started_height: u64,
+    /// Block time at which the stream started, in nanoseconds.
+    pub started_time: u64,
+    pub completed_rounds: u64,
+    /// Whether a ping timed out, which stops the stream.
+    pub timed_out: bool,
+    /// Height from which the pong of the round in flight can no longer be received, the round
+    /// must then be retried with [`crate::msg::ExecuteMsg::RetryStream`].
+    #[serde(default)]
+    pub pong_timeout_height: u64,
+    pub total_blocks: u64,
+    /// Sum of the round trip times, in nanoseconds.
+    pub total_time: u64,
+    pub min_time: Option<u64>,
+    pub max_time: Option<u64>,
+}
+
+impl Stream {
+    pub fn is_finished(&self) -> bool {
+        self.timed_out || self.completed_rounds >= self.rounds
+    }
+
+    /// Whether the pong of the round in flight timed out at `height`.
+    pub fn pong_timed_out(&self, height: u64) -> bool {
+        !self.is_finished() && height >= self.pong_timeout_height
+    }
+
+    /// Record a round trip of `blocks` blocks and `time` nanoseconds.
+    pub fn record(&mut self, blocks: u64, time: u64) {
+        self.completed_rounds += 1;
+        self.total_blocks += blocks;
+        self.total_time += time;
+        self.min_time = Some(self.min_time.map_or(time, |min| min.min(time)));
+        self.max_time = Some(self.max_time.map_or(time, |max| max.max(time)));
+    }
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct LatencyHistogram {
+    /// Counts of round trips per time bucket, see [`LATENCY_BUCKETS`].
+    pub time: [u64; LATENCY_BUCKETS.len() + 1],
+    /// Counts of round trips per block bucket, see [`LATENCY_BUCKETS`].
+    pub blocks: [u64; LATENCY_BUCKETS.len() + 1],
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, blocks: u64, time_nanos: u64) {
+        self.blocks[bucket(blocks)] += 1;
+        self.time[bucket(time_nanos / 1_000_000_000)] += 1;
+    }
+}
+
+fn bucket(value: u64) -> usize {
+    LATENCY_BUCKETS
+        .iter()
+        .position(|bound| value <= *bound)
+        .unwrap_or(LATENCY_BUCKETS.len())
+}
@@ -10,6 +10,8 @@ use crate::{protocol::ProtocolError, types::Fees};
 
 pub const DEFAULT_PFM_TIMEOUT: &str = "1m";
 pub const DEFAULT_PFM_RETRIES: u8 = 0;
+/// Upper bound on the number of hops a single forward route can describe, including the first one.
+pub const MAX_PFM_HOPS: usize = 8;
 
 pub const PFM_MODULE_NAME: &str = "packetforwardmiddleware";
 
@@ -39,6 +41,10 @@ pub enum PacketForwardError {
     InvalidEncoding,
     #[error("Unable to index for reply message in stack")]
     NoReplyMessageInStack,
+    #[error("Forward route has {hops} hops, the maximum is {max}")]
+    TooManyHops { hops: usize, max: usize },
+    #[error("Forward hop {hop} must not set `return_info`, it is reserved for the middleware")]
+    ReturnInfoNotAllowed { hop: usize },
 }
 
 pub fn default_pfm_timeout() -> String {
@@ -105,6 +111,11 @@ pub struct PacketForward {
     pub timeout: String,
     #[serde(default = "default_pfm_retries")]
     pub retries: u8,
+    /// The remaining route, executed by the chain receiving this hop.
+    ///
+    /// For compatibility with ibc-go's packet forward middleware, this can either be a json object or
+    /// a string containing the json encoded object.
+    #[serde(default, deserialize_with = "deserialize_next_hop")]
     pub next: Option<Box<PacketForward>>,
     pub return_info: Option<PacketId>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -112,7 +123,55 @@ pub struct PacketForward {
     pub fees: Option<Fees>,
 }
 
+/// Shape of `next` accepted on the wire. Always serialized as [`NextHop::Typed`].
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NextHop {
+    Typed(Box<PacketForward>),
+    Encoded(String),
+}
+
+fn deserialize_next_hop<'de, D>(deserializer: D) -> Result<Option<Box<PacketForward>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Option::<NextHop>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(NextHop::Typed(next)) => Ok(Some(next)),
+        // An empty string is what ibc-go produces for the last hop.
+        Some(NextHop::Encoded(next)) if next.is_empty() => Ok(None),
+        Some(NextHop::Encoded(next)) => serde_json_wasm::from_str::<Memo>(&next)
+            .map_err(serde::de::Error::custom)
+            .and_then(|memo| match memo {
+                Memo::Forward { forward } => Ok(Some(Box::new(forward))),
+                Memo::None {} => Err(serde::de::Error::custom("next hop must be a forward memo")),
+            }),
+    }
+}
+
 impl PacketForward {
+    /// Iterate over every hop of the route, starting with this one.
+    pub fn hops(&self) -> impl Iterator<Item = &PacketForward> {
+        std::iter::successors(Some(self), |hop| hop.next.as_deref())
+    }
+
+    /// Validate the full route before any funds are moved.
+    ///
+    /// The receiver of every hop is already guaranteed to be non empty by [`PfmReceiver`].
+    pub fn validate_route(&self) -> Result<(), PacketForwardError> {
+        let hops = self.hops().count();
+        if hops > MAX_PFM_HOPS {
+            return Err(PacketForwardError::TooManyHops {
+                hops,
+                max: MAX_PFM_HOPS,
+            });
+        }
+        match self.hops().position(|hop| hop.return_info.is_some()) {
+            Some(hop) => Err(PacketForwardError::ReturnInfoNotAllowed { hop }),
+            None => Ok(()),
+        }
+    }
+
     /// Effective timeout is equivalent to `timeout * retries`.
     ///
     /// If the `timeout` is invalid or cannot be parsed, the default timeout is used.
@@ -164,7 +223,92 @@ impl<T: Into<String> + From<String>> Validate<T> for NotEmptyString {
 
 #[cfg(test)]
 mod tests {
-    use super::Memo;
+    use super::{Memo, PacketForward, PacketForwardError, MAX_PFM_HOPS};
+
+    fn hop(channel: &str, next: Option<String>) -> String {
+        format!(
+            "{{\"receiver\":\"receiver-{channel}\",\"port\":\"wasm.relay\",\"channel\":\"{channel}\",\"timeout\":\"10m\"{}}}",
+            next.map(|next| format!(",\"next\":{next}")).unwrap_or_default()
+        )
+    }
+
+    fn parse_forward(memo: &str) -> PacketForward {
+        match serde_json_wasm::from_str::<Memo>(memo).unwrap() {
+            Memo::Forward { forward } => forward,
+            Memo::None {} => panic!("expected a forward memo"),
+        }
+    }
+
+    #[test]
+    fn parses_multi_hop_route() {
+        let route = hop(
+            "channel-1",
+            Some(hop("channel-2", Some(hop("channel-3", None)))),
+        );
+        let forward = parse_forward(&format!("{{\"forward\":{route}}}"));
+
+        assert_eq!(
+            forward
+                .hops()
+                .map(|hop| hop.channel.clone().value())
+                .collect::<Vec<_>>(),
+            ["channel-1", "channel-2", "channel-3"]
+        );
+        assert_eq!(forward.validate_route(), Ok(()));
+        assert_eq!(forward.get_effective_timeout(), Ok(600));
+    }
+
+    #[test]
+    fn parses_string_encoded_next_hop() {
+        let next =
+            serde_json_wasm::to_string(&format!("{{\"forward\":{}}}", hop("channel-2", None)))
+                .unwrap();
+        let forward = parse_forward(&format!("{{\"forward\":{}}}", hop("channel-1", Some(next))));
+
+        let next = forward.next.as_deref().unwrap();
+        assert_eq!(next.channel.clone().value(), "channel-2");
+        assert_eq!(next.receiver.clone().value(), "receiver-channel-2");
+        assert!(next.next.is_none());
+
+        // ibc-go sets an empty `next` on the last hop
+        let forward = parse_forward(&format!(
+            "{{\"forward\":{}}}",
+            hop("channel-1", Some("\"\"".into()))
+        ));
+        assert!(forward.next.is_none());
+    }
+
+    #[test]
+    fn rejects_too_many_hops() {
+        let route =
+            (0..=MAX_PFM_HOPS).fold(None, |next, i| Some(hop(&format!("channel-{i}"), next)));
+        let forward = parse_forward(&format!("{{\"forward\":{}}}", route.unwrap()));
+
+        assert_eq!(
+            forward.validate_route(),
+            Err(PacketForwardError::TooManyHops {
+                hops: MAX_PFM_HOPS + 1,
+                max: MAX_PFM_HOPS
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_user_provided_return_info() {
+        let mut forward = parse_forward(&format!(
+            "{{\"forward\":{}}}",
+            hop("channel-1", Some(hop("channel-2", None)))
+        ));
+        forward.next.as_mut().unwrap().return_info = Some(super::PacketId {
+            height: 1,
+            index: 0,
+        });
+
+        assert_eq!(
+            forward.validate_route(),
+            Err(PacketForwardError::ReturnInfoNotAllowed { hop: 1 })
+        );
+    }
 
     #[test]
     fn serde_parses_memo() {
@@ -9,7 +9,7 @@ use thiserror::Error;
 use unionlabs::encoding::{self, Decode, DecodeErrorOf, Encode};
 
 use crate::{
-    middleware::{InFlightPfmPacket, Memo, PacketForward},
+    middleware::{InFlightPfmPacket, Memo, PacketForward, PacketForwardError},
     types::{
         EncodingError, GenericAck, NormalizedTransferToken, TransferPacket, TransferPacketCommon,
         TransferToken,
@@ -54,6 +54,8 @@ pub enum ProtocolError {
     Unauthorized,
     #[error("timeout must be greater than or equal to 1 second")]
     InvalidTimeout,
+    #[error("invalid forward route: {0}")]
+    InvalidForwardRoute(#[from] PacketForwardError),
 }
 
 pub type PacketExtensionOf<T> = <<T as TransferProtocol>::Packet as TransferPacket>::Extension;
@@ -375,7 +377,8 @@ pub trait TransferProtocol {
             if let Ok(memo) = serde_json_wasm::from_str::<Memo>(&memo) {
                 match memo {
                     Memo::Forward { forward } => {
-                        return self.packet_forward(packet, original_packet, forward)
+                        forward.validate_route().map_err(ProtocolError::from)?;
+                        return self.packet_forward(packet, original_packet, forward);
                     }
                     Memo::None { .. } => {}
                 };
@@ -35,12 +35,21 @@ pub enum EncodingError {
 /// A json encoding specific to [`serde_json_wasm`] as it does not use the same error types as `serde_json`.
 ///
 /// Note that we can't do a blanket impl here, as both [`Encode`]/[`Decode`] and [`serde::Serialize`]/[`serde::Deserialize`] are foreign traits.
+///
+/// ICS20 packets and acknowledgements are encoded byte for byte as ibc-go does, that is, sorted
+/// keys, no whitespace and strings escaped by Go's `encoding/json`, see [`GoJsonObject`].
 pub enum JsonWasm {}
 impl Encoding for JsonWasm {}
 
 impl Encode<JsonWasm> for Ics20Ack {
     fn encode(self) -> Vec<u8> {
-        serde_json_wasm::to_vec(&self).expect("json serialization should be infallible")
+        match self {
+            Ics20Ack::Result(result) => {
+                GoJsonObject::default().field("result", &result.to_base64())
+            }
+            Ics20Ack::Error(error) => GoJsonObject::default().field("error", &error),
+        }
+        .finish()
     }
 }
 
@@ -54,7 +63,19 @@ impl Decode<JsonWasm> for Ics20Ack {
 
 impl Encode<JsonWasm> for Ics20Packet {
     fn encode(self) -> Vec<u8> {
-        serde_json_wasm::to_vec(&self).expect("json serialization should be infallible")
+        let object = GoJsonObject::default()
+            .field("amount", &self.amount.to_string())
+            .field("denom", &self.denom);
+        // Go omits the memo if empty.
+        let object = if self.memo.is_empty() {
+            object
+        } else {
+            object.field("memo", &self.memo)
+        };
+        object
+            .field("receiver", &self.receiver)
+            .field("sender", &self.sender)
+            .finish()
     }
 }
 
@@ -66,6 +87,56 @@ impl Decode<JsonWasm> for Ics20Packet {
     }
 }
 
+/// A flat json object of string values, encoded as ibc-go encodes packets and acknowledgements:
+/// `sdk.MustSortJSON` re-marshals them with Go's `encoding/json`, which sorts the keys and escapes
+/// `<`, `>`, `&`, U+2028 and U+2029 on top of the usual escaping.
+///
+/// This matches ibc-go v8 as built for uniond, that is with Go 1.21 (see `uniond/uniond.nix`).
+/// Go 1.22 and later escape backspace and form feed as `\b` and `\f` instead of `\u0008` and
+/// `\u000c`, chains built with them won't produce the same bytes for such strings.
+///
+/// The fields must be added in lexicographic order.
+#[derive(Debug, Default)]
+struct GoJsonObject {
+    json: String,
+}
+
+impl GoJsonObject {
+    fn field(mut self, key: &str, value: &str) -> Self {
+        self.json.push(if self.json.is_empty() { '{' } else { ',' });
+        write_go_json_string(&mut self.json, key);
+        self.json.push(':');
+        write_go_json_string(&mut self.json, value);
+        self
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.json.is_empty() {
+            self.json.push('{');
+        }
+        self.json.push('}');
+        self.json.into_bytes()
+    }
+}
+
+fn write_go_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{0}'..='\u{1f}' | '<' | '>' | '&' | '\u{2028}' | '\u{2029}' => {
+                out.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct TransferPacketCommon<T> {
     pub sender: String,
@@ -243,12 +314,12 @@ impl Decode<encoding::EthAbi> for Ucs01TransferPacket {
 // https://github.com/cosmos/ibc-go/blob/d02ab9db8fc80eb5e55041d3d6416370c33441f7/proto/ibc/applications/transfer/v2/packet.proto
 #[cw_serde]
 pub struct Ics20Packet {
-    pub denom: String,
     pub amount: Uint128,
-    pub sender: String,
-    pub receiver: String,
+    pub denom: String,
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub memo: String,
+    pub receiver: String,
+    pub sender: String,
 }
 
 pub trait TransferPacket {
@@ -414,21 +485,22 @@ impl<'a> From<(&'a str, &IbcEndpoint)> for DenomOrigin<'a> {
     fn from((denom, remote_endpoint): (&'a str, &IbcEndpoint)) -> Self {
         // https://github.com/cosmos/ibc/blob/main/spec/app/ics-020-fungible-token-transfer/README.md#data-structures
         // SPEC: {ics20Port}/{ics20Channel}/{denom}
-        // The denom is local IFF we can strip all prefixes
+        // The denom is local IFF we can strip all prefixes and a base denom remains
         match denom
             .strip_prefix(&remote_endpoint.port_id)
             .and_then(|denom| denom.strip_prefix('/'))
             .and_then(|denom| denom.strip_prefix(&remote_endpoint.channel_id))
             .and_then(|denom| denom.strip_prefix('/'))
         {
-            Some(denom) => DenomOrigin::Local { denom },
-            None => DenomOrigin::Remote { denom },
+            Some(denom) if !denom.is_empty() => DenomOrigin::Local { denom },
+            _ => DenomOrigin::Remote { denom },
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use cosmwasm_schema::cw_serde;
     use cosmwasm_std::{IbcEndpoint, Uint128};
     use unionlabs::encoding::{Decode, DecodeAs, Encode, EncodeAs};
 
@@ -544,4 +616,148 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn denom_origin_parse_local_multi_slash() {
+        assert_eq!(
+            DenomOrigin::from((
+                "transfer/channel-1/transfer/channel-0/gamm/pool/1",
+                &IbcEndpoint {
+                    port_id: "transfer".into(),
+                    channel_id: "channel-1".into(),
+                }
+            )),
+            DenomOrigin::Local {
+                denom: "transfer/channel-0/gamm/pool/1"
+            }
+        );
+    }
+
+    #[test]
+    fn denom_origin_parse_remote_similar_channel() {
+        assert_eq!(
+            DenomOrigin::from((
+                "transfer/channel-10/muno",
+                &IbcEndpoint {
+                    port_id: "transfer".into(),
+                    channel_id: "channel-1".into(),
+                }
+            )),
+            DenomOrigin::Remote {
+                denom: "transfer/channel-10/muno"
+            }
+        );
+    }
+
+    #[test]
+    fn denom_origin_parse_remote_without_base_denom() {
+        assert_eq!(
+            DenomOrigin::from((
+                "transfer/channel-1/",
+                &IbcEndpoint {
+                    port_id: "transfer".into(),
+                    channel_id: "channel-1".into(),
+                }
+            )),
+            DenomOrigin::Remote {
+                denom: "transfer/channel-1/"
+            }
+        );
+    }
+
+    // Expected encodings are the ones produced by ibc-go's `FungibleTokenPacketData.GetBytes` and
+    // `Acknowledgement.Acknowledgement`, for ibc-go v8 built with Go 1.21 as uniond is.
+    const ICS20_CONFORMANCE: &str = include_str!("testdata/ics20-conformance.json");
+
+    #[cw_serde]
+    struct Ics20Conformance {
+        packets: Vec<PacketCase>,
+        decode_only_packets: Vec<PacketCase>,
+        invalid_packets: Vec<InvalidPacketCase>,
+        acks: Vec<AckCase>,
+    }
+
+    #[cw_serde]
+    struct PacketCase {
+        name: String,
+        packet: Ics20Packet,
+        encoded: String,
+    }
+
+    #[cw_serde]
+    struct InvalidPacketCase {
+        name: String,
+        encoded: String,
+    }
+
+    #[cw_serde]
+    struct AckCase {
+        name: String,
+        ack: Ics20Ack,
+        encoded: String,
+    }
+
+    fn ics20_conformance() -> Ics20Conformance {
+        serde_json_wasm::from_str(ICS20_CONFORMANCE).unwrap()
+    }
+
+    #[test]
+    fn ics20_packet_conformance() {
+        for PacketCase {
+            name,
+            packet,
+            encoded,
+        } in ics20_conformance().packets
+        {
+            assert_eq!(
+                String::from_utf8(packet.clone().encode_as::<JsonWasm>()).unwrap(),
+                encoded,
+                "{name}"
+            );
+            assert_eq!(
+                Ics20Packet::decode_as::<JsonWasm>(encoded.as_bytes()).unwrap(),
+                packet,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn ics20_packet_decode_conformance() {
+        let conformance = ics20_conformance();
+        for PacketCase {
+            name,
+            packet,
+            encoded,
+        } in conformance.decode_only_packets
+        {
+            assert_eq!(
+                Ics20Packet::decode_as::<JsonWasm>(encoded.as_bytes()).unwrap(),
+                packet,
+                "{name}"
+            );
+        }
+        for InvalidPacketCase { name, encoded } in conformance.invalid_packets {
+            assert!(
+                Ics20Packet::decode_as::<JsonWasm>(encoded.as_bytes()).is_err(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn ics20_ack_conformance() {
+        for AckCase { name, ack, encoded } in ics20_conformance().acks {
+            assert_eq!(
+                String::from_utf8(ack.clone().encode_as::<JsonWasm>()).unwrap(),
+                encoded,
+                "{name}"
+            );
+            assert_eq!(
+                Ics20Ack::decode_as::<JsonWasm>(encoded.as_bytes()).unwrap(),
+                ack,
+                "{name}"
+            );
+        }
+    }
 }
@@ -5,6 +5,7 @@ use cosmwasm_std::{
     PortIdResponse, Response, StdError, StdResult,
 };
 use cw2::set_contract_version;
+use cw_storage_plus::Bound;
 use token_factory_api::TokenFactoryMsg;
 use ucs01_relay_api::{
     protocol::{TransferInput, TransferProtocol},
@@ -13,18 +14,23 @@ use ucs01_relay_api::{
 
 use crate::{
     error::ContractError,
-    ibc::enforce_order_and_version,
+    fee,
+    ibc::{enforce_order_and_version, register_channel},
     msg::{
-        ChannelResponse, ConfigResponse, ExecuteMsg, InstantiateMsg, ListChannelsResponse,
-        MigrateMsg, PortResponse, QueryMsg, TransferMsg,
+        ChannelResponse, ConfigResponse, DenomMapping, ExecuteMsg, InstantiateMsg,
+        ListChannelsResponse, ListDenomsResponse, ListFeeEscrowsResponse, MigrateMsg, PortResponse,
+        QueryMsg, TransferMsg,
     },
-    protocol::{Ics20Protocol, ProtocolCommon, Ucs01Protocol},
+    protocol::{hash_denom_str, Ics20Protocol, ProtocolCommon, Ucs01Protocol},
     state::{
-        ChannelInfo, Config, ADMIN, CHANNEL_INFO, CHANNEL_STATE, CONFIG, FOREIGN_DENOM_TO_HASH,
-        HASH_TO_FOREIGN_DENOM,
+        Config, PacketFeeEscrow, ADMIN, CHANNEL_INFO, CHANNEL_STATE, CONFIG, COUNTERPARTY_PAYEES,
+        FEE_ENABLED_CHANNELS, FEE_ESCROWS, FOREIGN_DENOM_TO_HASH, HASH_TO_FOREIGN_DENOM,
     },
 };
 
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
 // REVIEW: This isn't on crates.io, what else should we use?
 const CONTRACT_NAME: &str = "crates.io:ucs01-relay";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -52,13 +58,7 @@ pub fn instantiate(
         // would depend on the contract's address before it's initialization.
         channel.endpoint.port_id = format!("wasm.{}", env.contract.address);
         enforce_order_and_version(&channel, None)?;
-        let info = ChannelInfo {
-            endpoint: channel.endpoint,
-            counterparty_endpoint: channel.counterparty_endpoint,
-            connection_id: channel.connection_id,
-            protocol_version: channel.version,
-        };
-        CHANNEL_INFO.save(deps.storage, &info.endpoint.channel_id, &info)?;
+        register_channel(deps.storage, channel)?;
     }
 
     Ok(Response::default())
@@ -99,6 +99,25 @@ pub fn execute(
                 Ok(Response::default())
             }
         }
+        ExecuteMsg::RegisterCounterpartyPayee {
+            channel,
+            counterparty_payee,
+        } => {
+            if !FEE_ENABLED_CHANNELS.has(deps.storage, &channel) {
+                return Err(ContractError::FeeNotEnabled {
+                    channel_id: channel,
+                });
+            }
+            if counterparty_payee.is_empty() {
+                return Err(ContractError::EmptyCounterpartyPayee);
+            }
+            COUNTERPARTY_PAYEES.save(
+                deps.storage,
+                (&channel, &info.sender),
+                &counterparty_payee,
+            )?;
+            Ok(Response::default())
+        }
         ExecuteMsg::BatchExecute { msgs } => {
             if info.sender != env.contract.address {
                 Err(ContractError::Unauthorized)
@@ -110,13 +129,15 @@ pub fn execute(
 }
 
 pub fn execute_transfer(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: TransferMsg,
 ) -> Result<Response<TokenFactoryMsg>, ContractError> {
     let fees = msg.fees.unwrap_or_default();
-    let tokens: Vec<TransferToken> = Coins::try_from(info.funds.clone())
+    let relayer_fee = msg.relayer_fee.unwrap_or_default();
+    let funds = fee::deduct_relayer_fee(info.funds.clone(), &relayer_fee)?;
+    let tokens: Vec<TransferToken> = Coins::try_from(funds)
         .map_err(|_| StdError::generic_err("Couldn't decode funds to Coins"))?
         .into_vec()
         .into_iter()
@@ -139,6 +160,11 @@ pub fn execute_transfer(
 
     let config = CONFIG.load(deps.storage)?;
 
+    let escrow = (!relayer_fee.is_empty()).then(|| PacketFeeEscrow {
+        refund_address: info.sender.clone(),
+        fee: relayer_fee,
+    });
+
     let input = TransferInput {
         current_time: env.block.time,
         timeout_delta: msg.timeout.unwrap_or(config.default_timeout),
@@ -147,10 +173,10 @@ pub fn execute_transfer(
         tokens,
     };
 
-    match channel_info.protocol_version.as_str() {
+    let response = match channel_info.protocol_version.as_str() {
         Ics20Protocol::VERSION => Ics20Protocol {
             common: ProtocolCommon {
-                deps,
+                deps: deps.branch(),
                 env,
                 info,
                 channel: channel_info,
@@ -159,7 +185,7 @@ pub fn execute_transfer(
         .send(input, msg.memo),
         Ucs01Protocol::VERSION => Ucs01Protocol {
             common: ProtocolCommon {
-                deps,
+                deps: deps.branch(),
                 env,
                 info,
                 channel: channel_info,
@@ -167,9 +193,14 @@ pub fn execute_transfer(
         }
         .send(input, msg.memo),
         v => Err(ContractError::UnknownProtocol {
-            channel_id: msg.channel,
+            channel_id: msg.channel.clone(),
             protocol_version: v.into(),
         }),
+    }?;
+
+    match escrow {
+        Some(escrow) => fee::attach_relayer_fee(deps.storage, response, &msg.channel, escrow),
+        None => Ok(response),
     }
 }
 
@@ -179,13 +210,30 @@ pub fn migrate(_: DepsMut, _: Env, _: MigrateMsg) -> Result<Response, ContractEr
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Port {} => to_json_binary(&query_port(deps)?),
         QueryMsg::ListChannels {} => to_json_binary(&query_list(deps)?),
         QueryMsg::Channel { id } => to_json_binary(&query_channel(deps, id)?),
         QueryMsg::Config {} => to_json_binary(&query_config(deps)?),
         QueryMsg::Admin {} => to_json_binary(&ADMIN.query_admin(deps)?),
+        QueryMsg::FeeEscrow { channel, sequence } => {
+            to_json_binary(&FEE_ESCROWS.may_load(deps.storage, (&channel, sequence))?)
+        }
+        QueryMsg::CounterpartyPayee { channel, relayer } => {
+            let relayer = deps.api.addr_validate(&relayer)?;
+            to_json_binary(&COUNTERPARTY_PAYEES.may_load(deps.storage, (&channel, &relayer))?)
+        }
+        QueryMsg::ListFeeEscrows {
+            channel,
+            start_after,
+            limit,
+        } => to_json_binary(&query_fee_escrows(deps, channel, start_after, limit)?),
+        QueryMsg::ListDenoms {
+            channel,
+            start_after,
+            limit,
+        } => to_json_binary(&query_denoms(deps, env, channel, start_after, limit)?),
     }
 }
 
@@ -214,6 +262,58 @@ pub fn query_channel(deps: Deps, id: String) -> StdResult<ChannelResponse> {
     Ok(ChannelResponse { info, balances })
 }
 
+fn query_fee_escrows(
+    deps: Deps,
+    channel: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ListFeeEscrowsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let escrows = FEE_ESCROWS
+        .prefix(&channel)
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+    Ok(ListFeeEscrowsResponse { escrows })
+}
+
+fn query_denoms(
+    deps: Deps,
+    env: Env,
+    channel: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ListDenomsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let endpoint = CHANNEL_INFO.load(deps.storage, &channel)?.endpoint;
+    let denoms = FOREIGN_DENOM_TO_HASH
+        .prefix(endpoint.into())
+        .keys(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|r| {
+            r.map(|foreign_denom| DenomMapping {
+                local_denom: format!(
+                    "factory/{}/{}",
+                    env.contract.address,
+                    hash_denom_str(&foreign_denom)
+                ),
+                foreign_denom,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ListDenomsResponse { denoms })
+}
+
 fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     let cfg = CONFIG.load(deps.storage)?;
     let admin = ADMIN.get(deps)?.unwrap_or_else(|| Addr::unchecked(""));
@@ -223,3 +323,188 @@ fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     };
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{coin, from_json, Addr};
+    use ucs01_relay_api::types::make_foreign_denom;
+
+    use super::query;
+    use crate::{
+        error::ContractError,
+        msg::{DenomMapping, ExecuteMsg, ListDenomsResponse, QueryMsg, TransferMsg},
+        protocol::hash_denom_str,
+        state::{RelayerFee, FEE_ESCROWS},
+        test_utils::{connect, connect_fee_enabled, Chain},
+    };
+
+    fn transfer_msg(receiver: &Addr, memo: &str) -> TransferMsg {
+        TransferMsg {
+            channel: "channel-0".into(),
+            receiver: receiver.to_string(),
+            timeout: None,
+            memo: memo.into(),
+            fees: None,
+            relayer_fee: Some(RelayerFee {
+                recv_fee: vec![coin(10, "muno")],
+                ack_fee: vec![coin(5, "muno")],
+                timeout_fee: vec![coin(3, "muno")],
+            }),
+        }
+    }
+
+    /// Chains `A <-> B` connected with the fee enabled `A/channel-0 <-> B/channel-1`, the relayer of
+    /// B registered a payee on A. The sender on A transfers 100 muno plus an 18 muno relayer fee.
+    fn transfer_with_relayer_fee(
+        memo: impl FnOnce(&Chain) -> String,
+    ) -> (Chain, Chain, Addr, Addr) {
+        let (mut a, mut b) = (Chain::new(), Chain::new());
+        connect_fee_enabled(&mut a, "channel-0", &mut b, "channel-1");
+        let payee = a.addr("payee");
+        let relayer = b.addr("relayer");
+        b.execute(
+            &relayer,
+            ExecuteMsg::RegisterCounterpartyPayee {
+                channel: "channel-1".into(),
+                counterparty_payee: payee.to_string(),
+            },
+            vec![],
+        )
+        .unwrap();
+
+        let sender = a.addr("sender");
+        a.mint(&sender, coin(118, "muno"));
+        let receiver = b.addr("receiver");
+        a.transfer(
+            &sender,
+            transfer_msg(&receiver, &memo(&b)),
+            vec![coin(118, "muno")],
+        )
+        .unwrap();
+        assert!(FEE_ESCROWS.has(&a.deps.storage, ("channel-0", 1)));
+        assert_eq!(a.balance(&a.contract(), "muno"), 118);
+        (a, b, sender, payee)
+    }
+
+    #[test]
+    fn relayer_fee_paid_on_ack() {
+        let (mut a, mut b, sender, payee) = transfer_with_relayer_fee(|_| String::new());
+
+        b.receive(a.pop_packet().unwrap()).unwrap();
+        a.acknowledge(b.pop_ack().unwrap()).unwrap();
+
+        assert_eq!(a.balance(&payee, "muno"), 10);
+        assert_eq!(a.balance(&a.addr("relayer"), "muno"), 5);
+        assert_eq!(a.balance(&sender, "muno"), 3);
+        assert_eq!(a.balance(&a.contract(), "muno"), 100);
+        assert_eq!(a.outstanding("channel-0", "muno"), 100);
+        assert!(FEE_ESCROWS.is_empty(&a.deps.storage));
+    }
+
+    #[test]
+    fn relayer_fee_paid_on_failure_ack_and_transfer_refunded() {
+        // B can't forward on a channel that doesn't exist and acknowledges a failure.
+        let (mut a, mut b, sender, payee) = transfer_with_relayer_fee(|b| {
+            format!(
+                r#"{{"forward":{{"receiver":"receiver","port":"{}","channel":"channel-9"}}}}"#,
+                b.endpoint("channel-9").port_id
+            )
+        });
+
+        b.receive(a.pop_packet().unwrap()).unwrap();
+        a.acknowledge(b.pop_ack().unwrap()).unwrap();
+
+        assert_eq!(a.balance(&payee, "muno"), 10);
+        assert_eq!(a.balance(&a.addr("relayer"), "muno"), 5);
+        assert_eq!(a.balance(&sender, "muno"), 103);
+        assert_eq!(a.balance(&a.contract(), "muno"), 0);
+        assert_eq!(a.outstanding("channel-0", "muno"), 0);
+        assert!(FEE_ESCROWS.is_empty(&a.deps.storage));
+    }
+
+    #[test]
+    fn relayer_fee_paid_on_timeout_and_transfer_refunded() {
+        let (mut a, _, sender, payee) = transfer_with_relayer_fee(|_| String::new());
+
+        let packet = a.pop_packet().unwrap();
+        a.timeout(packet).unwrap();
+
+        assert_eq!(a.balance(&payee, "muno"), 0);
+        assert_eq!(a.balance(&a.addr("relayer"), "muno"), 3);
+        assert_eq!(a.balance(&sender, "muno"), 115);
+        assert_eq!(a.balance(&a.contract(), "muno"), 0);
+        assert_eq!(a.outstanding("channel-0", "muno"), 0);
+        assert!(FEE_ESCROWS.is_empty(&a.deps.storage));
+    }
+
+    #[test]
+    fn relayer_fee_requires_fee_enabled_channel() {
+        let (mut a, mut b) = (Chain::new(), Chain::new());
+        connect(&mut a, "channel-0", &mut b, "channel-1");
+        let sender = a.addr("sender");
+        a.mint(&sender, coin(118, "muno"));
+
+        assert_eq!(
+            a.transfer(
+                &sender,
+                transfer_msg(&b.addr("receiver"), ""),
+                vec![coin(118, "muno")]
+            ),
+            Err(ContractError::FeeNotEnabled {
+                channel_id: "channel-0".into()
+            }
+            .to_string())
+        );
+        assert_eq!(a.balance(&sender, "muno"), 118);
+        assert!(a.pop_packet().is_none());
+    }
+
+    #[test]
+    fn list_denoms_after_receive() {
+        let (mut a, mut b) = (Chain::new(), Chain::new());
+        connect(&mut a, "channel-0", &mut b, "channel-1");
+        let sender = a.addr("sender");
+        let receiver = b.addr("receiver");
+        a.mint(&sender, coin(100, "muno"));
+        a.transfer(
+            &sender,
+            TransferMsg {
+                fees: None,
+                relayer_fee: None,
+                ..transfer_msg(&receiver, "")
+            },
+            vec![coin(100, "muno")],
+        )
+        .unwrap();
+        b.receive(a.pop_packet().unwrap()).unwrap();
+
+        let foreign_denom = make_foreign_denom(&b.endpoint("channel-1"), "muno");
+        let local_denom = format!(
+            "factory/{}/{}",
+            b.contract(),
+            hash_denom_str(&foreign_denom)
+        );
+        let response: ListDenomsResponse = from_json(
+            query(
+                b.deps.as_ref(),
+                b.env.clone(),
+                QueryMsg::ListDenoms {
+                    channel: "channel-1".into(),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            response.denoms,
+            vec![DenomMapping {
+                foreign_denom,
+                local_denom: local_denom.clone(),
+            }]
+        );
+        assert_eq!(b.balance(&receiver, &local_denom), 100);
+    }
+}
@@ -38,6 +38,15 @@ pub enum ContractError {
     #[error("Insufficient funds to redeem on channel")]
     InsufficientFunds,
 
+    #[error("Funds don't cover the relayer fee in {denom}")]
+    InsufficientRelayerFee { denom: String },
+
+    #[error("Channel {channel_id} is not fee enabled")]
+    FeeNotEnabled { channel_id: String },
+
+    #[error("The counterparty payee can't be empty")]
+    EmptyCounterpartyPayee,
+
     #[error("Got a submessage reply with unknown id: {id} and variant: {variant:?}")]
     UnknownReply { id: u64, variant: SubMsgResult },
 
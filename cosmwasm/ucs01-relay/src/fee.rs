@@ -0,0 +1,472 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{
+    from_json, to_json_binary, Addr, BankMsg, Binary, Coin, Coins, DepsMut, Event,
+    IbcAcknowledgement, IbcPacket, IbcPacketAckMsg, Response, StdResult, Storage,
+};
+use token_factory_api::TokenFactoryMsg;
+use ucs01_relay_api::protocol::IBC_SEND_ID;
+
+use crate::{
+    error::ContractError,
+    state::{
+        PacketFeeEscrow, RelayerFee, SendPayload, COUNTERPARTY_PAYEES, FEE_ENABLED_CHANNELS,
+        FEE_ESCROWS,
+    },
+};
+
+// Event and attribute names mirror the ibc-go 29-fee module.
+pub const INCENTIVIZED_PACKET_EVENT: &str = "incentivized_ibc_packet";
+pub const DISTRIBUTE_FEE_EVENT: &str = "distribute_fee";
+
+pub const ATTR_CHANNEL_ID: &str = "channel_id";
+pub const ATTR_PACKET_SEQUENCE: &str = "packet_sequence";
+pub const ATTR_RECV_FEE: &str = "recv_fee";
+pub const ATTR_ACK_FEE: &str = "ack_fee";
+pub const ATTR_TIMEOUT_FEE: &str = "timeout_fee";
+pub const ATTR_RECEIVER: &str = "receiver";
+pub const ATTR_FEE: &str = "fee";
+
+/// https://github.com/cosmos/ibc-go/blob/5ca37ef6e56a98683cf2b3b1570619dc9b322977/modules/apps/29-fee/types/keys.go#L15
+pub const FEE_VERSION: &str = "ics29-1";
+
+/// Version of ICS-29 fee enabled channels, wrapping the version of the application.
+#[cw_serde]
+pub struct FeeChannelVersion {
+    pub fee_version: String,
+    pub app_version: String,
+}
+
+/// Acknowledgement written on fee enabled channels, carrying the payee of the relayer that
+/// delivered the packet. Identical to the ICS-29 `IncentivizedAcknowledgement`.
+#[cw_serde]
+pub struct IncentivizedAcknowledgement {
+    pub app_acknowledgement: Binary,
+    pub forward_relayer_address: String,
+    pub underlying_app_success: bool,
+}
+
+/// Split a channel version into the version of the application and whether the channel is fee enabled.
+pub fn split_channel_version(version: &str) -> (String, bool) {
+    match serde_json_wasm::from_str::<FeeChannelVersion>(version) {
+        Ok(fee_version) if fee_version.fee_version == FEE_VERSION => {
+            (fee_version.app_version, true)
+        }
+        _ => (version.to_string(), false),
+    }
+}
+
+/// Remove the relayer fee from the funds attached to a transfer, returning the funds to transfer.
+pub fn deduct_relayer_fee(funds: Vec<Coin>, fee: &RelayerFee) -> Result<Vec<Coin>, ContractError> {
+    let mut funds = Coins::try_from(funds)?;
+    for coin in fee
+        .recv_fee
+        .iter()
+        .chain(&fee.ack_fee)
+        .chain(&fee.timeout_fee)
+    {
+        funds
+            .sub(coin.clone())
+            .map_err(|_| ContractError::InsufficientRelayerFee {
+                denom: coin.denom.clone(),
+            })?;
+    }
+    Ok(funds.into_vec())
+}
+
+/// Attach the relayer fee to the send packet submessage of a transfer on `channel_id`. The fee is
+/// escrowed in the reply, once the sequence of the packet is known.
+pub fn attach_relayer_fee(
+    storage: &dyn Storage,
+    mut response: Response<TokenFactoryMsg>,
+    channel_id: &str,
+    escrow: PacketFeeEscrow,
+) -> Result<Response<TokenFactoryMsg>, ContractError> {
+    if !FEE_ENABLED_CHANNELS.has(storage, channel_id) {
+        return Err(ContractError::FeeNotEnabled {
+            channel_id: channel_id.to_string(),
+        });
+    }
+    let payload = serde_json_wasm::to_vec(&SendPayload::RelayerFee {
+        channel_id: channel_id.to_string(),
+        escrow,
+    })
+    .expect("can serialize");
+    for submessage in response
+        .messages
+        .iter_mut()
+        .filter(|submessage| submessage.id == IBC_SEND_ID)
+    {
+        submessage.payload = payload.clone().into();
+    }
+    Ok(response)
+}
+
+/// Escrow the relayer fee of the packet that has just been sent.
+pub fn on_packet_sent(
+    deps: DepsMut,
+    channel_id: &str,
+    sequence: u64,
+    escrow: PacketFeeEscrow,
+) -> StdResult<Vec<Event>> {
+    FEE_ESCROWS.save(deps.storage, (channel_id, sequence), &escrow)?;
+    Ok(vec![Event::new(INCENTIVIZED_PACKET_EVENT).add_attributes(
+        [
+            (ATTR_CHANNEL_ID, channel_id.to_string()),
+            (ATTR_PACKET_SEQUENCE, sequence.to_string()),
+            (ATTR_RECV_FEE, coins_to_string(&escrow.fee.recv_fee)),
+            (ATTR_ACK_FEE, coins_to_string(&escrow.fee.ack_fee)),
+            (ATTR_TIMEOUT_FEE, coins_to_string(&escrow.fee.timeout_fee)),
+        ],
+    )])
+}
+
+/// The address to put in the acknowledgement of a packet delivered by `relayer` on `channel_id`,
+/// `None` if the channel is not fee enabled.
+///
+/// This is the counterparty payee registered by the relayer, or the relayer itself.
+pub fn forward_relayer(
+    storage: &dyn Storage,
+    channel_id: &str,
+    relayer: &Addr,
+) -> StdResult<Option<String>> {
+    if !FEE_ENABLED_CHANNELS.has(storage, channel_id) {
+        return Ok(None);
+    }
+    Ok(Some(
+        COUNTERPARTY_PAYEES
+            .may_load(storage, (channel_id, relayer))?
+            .unwrap_or_else(|| relayer.to_string()),
+    ))
+}
+
+/// Wrap the acknowledgement of a packet received on a fee enabled channel.
+pub fn incentivized_ack(ack: Binary, forward_relayer: String, success: bool) -> Binary {
+    to_json_binary(&IncentivizedAcknowledgement {
+        app_acknowledgement: ack,
+        forward_relayer_address: forward_relayer,
+        underlying_app_success: success,
+    })
+    .expect("can serialize")
+}
+
+/// Unwrap the acknowledgement of fee enabled channels and pay the relayers of the packet.
+///
+/// The recv fee is paid to the payee of the relayer that delivered the packet, the ack fee to the
+/// relayer submitting the acknowledgement, and the timeout fee is refunded. As in ICS-29, the recv
+/// fee is refunded if the payee is not a valid address.
+pub fn on_packet_ack(
+    deps: DepsMut,
+    mut msg: IbcPacketAckMsg,
+) -> Result<(IbcPacketAckMsg, Vec<BankMsg>, Vec<Event>), ContractError> {
+    if !FEE_ENABLED_CHANNELS.has(deps.storage, &msg.original_packet.src.channel_id) {
+        return Ok((msg, vec![], vec![]));
+    }
+    let ack = from_json::<IncentivizedAcknowledgement>(&msg.acknowledgement.data)?;
+    msg.acknowledgement = IbcAcknowledgement::new(ack.app_acknowledgement);
+    let payee = deps.api.addr_validate(&ack.forward_relayer_address).ok();
+    let (msgs, events) = distribute(deps, &msg.original_packet, |escrow| {
+        vec![
+            (
+                payee.unwrap_or_else(|| escrow.refund_address.clone()),
+                escrow.fee.recv_fee,
+            ),
+            (msg.relayer.clone(), escrow.fee.ack_fee),
+            (escrow.refund_address, escrow.fee.timeout_fee),
+        ]
+    })?;
+    Ok((msg, msgs, events))
+}
+
+/// Pay the relayer submitting the timeout and refund the recv and ack fees.
+pub fn on_packet_timeout(
+    deps: DepsMut,
+    packet: &IbcPacket,
+    relayer: &Addr,
+) -> Result<(Vec<BankMsg>, Vec<Event>), ContractError> {
+    distribute(deps, packet, |escrow| {
+        vec![
+            (relayer.clone(), escrow.fee.timeout_fee),
+            (
+                escrow.refund_address,
+                [escrow.fee.recv_fee, escrow.fee.ack_fee].concat(),
+            ),
+        ]
+    })
+}
+
+fn distribute(
+    deps: DepsMut,
+    packet: &IbcPacket,
+    split: impl FnOnce(PacketFeeEscrow) -> Vec<(Addr, Vec<Coin>)>,
+) -> Result<(Vec<BankMsg>, Vec<Event>), ContractError> {
+    let key = (packet.src.channel_id.as_str(), packet.sequence);
+    let Some(escrow) = FEE_ESCROWS.may_load(deps.storage, key)? else {
+        return Ok(Default::default());
+    };
+    FEE_ESCROWS.remove(deps.storage, key);
+
+    let mut msgs = vec![];
+    let mut events = vec![];
+    for (receiver, fee) in split(escrow) {
+        let mut amount = Coins::default();
+        for coin in fee {
+            amount.add(coin)?;
+        }
+        if amount.is_empty() {
+            continue;
+        }
+        events.push(Event::new(DISTRIBUTE_FEE_EVENT).add_attributes([
+            (ATTR_RECEIVER, receiver.to_string()),
+            (ATTR_FEE, amount.to_string()),
+        ]));
+        msgs.push(BankMsg::Send {
+            to_address: receiver.into(),
+            amount: amount.into_vec(),
+        });
+    }
+    Ok((msgs, events))
+}
+
+fn coins_to_string(coins: &[Coin]) -> String {
+    coins
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{
+        coin, testing::mock_dependencies, Addr, BankMsg, Binary, DepsMut, IbcAcknowledgement,
+        IbcEndpoint, IbcPacket, IbcPacketAckMsg, IbcTimeout, Timestamp,
+    };
+
+    use super::{
+        deduct_relayer_fee, forward_relayer, incentivized_ack, on_packet_ack, on_packet_sent,
+        on_packet_timeout, split_channel_version,
+    };
+    use crate::{
+        error::ContractError,
+        state::{
+            PacketFeeEscrow, RelayerFee, COUNTERPARTY_PAYEES, FEE_ENABLED_CHANNELS, FEE_ESCROWS,
+        },
+    };
+
+    fn relayer_fee() -> RelayerFee {
+        RelayerFee {
+            recv_fee: vec![coin(10, "muno")],
+            ack_fee: vec![coin(5, "muno")],
+            timeout_fee: vec![coin(3, "muno"), coin(1, "uatom")],
+        }
+    }
+
+    fn packet(sequence: u64) -> IbcPacket {
+        IbcPacket::new(
+            vec![],
+            IbcEndpoint {
+                port_id: "wasm.relay".into(),
+                channel_id: "channel-1".into(),
+            },
+            IbcEndpoint {
+                port_id: "transfer".into(),
+                channel_id: "channel-9".into(),
+            },
+            sequence,
+            IbcTimeout::with_timestamp(Timestamp::from_seconds(1)),
+        )
+    }
+
+    fn escrow(deps: DepsMut, sequence: u64, fee: RelayerFee) {
+        FEE_ENABLED_CHANNELS
+            .save(deps.storage, "channel-1", &Default::default())
+            .unwrap();
+        assert_eq!(
+            on_packet_sent(
+                deps,
+                "channel-1",
+                sequence,
+                PacketFeeEscrow {
+                    refund_address: Addr::unchecked("sender"),
+                    fee,
+                },
+            )
+            .unwrap()
+            .len(),
+            1
+        );
+    }
+
+    fn ack(sequence: u64, forward_relayer: &str) -> IbcPacketAckMsg {
+        IbcPacketAckMsg::new(
+            IbcAcknowledgement::new(incentivized_ack(
+                Binary::from(b"app ack".to_vec()),
+                forward_relayer.into(),
+                true,
+            )),
+            packet(sequence),
+            Addr::unchecked("relayer"),
+        )
+    }
+
+    #[test]
+    fn split_fee_channel_version() {
+        assert_eq!(
+            split_channel_version(r#"{"fee_version":"ics29-1","app_version":"ics20-1"}"#),
+            ("ics20-1".to_string(), true)
+        );
+        assert_eq!(
+            split_channel_version("ics20-1"),
+            ("ics20-1".to_string(), false)
+        );
+        assert_eq!(
+            split_channel_version(r#"{"fee_version":"ics29-2","app_version":"ics20-1"}"#),
+            (
+                r#"{"fee_version":"ics29-2","app_version":"ics20-1"}"#.to_string(),
+                false
+            )
+        );
+    }
+
+    #[test]
+    fn forward_relayer_is_registered_payee() {
+        let mut deps = mock_dependencies();
+        let relayer = Addr::unchecked("relayer");
+        assert_eq!(
+            forward_relayer(&deps.storage, "channel-1", &relayer).unwrap(),
+            None
+        );
+        FEE_ENABLED_CHANNELS
+            .save(&mut deps.storage, "channel-1", &Default::default())
+            .unwrap();
+        assert_eq!(
+            forward_relayer(&deps.storage, "channel-1", &relayer).unwrap(),
+            Some("relayer".into())
+        );
+        COUNTERPARTY_PAYEES
+            .save(
+                &mut deps.storage,
+                ("channel-1", &relayer),
+                &"counterparty-payee".into(),
+            )
+            .unwrap();
+        assert_eq!(
+            forward_relayer(&deps.storage, "channel-1", &relayer).unwrap(),
+            Some("counterparty-payee".into())
+        );
+    }
+
+    #[test]
+    fn deduct_fee_from_funds() {
+        assert_eq!(
+            deduct_relayer_fee(vec![coin(100, "muno"), coin(1, "uatom")], &relayer_fee()).unwrap(),
+            vec![coin(82, "muno")]
+        );
+    }
+
+    #[test]
+    fn deduct_fee_insufficient_funds() {
+        assert!(matches!(
+            deduct_relayer_fee(vec![coin(100, "muno")], &relayer_fee()),
+            Err(ContractError::InsufficientRelayerFee { denom }) if denom == "uatom"
+        ));
+    }
+
+    #[test]
+    fn ack_pays_relayers_and_refunds_timeout_fee() {
+        let mut deps = mock_dependencies();
+        let forward_relayer = deps.api.addr_make("forward-relayer");
+        escrow(deps.as_mut(), 4, relayer_fee());
+
+        // unrelated packet
+        assert_eq!(
+            on_packet_ack(deps.as_mut(), ack(3, forward_relayer.as_str()))
+                .unwrap()
+                .1,
+            vec![]
+        );
+
+        let (msg, msgs, events) =
+            on_packet_ack(deps.as_mut(), ack(4, forward_relayer.as_str())).unwrap();
+        assert_eq!(msg.acknowledgement.data, Binary::from(b"app ack".to_vec()));
+        assert_eq!(
+            msgs,
+            vec![
+                BankMsg::Send {
+                    to_address: forward_relayer.to_string(),
+                    amount: vec![coin(10, "muno")],
+                },
+                BankMsg::Send {
+                    to_address: "relayer".into(),
+                    amount: vec![coin(5, "muno")],
+                },
+                BankMsg::Send {
+                    to_address: "sender".into(),
+                    amount: vec![coin(3, "muno"), coin(1, "uatom")],
+                },
+            ]
+        );
+        assert_eq!(events.len(), 3);
+        assert!(FEE_ESCROWS.is_empty(&deps.storage));
+
+        // paid only once
+        assert_eq!(
+            on_packet_ack(deps.as_mut(), ack(4, forward_relayer.as_str()))
+                .unwrap()
+                .1,
+            vec![]
+        );
+    }
+
+    #[test]
+    fn ack_refunds_recv_fee_of_invalid_payee() {
+        let mut deps = mock_dependencies();
+        escrow(
+            deps.as_mut(),
+            1,
+            RelayerFee {
+                timeout_fee: vec![],
+                ..relayer_fee()
+            },
+        );
+
+        let (_, msgs, _) = on_packet_ack(deps.as_mut(), ack(1, "not an address")).unwrap();
+        assert_eq!(
+            msgs,
+            vec![
+                BankMsg::Send {
+                    to_address: "sender".into(),
+                    amount: vec![coin(10, "muno")],
+                },
+                BankMsg::Send {
+                    to_address: "relayer".into(),
+                    amount: vec![coin(5, "muno")],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn timeout_pays_relayer_and_refunds_recv_and_ack_fees() {
+        let mut deps = mock_dependencies();
+        escrow(
+            deps.as_mut(),
+            1,
+            RelayerFee {
+                timeout_fee: vec![],
+                ..relayer_fee()
+            },
+        );
+
+        let (msgs, _) =
+            on_packet_timeout(deps.as_mut(), &packet(1), &Addr::unchecked("relayer")).unwrap();
+        assert_eq!(
+            msgs,
+            vec![BankMsg::Send {
+                to_address: "sender".into(),
+                amount: vec![coin(15, "muno")],
+            }]
+        );
+        assert!(FEE_ESCROWS.is_empty(&deps.storage));
+    }
+}
@@ -3,20 +3,26 @@ use cosmwasm_std::entry_point;
 use cosmwasm_std::{
     DepsMut, Env, Ibc3ChannelOpenResponse, IbcBasicResponse, IbcChannel, IbcChannelCloseMsg,
     IbcChannelConnectMsg, IbcChannelOpenMsg, IbcPacketAckMsg, IbcPacketReceiveMsg,
-    IbcPacketTimeoutMsg, IbcReceiveResponse, MessageInfo, Reply, Response, SubMsgResult,
+    IbcPacketTimeoutMsg, IbcReceiveResponse, MessageInfo, Reply, ReplyOn, Response, StdResult,
+    Storage, SubMsgResult,
 };
 use prost::{Message, Name};
 use protos::cosmwasm::wasm::v1::MsgIbcSendResponse;
 use token_factory_api::TokenFactoryMsg;
 use ucs01_relay_api::{
-    middleware::InFlightPfmPacket,
     protocol::{TransferProtocol, IBC_SEND_ID},
+    types::GenericAck,
 };
+use unionlabs::encoding::Decode;
 
 use crate::{
     error::ContractError,
+    fee,
     protocol::{protocol_ordering, Ics20Protocol, ProtocolCommon, Ucs01Protocol},
-    state::{ChannelInfo, PfmRefundPacketKey, CHANNEL_INFO, IN_FLIGHT_PFM_PACKETS},
+    state::{
+        ChannelInfo, PfmRefundPacketKey, SendPayload, CHANNEL_INFO, FEE_ENABLED_CHANNELS,
+        IN_FLIGHT_PFM_PACKETS,
+    },
 };
 
 fn to_response<T>(
@@ -40,23 +46,68 @@ fn to_response<T>(
     }
 }
 
+/// Wrap the acknowledgement of a packet received on a fee enabled channel, `forward_relayer`
+/// being the payee of the relayer that delivered it.
+fn incentivize<T: TransferProtocol<CustomMsg = TokenFactoryMsg>>(
+    mut response: IbcReceiveResponse<TokenFactoryMsg>,
+    forward_relayer: Option<String>,
+) -> IbcReceiveResponse<TokenFactoryMsg> {
+    let Some(forward_relayer) = forward_relayer else {
+        return response;
+    };
+    // The acknowledgement is overwritten by the reply if a submessage fails, which must wrap it as well.
+    for submessage in response.messages.iter_mut().filter(|submessage| {
+        submessage.id == T::RECEIVE_REPLY_ID && submessage.reply_on == ReplyOn::Error
+    }) {
+        submessage.payload = forward_relayer.clone().into_bytes().into();
+    }
+    response.acknowledgement = response.acknowledgement.map(|ack| {
+        let success = <T::Ack as Decode<T::Encoding>>::decode(&ack)
+            .is_ok_and(|ack| Into::<GenericAck>::into(ack).is_ok());
+        fee::incentivized_ack(ack, forward_relayer, success)
+    });
+    response
+}
+
+/// Record the channel in CHANNEL_INFO, along with whether it is fee enabled.
+pub(crate) fn register_channel(storage: &mut dyn Storage, channel: IbcChannel) -> StdResult<()> {
+    let (protocol_version, fee_enabled) = fee::split_channel_version(&channel.version);
+    let info = ChannelInfo {
+        endpoint: channel.endpoint,
+        counterparty_endpoint: channel.counterparty_endpoint,
+        connection_id: channel.connection_id,
+        protocol_version,
+    };
+    if fee_enabled {
+        FEE_ENABLED_CHANNELS.save(storage, &info.endpoint.channel_id, &Default::default())?;
+    }
+    CHANNEL_INFO.save(storage, &info.endpoint.channel_id, &info)
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn reply(
-    deps: DepsMut,
+    mut deps: DepsMut,
     _: Env,
     reply: Reply,
 ) -> Result<Response<TokenFactoryMsg>, ContractError> {
     match (reply.id, reply.result) {
         // RECEIVE_REPLY_ID is associated with submessages emitted during handling of `ibc_packet_receive`
+        // the payload is the forward relayer if the packet was received on a fee enabled channel
         (Ics20Protocol::RECEIVE_REPLY_ID, SubMsgResult::Err(err)) => {
-            Ok(to_response(Ics20Protocol::receive_error(err)))
+            Ok(to_response(incentivize::<Ics20Protocol>(
+                Ics20Protocol::receive_error(err),
+                forward_relayer_of(&reply.payload),
+            )))
         }
         (Ucs01Protocol::RECEIVE_REPLY_ID, SubMsgResult::Err(err)) => {
-            Ok(to_response(Ucs01Protocol::receive_error(err)))
+            Ok(to_response(incentivize::<Ucs01Protocol>(
+                Ucs01Protocol::receive_error(err),
+                forward_relayer_of(&reply.payload),
+            )))
         }
         // IBC_SEND_ID is associated with submessages emitted during handling of `send`, which is called via `execute_transfer`, which is used both in PFM and non-PFM contexts
         (IBC_SEND_ID, SubMsgResult::Ok(value)) => {
-            // this means this is not pfm
+            // this means this is neither pfm nor a packet with an escrowed relayer fee
             if reply.payload.is_empty() {
                 return Ok(Response::new());
             }
@@ -71,8 +122,19 @@ pub fn reply(
                 MsgIbcSendResponse::decode(msg_response.value.as_slice()).expect("is type url");
 
             let in_flight_packet =
-                serde_json_wasm::from_slice::<InFlightPfmPacket>(reply.payload.as_slice())
-                    .expect("binary is type");
+                match serde_json_wasm::from_slice::<SendPayload>(reply.payload.as_slice())
+                    .expect("binary is type")
+                {
+                    SendPayload::Forward(in_flight_packet) => in_flight_packet,
+                    SendPayload::RelayerFee { channel_id, escrow } => {
+                        return Ok(Response::new().add_events(fee::on_packet_sent(
+                            deps.branch(),
+                            &channel_id,
+                            send_response.sequence,
+                            escrow,
+                        )?));
+                    }
+                };
 
             let refund_packet_key = PfmRefundPacketKey {
                 channel_id: in_flight_packet.forward_src_channel_id.clone(),
@@ -90,19 +152,29 @@ pub fn reply(
             )
         }
         (IBC_SEND_ID, SubMsgResult::Err(err)) => {
-            // this means this is not pfm
-            if reply.payload.is_empty() {
-                return Err(ContractError::PfmSendPacketError { err });
-            }
-
             // decode the payload to figure out the source channel
             let in_flight_packet =
-                serde_json_wasm::from_slice::<InFlightPfmPacket>(reply.payload.as_slice())
-                    .expect("binary is type");
+                match serde_json_wasm::from_slice::<SendPayload>(reply.payload.as_slice()) {
+                    Ok(SendPayload::Forward(in_flight_packet)) => in_flight_packet,
+                    // this means this is not pfm
+                    _ => return Err(ContractError::PfmSendPacketError { err }),
+                };
+
+            let forward_relayer = fee::forward_relayer(
+                deps.storage,
+                &in_flight_packet.origin_packet.dest.channel_id,
+                &in_flight_packet.origin_sender_addr,
+            )?;
 
             match &*in_flight_packet.origin_protocol_version {
-                Ucs01Protocol::VERSION => Ok(to_response(Ucs01Protocol::receive_error(err))),
-                Ics20Protocol::VERSION => Ok(to_response(Ics20Protocol::receive_error(err))),
+                Ucs01Protocol::VERSION => Ok(to_response(incentivize::<Ucs01Protocol>(
+                    Ucs01Protocol::receive_error(err),
+                    forward_relayer,
+                ))),
+                Ics20Protocol::VERSION => Ok(to_response(incentivize::<Ics20Protocol>(
+                    Ics20Protocol::receive_error(err),
+                    forward_relayer,
+                ))),
                 // in_flight_packet.origin_protocol_version is only ever set by us, so if it is set incorrectly then it is a bug
                 version => unreachable!("unknown protocol version: {version}"),
             }
@@ -133,14 +205,7 @@ pub fn ibc_channel_connect(
     msg: IbcChannelConnectMsg,
 ) -> Result<IbcBasicResponse, ContractError> {
     enforce_order_and_version(msg.channel(), msg.counterparty_version())?;
-    let channel: IbcChannel = msg.into();
-    let info = ChannelInfo {
-        endpoint: channel.endpoint,
-        counterparty_endpoint: channel.counterparty_endpoint,
-        connection_id: channel.connection_id,
-        protocol_version: channel.version,
-    };
-    CHANNEL_INFO.save(deps.storage, &info.endpoint.channel_id, &info)?;
+    register_channel(deps.storage, msg.into())?;
     Ok(IbcBasicResponse::default())
 }
 
@@ -148,13 +213,13 @@ pub(crate) fn enforce_order_and_version(
     channel: &IbcChannel,
     counterparty_version: Option<&str>,
 ) -> Result<(), ContractError> {
-    let channel_ordering =
-        protocol_ordering(&channel.version).ok_or(ContractError::UnknownProtocol {
+    let channel_ordering = protocol_ordering(&fee::split_channel_version(&channel.version).0)
+        .ok_or(ContractError::UnknownProtocol {
             channel_id: channel.endpoint.channel_id.clone(),
             protocol_version: channel.version.clone(),
         })?;
     if let Some(version) = counterparty_version {
-        if protocol_ordering(version).is_none() {
+        if protocol_ordering(&fee::split_channel_version(version).0).is_none() {
             return Err(ContractError::UnknownProtocol {
                 channel_id: channel.endpoint.channel_id.clone(),
                 protocol_version: version.to_string(),
@@ -196,30 +261,39 @@ pub fn ibc_packet_receive(
 ) -> Result<IbcReceiveResponse<TokenFactoryMsg>, ContractError> {
     let channel_info = CHANNEL_INFO.load(deps.storage, &msg.packet.dest.channel_id)?;
 
+    let forward_relayer =
+        fee::forward_relayer(deps.storage, &msg.packet.dest.channel_id, &msg.relayer)?;
+
     let info = MessageInfo {
         sender: msg.relayer,
         funds: Default::default(),
     };
 
     match channel_info.protocol_version.as_str() {
-        Ics20Protocol::VERSION => Ok(Ics20Protocol {
-            common: ProtocolCommon {
-                deps,
-                env,
-                info,
-                channel: channel_info,
-            },
-        }
-        .receive(msg.packet)),
-        Ucs01Protocol::VERSION => Ok(Ucs01Protocol {
-            common: ProtocolCommon {
-                deps,
-                env,
-                info,
-                channel: channel_info,
-            },
-        }
-        .receive(msg.packet)),
+        Ics20Protocol::VERSION => Ok(incentivize::<Ics20Protocol>(
+            Ics20Protocol {
+                common: ProtocolCommon {
+                    deps,
+                    env,
+                    info,
+                    channel: channel_info,
+                },
+            }
+            .receive(msg.packet),
+            forward_relayer,
+        )),
+        Ucs01Protocol::VERSION => Ok(incentivize::<Ucs01Protocol>(
+            Ucs01Protocol {
+                common: ProtocolCommon {
+                    deps,
+                    env,
+                    info,
+                    channel: channel_info,
+                },
+            }
+            .receive(msg.packet),
+            forward_relayer,
+        )),
         v => Err(ContractError::UnknownProtocol {
             channel_id: msg.packet.dest.channel_id,
             protocol_version: v.into(),
@@ -230,12 +304,14 @@ pub fn ibc_packet_receive(
 #[cfg_attr(not(feature = "library"), entry_point)]
 /// check if success or failure and update balance, or return funds
 pub fn ibc_packet_ack(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     msg: IbcPacketAckMsg,
 ) -> Result<IbcBasicResponse<TokenFactoryMsg>, ContractError> {
     let channel_info = CHANNEL_INFO.load(deps.storage, &msg.original_packet.src.channel_id)?;
 
+    let (msg, fee_msgs, fee_events) = fee::on_packet_ack(deps.branch(), msg)?;
+
     let info = MessageInfo {
         sender: msg.relayer.clone(),
         funds: Default::default(),
@@ -265,17 +341,20 @@ pub fn ibc_packet_ack(
             protocol_version: v.into(),
         }),
     }
+    .map(|response| response.add_messages(fee_msgs).add_events(fee_events))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 /// return fund to original sender (same as failure in ibc_packet_ack)
 pub fn ibc_packet_timeout(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     msg: IbcPacketTimeoutMsg,
 ) -> Result<IbcBasicResponse<TokenFactoryMsg>, ContractError> {
     let channel_info = CHANNEL_INFO.load(deps.storage, &msg.packet.src.channel_id)?;
 
+    let (fee_msgs, fee_events) = fee::on_packet_timeout(deps.branch(), &msg.packet, &msg.relayer)?;
+
     let info = MessageInfo {
         sender: msg.relayer,
         funds: Default::default(),
@@ -305,19 +384,465 @@ pub fn ibc_packet_timeout(
             protocol_version: v.into(),
         }),
     }
+    .map(|response| response.add_messages(fee_msgs).add_events(fee_events))
+}
+
+fn forward_relayer_of(payload: &[u8]) -> Option<String> {
+    (!payload.is_empty())
+        .then(|| String::from_utf8(payload.to_vec()).expect("the payload is the forward relayer"))
 }
 
 #[cfg(test)]
 mod tests {
-    use cosmwasm_std::{IbcChannel, IbcEndpoint};
-    use ucs01_relay_api::protocol::TransferProtocol;
+    use cosmwasm_std::{
+        from_json,
+        testing::{mock_dependencies, mock_env},
+        Addr, BankMsg, Binary, Coin, CosmosMsg, DepsMut, IbcAcknowledgement, IbcChannel,
+        IbcEndpoint, IbcPacket, IbcPacketAckMsg, IbcPacketTimeoutMsg, IbcTimeout, Timestamp,
+        Uint128, Uint512,
+    };
+    use prost::Name;
+    use protos::deferredack::v1beta1::MsgWriteDeferredAck;
+    use token_factory_api::TokenFactoryMsg;
+    use ucs01_relay_api::{
+        middleware::{InFlightPfmPacket, Memo, PacketForward},
+        protocol::TransferProtocol,
+        types::{make_foreign_denom, Ics20Ack, Ics20Packet, JsonWasm},
+    };
+    use unionlabs::{encoding::EncodeAs, validated::Validated};
 
-    use super::enforce_order_and_version;
+    use super::{enforce_order_and_version, ibc_packet_ack, ibc_packet_timeout};
     use crate::{
         error::ContractError,
-        protocol::{Ics20Protocol, Ucs01Protocol},
+        msg::TransferMsg,
+        protocol::{hash_denom_str, Ics20Protocol, Ucs01Protocol},
+        state::{
+            ChannelInfo, ChannelState, PfmRefundPacketKey, CHANNEL_INFO, CHANNEL_STATE,
+            IN_FLIGHT_PFM_PACKETS,
+        },
+        test_utils::{connect, Chain},
     };
 
+    fn endpoint(port_id: &str, channel_id: &str) -> IbcEndpoint {
+        IbcEndpoint {
+            port_id: port_id.into(),
+            channel_id: channel_id.into(),
+        }
+    }
+
+    fn save_channel(deps: DepsMut, endpoint: IbcEndpoint, counterparty_endpoint: IbcEndpoint) {
+        CHANNEL_INFO
+            .save(
+                deps.storage,
+                &endpoint.channel_id.clone(),
+                &ChannelInfo {
+                    endpoint,
+                    counterparty_endpoint,
+                    connection_id: "connection-0".into(),
+                    protocol_version: Ics20Protocol::VERSION.into(),
+                },
+            )
+            .unwrap();
+    }
+
+    fn ics20_packet(
+        src: IbcEndpoint,
+        dest: IbcEndpoint,
+        sequence: u64,
+        denom: &str,
+        sender: &str,
+    ) -> IbcPacket {
+        IbcPacket::new(
+            Ics20Packet {
+                denom: denom.into(),
+                amount: Uint128::from(100u128),
+                sender: sender.into(),
+                receiver: "receiver".into(),
+                memo: String::new(),
+            }
+            .encode_as::<JsonWasm>(),
+            src,
+            dest,
+            sequence,
+            IbcTimeout::with_timestamp(Timestamp::from_seconds(1)),
+        )
+    }
+
+    /// Setup the intermediate chain of a route `prev -> here -> next`, with the forward already in
+    /// flight. Returns the forwarded packet.
+    fn setup_in_flight_hop(
+        mut deps: DepsMut,
+        (prev, here_from_prev): (IbcEndpoint, IbcEndpoint),
+        (here_to_next, next): (IbcEndpoint, IbcEndpoint),
+        origin_denom: &str,
+        forwarded_denom: &str,
+    ) -> IbcPacket {
+        let contract_address = mock_env().contract.address;
+        save_channel(deps.branch(), here_from_prev.clone(), prev.clone());
+        save_channel(deps.branch(), here_to_next.clone(), next.clone());
+        // The forwarded tokens are escrowed on the outgoing channel.
+        CHANNEL_STATE
+            .save(
+                deps.storage,
+                (&here_to_next.channel_id, forwarded_denom),
+                &ChannelState {
+                    outstanding: Uint512::from(100u128),
+                },
+            )
+            .unwrap();
+        let forward_packet = ics20_packet(
+            here_to_next.clone(),
+            next,
+            1,
+            forwarded_denom,
+            contract_address.as_str(),
+        );
+        IN_FLIGHT_PFM_PACKETS
+            .save(
+                deps.storage,
+                PfmRefundPacketKey {
+                    channel_id: here_to_next.channel_id.clone(),
+                    port_id: here_to_next.port_id.clone(),
+                    sequence: 1,
+                },
+                &InFlightPfmPacket {
+                    origin_sender_addr: Addr::unchecked("relayer"),
+                    origin_protocol_version: Ics20Protocol::VERSION.into(),
+                    origin_packet: ics20_packet(prev, here_from_prev, 7, origin_denom, "sender"),
+                    forward_src_channel_id: here_to_next.channel_id,
+                    forward_src_port_id: here_to_next.port_id,
+                    forward_timeout: 60,
+                },
+            )
+            .unwrap();
+        forward_packet
+    }
+
+    fn assert_deferred_ack(msg: &CosmosMsg<TokenFactoryMsg>) {
+        match msg {
+            CosmosMsg::Any(any) => assert_eq!(any.type_url, MsgWriteDeferredAck::type_url()),
+            msg => panic!("expected a deferred ack, got {msg:?}"),
+        }
+    }
+
+    // Route: A -> B -> C -> D, we are C and the hop to D timed out. The voucher minted for B's
+    // token must be refunded then burnt before the failure is propagated back to B.
+    #[test]
+    fn pfm_timeout_reverts_intermediate_hop() {
+        let mut deps = mock_dependencies();
+        let contract_address = mock_env().contract.address;
+        let here_from_b = endpoint("wasm.c", "channel-3");
+        let voucher = format!(
+            "factory/{contract_address}/{}",
+            hash_denom_str("wasm.c/channel-3/muno")
+        );
+        let forward_packet = setup_in_flight_hop(
+            deps.as_mut(),
+            (endpoint("wasm.b", "channel-2"), here_from_b),
+            (
+                endpoint("wasm.c", "channel-4"),
+                endpoint("transfer", "channel-5"),
+            ),
+            "muno",
+            &voucher,
+        );
+
+        let response = ibc_packet_timeout(
+            deps.as_mut(),
+            mock_env(),
+            IbcPacketTimeoutMsg::new(forward_packet, Addr::unchecked("relayer")),
+        )
+        .unwrap();
+
+        let msgs = response
+            .messages
+            .into_iter()
+            .map(|sub| sub.msg)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            msgs[..2],
+            [
+                BankMsg::Send {
+                    to_address: contract_address.to_string(),
+                    amount: vec![Coin::new(100u128, voucher.clone())],
+                }
+                .into(),
+                TokenFactoryMsg::BurnTokens {
+                    denom: voucher.clone(),
+                    amount: Uint128::from(100u128),
+                    burn_from_address: contract_address.to_string(),
+                }
+                .into(),
+            ]
+        );
+        assert_deferred_ack(&msgs[2]);
+        assert_eq!(msgs.len(), 3);
+        assert!(IN_FLIGHT_PFM_PACKETS.is_empty(&deps.storage));
+        assert_eq!(
+            CHANNEL_STATE
+                .load(&deps.storage, ("channel-4", &voucher))
+                .unwrap()
+                .outstanding,
+            Uint512::zero()
+        );
+    }
+
+    // Route: A -> B -> C -> D, we are B and C acknowledged a failure (propagated from D). The
+    // local token unescrowed when receiving from A must be escrowed again.
+    #[test]
+    fn pfm_failure_ack_reescrows_intermediate_hop() {
+        let mut deps = mock_dependencies();
+        let contract_address = mock_env().contract.address;
+        let forward_packet = setup_in_flight_hop(
+            deps.as_mut(),
+            (
+                endpoint("transfer", "channel-0"),
+                endpoint("wasm.b", "channel-1"),
+            ),
+            (
+                endpoint("wasm.b", "channel-2"),
+                endpoint("wasm.c", "channel-3"),
+            ),
+            "transfer/channel-0/muno",
+            "muno",
+        );
+
+        let response = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            IbcPacketAckMsg::new(
+                IbcAcknowledgement::new(
+                    Ics20Ack::Error("giving up on forwarded packet after timeout".into())
+                        .encode_as::<JsonWasm>(),
+                ),
+                forward_packet,
+                Addr::unchecked("relayer"),
+            ),
+        )
+        .unwrap();
+
+        let msgs = response
+            .messages
+            .into_iter()
+            .map(|sub| sub.msg)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            msgs[0],
+            BankMsg::Send {
+                to_address: contract_address.to_string(),
+                amount: vec![Coin::new(100u128, "muno")],
+            }
+            .into()
+        );
+        assert_deferred_ack(&msgs[1]);
+        assert_eq!(msgs.len(), 2);
+        assert!(IN_FLIGHT_PFM_PACKETS.is_empty(&deps.storage));
+        assert_eq!(
+            CHANNEL_STATE
+                .load(&deps.storage, ("channel-2", "muno"))
+                .unwrap()
+                .outstanding,
+            Uint512::zero()
+        );
+        assert_eq!(
+            CHANNEL_STATE
+                .load(&deps.storage, ("channel-1", "muno"))
+                .unwrap()
+                .outstanding,
+            Uint512::from(100u128)
+        );
+    }
+
+    #[test]
+    fn pfm_success_ack_only_forwards_ack() {
+        let mut deps = mock_dependencies();
+        let forward_packet = setup_in_flight_hop(
+            deps.as_mut(),
+            (
+                endpoint("transfer", "channel-0"),
+                endpoint("wasm.b", "channel-1"),
+            ),
+            (
+                endpoint("wasm.b", "channel-2"),
+                endpoint("wasm.c", "channel-3"),
+            ),
+            "transfer/channel-0/muno",
+            "muno",
+        );
+
+        let response = ibc_packet_ack(
+            deps.as_mut(),
+            mock_env(),
+            IbcPacketAckMsg::new(
+                IbcAcknowledgement::new(Ics20Ack::Result(vec![1].into()).encode_as::<JsonWasm>()),
+                forward_packet,
+                Addr::unchecked("relayer"),
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(response.messages.len(), 1);
+        assert_deferred_ack(&response.messages[0].msg);
+        assert!(IN_FLIGHT_PFM_PACKETS.is_empty(&deps.storage));
+        assert_eq!(
+            CHANNEL_STATE
+                .load(&deps.storage, ("channel-2", "muno"))
+                .unwrap()
+                .outstanding,
+            Uint512::from(100u128)
+        );
+    }
+
+    /// Chains `A <-> B <-> C`, connected with `A/channel-0 <-> B/channel-1` and
+    /// `B/channel-2 <-> C/channel-3`.
+    fn three_chains() -> (Chain, Chain, Chain) {
+        let (mut a, mut b, mut c) = (Chain::new(), Chain::new(), Chain::new());
+        connect(&mut a, "channel-0", &mut b, "channel-1");
+        connect(&mut b, "channel-2", &mut c, "channel-3");
+        (a, b, c)
+    }
+
+    fn forward_hop(chain: &Chain, channel_id: &str, receiver: &str) -> PacketForward {
+        PacketForward {
+            receiver: Validated::new(receiver.into()).unwrap(),
+            port: Validated::new(chain.endpoint(channel_id).port_id).unwrap(),
+            channel: Validated::new(channel_id.into()).unwrap(),
+            timeout: "1m".into(),
+            retries: 0,
+            next: None,
+            return_info: None,
+            fees: None,
+        }
+    }
+
+    /// Send 100 muno from `sender` on A, to be forwarded by B to `receiver` on C with the given memo
+    /// for C.
+    fn send_through_b(
+        a: &mut Chain,
+        b: &Chain,
+        sender: &Addr,
+        receiver: &str,
+        next: Option<PacketForward>,
+    ) {
+        a.mint(sender, Coin::new(100u128, "muno"));
+        let forward = PacketForward {
+            next: next.map(Box::new),
+            ..forward_hop(b, "channel-2", receiver)
+        };
+        a.transfer(
+            sender,
+            TransferMsg {
+                channel: "channel-0".into(),
+                receiver: b.contract().into(),
+                timeout: None,
+                memo: serde_json_wasm::to_string(&Memo::Forward { forward }).unwrap(),
+                fees: None,
+                relayer_fee: None,
+            },
+            vec![Coin::new(100u128, "muno")],
+        )
+        .unwrap();
+    }
+
+    /// The voucher minted on B for the muno of A.
+    fn b_voucher(b: &Chain) -> String {
+        format!(
+            "factory/{}/{}",
+            b.contract(),
+            hash_denom_str(&make_foreign_denom(&b.endpoint("channel-1"), "muno"))
+        )
+    }
+
+    fn assert_error_ack(ack: &Binary) {
+        assert!(matches!(from_json(ack), Ok(Ics20Ack::Error(_))), "{ack}");
+    }
+
+    #[test]
+    fn pfm_three_chains_forward() {
+        let (mut a, mut b, mut c) = three_chains();
+        let sender = a.addr("sender");
+        let receiver = c.addr("receiver");
+        send_through_b(&mut a, &b, &sender, receiver.as_str(), None);
+
+        b.receive(a.pop_packet().unwrap()).unwrap();
+        // The acknowledgement is deferred until C acknowledges the forward.
+        assert!(b.pop_ack().is_none());
+        c.receive(b.pop_packet().unwrap()).unwrap();
+        b.acknowledge(c.pop_ack().unwrap()).unwrap();
+        let ack = b.pop_ack().unwrap();
+        assert_eq!(
+            from_json::<Ics20Ack>(&ack.1).unwrap(),
+            Ics20Ack::Result(vec![1].into())
+        );
+        a.acknowledge(ack).unwrap();
+
+        let b_voucher = b_voucher(&b);
+        let c_voucher = format!(
+            "factory/{}/{}",
+            c.contract(),
+            hash_denom_str(&make_foreign_denom(&c.endpoint("channel-3"), &b_voucher))
+        );
+        assert_eq!(c.balance(&receiver, &c_voucher), 100);
+        assert_eq!(b.balance(&b.contract(), &b_voucher), 100);
+        assert_eq!(b.outstanding("channel-2", &b_voucher), 100);
+        assert_eq!(a.balance(&sender, "muno"), 0);
+        assert_eq!(a.outstanding("channel-0", "muno"), 100);
+        assert!(IN_FLIGHT_PFM_PACKETS.is_empty(&b.deps.storage));
+    }
+
+    #[test]
+    fn pfm_three_chains_refund_on_failed_hop() {
+        let (mut a, mut b, mut c) = three_chains();
+        let sender = a.addr("sender");
+        let contract = c.contract();
+        // C cannot forward on a channel that does not exist.
+        send_through_b(
+            &mut a,
+            &b,
+            &sender,
+            contract.as_str(),
+            Some(forward_hop(&c, "channel-9", "receiver")),
+        );
+
+        b.receive(a.pop_packet().unwrap()).unwrap();
+        c.receive(b.pop_packet().unwrap()).unwrap();
+        let ack = c.pop_ack().unwrap();
+        assert_error_ack(&ack.1);
+        b.acknowledge(ack).unwrap();
+        let ack = b.pop_ack().unwrap();
+        assert_error_ack(&ack.1);
+        a.acknowledge(ack).unwrap();
+
+        let b_voucher = b_voucher(&b);
+        assert_eq!(b.balance(&b.contract(), &b_voucher), 0);
+        assert_eq!(b.outstanding("channel-2", &b_voucher), 0);
+        assert_eq!(a.balance(&sender, "muno"), 100);
+        assert_eq!(a.outstanding("channel-0", "muno"), 0);
+        assert!(IN_FLIGHT_PFM_PACKETS.is_empty(&b.deps.storage));
+        assert!(c.pop_packet().is_none());
+    }
+
+    #[test]
+    fn pfm_three_chains_refund_on_timeout() {
+        let (mut a, mut b, _) = three_chains();
+        let sender = a.addr("sender");
+        send_through_b(&mut a, &b, &sender, "receiver", None);
+
+        b.receive(a.pop_packet().unwrap()).unwrap();
+        let packet = b.pop_packet().unwrap();
+        b.timeout(packet).unwrap();
+        let ack = b.pop_ack().unwrap();
+        assert_error_ack(&ack.1);
+        a.acknowledge(ack).unwrap();
+
+        let b_voucher = b_voucher(&b);
+        assert_eq!(b.balance(&b.contract(), &b_voucher), 0);
+        assert_eq!(b.outstanding("channel-2", &b_voucher), 0);
+        assert_eq!(a.balance(&sender, "muno"), 100);
+        assert_eq!(a.outstanding("channel-0", "muno"), 0);
+        assert!(IN_FLIGHT_PFM_PACKETS.is_empty(&b.deps.storage));
+    }
+
     #[test]
     fn enforce_channel_version_ucs01() {
         let port_id = "port-1";
@@ -1,9 +1,13 @@
 pub mod contract;
 pub mod error;
+pub mod fee;
 pub mod ibc;
 pub mod msg;
 pub mod protocol;
 pub mod state;
+#[cfg(test)]
+mod test_utils;
 
+#[cfg(not(feature = "library"))]
 #[global_allocator]
 static ALLOC: dlmalloc::GlobalDlmalloc = dlmalloc::GlobalDlmalloc;
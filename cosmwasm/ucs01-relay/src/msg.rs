@@ -3,7 +3,7 @@ use cosmwasm_std::{Binary, CosmosMsg, IbcChannel, IbcEndpoint, Uint512};
 use token_factory_api::TokenFactoryMsg;
 use ucs01_relay_api::types::Fees;
 
-use crate::state::ChannelInfo;
+use crate::state::{ChannelInfo, PacketFeeEscrow, RelayerFee};
 
 #[cw_serde]
 pub struct InstantiateMsg {
@@ -30,6 +30,12 @@ pub enum ExecuteMsg {
     },
     /// Change the admin (must be called by current admin)
     UpdateAdmin { admin: String },
+    /// Register the address on the counterparty chain to be paid the recv fee of the packets the
+    /// sender relays on a fee enabled channel.
+    RegisterCounterpartyPayee {
+        channel: String,
+        counterparty_payee: String,
+    },
     BatchExecute {
         msgs: Vec<CosmosMsg<TokenFactoryMsg>>,
     },
@@ -48,6 +54,10 @@ pub struct TransferMsg {
     pub memo: String,
     /// Fee associated with the transfer, denominated in transferred coins
     pub fees: Option<Fees>,
+    /// Relayer fee escrowed until the packet is acknowledged or timed out, must be sent in addition
+    /// to the transferred coins. The unused part is refunded to the sender. Only available on fee
+    /// enabled channels.
+    pub relayer_fee: Option<RelayerFee>,
 }
 
 #[cw_serde]
@@ -67,6 +77,26 @@ pub enum QueryMsg {
     Config {},
     #[returns(cw_controllers::AdminResponse)]
     Admin {},
+    /// Returns the relayer fee escrowed for a packet sent on the given channel, if any.
+    #[returns(Option<PacketFeeEscrow>)]
+    FeeEscrow { channel: String, sequence: u64 },
+    /// Returns the counterparty payee registered by a relayer on the given channel, if any.
+    #[returns(Option<String>)]
+    CounterpartyPayee { channel: String, relayer: String },
+    /// List the relayer fees escrowed for in flight packets of the given channel.
+    #[returns(ListFeeEscrowsResponse)]
+    ListFeeEscrows {
+        channel: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// List the foreign denoms received on the given channel and the local token factory denoms they are minted as.
+    #[returns(ListDenomsResponse)]
+    ListDenoms {
+        channel: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
 }
 
 #[cw_serde]
@@ -74,6 +104,25 @@ pub struct ListChannelsResponse {
     pub channels: Vec<ChannelInfo>,
 }
 
+#[cw_serde]
+pub struct ListFeeEscrowsResponse {
+    /// The escrows indexed by packet sequence.
+    pub escrows: Vec<(u64, PacketFeeEscrow)>,
+}
+
+#[cw_serde]
+pub struct ListDenomsResponse {
+    pub denoms: Vec<DenomMapping>,
+}
+
+#[cw_serde]
+pub struct DenomMapping {
+    /// The denom of the token on the counterparty, prefixed with the local port and channel.
+    pub foreign_denom: String,
+    /// The token factory denom minted for the foreign denom.
+    pub local_denom: String,
+}
+
 #[cw_serde]
 pub struct ChannelResponse {
     /// Information on the channel's connection
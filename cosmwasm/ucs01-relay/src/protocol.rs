@@ -13,19 +13,24 @@ use ucs01_relay_api::{
         ATTR_VALUE_PFM_ACK, IBC_SEND_ID,
     },
     types::{
-        make_foreign_denom, DenomOrigin, EncodingError, GenericAck, Ics20Ack, Ics20Packet,
-        JsonWasm, NormalizedTransferToken, TransferToken, Ucs01Ack, Ucs01TransferPacket,
+        make_foreign_denom, DenomOrigin, EncodingError, FeePerU128, GenericAck, Ics20Ack,
+        Ics20Packet, JsonWasm, NormalizedTransferToken, TransferPacket, TransferToken, Ucs01Ack,
+        Ucs01TransferPacket,
     },
 };
-use unionlabs::{encoding, ibc::core::client::height::Height};
+use unionlabs::{
+    encoding::{self, DecodeAs},
+    ibc::core::client::height::Height,
+};
 
 use crate::{
     contract::execute_transfer,
     error::ContractError,
+    fee,
     msg::{ExecuteMsg, TransferMsg},
     state::{
-        ChannelInfo, Hash, PfmRefundPacketKey, CHANNEL_INFO, CHANNEL_STATE, FOREIGN_DENOM_TO_HASH,
-        HASH_LENGTH, HASH_TO_FOREIGN_DENOM, IN_FLIGHT_PFM_PACKETS,
+        ChannelInfo, Hash, PfmRefundPacketKey, SendPayload, CHANNEL_INFO, CHANNEL_STATE,
+        FOREIGN_DENOM_TO_HASH, HASH_LENGTH, HASH_TO_FOREIGN_DENOM, IN_FLIGHT_PFM_PACKETS,
     },
 };
 
@@ -60,6 +65,7 @@ pub trait TransferProtocolExt<'a>:
         let ack =
             self.convert_ack_to_foreign_protocol(&refund_info.origin_protocol_version, ack)?;
 
+        let success = ack.is_ok();
         let (mut ack_msgs, mut ack_attrs, ack_bytes) = match ack {
             Ok(value) => {
                 let value_string = Binary::from(value.clone()).to_string();
@@ -72,14 +78,26 @@ pub trait TransferProtocolExt<'a>:
                     value.to_vec(),
                 )
             }
-            Err(error) => (
-                self.send_tokens_failure(sender, &Default::default(), tokens)?,
-                Vec::from_iter((!error.is_empty()).then_some(Attribute::new(
-                    ATTR_ERROR,
-                    Binary::from(error.clone()).to_string(),
-                ))),
-                error.to_vec(),
-            ),
+            Err(error) => {
+                // The forwarded tokens are refunded to ourself, we then revert the receive of the
+                // origin packet so that the previous hop can safely refund on its side when it
+                // processes the failure acknowledgement.
+                let mut msgs = self.send_tokens_failure(sender, &Default::default(), tokens)?;
+                let common = self.common_mut();
+                msgs.extend(revert_pfm_receive(
+                    common.deps.branch(),
+                    &common.env.contract.address,
+                    &refund_info,
+                )?);
+                (
+                    msgs,
+                    Vec::from_iter((!error.is_empty()).then_some(Attribute::new(
+                        ATTR_ERROR,
+                        Binary::from(error.clone()).to_string(),
+                    ))),
+                    error.to_vec(),
+                )
+            }
         };
 
         let packet_timeout_timestamp: u64 = refund_info
@@ -99,6 +117,20 @@ pub trait TransferProtocolExt<'a>:
             },
         };
 
+        // The acknowledgement is written on the channel the origin packet was received on.
+        let ack_bytes = match fee::forward_relayer(
+            self.common().deps.storage,
+            &refund_info.origin_packet.dest.channel_id,
+            &refund_info.origin_sender_addr,
+        )
+        .map_err(ContractError::from)?
+        {
+            Some(forward_relayer) => {
+                fee::incentivized_ack(ack_bytes.into(), forward_relayer, success).to_vec()
+            }
+            None => ack_bytes,
+        };
+
         let deferred_packet_into = DeferredPacketInfo {
             refund_channel_id: refund_info.origin_packet.dest.channel_id,
             refund_port_id: refund_info.origin_packet.dest.port_id,
@@ -164,6 +196,7 @@ pub trait TransferProtocolExt<'a>:
             timeout: Some(timeout),
             memo,
             fees: forward.fees,
+            relayer_fee: None,
         };
 
         // Send forward message
@@ -189,9 +222,10 @@ pub trait TransferProtocolExt<'a>:
             .iter_mut()
             .find(|sub| sub.id == IBC_SEND_ID)
         {
-            *reply_sub = reply_sub
-                .clone()
-                .with_payload(serde_json_wasm::to_vec(&in_flight_packet).expect("can serialize"));
+            *reply_sub = reply_sub.clone().with_payload(
+                serde_json_wasm::to_vec(&SendPayload::Forward(in_flight_packet))
+                    .expect("can serialize"),
+            );
         } else {
             return Err(
                 ContractError::MiddlewareError(MiddlewareError::PacketForward(
@@ -239,6 +273,58 @@ pub fn hash_denom_str(denom: &str) -> String {
     format!("0x{}", hex::encode(hash_denom(denom)))
 }
 
+/// Decode the tokens of the packet that initiated a forward, using the protocol of the channel it was received on.
+fn in_flight_origin_tokens(
+    in_flight_packet: &InFlightPfmPacket,
+) -> Result<Vec<TransferToken>, ContractError> {
+    let data = in_flight_packet.origin_packet.data.as_slice();
+    match &*in_flight_packet.origin_protocol_version {
+        Ics20Protocol::VERSION => Ok(Ics20Packet::decode_as::<JsonWasm>(data)?.tokens()),
+        Ucs01Protocol::VERSION => {
+            let packet = Ucs01TransferPacket::decode_as::<encoding::EthAbi>(data)?;
+            Ok(packet.tokens().clone())
+        }
+        version => Err(ContractError::UnknownProtocol {
+            channel_id: in_flight_packet.origin_packet.dest.channel_id.clone(),
+            protocol_version: version.into(),
+        }),
+    }
+}
+
+/// Undo the receive of the packet that initiated a forward, after the forward failed or timed out.
+///
+/// Given a route A -> B -> C where we are B, the funds received from A are held by this contract once
+/// the B -> C refund is processed. Wrapped tokens minted on receive are burnt and local tokens
+/// unescrowed on receive are escrowed again, which is exactly sending the tokens back to A.
+fn revert_pfm_receive(
+    deps: DepsMut,
+    contract_address: &Addr,
+    in_flight_packet: &InFlightPfmPacket,
+) -> Result<Vec<CosmosMsg<TokenFactoryMsg>>, ContractError> {
+    let origin_packet = &in_flight_packet.origin_packet;
+    let tokens = in_flight_origin_tokens(in_flight_packet)?
+        .into_iter()
+        .map(|token| {
+            // Renormalize the denom from the POV of A, as expected by `ForTokens::execute`.
+            let denom = match DenomOrigin::from((token.denom.as_str(), &origin_packet.src)) {
+                DenomOrigin::Local { denom } => denom.to_string(),
+                DenomOrigin::Remote { denom } => make_foreign_denom(&origin_packet.dest, denom),
+            };
+            TransferToken {
+                denom,
+                amount: token.amount,
+                // Fees are never cut on a hop, the full amount is held.
+                fee: FeePerU128::zero(),
+            }
+        })
+        .collect();
+    StatefulSendTokens {
+        deps,
+        contract_address: contract_address.to_string(),
+    }
+    .execute(contract_address, &origin_packet.dest, tokens)
+}
+
 pub fn protocol_ordering(version: &str) -> Option<IbcOrder> {
     match version {
         Ics20Protocol::VERSION => Some(Ics20Protocol::ORDERING),
@@ -1,7 +1,8 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{IbcEndpoint, Uint512};
+use cosmwasm_std::{Addr, Coin, Empty, IbcEndpoint, Uint512};
 use cw_controllers::Admin;
 use cw_storage_plus::{Item, KeyDeserialize, Map, Prefixer, PrimaryKey};
+use serde::{Deserialize, Serialize};
 use ucs01_relay_api::middleware::InFlightPfmPacket;
 
 pub const ADMIN: Admin = Admin::new("admin");
@@ -19,6 +20,18 @@ pub const CHANNEL_STATE: Map<(&str, &str), ChannelState> = Map::new("channel_sta
 pub const IN_FLIGHT_PFM_PACKETS: Map<PfmRefundPacketKey, InFlightPfmPacket> =
     Map::new("in_flight_pfm_packets");
 
+/// Relayer fees escrowed for packets sent by this contract, paid once the packet is acknowledged or timed out.
+/// Indexed by the (source channel_id, sequence) of the packet.
+pub const FEE_ESCROWS: Map<(&str, u64), PacketFeeEscrow> = Map::new("fee_escrows");
+
+/// Channels negotiated with the ICS-29 fee version, on which acknowledgements carry the payee of
+/// the relayer that delivered the packet.
+pub const FEE_ENABLED_CHANNELS: Map<&str, Empty> = Map::new("fee_enabled_channels");
+
+/// Address on the counterparty chain paying the recv fee of the packets delivered by a relayer.
+/// Indexed by (channel_id, relayer).
+pub const COUNTERPARTY_PAYEES: Map<(&str, &Addr), String> = Map::new("counterparty_payees");
+
 // TokenFactory limitation
 // MaxSubdenomLength = 44
 // HASH_LENGTH = (MaxSubdenomLength - size_of("0x")) / 2 = 42
@@ -149,3 +162,41 @@ pub struct ChannelInfo {
     /// the protocol version, used to branch on the implementation
     pub protocol_version: String,
 }
+
+/// ICS-29 like relayer incentivization, paid on top of the transferred funds.
+#[cw_serde]
+#[derive(Default)]
+pub struct RelayerFee {
+    /// Fee for relaying the packet to the counterparty.
+    pub recv_fee: Vec<Coin>,
+    /// Fee for relaying the acknowledgement back.
+    pub ack_fee: Vec<Coin>,
+    /// Fee for relaying the timeout, in which case the recv and ack fees are refunded.
+    pub timeout_fee: Vec<Coin>,
+}
+
+impl RelayerFee {
+    pub fn is_empty(&self) -> bool {
+        self.recv_fee.is_empty() && self.ack_fee.is_empty() && self.timeout_fee.is_empty()
+    }
+}
+
+#[cw_serde]
+pub struct PacketFeeEscrow {
+    /// Receives the unused part of the fee, this is the sender of the transfer.
+    pub refund_address: Addr,
+    pub fee: RelayerFee,
+}
+
+/// Payload of the send packet submessage, processed in its reply once the packet sequence is known.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SendPayload {
+    /// The packet forwards a packet received from another chain.
+    Forward(InFlightPfmPacket),
+    /// The relayer fee to escrow for the packet.
+    RelayerFee {
+        channel_id: String,
+        escrow: PacketFeeEscrow,
+    },
+}
@@ -0,0 +1,458 @@
+//! An in memory IBC network of relay contracts, used to test packets going through several chains.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use cosmwasm_std::{
+    from_json,
+    testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage},
+    Addr, AnyMsg, BankMsg, Binary, Coin, CosmosMsg, Env, IbcAcknowledgement, IbcChannel,
+    IbcChannelConnectMsg, IbcEndpoint, IbcMsg, IbcOrder, IbcPacket, IbcPacketAckMsg,
+    IbcPacketReceiveMsg, IbcPacketTimeoutMsg, IbcTimeout, MessageInfo, MsgResponse, Order,
+    OwnedDeps, Reply, ReplyOn, Response, Storage, SubMsg, SubMsgResponse, SubMsgResult, Timestamp,
+    Uint128, WasmMsg,
+};
+use prost::{Message, Name};
+use protos::{cosmwasm::wasm::v1::MsgIbcSendResponse, deferredack::v1beta1::MsgWriteDeferredAck};
+use token_factory_api::TokenFactoryMsg;
+use ucs01_relay_api::protocol::TransferProtocol;
+
+use crate::{
+    contract::{self, instantiate},
+    fee::{FeeChannelVersion, FEE_VERSION},
+    ibc::{ibc_channel_connect, ibc_packet_ack, ibc_packet_receive, ibc_packet_timeout, reply},
+    msg::{ExecuteMsg, InstantiateMsg, TransferMsg},
+    protocol::Ics20Protocol,
+    state::{CHANNEL_INFO, CHANNEL_STATE},
+};
+
+/// A chain running the relay contract, with a minimal bank and token factory.
+///
+/// Messages are executed the way wasmd does: a failing submessage is reverted and reported to the
+/// reply handler if requested, the data returned by a reply overrides the data of the caller.
+pub struct Chain {
+    pub deps: OwnedDeps<MockStorage, MockApi, MockQuerier>,
+    pub env: Env,
+    /// Indexed by (address, denom).
+    balances: BTreeMap<(String, String), u128>,
+    next_sequences: BTreeMap<String, u64>,
+    /// Packets sent and not relayed yet.
+    packets: VecDeque<IbcPacket>,
+    /// Acknowledgements written and not relayed yet, along with the acknowledged packet.
+    acks: VecDeque<(IbcPacket, Binary)>,
+}
+
+struct Snapshot {
+    storage: Vec<(Vec<u8>, Vec<u8>)>,
+    balances: BTreeMap<(String, String), u128>,
+    next_sequences: BTreeMap<String, u64>,
+    packets: usize,
+    acks: usize,
+}
+
+impl Chain {
+    pub fn new() -> Self {
+        let mut chain = Self {
+            deps: mock_dependencies(),
+            env: mock_env(),
+            balances: Default::default(),
+            next_sequences: Default::default(),
+            packets: Default::default(),
+            acks: Default::default(),
+        };
+        let admin = chain.addr("admin");
+        instantiate(
+            chain.deps.as_mut(),
+            chain.env.clone(),
+            MessageInfo {
+                sender: admin.clone(),
+                funds: vec![],
+            },
+            InstantiateMsg {
+                default_timeout: 300,
+                gov_contract: admin.into(),
+                channel: None,
+            },
+        )
+        .unwrap();
+        chain
+    }
+
+    pub fn addr(&self, name: &str) -> Addr {
+        self.deps.api.addr_make(name)
+    }
+
+    pub fn contract(&self) -> Addr {
+        self.env.contract.address.clone()
+    }
+
+    pub fn endpoint(&self, channel_id: &str) -> IbcEndpoint {
+        IbcEndpoint {
+            port_id: format!("wasm.{}", self.contract()),
+            channel_id: channel_id.into(),
+        }
+    }
+
+    pub fn mint(&mut self, address: &Addr, coin: Coin) {
+        *self
+            .balances
+            .entry((address.to_string(), coin.denom))
+            .or_default() += coin.amount.u128();
+    }
+
+    pub fn balance(&self, address: &Addr, denom: &str) -> u128 {
+        self.balances
+            .get(&(address.to_string(), denom.to_string()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn outstanding(&self, channel_id: &str, denom: &str) -> u128 {
+        CHANNEL_STATE
+            .may_load(&self.deps.storage, (channel_id, denom))
+            .unwrap()
+            .map(|state| Uint128::try_from(state.outstanding).unwrap().u128())
+            .unwrap_or_default()
+    }
+
+    pub fn pop_packet(&mut self) -> Option<IbcPacket> {
+        self.packets.pop_front()
+    }
+
+    pub fn pop_ack(&mut self) -> Option<(IbcPacket, Binary)> {
+        self.acks.pop_front()
+    }
+
+    pub fn transfer(
+        &mut self,
+        sender: &Addr,
+        msg: TransferMsg,
+        funds: Vec<Coin>,
+    ) -> Result<(), String> {
+        self.execute(sender, ExecuteMsg::Transfer(msg), funds)
+    }
+
+    pub fn execute(
+        &mut self,
+        sender: &Addr,
+        msg: ExecuteMsg,
+        funds: Vec<Coin>,
+    ) -> Result<(), String> {
+        self.atomically(|chain| {
+            let contract = chain.contract();
+            for coin in &funds {
+                chain.move_funds(sender.as_str(), contract.as_str(), coin)?;
+            }
+            let response = contract::execute(
+                chain.deps.as_mut(),
+                chain.env.clone(),
+                MessageInfo {
+                    sender: sender.clone(),
+                    funds,
+                },
+                msg,
+            )
+            .map_err(|err| err.to_string())?;
+            chain.execute_response(response).map(drop)
+        })
+    }
+
+    /// Receive a packet, the acknowledgement is written unless it is deferred by the contract.
+    pub fn receive(&mut self, packet: IbcPacket) -> Result<(), String> {
+        let relayer = self.addr("relayer");
+        let ack = self.atomically(|chain| {
+            let response = ibc_packet_receive(
+                chain.deps.as_mut(),
+                chain.env.clone(),
+                IbcPacketReceiveMsg::new(packet.clone(), relayer),
+            )
+            .map_err(|err| err.to_string())?;
+            let data = chain.run_submessages(response.messages)?;
+            Ok(data.or(response.acknowledgement))
+        })?;
+        if let Some(ack) = ack {
+            self.acks.push_back((packet, ack));
+        }
+        Ok(())
+    }
+
+    pub fn acknowledge(&mut self, (packet, ack): (IbcPacket, Binary)) -> Result<(), String> {
+        let relayer = self.addr("relayer");
+        self.atomically(|chain| {
+            let response = ibc_packet_ack(
+                chain.deps.as_mut(),
+                chain.env.clone(),
+                IbcPacketAckMsg::new(IbcAcknowledgement::new(ack), packet, relayer),
+            )
+            .map_err(|err| err.to_string())?;
+            chain.run_submessages(response.messages).map(drop)
+        })
+    }
+
+    pub fn timeout(&mut self, packet: IbcPacket) -> Result<(), String> {
+        let relayer = self.addr("relayer");
+        self.atomically(|chain| {
+            let response = ibc_packet_timeout(
+                chain.deps.as_mut(),
+                chain.env.clone(),
+                IbcPacketTimeoutMsg::new(packet, relayer),
+            )
+            .map_err(|err| err.to_string())?;
+            chain.run_submessages(response.messages).map(drop)
+        })
+    }
+
+    fn execute_response(
+        &mut self,
+        response: Response<TokenFactoryMsg>,
+    ) -> Result<Option<Binary>, String> {
+        let data = self.run_submessages(response.messages)?;
+        Ok(data.or(response.data))
+    }
+
+    #[allow(deprecated)]
+    fn run_submessages(
+        &mut self,
+        submessages: Vec<SubMsg<TokenFactoryMsg>>,
+    ) -> Result<Option<Binary>, String> {
+        let mut data = None;
+        for submessage in submessages {
+            let result = match (
+                self.atomically(|chain| chain.dispatch(submessage.msg)),
+                submessage.reply_on,
+            ) {
+                (Ok(msg_responses), ReplyOn::Always | ReplyOn::Success) => {
+                    SubMsgResult::Ok(SubMsgResponse {
+                        events: vec![],
+                        data: None,
+                        msg_responses,
+                    })
+                }
+                (Err(err), ReplyOn::Always | ReplyOn::Error) => SubMsgResult::Err(err),
+                (Ok(_), _) => continue,
+                (Err(err), _) => return Err(err),
+            };
+            let response = reply(
+                self.deps.as_mut(),
+                self.env.clone(),
+                Reply {
+                    id: submessage.id,
+                    payload: submessage.payload,
+                    gas_used: 0,
+                    result,
+                },
+            )
+            .map_err(|err| err.to_string())?;
+            if let Some(reply_data) = self.execute_response(response)? {
+                data = Some(reply_data);
+            }
+        }
+        Ok(data)
+    }
+
+    fn dispatch(&mut self, msg: CosmosMsg<TokenFactoryMsg>) -> Result<Vec<MsgResponse>, String> {
+        let contract = self.contract();
+        match msg {
+            CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                for coin in &amount {
+                    self.move_funds(contract.as_str(), &to_address, coin)?;
+                }
+            }
+            CosmosMsg::Custom(TokenFactoryMsg::MintTokens {
+                denom,
+                amount,
+                mint_to_address,
+            }) => self.mint(&Addr::unchecked(mint_to_address), Coin { denom, amount }),
+            CosmosMsg::Custom(TokenFactoryMsg::BurnTokens {
+                denom,
+                amount,
+                burn_from_address,
+            }) => self.burn(&burn_from_address, &Coin { denom, amount })?,
+            CosmosMsg::Custom(_) => {}
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr,
+                msg,
+                funds,
+            }) => {
+                assert_eq!(
+                    contract_addr,
+                    contract.as_str(),
+                    "only self calls are supported"
+                );
+                assert!(funds.is_empty());
+                let response = contract::execute(
+                    self.deps.as_mut(),
+                    self.env.clone(),
+                    MessageInfo {
+                        sender: contract,
+                        funds,
+                    },
+                    from_json(msg).unwrap(),
+                )
+                .map_err(|err| err.to_string())?;
+                self.execute_response(response)?;
+            }
+            CosmosMsg::Ibc(IbcMsg::SendPacket {
+                channel_id,
+                data,
+                timeout,
+            }) => {
+                let info = CHANNEL_INFO
+                    .load(&self.deps.storage, &channel_id)
+                    .map_err(|err| err.to_string())?;
+                let sequence = self.next_sequences.entry(channel_id).or_default();
+                *sequence += 1;
+                self.packets.push_back(IbcPacket::new(
+                    data,
+                    info.endpoint,
+                    info.counterparty_endpoint,
+                    *sequence,
+                    timeout,
+                ));
+                return Ok(vec![MsgResponse {
+                    type_url: MsgIbcSendResponse::type_url(),
+                    value: MsgIbcSendResponse {
+                        sequence: *sequence,
+                    }
+                    .encode_to_vec()
+                    .into(),
+                }]);
+            }
+            CosmosMsg::Any(AnyMsg { type_url, value })
+                if type_url == MsgWriteDeferredAck::type_url() =>
+            {
+                let msg = MsgWriteDeferredAck::decode(value.as_slice()).unwrap();
+                let info = msg.deferred_packet_info.unwrap();
+                let packet = IbcPacket::new(
+                    info.packet_data,
+                    IbcEndpoint {
+                        port_id: info.packet_src_port_id,
+                        channel_id: info.packet_src_channel_id,
+                    },
+                    IbcEndpoint {
+                        port_id: info.refund_port_id,
+                        channel_id: info.refund_channel_id,
+                    },
+                    info.sequence,
+                    IbcTimeout::with_timestamp(Timestamp::from_nanos(
+                        info.packet_timeout_timestamp,
+                    )),
+                );
+                self.acks.push_back((packet, msg.ack.into()));
+            }
+            msg => panic!("unsupported message: {msg:?}"),
+        }
+        Ok(vec![])
+    }
+
+    fn move_funds(&mut self, from: &str, to: &str, coin: &Coin) -> Result<(), String> {
+        self.burn(from, coin)?;
+        self.mint(&Addr::unchecked(to), coin.clone());
+        Ok(())
+    }
+
+    fn burn(&mut self, address: &str, coin: &Coin) -> Result<(), String> {
+        let balance = self
+            .balances
+            .entry((address.to_string(), coin.denom.clone()))
+            .or_default();
+        match balance.checked_sub(coin.amount.u128()) {
+            Some(remaining) => {
+                *balance = remaining;
+                Ok(())
+            }
+            None => Err(format!(
+                "insufficient funds: {address} has {balance}{}",
+                coin.denom
+            )),
+        }
+    }
+
+    /// Run `f`, reverting every change it made if it fails.
+    fn atomically<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let snapshot = self.snapshot();
+        let result = f(self);
+        if result.is_err() {
+            self.restore(snapshot);
+        }
+        result
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            storage: self
+                .deps
+                .storage
+                .range(None, None, Order::Ascending)
+                .collect(),
+            balances: self.balances.clone(),
+            next_sequences: self.next_sequences.clone(),
+            packets: self.packets.len(),
+            acks: self.acks.len(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        let keys = self
+            .deps
+            .storage
+            .range(None, None, Order::Ascending)
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        for key in keys {
+            self.deps.storage.remove(&key);
+        }
+        for (key, value) in snapshot.storage {
+            self.deps.storage.set(&key, &value);
+        }
+        self.balances = snapshot.balances;
+        self.next_sequences = snapshot.next_sequences;
+        self.packets.truncate(snapshot.packets);
+        self.acks.truncate(snapshot.acks);
+    }
+}
+
+/// Open an ICS20 channel between `a` and `b`.
+pub fn connect(a: &mut Chain, a_channel_id: &str, b: &mut Chain, b_channel_id: &str) {
+    connect_with_version(a, a_channel_id, b, b_channel_id, Ics20Protocol::VERSION);
+}
+
+/// Open an ICS20 channel between `a` and `b`, wrapped by ICS-29.
+pub fn connect_fee_enabled(a: &mut Chain, a_channel_id: &str, b: &mut Chain, b_channel_id: &str) {
+    let version = serde_json_wasm::to_string(&FeeChannelVersion {
+        fee_version: FEE_VERSION.into(),
+        app_version: Ics20Protocol::VERSION.into(),
+    })
+    .unwrap();
+    connect_with_version(a, a_channel_id, b, b_channel_id, &version);
+}
+
+fn connect_with_version(
+    a: &mut Chain,
+    a_channel_id: &str,
+    b: &mut Chain,
+    b_channel_id: &str,
+    version: &str,
+) {
+    let a_endpoint = a.endpoint(a_channel_id);
+    let b_endpoint = b.endpoint(b_channel_id);
+    for (chain, endpoint, counterparty_endpoint) in [
+        (a, a_endpoint.clone(), b_endpoint.clone()),
+        (b, b_endpoint, a_endpoint),
+    ] {
+        ibc_channel_connect(
+            chain.deps.as_mut(),
+            chain.env.clone(),
+            IbcChannelConnectMsg::new_confirm(IbcChannel::new(
+                endpoint,
+                counterparty_endpoint,
+                IbcOrder::Unordered,
+                version,
+                "connection-0",
+            )),
+        )
+        .unwrap();
+    }
+}
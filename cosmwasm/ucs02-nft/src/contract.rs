@@ -115,6 +115,45 @@ pub struct UCS02NonFungibleTokenPacketData {
     pub receiver: String,
     /// Memo to add custom string to the msg
     pub memo: Option<String>,
+    /// Optional URL that points to metadata about the collection.
+    pub class_uri: Option<String>,
+    /// The raw class data of the collection, as sent by ICS721. This
+    /// carries everything we do not map to a dedicated field
+    /// (i.e. [`SgCollectionData::collection_info`] and its royalty info).
+    pub class_data: Option<Binary>,
+    /// Optional opaque data for each token being transferred,
+    /// `tokenData[N]` holds the data of `tokenIds[N]`.
+    pub token_data: Option<Vec<Binary>>,
+}
+
+/// ABI layout of a UCS02 packet, see `NFTPacket` in `evm/contracts/apps/ucs/02-nft/NFT.sol`.
+fn ucs02_packet_abi() -> [ParamType; 12] {
+    [
+        // class_owner
+        ParamType::String,
+        // class_id
+        ParamType::String,
+        // class_name
+        ParamType::String,
+        // class_symbol
+        ParamType::String,
+        // token_ids
+        ParamType::Array(Box::new(ParamType::Uint(256))),
+        // token_uris
+        ParamType::Array(Box::new(ParamType::String)),
+        // sender
+        ParamType::String,
+        // receiver
+        ParamType::String,
+        // memo
+        ParamType::String,
+        // class_uri
+        ParamType::String,
+        // class_data
+        ParamType::Bytes,
+        // token_data
+        ParamType::Array(Box::new(ParamType::Bytes)),
+    ]
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -162,6 +201,9 @@ impl TryFrom<NonFungibleTokenPacketData> for UCS02NonFungibleTokenPacketData {
             sender: value.sender,
             receiver: value.receiver,
             memo: value.memo,
+            class_uri: value.class_uri,
+            class_data: value.class_data,
+            token_data: value.token_data,
         })
     }
 }
@@ -170,8 +212,9 @@ impl From<UCS02NonFungibleTokenPacketData> for NonFungibleTokenPacketData {
     fn from(value: UCS02NonFungibleTokenPacketData) -> Self {
         Self {
             class_id: value.class_id,
-            class_uri: Default::default(),
-            class_data: Some(
+            class_uri: value.class_uri,
+            // Packets originating from EVM don't carry class data, only the collection fields.
+            class_data: Some(value.class_data.unwrap_or_else(|| {
                 to_json_binary(&ics721::state::CollectionData {
                     owner: Some(value.class_owner),
                     contract_info: None,
@@ -179,15 +222,15 @@ impl From<UCS02NonFungibleTokenPacketData> for NonFungibleTokenPacketData {
                     symbol: value.class_symbol,
                     num_tokens: None,
                 })
-                .expect("impossible"),
-            ),
+                .expect("impossible")
+            })),
             token_ids: value
                 .token_ids
                 .into_iter()
                 .map(|token_id| ics721::TokenId::new(token_id.to_string()))
                 .collect(),
             token_uris: value.token_uris,
-            token_data: Default::default(),
+            token_data: value.token_data,
             sender: value.sender,
             receiver: value.receiver,
             memo: value.memo,
@@ -197,23 +240,28 @@ impl From<UCS02NonFungibleTokenPacketData> for NonFungibleTokenPacketData {
 
 impl UCS02NonFungibleTokenPacketData {
     pub fn decode(bz: impl AsRef<[u8]>) -> Result<Self, ContractError> {
-        let values = ethabi::decode(
-            &[
-                ParamType::String,
-                ParamType::String,
-                ParamType::String,
-                ParamType::String,
-                ParamType::Array(Box::new(ParamType::Uint(256))),
-                ParamType::Array(Box::new(ParamType::String)),
-                ParamType::String,
-                ParamType::String,
-                ParamType::String,
-            ],
-            bz.as_ref(),
-        )
-        .map_err(|_| ContractError::EthAbiDecoding)?;
+        let bz = bz.as_ref();
+        let values = match ethabi::decode(&ucs02_packet_abi(), bz) {
+            // A legacy packet may happen to decode with the current layout,
+            // only a canonical round trip proves it was encoded with it.
+            Ok(values) if ethabi::encode(&values) == bz => values,
+            // Packets sent before the class and token metadata were added
+            // only carry the first 9 fields, they must still decode for
+            // their acknowledgement or timeout to refund the tokens.
+            _ => {
+                let mut values = ethabi::decode(&ucs02_packet_abi()[..9], bz)
+                    .map_err(|_| ContractError::EthAbiDecoding)?;
+                values.extend([
+                    Token::String(String::new()),
+                    Token::Bytes(Vec::new()),
+                    Token::Array(Vec::new()),
+                ]);
+                values
+            }
+        };
         match &values[..] {
-            [Token::String(class_owner), Token::String(class_id), Token::String(class_name), Token::String(class_symbol), Token::Array(token_ids), Token::Array(token_uris), Token::String(sender), Token::String(receiver), Token::String(memo)] => {
+            [Token::String(class_owner), Token::String(class_id), Token::String(class_name), Token::String(class_symbol), Token::Array(token_ids), Token::Array(token_uris), Token::String(sender), Token::String(receiver), Token::String(memo), Token::String(class_uri), Token::Bytes(class_data), Token::Array(token_data)] =>
+            {
                 Ok(UCS02NonFungibleTokenPacketData {
                     class_owner: class_owner.into(),
                     class_id: ics721::ClassId::new(class_id),
@@ -227,10 +275,9 @@ impl UCS02NonFungibleTokenPacketData {
                             _ => Err(ContractError::EthAbiDecoding),
                         })
                         .collect::<Result<Vec<_>, _>>()?,
-                    token_uris: if token_uris.is_empty() {
-                        None
-                    } else {
-                        Some(
+                    // Empty values are used on the EVM side to encode the absence of a value.
+                    token_uris: (!token_uris.is_empty())
+                        .then(|| {
                             token_uris
                                 .iter()
                                 .cloned()
@@ -238,16 +285,26 @@ impl UCS02NonFungibleTokenPacketData {
                                     Token::String(token_uri) => Ok(token_uri),
                                     _ => Err(ContractError::EthAbiDecoding),
                                 })
-                                .collect::<Result<Vec<_>, _>>()?,
-                        )
-                    },
+                                .collect::<Result<Vec<_>, _>>()
+                        })
+                        .transpose()?,
                     sender: sender.clone(),
                     receiver: receiver.clone(),
-                    memo: if memo.is_empty() {
-                        None
-                    } else {
-                        Some(memo.clone())
-                    },
+                    memo: (!memo.is_empty()).then(|| memo.clone()),
+                    class_uri: (!class_uri.is_empty()).then(|| class_uri.clone()),
+                    class_data: (!class_data.is_empty()).then(|| class_data.clone().into()),
+                    token_data: (!token_data.is_empty())
+                        .then(|| {
+                            token_data
+                                .iter()
+                                .cloned()
+                                .map(|token_data| match token_data {
+                                    Token::Bytes(token_data) => Ok(token_data.into()),
+                                    _ => Err(ContractError::EthAbiDecoding),
+                                })
+                                .collect::<Result<Vec<_>, _>>()
+                        })
+                        .transpose()?,
                 })
             }
             _ => Err(ContractError::EthAbiDecoding),
@@ -276,10 +333,26 @@ impl UCS02NonFungibleTokenPacketData {
             Token::String(self.sender),
             Token::String(self.receiver),
             Token::String(self.memo.unwrap_or_default()),
+            Token::String(self.class_uri.unwrap_or_default()),
+            Token::Bytes(self.class_data.unwrap_or_default().into()),
+            Token::Array(
+                self.token_data
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|token_data| Token::Bytes(token_data.into()))
+                    .collect(),
+            ),
         ])
     }
 }
 
+/// Decode an EVM encoded UCS02 packet and reencode it in the ICS721 JSON format.
+fn ucs02_to_ics721_packet_data(data: &Binary) -> Result<Binary, ContractError> {
+    Ok(to_json_binary(&NonFungibleTokenPacketData::from(
+        UCS02NonFungibleTokenPacketData::decode(data)?,
+    ))?)
+}
+
 pub fn validate_order_and_version(
     channel: &IbcChannel,
     counterparty_version: Option<&str>,
@@ -424,11 +497,17 @@ pub fn ibc_packet_receive(
         Version::ICS721 => msg,
         Version::UCS02 => {
             // Decode eth abi encoded NFT packet, reencode in JSON
-            let nft_data = NonFungibleTokenPacketData::from(
-                UCS02NonFungibleTokenPacketData::decode(&msg.packet.data).expect("impossible"),
-            );
-            msg.packet.data = to_json_binary(&nft_data).expect("impossible");
-            msg
+            match ucs02_to_ics721_packet_data(&msg.packet.data) {
+                Ok(data) => {
+                    msg.packet.data = data;
+                    msg
+                }
+                Err(error) => {
+                    return Ok(IbcReceiveResponse::new(vec![0u8])
+                        .add_attribute("method", "ibc_packet_receive")
+                        .add_attribute("error", error.to_string()))
+                }
+            }
         }
     };
     // We store the version we are handling to hook the ack on reply
@@ -468,9 +547,7 @@ pub fn ibc_packet_ack(
         Version::ICS721 => msg,
         Version::UCS02 => {
             // Decode eth abi encoded NFT packet, reencode in JSON
-            msg.original_packet.data = to_json_binary(&NonFungibleTokenPacketData::from(
-                UCS02NonFungibleTokenPacketData::decode(&msg.original_packet.data)?,
-            ))?;
+            msg.original_packet.data = ucs02_to_ics721_packet_data(&msg.original_packet.data)?;
             // Decode eth abi encoded ACK, reencode in JSON
             msg.acknowledgement.data = match msg.acknowledgement.data.as_ref() {
                 [0] => ics721::ibc_helpers::ack_fail("evm execution reverted".into()),
@@ -493,7 +570,119 @@ pub fn ibc_packet_ack(
 pub fn ibc_packet_timeout(
     deps: DepsMut,
     env: Env,
-    msg: IbcPacketTimeoutMsg,
-) -> Result<IbcBasicResponse, ics721::ContractError> {
-    ics721_base::state::Ics721Contract::default().ibc_packet_timeout(deps, env, msg)
+    mut msg: IbcPacketTimeoutMsg,
+) -> Result<IbcBasicResponse, ContractError> {
+    let version = CHANNEL_VERSION
+        .load(deps.storage, &msg.packet.src.channel_id)
+        .expect("impossible");
+    if let Version::UCS02 = version {
+        // Decode eth abi encoded NFT packet, reencode in JSON
+        msg.packet.data = ucs02_to_ics721_packet_data(&msg.packet.data)?;
+    }
+    ics721_base::state::Ics721Contract::default()
+        .ibc_packet_timeout(deps, env, msg)
+        .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::{from_json, Decimal, HexBinary};
+    use ics721::NonFungibleTokenPacketData;
+    use unionlabs::uint::U256;
+
+    use super::{SgCollectionData, UCS02NonFungibleTokenPacketData};
+
+    const ICS721_PACKET: &str = include_str!("testdata/ics721-packet.json");
+    const UCS02_PACKET: &str = include_str!("testdata/ucs02-packet.hex");
+
+    fn ucs02_packet() -> Vec<u8> {
+        HexBinary::from_hex(UCS02_PACKET.trim()).unwrap().to_vec()
+    }
+
+    fn ics721_packet() -> NonFungibleTokenPacketData {
+        from_json(ICS721_PACKET).unwrap()
+    }
+
+    #[test]
+    fn ucs02_packet_encode_decode_iso() {
+        let packet = UCS02NonFungibleTokenPacketData::decode(ucs02_packet()).unwrap();
+        assert_eq!(packet.encode(), ucs02_packet());
+    }
+
+    #[test]
+    fn legacy_ucs02_packet_decodes() {
+        // layout of the packets sent before the class and token metadata were added
+        let legacy = ethabi::encode(&[
+            Token::String("stars1owner".into()),
+            Token::String("0xc0ffee".into()),
+            Token::String("Bad Kids".into()),
+            Token::String("BAD".into()),
+            Token::Array(vec![Token::Uint(1u64.into())]),
+            Token::Array(vec![Token::String("ipfs://1".into())]),
+            Token::String("0xsender".into()),
+            Token::String("union1receiver".into()),
+            Token::String(String::new()),
+        ]);
+        let packet = UCS02NonFungibleTokenPacketData::decode(legacy).unwrap();
+        assert_eq!(packet.class_owner, "stars1owner");
+        assert_eq!(packet.class_id, ics721::ClassId::new("0xc0ffee"));
+        assert_eq!(packet.token_ids, vec![U256::from(1u64)]);
+        assert_eq!(packet.token_uris, Some(vec!["ipfs://1".into()]));
+        assert_eq!(packet.receiver, "union1receiver");
+        assert_eq!(packet.memo, None);
+        assert_eq!(packet.class_uri, None);
+        assert_eq!(packet.class_data, None);
+        assert_eq!(packet.token_data, None);
+    }
+
+    #[test]
+    fn ics721_to_ucs02_matches_fixture() {
+        let packet = UCS02NonFungibleTokenPacketData::try_from(ics721_packet()).unwrap();
+        assert_eq!(packet.class_owner, "stars1owner");
+        assert_eq!(packet.class_name, "Bad Kids");
+        assert_eq!(packet.class_symbol, "BAD");
+        assert_eq!(packet.token_ids, vec![U256::from(1u64), U256::MAX]);
+        assert_eq!(packet.encode(), ucs02_packet());
+    }
+
+    #[test]
+    fn ucs02_to_ics721_preserves_metadata() {
+        let packet = NonFungibleTokenPacketData::from(
+            UCS02NonFungibleTokenPacketData::decode(ucs02_packet()).unwrap(),
+        );
+        assert_eq!(packet, ics721_packet());
+
+        let class_data = from_json::<SgCollectionData>(packet.class_data.unwrap()).unwrap();
+        let royalty_info = class_data.collection_info.unwrap().royalty_info.unwrap();
+        assert_eq!(royalty_info.payment_address, "stars1royalty");
+        assert_eq!(royalty_info.share, Decimal::percent(5));
+    }
+
+    #[test]
+    fn evm_originated_packet_builds_class_data() {
+        let packet = NonFungibleTokenPacketData::from(UCS02NonFungibleTokenPacketData {
+            class_owner: String::new(),
+            class_id: ics721::ClassId::new("0xc0ffee"),
+            class_name: "Evm Collection".into(),
+            class_symbol: "EVM".into(),
+            token_ids: vec![U256::from(7u64)],
+            token_uris: None,
+            sender: "0xsender".into(),
+            receiver: "union1receiver".into(),
+            memo: None,
+            class_uri: None,
+            class_data: None,
+            token_data: None,
+        });
+        let class_data =
+            from_json::<ics721::state::CollectionData>(packet.class_data.unwrap()).unwrap();
+        assert_eq!(class_data.name, "Evm Collection");
+        assert_eq!(class_data.symbol, "EVM");
+        assert_eq!(packet.token_data, None);
+
+        // and back, the generated class data is carried as is
+        let packet = UCS02NonFungibleTokenPacketData::try_from(packet).unwrap();
+        assert_eq!(packet.class_name, "Evm Collection");
+        assert!(packet.class_data.is_some());
+    }
 }
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use crate::{
+    log_path,
+    process_compose::{
+        LogConfiguration, Probe, Process, ProcessDependency, RestartPolicy, ShutdownConfig,
+    },
+    Network,
+};
+
+/// The config of the cometbls client created on an EVM network to track Union.
+pub const COMETBLS_CLIENT_CONFIG: &str = r#"'{"client_type":"cometbls"}'"#;
+
+/// The address the Ethereum devnet deploys the IBC handler at, the `ibc_handler_address` of `voyager-config.json`.
+pub const IBC_HANDLER_ADDRESS: &str = "0xed2af2ad7fe0d92011b26a2e5d1b4dc7d12a47c5";
+
+/// geth's HTTP JSON-RPC on the Ethereum devnet.
+pub const ETHEREUM_JSON_RPC_PORT: u16 = 8545;
+
+pub fn anvil_command(port: u16) -> String {
+    format!(
+        "nix shell --inputs-from . foundry -c anvil --host 127.0.0.1 --port {port} --block-time 1"
+    )
+}
+
+/// Probes an EVM node by requesting its block number over JSON-RPC.
+pub fn json_rpc_probe(port: u16) -> Probe {
+    Probe::exec(&format!(
+        r#"curl -sf -X POST -H 'Content-Type: application/json' --data '{{"jsonrpc":"2.0","method":"eth_blockNumber","params":[],"id":1}}' http://127.0.0.1:{port}"#
+    ))
+}
+
+/// Probes the Ethereum devnet, whose IBC handler is deployed by forge once the beacon chain produces blocks. The
+/// devnet is only ready once the handler has code, as clients and connections cannot be created before.
+pub fn ethereum_probe(beacon_port: u16) -> Probe {
+    Probe::exec(&format!(
+        r#"curl -sf http://127.0.0.1:{beacon_port}/eth/v2/beacon/blocks/2 > /dev/null && curl -sf -X POST -H 'Content-Type: application/json' --data '{{"jsonrpc":"2.0","method":"eth_getCode","params":["{IBC_HANDLER_ADDRESS}","latest"],"id":1}}' http://127.0.0.1:{ETHEREUM_JSON_RPC_PORT} | grep -q '"result":"0x[0-9a-fA-F]'"#
+    ))
+}
+
+/// Deploys the IBC handler and apps to `network`, which unlike the Ethereum devnet does not deploy them itself.
+pub fn deploy_process(network: Network) -> Process {
+    let name = format!("{}-deploy", network.network_id());
+    Process {
+        name: name.clone(),
+        disabled: None,
+        is_daemon: None,
+        command: format!("nix run .#eth-deploy-{}-full", network.chain_name()),
+        depends_on: Some(HashMap::from([(
+            network.network_id(),
+            ProcessDependency::healthy(),
+        )])),
+        liveliness_probe: None,
+        readiness_probe: None,
+        log_configuration: LogConfiguration::default(),
+        log_location: log_path(&name),
+        shutdown: ShutdownConfig::default(),
+        availability: Some(RestartPolicy::on_failure(2)),
+    }
+}
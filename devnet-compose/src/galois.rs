@@ -26,16 +26,16 @@ pub fn download_circuit_process() -> Process {
     }
 }
 
-pub fn galoisd_process() -> Process {
+pub fn galoisd_process(port: u16) -> Process {
     let name = "galoisd".to_string();
     Process {
         name: name.clone(),
         disabled: None,
         is_daemon: None,
-        command: format!("nix run .#galoisd -- serve localhost:9999 --cs-path={CIRCUIT_BASE_PATH}r1cs.bin --pk-path={CIRCUIT_BASE_PATH}pk.bin --vk-path={CIRCUIT_BASE_PATH}vk.bin"),
+        command: format!("nix run .#galoisd -- serve localhost:{port} --cs-path={CIRCUIT_BASE_PATH}r1cs.bin --pk-path={CIRCUIT_BASE_PATH}pk.bin --vk-path={CIRCUIT_BASE_PATH}vk.bin"),
         depends_on: Some(HashMap::from([(download_circuit_process().name, ProcessDependency::completed_successfully())])),
         liveliness_probe: None,
-        readiness_probe: Some(Probe::exec("nix run .#galoisd -- query-stats localhost:9999")),
+        readiness_probe: Some(Probe::exec(&format!("nix run .#galoisd -- query-stats localhost:{port}"))),
         log_configuration: LogConfiguration::default(),
         log_location: log_path(&name),
         shutdown: ShutdownConfig::default(),
@@ -1,5 +1,11 @@
-use std::{collections::HashMap, fs};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
+use clap::Parser;
 use cliclack::{intro, multiselect};
 use console::style;
 use itertools::Itertools;
@@ -8,37 +14,75 @@ use process_compose::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::process_compose::RestartPolicy;
+use crate::{
+    process_compose::RestartPolicy,
+    topology::{ChannelConfig, ChannelOrdering, ConnectionConfig, DevnetConfig, NetworkConfig},
+};
 
+mod evm;
 mod galois;
 mod process_compose;
 mod theme;
+mod topology;
 mod voyager;
 
+#[derive(Debug, Parser)]
+struct Args {
+    /// Generate the devnet described by this topology file (TOML or JSON) without prompting.
+    #[arg(long)]
+    config: Option<PathBuf>,
+    #[arg(long, default_value = "process-compose.yml")]
+    output: PathBuf,
+}
+
 const LOGS_BASE_PATH: &str = "./.devnet/logs/";
 
+/// The `validatorCount` of the Cosmos devnets in `networks/devnet.nix`.
+const COSMOS_VALIDATORS: u16 = 4;
+
 pub fn log_path(process_name: &str) -> String {
     format!("{LOGS_BASE_PATH}{process_name}.log")
 }
 
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, strum::Display)]
+#[serde(rename_all = "lowercase")]
 pub enum Network {
     Union,
     Osmosis,
     Stargaze,
     Simd,
+    /// The local Ethereum devnet (geth and lodestar), with the IBC contracts deployed.
+    Ethereum,
+    /// An anvil node standing in for an EVM L2, with the IBC contracts deployed by [`evm::deploy_process`]. It has no
+    /// beacon chain to verify its state with, so it cannot be connected to other networks.
+    Anvil,
 }
 
 impl Network {
     fn to_process(self) -> Process {
+        let (command, readiness_probe) = match self {
+            Network::Ethereum => (
+                "nix run .#devnet-eth".to_string(),
+                evm::ethereum_probe(self.rpc_port()),
+            ),
+            Network::Anvil => (
+                evm::anvil_command(self.rpc_port()),
+                evm::json_rpc_probe(self.rpc_port()),
+            ),
+            _ => (
+                format!("nix run .#{}", self.network_id()),
+                Probe::http_get(self.rpc_port().into(), "/block?height=2"),
+            ),
+        };
+
         Process {
             name: self.network_id().clone(),
-            command: format!("nix run .#{}", self.network_id()),
+            command,
             is_daemon: None,
             disabled: None,
             depends_on: None,
             liveliness_probe: None,
-            readiness_probe: Some(Probe::http_get(self.probe_port(), "/block?height=2")),
+            readiness_probe: Some(readiness_probe),
             log_configuration: LogConfiguration::default(),
             log_location: log_path(&self.network_id()),
             shutdown: ShutdownConfig::default(),
@@ -50,13 +94,45 @@ impl Network {
         format!("devnet-{}", self.to_string().to_lowercase())
     }
 
-    // TODO: Put this in a config file
-    fn probe_port(&self) -> usize {
+    /// The name of the network's chain in `voyager-config.json`.
+    fn chain_name(&self) -> String {
+        format!("{}-devnet", self.to_string().to_lowercase())
+    }
+
+    fn is_evm(&self) -> bool {
+        matches!(self, Network::Ethereum | Network::Anvil)
+    }
+
+    /// The port the network is probed on for readiness: the CometBFT RPC of the first validator of Cosmos networks, the
+    /// beacon API of Ethereum and the JSON-RPC of anvil.
+    fn rpc_port(&self) -> u16 {
+        match self {
+            Network::Ethereum => 9596,
+            Network::Anvil => 8645,
+            _ => 26657 + self.port_increase(),
+        }
+    }
+
+    /// The offset of the ports of a Cosmos network, its `portIncrease` in `networks/devnet.nix`.
+    fn port_increase(&self) -> u16 {
+        match self {
+            Network::Union | Network::Ethereum | Network::Anvil => 0,
+            Network::Stargaze => 100,
+            Network::Osmosis => 200,
+            Network::Simd => 300,
+        }
+    }
+
+    /// The host ports bound by the network's devnet, as set in `networks/devnet.nix`. Each validator of a Cosmos network
+    /// binds its CometBFT RPC, gRPC and REST API on the next port.
+    fn fixed_ports(&self) -> Vec<u16> {
         match self {
-            Network::Union => 26657,
-            Network::Stargaze => 26757,
-            Network::Osmosis => 26857,
-            Network::Simd => 26957,
+            // geth's HTTP, websocket and engine JSON-RPC, and lodestar's beacon API
+            Network::Ethereum => vec![evm::ETHEREUM_JSON_RPC_PORT, 8546, 8551, 9596],
+            Network::Anvil => vec![self.rpc_port()],
+            _ => (0..COSMOS_VALIDATORS)
+                .flat_map(|idx| [26657, 9090, 1317].map(|port| port + self.port_increase() + idx))
+                .collect(),
         }
     }
 
@@ -66,26 +142,26 @@ impl Network {
             self != &Network::Union,
             "Tried to get cometbls client id on union"
         );
-        let cometbls_lightclient_checksum = fs::read_to_string(format!(
-            "./.devnet/homes/{}/code-ids/cometbls_light_client",
+        self.wasm_light_client_config("cometbls_light_client")
+    }
+
+    /// The config of a wasm client on this network, using the light client stored as `light_client` in its genesis.
+    fn wasm_light_client_config(&self, light_client: &str) -> String {
+        let checksum = fs::read_to_string(format!(
+            "./.devnet/homes/{}/code-ids/{light_client}",
             self.to_string().to_lowercase()
         ))
-        .unwrap_or_else(|_| panic!("could not find code-id for cometbls_light_client on {self}"));
+        .unwrap_or_else(|_| panic!("could not find code-id for {light_client} on {self}"));
 
-        let cometbls_lightclient_checksum = cometbls_lightclient_checksum.trim().to_string();
+        let checksum = checksum.trim().to_string();
 
-        format!("'{{\"checksum\":\"0x{cometbls_lightclient_checksum}\"}}'")
+        format!("'{{\"checksum\":\"0x{checksum}\"}}'")
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct DevnetConfig {
-    pub networks: Vec<Network>,
-    pub connections: Vec<(Network, Network)>,
-}
-
-pub fn connection_to_process((net_a, net_b): &(Network, Network)) -> Process {
+pub fn connection_to_process(conn: &ConnectionConfig) -> Process {
     use Network::*;
+    let (net_a, net_b) = (conn.a, conn.b);
     let name = format!(
         "connection-{}-{}",
         net_a.to_string().to_lowercase(),
@@ -93,23 +169,39 @@ pub fn connection_to_process((net_a, net_b): &(Network, Network)) -> Process {
     );
 
     let (client_a_config, client_b_config) = match (net_a, net_b) {
+        (Union, Ethereum) => (
+            Union.wasm_light_client_config("ethereum_light_client_minimal"),
+            evm::COMETBLS_CLIENT_CONFIG.to_string(),
+        ),
+        (Ethereum, Union) => (
+            evm::COMETBLS_CLIENT_CONFIG.to_string(),
+            Union.wasm_light_client_config("ethereum_light_client_minimal"),
+        ),
         (Union, n) => ("null".to_string(), n.cometbls_light_client_config()),
         (n, Union) => (n.cometbls_light_client_config(), "null".to_string()),
         (_, _) => ("null".to_string(), "null".to_string()),
     };
 
+    let channel_args = match &conn.channel {
+        Some(channel) => format!(
+            " --open-channel --port-a {} --port-b {} --channel-version {} --channel-ordering {}",
+            channel.port_a, channel.port_b, channel.version, channel.ordering
+        ),
+        None => String::new(),
+    };
+
     Process {
         name: name.clone(),
         disabled: None,
         is_daemon: Some(true),
-        command: format!("set -o pipefail; nix run .#voy-send-msg -- \"$(nix run -L .#voyager -- -c ./voyager-config.json handshake {} {} --client-a-config {} --client-b-config {} --create-clients --open-connection --connection-ordering unordered --init-fetch)\"",net_a.network_id(), net_b.network_id(), client_a_config, client_b_config ),
+        command: format!("set -o pipefail; nix run .#voy-send-msg -- \"$(nix run -L .#voyager -- -c {} handshake {} {} --client-a-config {} --client-b-config {} --create-clients --open-connection --connection-ordering unordered{} --init-fetch)\"", voyager::CONFIG_PATH, net_a.chain_name(), net_b.chain_name(), client_a_config, client_b_config, channel_args),
 
         log_configuration: LogConfiguration::default(),
         log_location: log_path(&name),
         depends_on: Some(HashMap::from([
-            (net_a.to_process().name,ProcessDependency::healthy()),
-            (net_b.to_process().name,ProcessDependency::healthy()),
-            (voyager::relay_process(&[]).name,ProcessDependency::healthy())
+            (net_a.network_id(),ProcessDependency::healthy()),
+            (net_b.network_id(),ProcessDependency::healthy()),
+            (voyager::RELAY_PROCESS_NAME.to_string(),ProcessDependency::healthy())
         ])),
         liveliness_probe: None,
         readiness_probe: None, // TODO
@@ -123,32 +215,67 @@ impl DevnetConfig {
         let mut project = Project::default();
 
         // Add a devnet for each network
-        for network in self.networks.clone() {
-            project.add_process(network.to_process());
+        for network in &self.networks {
+            project.add_process(network.network.to_process());
+            if network.network == Network::Anvil {
+                project.add_process(evm::deploy_process(network.network));
+            }
         }
 
-        if !self.connections.is_empty() {
+        if self.voyager_enabled() {
             // There are connections, so we need voyager running with applied migrations
             project.add_process(voyager::queue_process());
             project.add_process(voyager::migrations_process());
-            project.add_process(voyager::relay_process(&self.networks));
+            project.add_process(voyager::relay_process(self));
+        }
 
-            if self.networks.contains(&Network::Union) {
-                // There are connections to Union, so we need to prove Union consensus
-                project.add_process(galois::download_circuit_process());
-                project.add_process(galois::galoisd_process());
-            }
+        if self.galois_enabled() {
+            // There are connections to Union, so we need to prove Union consensus
+            project.add_process(galois::download_circuit_process());
+            project.add_process(galois::galoisd_process(self.galois.port));
+        }
 
-            for conn in &self.connections {
-                project.add_process(connection_to_process(conn))
-            }
+        for conn in &self.connections {
+            project.add_process(connection_to_process(conn))
         }
 
         project
     }
 }
 
-fn main() {
+/// Writes the `process-compose.yml` (and the voyager config it uses) for `config`, or reports why the topology is
+/// invalid.
+fn generate(config: &DevnetConfig, output: &Path) -> Result<(), String> {
+    config
+        .validate()
+        .map_err(|errors| errors.iter().map(|err| format!(" - {err}")).join("\n"))?;
+
+    if config.voyager_enabled() {
+        voyager::write_config(config);
+    }
+
+    let project = config.to_process_compose();
+    let project = serde_json::to_string_pretty(&project).expect("failed to serialize project");
+
+    fs::write(output, project).map_err(|err| format!("could not write {}: {err}", output.display()))
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    if let Some(path) = args.config {
+        return match DevnetConfig::read(&path).and_then(|config| generate(&config, &args.output)) {
+            Ok(()) => {
+                println!("Generated {}", args.output.display());
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("Invalid topology {}:\n{err}", path.display());
+                ExitCode::FAILURE
+            }
+        };
+    }
+
     cliclack::set_theme(theme::UnionTheme);
     let _ = cliclack::clear_screen();
 
@@ -159,16 +286,19 @@ fn main() {
         .item(Osmosis, "Osmosis", "")
         .item(Stargaze, "Stargaze", "")
         .item(Simd, "Simd", "")
+        .item(Ethereum, "Ethereum", "geth and lodestar")
+        .item(Anvil, "Anvil", "EVM L2 stand-in, no connections")
         .interact()
         .unwrap();
 
-    let mut connections: Vec<(Network, Network)> = Vec::new();
+    let mut connections: Vec<ConnectionConfig> = Vec::new();
     if networks.len() > 1 {
         let connection_options: Vec<((Network, Network), String, String)> = networks
             .clone()
             .into_iter()
             .combinations(2)
             .map(|mut combo| (combo.remove(0), combo.remove(0)))
+            .filter(|(net_a, net_b)| topology::unsupported_connection(*net_a, *net_b).is_none())
             .map(|combo @ (net_a, net_b)| {
                 (
                     combo,
@@ -184,8 +314,37 @@ fn main() {
         connections = multiselect("Which IBC connections do you want to set up?")
             .items(&connection_options)
             .required(false)
+            .interact()
+            .unwrap()
+            .into_iter()
+            .map(|(a, b)| ConnectionConfig::new(a, b))
+            .collect();
+    }
+
+    for conn in &mut connections {
+        let port_a: String = cliclack::input(format!(
+            "Port on {} to open a channel on {} <-> {} with (leave empty to skip)",
+            conn.a, conn.a, conn.b
+        ))
+        .required(false)
+        .interact()
+        .unwrap();
+        if port_a.is_empty() {
+            continue;
+        }
+        let port_b: String = cliclack::input(format!("Port on {}", conn.b))
             .interact()
             .unwrap();
+        let version: String = cliclack::input("Channel version")
+            .default_input("ucs01-relay-1")
+            .interact()
+            .unwrap();
+        conn.channel = Some(ChannelConfig {
+            port_a,
+            port_b,
+            version,
+            ordering: ChannelOrdering::Unordered,
+        });
     }
 
     let info_text = format!("Tips:\n - Run {} in a second terminal tab to view logs.\n - You can restart single processes in the interface with ctrl+r.\n - You can view the generated process composition at `process-compose.yml` in the repo root.\n - Processes are designed to be overridden for a fast dev feedback cycle.\n   For example, add a `process-compose.override.yml` to the repo root with the following contents\n   to use a cargo debug build of voyager instead of the nix build.", style("`nix run .#devnet-logs`").cyan().bold()) +
@@ -207,14 +366,15 @@ fn main() {
 
     use Network::*;
     let config = DevnetConfig {
-        networks,
+        networks: networks.into_iter().map(NetworkConfig::new).collect(),
         connections,
+        ..Default::default()
     };
 
-    let project = config.to_process_compose();
-    let project = serde_json::to_string_pretty(&project).expect("failed to serialize project");
-
-    fs::write("process-compose.yml", project).expect("failed to write contents");
+    if let Err(err) = generate(&config, &args.output) {
+        cliclack::outro_cancel(format!("Invalid devnet:\n{err}")).unwrap();
+        return ExitCode::FAILURE;
+    }
 
     let answer = cliclack::confirm("Ready to launch the devnet?")
         .initial_value(true)
@@ -227,4 +387,6 @@ fn main() {
         cliclack::outro_cancel("Generated a process-compose.yml but did not start the devnet")
             .unwrap()
     }
+
+    ExitCode::SUCCESS
 }
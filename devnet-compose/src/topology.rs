@@ -0,0 +1,382 @@
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{voyager, Network};
+
+/// The devnet to generate, either built from the interactive prompts or read from a topology file with `--config`.
+///
+/// ```toml
+/// networks = [{ network = "union" }, { network = "osmosis" }]
+///
+/// [[connections]]
+/// a = "union"
+/// b = "osmosis"
+/// channel = { port_a = "wasm.union1...", port_b = "wasm.osmo1...", version = "ucs01-relay-1" }
+///
+/// [voyager]
+/// num_workers = 20
+///
+/// [galois]
+/// port = 9999
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DevnetConfig {
+    pub networks: Vec<NetworkConfig>,
+    #[serde(default)]
+    pub connections: Vec<ConnectionConfig>,
+    #[serde(default)]
+    pub voyager: VoyagerConfig,
+    #[serde(default)]
+    pub galois: GaloisConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkConfig {
+    pub network: Network,
+}
+
+impl NetworkConfig {
+    pub fn new(network: Network) -> Self {
+        Self { network }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ConnectionConfig {
+    pub a: Network,
+    pub b: Network,
+    /// A channel to open on top of the connection.
+    pub channel: Option<ChannelConfig>,
+}
+
+impl ConnectionConfig {
+    pub fn new(a: Network, b: Network) -> Self {
+        Self {
+            a,
+            b,
+            channel: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelConfig {
+    pub port_a: String,
+    pub port_b: String,
+    pub version: String,
+    #[serde(default)]
+    pub ordering: ChannelOrdering,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, strum::Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ChannelOrdering {
+    #[default]
+    Unordered,
+    Ordered,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct VoyagerConfig {
+    /// Overrides `voyager.num_workers` of `voyager-config.json`.
+    pub num_workers: Option<u16>,
+    /// The port voyager serves its health check on.
+    #[serde(default = "default_voyager_port")]
+    pub port: u16,
+}
+
+impl Default for VoyagerConfig {
+    fn default() -> Self {
+        Self {
+            num_workers: None,
+            port: default_voyager_port(),
+        }
+    }
+}
+
+fn default_voyager_port() -> u16 {
+    65534
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GaloisConfig {
+    /// Whether to run galois. By default, it runs if there is a connection to Union.
+    pub enabled: Option<bool>,
+    #[serde(default = "default_galois_port")]
+    pub port: u16,
+}
+
+impl Default for GaloisConfig {
+    fn default() -> Self {
+        Self {
+            enabled: None,
+            port: default_galois_port(),
+        }
+    }
+}
+
+fn default_galois_port() -> u16 {
+    9999
+}
+
+/// The port of the postgres database used as voyager's queue.
+const POSTGRES_PORT: u16 = 5432;
+
+impl DevnetConfig {
+    /// Reads a topology file, as TOML if it has a `.toml` extension and as JSON otherwise.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {err}", path.display()))?;
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&contents).map_err(|err| err.to_string())
+        } else {
+            serde_json::from_str(&contents).map_err(|err| err.to_string())
+        }
+    }
+
+    pub fn voyager_enabled(&self) -> bool {
+        !self.connections.is_empty()
+    }
+
+    pub fn galois_enabled(&self) -> bool {
+        self.galois.enabled.unwrap_or_else(|| {
+            self.connections
+                .iter()
+                .any(|conn| conn.a == Network::Union || conn.b == Network::Union)
+        })
+    }
+
+    pub fn network(&self, network: Network) -> Option<&NetworkConfig> {
+        self.networks.iter().find(|n| n.network == network)
+    }
+
+    /// Checks the topology for mistakes that would only surface once the devnet is running.
+    pub fn validate(&self) -> Result<(), Vec<TopologyError>> {
+        let mut errors = Vec::new();
+
+        for (i, network) in self.networks.iter().enumerate() {
+            if self.networks[..i]
+                .iter()
+                .any(|n| n.network == network.network)
+            {
+                errors.push(TopologyError::DuplicateNetwork(network.network));
+            }
+        }
+
+        let mut ports: Vec<(u16, String)> = self
+            .networks
+            .iter()
+            .flat_map(|n| {
+                n.network
+                    .fixed_ports()
+                    .into_iter()
+                    .map(|port| (port, n.network.network_id()))
+            })
+            .collect();
+        if self.voyager_enabled() {
+            ports.push((POSTGRES_PORT, "voyager-queue".to_owned()));
+            ports.push((self.voyager.port, voyager::RELAY_PROCESS_NAME.to_owned()));
+        }
+        if self.galois_enabled() {
+            ports.push((self.galois.port, "galoisd".to_owned()));
+        }
+        let mut used: HashMap<u16, &str> = HashMap::new();
+        for (port, name) in &ports {
+            if let Some(other) = used.insert(*port, name) {
+                errors.push(TopologyError::PortCollision {
+                    port: *port,
+                    a: other.to_owned(),
+                    b: name.clone(),
+                });
+            }
+        }
+
+        for (i, conn) in self.connections.iter().enumerate() {
+            for network in [conn.a, conn.b] {
+                if self.network(network).is_none() {
+                    errors.push(TopologyError::UnknownNetwork {
+                        connection: (conn.a, conn.b),
+                        network,
+                    });
+                }
+            }
+            if let Some(reason) = unsupported_connection(conn.a, conn.b) {
+                errors.push(TopologyError::UnsupportedConnection {
+                    connection: (conn.a, conn.b),
+                    reason,
+                });
+            }
+            if self.connections[..i].iter().any(|other| {
+                (other.a, other.b) == (conn.a, conn.b) || (other.a, other.b) == (conn.b, conn.a)
+            }) {
+                errors.push(TopologyError::DuplicateConnection((conn.a, conn.b)));
+            }
+            if (conn.a == Network::Union || conn.b == Network::Union)
+                && self.galois.enabled == Some(false)
+            {
+                errors.push(TopologyError::GaloisRequired((conn.a, conn.b)));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Why voyager cannot connect `a` and `b`, if it can't.
+pub fn unsupported_connection(a: Network, b: Network) -> Option<&'static str> {
+    if a == b {
+        Some("a network cannot be connected to itself")
+    } else if a == Network::Anvil || b == Network::Anvil {
+        Some("anvil has no beacon chain to verify its state with")
+    } else if a.is_evm() && b.is_evm() {
+        Some("voyager cannot connect two EVM networks")
+    } else if (a.is_evm() || b.is_evm()) && a != Network::Union && b != Network::Union {
+        Some("EVM networks can only be connected to Union")
+    } else {
+        None
+    }
+}
+
+#[derive(Debug)]
+pub enum TopologyError {
+    DuplicateNetwork(Network),
+    PortCollision {
+        port: u16,
+        a: String,
+        b: String,
+    },
+    UnknownNetwork {
+        connection: (Network, Network),
+        network: Network,
+    },
+    UnsupportedConnection {
+        connection: (Network, Network),
+        reason: &'static str,
+    },
+    DuplicateConnection((Network, Network)),
+    GaloisRequired((Network, Network)),
+}
+
+impl fmt::Display for TopologyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopologyError::DuplicateNetwork(network) => {
+                write!(f, "{network} is listed more than once")
+            }
+            TopologyError::PortCollision { port, a, b } => {
+                write!(f, "port {port} is used by both {a} and {b}")
+            }
+            TopologyError::UnknownNetwork {
+                connection: (a, b),
+                network,
+            } => write!(
+                f,
+                "connection {a} <-> {b} uses {network}, which is not in the networks"
+            ),
+            TopologyError::UnsupportedConnection {
+                connection: (a, b),
+                reason,
+            } => write!(f, "connection {a} <-> {b} is not supported: {reason}"),
+            TopologyError::DuplicateConnection((a, b)) => {
+                write!(f, "connection {a} <-> {b} is listed more than once")
+            }
+            TopologyError::GaloisRequired((a, b)) => write!(
+                f,
+                "connection {a} <-> {b} requires galois to prove Union's consensus, but it is disabled"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_toml_topology() {
+        let config: DevnetConfig = toml::from_str(
+            r#"
+            networks = [{ network = "union" }, { network = "osmosis" }]
+
+            [[connections]]
+            a = "union"
+            b = "osmosis"
+            channel = { port_a = "wasm.union1", port_b = "wasm.osmo1", version = "ucs01-relay-1" }
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.validate().is_ok());
+        assert!(config.voyager_enabled());
+        assert!(config.galois_enabled());
+        assert_eq!(
+            config.network(Network::Union).unwrap().network.rpc_port(),
+            26657
+        );
+    }
+
+    #[test]
+    fn evm_networks_connect_to_union_only() {
+        assert_eq!(
+            unsupported_connection(Network::Union, Network::Ethereum),
+            None
+        );
+        assert_eq!(
+            unsupported_connection(Network::Ethereum, Network::Union),
+            None
+        );
+        assert!(unsupported_connection(Network::Ethereum, Network::Osmosis).is_some());
+        assert!(unsupported_connection(Network::Ethereum, Network::Anvil).is_some());
+        assert!(unsupported_connection(Network::Union, Network::Anvil).is_some());
+    }
+
+    #[test]
+    fn reports_all_errors() {
+        let config = DevnetConfig {
+            networks: vec![
+                NetworkConfig::new(Network::Union),
+                NetworkConfig::new(Network::Osmosis),
+            ],
+            connections: vec![
+                ConnectionConfig::new(Network::Union, Network::Union),
+                ConnectionConfig::new(Network::Union, Network::Simd),
+            ],
+            voyager: VoyagerConfig {
+                // osmosis' second validator
+                port: 26858,
+                ..Default::default()
+            },
+            galois: GaloisConfig {
+                enabled: Some(false),
+                ..Default::default()
+            },
+        };
+
+        let errors = config.validate().unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                TopologyError::PortCollision { port: 26858, .. },
+                TopologyError::UnsupportedConnection { .. },
+                TopologyError::GaloisRequired(_),
+                TopologyError::UnknownNetwork {
+                    network: Network::Simd,
+                    ..
+                },
+                TopologyError::GaloisRequired(_),
+            ]
+        ));
+    }
+}
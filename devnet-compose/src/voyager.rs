@@ -1,11 +1,50 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fs};
+
+use serde_json::Value;
 
 use crate::{
     log_path,
     process_compose::{LogConfiguration, Probe, ProcessDependency, RestartPolicy, ShutdownConfig},
-    Network, Process,
+    topology::DevnetConfig,
+    Process,
 };
 
+/// The voyager config checked into the repo, which the devnet config is derived from.
+const BASE_CONFIG_PATH: &str = "./voyager-config.json";
+pub const CONFIG_PATH: &str = "./.devnet/voyager-config.json";
+pub const RELAY_PROCESS_NAME: &str = "voyager-relay";
+
+/// Writes the voyager config used by the devnet to [`CONFIG_PATH`], overriding the ports and workers of the
+/// [`BASE_CONFIG_PATH`] with the ones of the topology.
+pub fn write_config(config: &DevnetConfig) {
+    let base = fs::read_to_string(BASE_CONFIG_PATH)
+        .unwrap_or_else(|_| panic!("could not read {BASE_CONFIG_PATH}"));
+    let mut voyager_config: Value =
+        serde_json::from_str(&base).unwrap_or_else(|_| panic!("invalid {BASE_CONFIG_PATH}"));
+
+    if let Some(num_workers) = config.voyager.num_workers {
+        voyager_config["voyager"]["num_workers"] = num_workers.into();
+    }
+    voyager_config["voyager"]["laddr"] = format!("0.0.0.0:{}", config.voyager.port).into();
+
+    for network in &config.networks {
+        let Some(chain) = voyager_config["chain"].get_mut(network.network.chain_name()) else {
+            continue;
+        };
+        if chain.get("prover_endpoints").is_some() {
+            chain["prover_endpoints"] =
+                vec![format!("http://localhost:{}", config.galois.port)].into();
+        }
+    }
+
+    fs::create_dir_all("./.devnet").expect("could not create ./.devnet");
+    fs::write(
+        CONFIG_PATH,
+        serde_json::to_string_pretty(&voyager_config).expect("failed to serialize voyager config"),
+    )
+    .unwrap_or_else(|_| panic!("could not write {CONFIG_PATH}"));
+}
+
 pub fn queue_process() -> Process {
     let name = "voyager-queue".to_string();
     Process {
@@ -31,8 +70,7 @@ pub fn migrations_process() -> Process {
         name: name.clone(),
         disabled: None,
         is_daemon: None,
-        command: "RUST_LOG=debug nix run -L .#voyager -- -c ./voyager-config.json run-migrations"
-            .into(),
+        command: format!("RUST_LOG=debug nix run -L .#voyager -- -c {CONFIG_PATH} run-migrations"),
         depends_on: Some(HashMap::from([(
             queue_process().name,
             ProcessDependency::healthy(),
@@ -45,8 +83,8 @@ pub fn migrations_process() -> Process {
         availability: Some(RestartPolicy::on_failure(2)),
     }
 }
-pub fn relay_process(networks: &[Network]) -> Process {
-    let name = "voyager-relay".to_string();
+pub fn relay_process(config: &DevnetConfig) -> Process {
+    let name = RELAY_PROCESS_NAME.to_string();
 
     let mut depends_on = HashMap::from([
         (queue_process().name, ProcessDependency::healthy()),
@@ -56,18 +94,18 @@ pub fn relay_process(networks: &[Network]) -> Process {
         ),
     ]);
 
-    for network in networks {
-        depends_on.insert(network.to_process().name, ProcessDependency::healthy());
+    for network in &config.networks {
+        depends_on.insert(network.network.network_id(), ProcessDependency::healthy());
     }
 
     Process {
         name: name.clone(),
         disabled: None,
         is_daemon: None,
-        command: "RUST_LOG=info nix run -L .#voyager -- -c ./voyager-config.json relay".into(),
+        command: format!("RUST_LOG=info nix run -L .#voyager -- -c {CONFIG_PATH} relay"),
         depends_on: Some(depends_on),
         liveliness_probe: None,
-        readiness_probe: Some(Probe::http_get(65534, "/health")),
+        readiness_probe: Some(Probe::http_get(config.voyager.port.into(), "/health")),
         log_configuration: LogConfiguration::default(),
         log_location: log_path(&name),
         shutdown: ShutdownConfig::default(),
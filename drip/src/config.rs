@@ -0,0 +1,229 @@
+//! The chains drip sends funds on, and the coins it sends on each of them.
+
+use std::{collections::HashMap, fmt};
+
+use chain_utils::{cosmos_sdk::GasConfig, keyring::KeyringConfig};
+use ethers::types::Address;
+use serde::{Deserialize, Serialize};
+use tendermint_rpc::WebSocketClientUrl;
+
+use crate::protection::{ProtectionConfig, SendError};
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFormat::Text => f.write_str("text"),
+            LogFormat::Json => f.write_str("json"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub chains: Vec<ChainConfig>,
+
+    #[serde(default)]
+    pub log_format: LogFormat,
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub bypass_secret: Option<String>,
+    pub max_request_polls: u32,
+    #[serde(default)]
+    pub protection: ProtectionConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainConfig {
+    /// The identifier of the chain in the `send` mutation.
+    pub id: String,
+    #[serde(flatten)]
+    pub ty: ChainType,
+    /// The signers sending the funds, each of them submits one batch at a time.
+    pub keyring: KeyringConfig,
+    pub coins: Vec<CoinConfig>,
+    #[serde(default)]
+    pub ratelimit_seconds: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChainType {
+    Cosmos {
+        ws_url: WebSocketClientUrl,
+        grpc_url: String,
+        gas_config: GasConfig,
+        memo: String,
+    },
+    Ethereum {
+        rpc_url: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoinConfig {
+    /// For EVM chains, the address of an ERC-20 contract, or any other name for the native token.
+    pub denom: String,
+    pub amount: u128,
+    /// Raise an alarm when the balance of a signer drops below this amount.
+    #[serde(default)]
+    pub refill_threshold: Option<u128>,
+    /// The maximum amount sent per day (UTC), across all requesters.
+    #[serde(default)]
+    pub daily_budget: Option<u128>,
+}
+
+/// The chains drip can be asked to send funds on, by id.
+pub struct Chains {
+    pub chains: HashMap<String, ChainInfo>,
+    /// The first chain of the config, sent on when a request doesn't specify a chain.
+    pub default_chain: Option<String>,
+}
+
+pub struct ChainInfo {
+    pub address_format: AddressFormat,
+    pub coins: HashMap<String, CoinConfig>,
+    /// The first coin of the chain in the config, sent when a request doesn't specify a denom.
+    pub default_denom: Option<String>,
+    pub ratelimit_seconds: u32,
+}
+
+impl Chains {
+    /// The chain and coin of a request, as `(chain_id, chain, denom, coin)`. Requests made before
+    /// multi-chain support don't specify them, they default to the first chain of the config and its
+    /// first coin, like the requests migrated by [`crate::db::migrate`].
+    pub fn get(
+        &self,
+        chain_id: Option<String>,
+        denom: Option<String>,
+    ) -> Result<(String, &ChainInfo, String, &CoinConfig), SendError> {
+        let chain_id = chain_id
+            .or_else(|| self.default_chain.clone())
+            .unwrap_or_default();
+        let Some(chain) = self.chains.get(&chain_id) else {
+            return Err(SendError::UnknownChain(chain_id));
+        };
+
+        let denom = denom
+            .or_else(|| chain.default_denom.clone())
+            .unwrap_or_default();
+        let Some(coin) = chain.coins.get(&denom) else {
+            return Err(SendError::UnknownDenom { chain_id, denom });
+        };
+
+        Ok((chain_id, chain, denom, coin))
+    }
+}
+
+pub enum AddressFormat {
+    Bech32(String),
+    Evm,
+}
+
+impl AddressFormat {
+    pub fn validate(&self, address: &str) -> Result<(), String> {
+        match self {
+            AddressFormat::Bech32(prefix) => {
+                match subtle_encoding::bech32::Bech32::lower_case().decode(address) {
+                    Ok((hrp, _bz)) => {
+                        if &hrp != prefix {
+                            return Err(format!(
+                                "incorrect bech32 prefix, expected `{prefix}` but found `{hrp}`"
+                            ));
+                        }
+                    }
+                    Err(err) => return Err(err.to_string()),
+                }
+            }
+            AddressFormat::Evm => {
+                address.parse::<Address>().map_err(|err| err.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coin(denom: &str) -> CoinConfig {
+        CoinConfig {
+            denom: denom.to_owned(),
+            amount: 1,
+            refill_threshold: None,
+            daily_budget: None,
+        }
+    }
+
+    fn chains() -> Chains {
+        let chain = |denoms: &[&str]| ChainInfo {
+            address_format: AddressFormat::Evm,
+            coins: denoms
+                .iter()
+                .map(|denom| (denom.to_string(), coin(denom)))
+                .collect(),
+            default_denom: denoms.first().map(|denom| denom.to_string()),
+            ratelimit_seconds: 0,
+        };
+
+        Chains {
+            chains: [
+                ("union-testnet-8".to_owned(), chain(&["muno", "uatom"])),
+                ("11155111".to_owned(), chain(&["eth"])),
+            ]
+            .into(),
+            default_chain: Some("union-testnet-8".to_owned()),
+        }
+    }
+
+    fn get(chain_id: Option<&str>, denom: Option<&str>) -> Result<(String, String), SendError> {
+        chains()
+            .get(chain_id.map(Into::into), denom.map(Into::into))
+            .map(|(chain_id, _, denom, _)| (chain_id, denom))
+    }
+
+    #[test]
+    fn get_defaults_to_first_chain_and_coin() {
+        assert_eq!(
+            get(None, None).unwrap(),
+            ("union-testnet-8".to_owned(), "muno".to_owned())
+        );
+        assert_eq!(
+            get(None, Some("uatom")).unwrap(),
+            ("union-testnet-8".to_owned(), "uatom".to_owned())
+        );
+        assert_eq!(
+            get(Some("11155111"), None).unwrap(),
+            ("11155111".to_owned(), "eth".to_owned())
+        );
+    }
+
+    #[test]
+    fn get_rejects_unknown_chain_and_denom() {
+        assert!(matches!(
+            get(Some("unknown"), None),
+            Err(SendError::UnknownChain(chain_id)) if chain_id == "unknown"
+        ));
+        assert!(matches!(
+            get(Some("11155111"), Some("muno")),
+            Err(SendError::UnknownDenom { denom, .. }) if denom == "muno"
+        ));
+        assert!(matches!(
+            Chains {
+                chains: HashMap::new(),
+                default_chain: None,
+            }
+            .get(None, None),
+            Err(SendError::UnknownChain(_))
+        ));
+    }
+}
@@ -0,0 +1,206 @@
+//! Sending funds on Cosmos SDK chains, batching the requests into a single `MsgMultiSend`.
+
+use std::collections::BTreeMap;
+
+use chain_utils::{
+    cosmos_sdk::{
+        BroadcastTxCommitError, CosmosKeyring, CosmosSdkChainExt, CosmosSdkChainRpcs, GasConfig,
+        SignedTx,
+    },
+    keyring::{KeyringConfig, KeyringEntry},
+};
+use prost::{Message, Name};
+use tendermint_rpc::{Client, WebSocketClient, WebSocketClientUrl};
+use unionlabs::{hash::H256, signer::CosmosSigner};
+
+use crate::worker::{PendingTx, SendResult};
+
+#[derive(Clone)]
+pub struct Chain {
+    chain_id: String,
+    grpc_url: String,
+    tm_client: WebSocketClient,
+    gas_config: GasConfig,
+    pub keyring: CosmosKeyring,
+}
+
+impl Chain {
+    pub async fn new(
+        grpc_url: String,
+        ws_url: WebSocketClientUrl,
+        gas_config: GasConfig,
+        keyring: KeyringConfig,
+    ) -> Self {
+        let (tm_client, driver) = WebSocketClient::builder(ws_url)
+            .compat_mode(tendermint_rpc::client::CompatMode::V0_37)
+            .build()
+            .await
+            .expect("unable to create tm client");
+
+        tokio::spawn(async move { driver.run().await });
+
+        let chain_id = tm_client
+            .status()
+            .await
+            .expect("unable to fetch status")
+            .node_info
+            .network
+            .to_string();
+
+        let prefix =
+            protos::cosmos::auth::v1beta1::query_client::QueryClient::connect(grpc_url.clone())
+                .await
+                .unwrap()
+                .bech32_prefix(protos::cosmos::auth::v1beta1::Bech32PrefixRequest {})
+                .await
+                .unwrap()
+                .into_inner()
+                .bech32_prefix;
+
+        Self {
+            keyring: CosmosKeyring::new(
+                keyring.name,
+                keyring.keys.into_iter().map(|entry| {
+                    let signer = CosmosSigner::new_from_bytes(
+                        H256::try_from(entry.value()).expect("invalid private key"),
+                        prefix.clone(),
+                    )
+                    .expect("invalid private key");
+
+                    KeyringEntry {
+                        name: entry.name(),
+                        address: signer.to_string(),
+                        signer,
+                    }
+                }),
+            ),
+            tm_client,
+            chain_id,
+            grpc_url,
+            gas_config,
+        }
+    }
+
+    /// Sign a `MultiSend` of `(address, denom, amount)` to the specified addresses.
+    pub async fn sign(
+        &self,
+        signer: &CosmosSigner,
+        to_send: Vec<(String, String, u128)>,
+        memo: String,
+    ) -> Result<SignedTx, BroadcastTxCommitError> {
+        // the sdk requires the coins to be sorted by denom
+        let mut totals = BTreeMap::<&str, u128>::new();
+        for (_, denom, amount) in &to_send {
+            *totals.entry(denom.as_str()).or_default() += amount;
+        }
+
+        let msg = protos::cosmos::bank::v1beta1::MsgMultiSend {
+            // this is required to be one element
+            inputs: vec![protos::cosmos::bank::v1beta1::Input {
+                address: signer.to_string(),
+                coins: totals
+                    .into_iter()
+                    .map(|(denom, amount)| protos::cosmos::base::v1beta1::Coin {
+                        denom: denom.to_owned(),
+                        amount: amount.to_string(),
+                    })
+                    .collect(),
+            }],
+            outputs: to_send
+                .iter()
+                .map(
+                    |(address, denom, amount)| protos::cosmos::bank::v1beta1::Output {
+                        address: address.clone(),
+                        coins: vec![protos::cosmos::base::v1beta1::Coin {
+                            denom: denom.clone(),
+                            amount: amount.to_string(),
+                        }],
+                    },
+                )
+                .collect(),
+        };
+
+        let msg = protos::google::protobuf::Any {
+            type_url: protos::cosmos::bank::v1beta1::MsgMultiSend::type_url(),
+            value: msg.encode_to_vec().into(),
+        };
+
+        self.sign_tx(signer, [msg], memo).await
+    }
+
+    /// Whether `pending` was included. The sequence of the signer is read first: if it is past the sequence of the
+    /// transaction while the transaction is not found, another transaction used the sequence and this one can never
+    /// be included.
+    pub async fn resolve(&self, pending: &PendingTx) -> SendResult {
+        let sequence = self.account_info(&pending.signer).await.sequence;
+
+        match self
+            .tm_client
+            .tx(pending.tx_hash.parse().expect("valid tx hash"), false)
+            .await
+        {
+            Ok(tx) if tx.tx_result.code.is_ok() => SendResult::Sent(pending.tx_hash.clone()),
+            Ok(tx) => SendResult::Failed(format!("transaction failed: {}", tx.tx_result.log)),
+            Err(_) if sequence > pending.nonce => {
+                SendResult::Failed("transaction was not included".to_string())
+            }
+            Err(_) => SendResult::Pending,
+        }
+    }
+
+    /// The balance of `denom` of every signer.
+    pub async fn balances(&self, denom: &str) -> Vec<(String, Result<u128, String>)> {
+        let mut query_client =
+            match protos::cosmos::bank::v1beta1::query_client::QueryClient::connect(
+                self.grpc_url.clone(),
+            )
+            .await
+            {
+                Ok(query_client) => query_client,
+                Err(err) => {
+                    return self
+                        .keyring
+                        .keys()
+                        .map(|(_, address)| (address.clone(), Err(err.to_string())))
+                        .collect()
+                }
+            };
+
+        let mut balances = vec![];
+        for (_, address) in self.keyring.keys() {
+            let balance = query_client
+                .balance(protos::cosmos::bank::v1beta1::QueryBalanceRequest {
+                    address: address.clone(),
+                    denom: denom.to_owned(),
+                })
+                .await
+                .map_err(|err| err.to_string())
+                .and_then(|response| {
+                    response
+                        .into_inner()
+                        .balance
+                        .map_or(Ok(0), |coin| coin.amount.parse().map_err(|_| coin.amount))
+                });
+            balances.push((address.clone(), balance));
+        }
+        balances
+    }
+}
+
+impl CosmosSdkChainRpcs for Chain {
+    fn tm_chain_id(&self) -> String {
+        self.chain_id.clone()
+    }
+
+    fn grpc_url(&self) -> String {
+        self.grpc_url.clone()
+    }
+
+    fn tm_client(&self) -> &WebSocketClient {
+        &self.tm_client
+    }
+
+    fn gas_config(&self) -> &GasConfig {
+        &self.gas_config
+    }
+}
@@ -0,0 +1,170 @@
+//! The requests table, shared by the GraphQL handlers queueing requests and the workers sending them.
+
+use async_sqlite::rusqlite::{params, Connection};
+use tracing::info;
+
+/// Create the requests table, or bring the table of a database created by an older version up to date. Requests of
+/// databases created before multi-chain support are attributed to `legacy_chain`, the first chain (and its first denom)
+/// of the config.
+pub fn migrate(
+    conn: &Connection,
+    legacy_chain: Option<(String, Option<String>)>,
+) -> async_sqlite::rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS requests (
+            id   INTEGER PRIMARY KEY AUTOINCREMENT,
+            chain_id TEXT,
+            denom TEXT,
+            address TEXT NOT NULL,
+            time TEXT,
+            tx_hash TEXT,
+            ip TEXT,
+            subnet TEXT,
+            claimed_at TEXT,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            pending_tx_hash TEXT,
+            pending_signer TEXT,
+            pending_nonce INTEGER
+        )",
+        (), // empty list of parameters.
+    )?;
+
+    // databases created before multi-chain support only track requests for a single chain and denom.
+    if !has_column(conn, "chain_id")? {
+        info!("migrating requests to multi-chain");
+        conn.execute("ALTER TABLE requests ADD COLUMN chain_id TEXT", ())?;
+        conn.execute("ALTER TABLE requests ADD COLUMN denom TEXT", ())?;
+        if let Some((chain_id, denom)) = legacy_chain {
+            conn.execute(
+                "UPDATE requests SET chain_id = ?1, denom = ?2",
+                params![chain_id, denom],
+            )?;
+        }
+    }
+
+    // the client address is tracked for the per-ip and per-subnet rate limits.
+    if !has_column(conn, "ip")? {
+        info!("migrating requests to client address tracking");
+        conn.execute("ALTER TABLE requests ADD COLUMN ip TEXT", ())?;
+        conn.execute("ALTER TABLE requests ADD COLUMN subnet TEXT", ())?;
+    }
+
+    // requests are claimed by a batch while in flight, and requeued on failure.
+    if !has_column(conn, "claimed_at")? {
+        info!("migrating requests to in-flight tracking");
+        conn.execute("ALTER TABLE requests ADD COLUMN claimed_at TEXT", ())?;
+        conn.execute(
+            "ALTER TABLE requests ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0",
+            (),
+        )?;
+    }
+
+    // the transaction of a batch is stored before it is broadcast, to check its inclusion if the batch is interrupted.
+    if !has_column(conn, "pending_tx_hash")? {
+        info!("migrating requests to pending transaction tracking");
+        conn.execute("ALTER TABLE requests ADD COLUMN pending_tx_hash TEXT", ())?;
+        conn.execute("ALTER TABLE requests ADD COLUMN pending_signer TEXT", ())?;
+        conn.execute("ALTER TABLE requests ADD COLUMN pending_nonce INTEGER", ())?;
+    }
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS requests_chain_id_tx_hash ON requests (chain_id, tx_hash)",
+        (),
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS requests_ip_time ON requests (ip, time)",
+        (),
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS requests_subnet_time ON requests (subnet, time)",
+        (),
+    )?;
+    Ok(())
+}
+
+/// Whether the requests table has the column `name`, which is not the case for databases created by older versions.
+fn has_column(conn: &Connection, name: &str) -> async_sqlite::rusqlite::Result<bool> {
+    conn.prepare("SELECT 1 FROM pragma_table_info('requests') WHERE name = ?1")?
+        .exists([name])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLUMNS: [&str; 11] = [
+        "chain_id",
+        "denom",
+        "address",
+        "ip",
+        "subnet",
+        "claimed_at",
+        "attempts",
+        "pending_tx_hash",
+        "pending_signer",
+        "pending_nonce",
+        "tx_hash",
+    ];
+
+    #[test]
+    fn migrate_creates_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn, None).unwrap();
+
+        for column in COLUMNS {
+            assert!(has_column(&conn, column).unwrap(), "missing {column}");
+        }
+        assert!(!has_column(&conn, "unknown").unwrap());
+    }
+
+    #[test]
+    fn migrate_legacy_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        // the table of databases created before multi-chain support
+        conn.execute_batch(
+            "CREATE TABLE requests (
+                id   INTEGER PRIMARY KEY AUTOINCREMENT,
+                address TEXT NOT NULL,
+                time TEXT,
+                tx_hash TEXT
+            );
+            INSERT INTO requests (address, time, tx_hash) VALUES ('union1abc', '2024-01-01 00:00:00', 'ABCD');",
+        )
+        .unwrap();
+
+        let legacy_chain = Some(("union-testnet-8".to_owned(), Some("muno".to_owned())));
+        migrate(&conn, legacy_chain.clone()).unwrap();
+        // migrating an up to date table changes nothing
+        migrate(&conn, legacy_chain).unwrap();
+
+        for column in COLUMNS {
+            assert!(has_column(&conn, column).unwrap(), "missing {column}");
+        }
+
+        let request: (String, String, String, u32, Option<String>) = conn
+            .query_row(
+                "SELECT chain_id, denom, tx_hash, attempts, claimed_at FROM requests",
+                (),
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            request,
+            (
+                "union-testnet-8".to_owned(),
+                "muno".to_owned(),
+                "ABCD".to_owned(),
+                0,
+                None
+            )
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Sending funds on EVM chains, with one transaction per request.
+
+use std::sync::Arc;
+
+use chain_utils::keyring::{ConcurrentKeyring, KeyringConfig, KeyringEntry};
+use ethers::{
+    middleware::{signer::SignerMiddlewareError, Middleware, SignerMiddleware},
+    providers::{Http, Provider, ProviderError},
+    signers::{LocalWallet, Signer, WalletError},
+    types::{
+        transaction::eip2718::TypedTransaction, Address, BlockNumber, Bytes, TransactionReceipt,
+        TransactionRequest, U256,
+    },
+};
+use tracing::{info, warn};
+use unionlabs::ErrorReporter;
+
+use crate::worker::{PendingTx, SendResult};
+
+pub type EthereumSignerMiddleware = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+#[derive(Clone)]
+pub struct EthereumChain {
+    provider: Provider<Http>,
+    pub keyring: ConcurrentKeyring<Address, Arc<EthereumSignerMiddleware>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum EthereumSendError {
+    #[error("error preparing transaction")]
+    Signer(#[from] SignerMiddlewareError<Provider<Http>, LocalWallet>),
+    #[error("error signing transaction")]
+    Wallet(#[from] WalletError),
+    #[error("transaction was rejected")]
+    Rejected(#[source] ProviderError),
+    #[error("error broadcasting transaction")]
+    Provider(#[from] ProviderError),
+}
+
+impl EthereumChain {
+    pub async fn new(rpc_url: String, keyring: KeyringConfig) -> Self {
+        let provider = Provider::<Http>::try_from(rpc_url).expect("invalid rpc url");
+
+        let chain_id = provider
+            .get_chainid()
+            .await
+            .expect("unable to fetch chain id")
+            .as_u64();
+
+        let keyring = ConcurrentKeyring::new(
+            keyring.name,
+            keyring.keys.into_iter().map(|entry| {
+                let wallet = LocalWallet::from_bytes(&entry.value())
+                    .expect("invalid private key")
+                    .with_chain_id(chain_id);
+
+                KeyringEntry {
+                    name: entry.name(),
+                    address: wallet.address(),
+                    signer: Arc::new(SignerMiddleware::new(provider.clone(), wallet)),
+                }
+            }),
+        );
+
+        Self { provider, keyring }
+    }
+
+    /// The next nonce of `signer`, counting its transactions in the mempool.
+    pub async fn next_nonce(
+        &self,
+        signer: &Arc<EthereumSignerMiddleware>,
+    ) -> Result<U256, ProviderError> {
+        self.provider
+            .get_transaction_count(signer.address(), Some(BlockNumber::Pending.into()))
+            .await
+    }
+
+    /// Sign a transfer of `amount` of `denom` to `to` with `nonce`, either as an ERC-20 transfer if `denom` is a
+    /// contract address, or in the native token.
+    pub async fn sign(
+        &self,
+        signer: &Arc<EthereumSignerMiddleware>,
+        to: Address,
+        denom: &str,
+        amount: u128,
+        nonce: U256,
+    ) -> Result<(Bytes, PendingTx), EthereumSendError> {
+        let mut tx: TypedTransaction = match denom.parse::<Address>() {
+            Ok(token) => {
+                contracts::erc20::ERC20::new(token, signer.clone())
+                    .transfer(to, amount.into())
+                    .tx
+            }
+            Err(_) => TransactionRequest::pay(to, amount).into(),
+        };
+        tx.set_nonce(nonce);
+        signer.fill_transaction(&mut tx, None).await?;
+
+        let signature = signer.signer().sign_transaction(&tx).await?;
+        let pending = PendingTx {
+            tx_hash: format!("{:?}", tx.hash(&signature)),
+            signer: format!("{:?}", signer.address()),
+            nonce: tx.nonce().expect("nonce is filled").as_u64(),
+        };
+
+        Ok((tx.rlp_signed(&signature), pending))
+    }
+
+    /// Broadcast a signed transaction, without waiting for its inclusion.
+    pub async fn broadcast(&self, raw: Bytes) -> Result<(), EthereumSendError> {
+        match self.provider.send_raw_transaction(raw).await {
+            Ok(_) => Ok(()),
+            // the node answered with an error, the transaction did not enter the mempool
+            Err(ProviderError::JsonRpcClientError(e)) if e.as_error_response().is_some() => Err(
+                EthereumSendError::Rejected(ProviderError::JsonRpcClientError(e)),
+            ),
+            Err(err) => Err(EthereumSendError::Provider(err)),
+        }
+    }
+
+    /// The receipt of `tx_hash`, if it was included.
+    pub async fn receipt(
+        &self,
+        tx_hash: &str,
+    ) -> Result<Option<TransactionReceipt>, ProviderError> {
+        self.provider
+            .get_transaction_receipt(
+                tx_hash
+                    .parse::<ethers::types::H256>()
+                    .expect("valid tx hash"),
+            )
+            .await
+    }
+
+    /// The result of a transfer of `denom` included with `receipt`.
+    pub fn receipt_result(&self, denom: &str, receipt: TransactionReceipt) -> SendResult {
+        if receipt.status != Some(1.into()) {
+            warn!(tx_hash = ?receipt.transaction_hash, denom, "transfer reverted");
+            return SendResult::Failed("transaction reverted".to_string());
+        }
+
+        info!(
+            tx_hash = ?receipt.transaction_hash,
+            gas_used = ?receipt.gas_used,
+            denom,
+            "submitted transfer"
+        );
+
+        SendResult::Sent(format!("{:?}", receipt.transaction_hash))
+    }
+
+    /// Whether `pending` was included. The nonce of the signer is read first: if it is past the nonce of the
+    /// transaction while there is no receipt for it, another transaction used the nonce and this one can never be
+    /// included.
+    pub async fn resolve(&self, pending: &PendingTx) -> SendResult {
+        let signer = pending
+            .signer
+            .parse::<Address>()
+            .expect("valid signer address");
+        let nonce = match self
+            .provider
+            .get_transaction_count(signer, Some(BlockNumber::Latest.into()))
+            .await
+        {
+            Ok(nonce) => nonce,
+            Err(err) => {
+                warn!(err = %ErrorReporter(&err), "unable to fetch the signer nonce");
+                return SendResult::Pending;
+            }
+        };
+
+        match self.receipt(&pending.tx_hash).await {
+            Ok(Some(receipt)) if receipt.status == Some(1.into()) => {
+                SendResult::Sent(pending.tx_hash.clone())
+            }
+            Ok(Some(_)) => SendResult::Failed("transaction reverted".to_string()),
+            Ok(None) if nonce > pending.nonce.into() => {
+                SendResult::Failed("transaction was not included".to_string())
+            }
+            Ok(None) => SendResult::Pending,
+            Err(err) => {
+                warn!(err = %ErrorReporter(&err), "unable to fetch the transaction receipt");
+                SendResult::Pending
+            }
+        }
+    }
+
+    /// The balance of `denom` of every signer.
+    pub async fn balances(&self, denom: &str) -> Vec<(String, Result<u128, String>)> {
+        let mut balances = vec![];
+        for (_, &address) in self.keyring.keys() {
+            let balance = match denom.parse::<Address>() {
+                Ok(token) => contracts::erc20::ERC20::new(token, Arc::new(self.provider.clone()))
+                    .balance_of(address)
+                    .call()
+                    .await
+                    .map_err(|err| err.to_string()),
+                Err(_) => self
+                    .provider
+                    .get_balance(address, None)
+                    .await
+                    .map_err(|err| err.to_string()),
+            };
+            balances.push((
+                format!("{address:?}"),
+                balance.map(|balance| balance.min(U256::from(u128::MAX)).as_u128()),
+            ));
+        }
+        balances
+    }
+}
@@ -0,0 +1,501 @@
+//! The GraphQL API of drip, queueing the requests checked against the limits of their chain and
+//! the protections.
+
+use std::{net::SocketAddr, sync::Arc};
+
+use async_graphql::{http::GraphiQLSource, *};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use async_sqlite::{
+    rusqlite::{params, Connection, OptionalExtension, Transaction, TransactionBehavior},
+    Pool,
+};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+    response::{self, IntoResponse},
+};
+use chrono::{NaiveDateTime, Utc};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::Chains,
+    protection::{
+        Challenge, ClientIp, ProofOfWork, ProofOfWorkSolution, ProtectionConfig, RateLimit,
+        SendError,
+    },
+};
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+pub struct MaxRequestPolls(pub u32);
+pub struct CaptchaBypassSecret(pub String);
+
+pub struct Mutation;
+
+#[derive(Debug)]
+pub struct CaptchaSecret(pub String);
+
+#[Object]
+impl Mutation {
+    /// Request `denom` on `chainId` for `toAddress`, returning the hash of the transaction sending it. Unless captchas are
+    /// disabled, either a `captchaToken` or a solved `proofOfWork` challenge is required. `chainId` and `denom` default to
+    /// the first chain of the config and its first coin.
+    ///
+    /// Rejected requests return an error with a `code` extension, such as `RATELIMITED` or `BUDGET_EXHAUSTED`.
+    async fn send<'ctx>(
+        &self,
+        ctx: &Context<'ctx>,
+        captcha_token: Option<String>,
+        proof_of_work: Option<ProofOfWorkSolution>,
+        chain_id: Option<String>,
+        denom: Option<String>,
+        to_address: String,
+    ) -> Result<String> {
+        let secret = ctx.data::<Option<CaptchaSecret>>().unwrap();
+        let bypass_secret = ctx.data::<Option<CaptchaBypassSecret>>().unwrap();
+        let max_request_polls = ctx.data::<MaxRequestPolls>().unwrap();
+        let chains = ctx.data::<Chains>().unwrap();
+        let protection = ctx.data::<ProtectionConfig>().unwrap();
+        let pow = ctx.data::<Option<ProofOfWork>>().unwrap();
+        let client_ip = ctx.data::<ClientIp>().unwrap();
+
+        let (chain_id, chain, denom, coin) =
+            chains.get(chain_id, denom).map_err(|err| err.extend())?;
+
+        chain
+            .address_format
+            .validate(&to_address)
+            .map_err(|err| SendError::InvalidAddress(err).extend())?;
+
+        if protection.is_denylisted(&to_address) {
+            info!(%to_address, "denylisted");
+            return Err(SendError::Denylisted.extend());
+        }
+
+        let allow_bypass = captcha_token.as_ref().is_some_and(|captcha_token| {
+            bypass_secret
+                .as_ref()
+                .is_some_and(|CaptchaBypassSecret(secret)| secret == captcha_token)
+        });
+
+        if !allow_bypass {
+            match (&proof_of_work, pow, &captcha_token, secret) {
+                (Some(solution), Some(pow), _, _) => {
+                    if !pow.verify(solution, &to_address) {
+                        return Err(SendError::InvalidProofOfWork.extend());
+                    }
+                }
+                (Some(_), None, _, _) => return Err(SendError::ProofOfWorkDisabled.extend()),
+                (None, _, Some(captcha_token), Some(secret)) => {
+                    recaptcha_verify::verify(&secret.0, captcha_token, None)
+                        .await
+                        .map_err(|err| SendError::InvalidCaptcha(format!("{:?}", err)).extend())?;
+                }
+                (None, _, None, Some(_)) => return Err(SendError::ChallengeRequired.extend()),
+                (None, _, _, None) => {}
+            }
+        }
+
+        let db = ctx.data::<Pool>().unwrap();
+
+        // allowlisted addresses are exempt from all rate limits, but not from the daily budget
+        let limits = if protection.is_allowlisted(&to_address) {
+            Limits {
+                daily_budget: coin.daily_budget,
+                amount: coin.amount,
+                ..Default::default()
+            }
+        } else {
+            Limits {
+                address_ratelimit_seconds: Some(chain.ratelimit_seconds),
+                ip_ratelimit: protection.ip_ratelimit.clone(),
+                subnet_ratelimit: protection.subnet_ratelimit.clone(),
+                daily_budget: coin.daily_budget,
+                amount: coin.amount,
+            }
+        };
+        let request = NewRequest {
+            chain_id,
+            denom,
+            address: to_address,
+            ip: client_ip.0.to_string(),
+            subnet: client_ip.subnet(),
+        };
+        let id = db
+            .conn(move |conn| queue_request(conn, &request, &limits))
+            .await?
+            .map_err(|err| err.extend())?;
+
+        let mut counter = 0;
+        let tx_hash = loop {
+            let tx_hash: Option<String> = db
+                .conn(move |conn| {
+                    conn.query_row(
+                        "SELECT tx_hash FROM requests WHERE id=? ORDER BY time DESC LIMIT 1",
+                        [&id],
+                        |row| row.get(0),
+                    )
+                })
+                .await?;
+
+            if let Some(tx_hash) = tx_hash {
+                break tx_hash;
+            } else {
+                if counter > max_request_polls.0 {
+                    return Err(SendError::Queued { id }.extend());
+                }
+                counter += 1;
+                debug!(counter, "no response yet, trying again");
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+        };
+
+        match tx_hash.strip_prefix("ERROR: ") {
+            Some(err) => Err(SendError::Failed(err.to_owned()).extend()),
+            None => Ok(tx_hash),
+        }
+    }
+}
+
+/// A request to be queued by `queue_request`.
+struct NewRequest {
+    chain_id: String,
+    denom: String,
+    address: String,
+    ip: String,
+    subnet: String,
+}
+
+/// The limits a request is checked against before being queued.
+#[derive(Default)]
+struct Limits {
+    /// The cooldown of the address on the chain.
+    address_ratelimit_seconds: Option<u32>,
+    ip_ratelimit: Option<RateLimit>,
+    subnet_ratelimit: Option<RateLimit>,
+    /// The maximum amount sent per day, in requests of `amount`.
+    daily_budget: Option<u128>,
+    amount: u128,
+}
+
+/// Check `request` against the `limits` and queue it, returning its id. The checks and the insert run in a single
+/// immediate transaction, which takes the write lock up front, so that concurrent requests can't all pass the checks
+/// before any of them is queued.
+fn queue_request(
+    conn: &Connection,
+    request: &NewRequest,
+    limits: &Limits,
+) -> async_sqlite::rusqlite::Result<Result<i64, SendError>> {
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+
+    if let Err(err) = check_limits(&tx, request, limits)? {
+        // dropping the transaction rolls it back
+        return Ok(Err(err));
+    }
+
+    let id = tx
+        .prepare_cached(
+            "INSERT INTO requests (chain_id, denom, address, ip, subnet, time) VALUES (?, ?, ?, ?, ?, datetime('now')) RETURNING id",
+        )?
+        .query_row(
+            [
+                &request.chain_id,
+                &request.denom,
+                &request.address,
+                &request.ip,
+                &request.subnet,
+            ],
+            |row| row.get(0),
+        )?;
+    tx.commit()?;
+
+    Ok(Ok(id))
+}
+
+/// Check `request` against the `limits`, returning the error to reject it with.
+fn check_limits(
+    conn: &Connection,
+    request: &NewRequest,
+    limits: &Limits,
+) -> async_sqlite::rusqlite::Result<Result<(), SendError>> {
+    let NewRequest {
+        chain_id,
+        denom,
+        address: to_address,
+        ..
+    } = request;
+
+    if let Some(ratelimit_seconds) = limits.address_ratelimit_seconds {
+        let last_request_ts: Option<String> = conn
+            .prepare_cached(
+                "select time from requests where chain_id = ?1 and denom = ?2 and address = ?3 order by id desc limit 1",
+            )?
+            .query_row([chain_id, denom, to_address], |row| row.get(0))
+            .optional()?;
+
+        match last_request_ts {
+            Some(ts) => {
+                let ts = NaiveDateTime::parse_from_str(&ts, DATETIME_FORMAT)
+                    .expect("invalid datetime present in database");
+
+                let now = Utc::now().naive_utc();
+
+                let delta = now - ts;
+                if delta.num_seconds() < 0 {
+                    error!(%now, %ts, %delta, "timestamp in the future?");
+                }
+                if delta.num_seconds() < ratelimit_seconds.into() {
+                    info!(
+                        %to_address,
+                        %chain_id,
+                        %denom,
+                        %delta,
+                        %ratelimit_seconds,
+                        "ratelimited"
+                    );
+
+                    return Ok(Err(SendError::Ratelimited {
+                        scope: "address",
+                        retry_after_seconds: i64::from(ratelimit_seconds) - delta.num_seconds(),
+                    }));
+                }
+            }
+            None => {
+                info!(%to_address, %chain_id, %denom, "new user");
+            }
+        }
+    }
+
+    if let Some(limit) = &limits.ip_ratelimit {
+        if let Err(err) = check_ratelimit(conn, "ip", &request.ip, limit)? {
+            return Ok(Err(err));
+        }
+    }
+    if let Some(limit) = &limits.subnet_ratelimit {
+        if let Err(err) = check_ratelimit(conn, "subnet", &request.subnet, limit)? {
+            return Ok(Err(err));
+        }
+    }
+
+    if let Some(daily_budget) = limits.daily_budget {
+        let sent_today: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM requests
+            WHERE chain_id = ?1 AND denom = ?2 AND time >= date('now')
+            AND (tx_hash IS NULL OR tx_hash NOT LIKE 'ERROR%')",
+            [chain_id, denom],
+            |row| row.get(0),
+        )?;
+
+        if (sent_today as u128 + 1) * limits.amount > daily_budget {
+            warn!(%chain_id, %denom, sent_today, daily_budget, "daily budget exhausted");
+            return Ok(Err(SendError::BudgetExhausted {
+                denom: denom.clone(),
+            }));
+        }
+    }
+
+    Ok(Ok(()))
+}
+
+/// Reject the request if `limit.max_requests` were already made from the `scope` (either `ip` or `subnet`) of the client
+/// within the window of the limit.
+fn check_ratelimit(
+    conn: &Connection,
+    scope: &'static str,
+    value: &str,
+    limit: &RateLimit,
+) -> async_sqlite::rusqlite::Result<Result<(), SendError>> {
+    let window_seconds = limit.window_seconds;
+    let (count, oldest): (u32, Option<String>) = conn.query_row(
+        &format!(
+            "SELECT COUNT(*), MIN(time) FROM requests WHERE {scope} = ?1 AND time > datetime('now', ?2)"
+        ),
+        params![value, format!("-{window_seconds} seconds")],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    if count < limit.max_requests {
+        return Ok(Ok(()));
+    }
+
+    let elapsed = oldest
+        .map(|oldest| {
+            let oldest = NaiveDateTime::parse_from_str(&oldest, DATETIME_FORMAT)
+                .expect("invalid datetime present in database");
+            (Utc::now().naive_utc() - oldest).num_seconds()
+        })
+        .unwrap_or_default();
+
+    info!(scope, count, "ratelimited");
+    Ok(Err(SendError::Ratelimited {
+        scope,
+        retry_after_seconds: i64::from(window_seconds) - elapsed,
+    }))
+}
+
+#[derive(SimpleObject)]
+struct Request {
+    id: i64,
+    chain_id: Option<String>,
+    denom: Option<String>,
+    address: String,
+    time: String,
+    tx_hash: Option<String>,
+}
+
+pub struct MaxPaginatedResponses(pub i32);
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// A proof of work challenge, which can be solved instead of a captcha.
+    async fn proof_of_work_challenge<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Challenge> {
+        match ctx.data::<Option<ProofOfWork>>().unwrap() {
+            Some(pow) => pow.challenge().map_err(|err| err.extend()),
+            None => Err(SendError::ProofOfWorkDisabled.extend()),
+        }
+    }
+
+    async fn handled_transfers<'ctx>(
+        &self,
+        ctx: &Context<'ctx>,
+        chain_id: Option<String>,
+        limit: Option<i32>,
+        offset_time: Option<String>,
+    ) -> FieldResult<Vec<Request>> {
+        let db = ctx.data::<Pool>().unwrap();
+        let max_paginated_responses = ctx.data::<MaxPaginatedResponses>().unwrap().0;
+        let limit = limit.unwrap_or(10).min(max_paginated_responses);
+        let offset_time = offset_time
+            .unwrap_or_else(|| Utc::now().naive_utc().format(DATETIME_FORMAT).to_string());
+        let requests: Vec<Request> = db
+            .conn(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, chain_id, denom, address, time, tx_hash
+                    FROM requests
+                    WHERE tx_hash IS NOT NULL
+                    AND tx_hash NOT LIKE 'ERROR%'
+                    AND (?1 IS NULL OR chain_id = ?1)
+                    AND time < ?2
+                    ORDER BY time DESC
+                    LIMIT ?3",
+                )?;
+                let rows =
+                    stmt.query_map(params![chain_id, offset_time, limit], Request::from_row)?;
+                let requests: Result<Vec<_>, _> = rows.collect();
+                requests
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(requests)
+    }
+
+    async fn transfers_for_address<'ctx>(
+        &self,
+        ctx: &Context<'ctx>,
+        address: String,
+        chain_id: Option<String>,
+        limit: Option<i32>,
+        offset_time: Option<String>,
+    ) -> FieldResult<Vec<Request>> {
+        let db = ctx.data::<Pool>().unwrap();
+        let max_paginated_responses = ctx.data::<MaxPaginatedResponses>().unwrap().0;
+        let limit = limit.unwrap_or(10).min(max_paginated_responses);
+        let offset_time = offset_time
+            .unwrap_or_else(|| Utc::now().naive_utc().format(DATETIME_FORMAT).to_string());
+        let requests: Vec<Request> = db
+            .conn(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, chain_id, denom, address, time, tx_hash
+                     FROM requests
+                     WHERE address = ?1
+                     AND (?2 IS NULL OR chain_id = ?2)
+                     AND time < ?3
+                     ORDER BY time DESC
+                     LIMIT ?4",
+                )?;
+                let rows = stmt.query_map(
+                    params![address, chain_id, offset_time, limit],
+                    Request::from_row,
+                )?;
+                let requests: Result<Vec<_>, _> = rows.collect();
+                requests
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(requests)
+    }
+
+    async fn unhandled_transfers<'ctx>(
+        &self,
+        ctx: &Context<'ctx>,
+        chain_id: Option<String>,
+        limit: Option<i32>,
+        offset_time: Option<String>,
+    ) -> FieldResult<Vec<Request>> {
+        let db = ctx.data::<Pool>().unwrap();
+        let max_paginated_responses = ctx.data::<MaxPaginatedResponses>().unwrap().0;
+        let limit = limit.unwrap_or(10).min(max_paginated_responses);
+        let offset_time = offset_time
+            .unwrap_or_else(|| Utc::now().naive_utc().format(DATETIME_FORMAT).to_string());
+        let requests: Vec<Request> = db
+            .conn(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT id, chain_id, denom, address, time, tx_hash
+                    FROM requests
+                    WHERE tx_hash IS NULL
+                    AND (?1 IS NULL OR chain_id = ?1)
+                    AND time < ?2
+                    ORDER BY time DESC
+                    LIMIT ?3",
+                )?;
+                let rows =
+                    stmt.query_map(params![chain_id, offset_time, limit], Request::from_row)?;
+                let requests: Result<Vec<_>, _> = rows.collect();
+                requests
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(requests)
+    }
+}
+
+impl Request {
+    fn from_row(row: &async_sqlite::rusqlite::Row<'_>) -> async_sqlite::rusqlite::Result<Self> {
+        Ok(Request {
+            id: row.get(0)?,
+            chain_id: row.get(1)?,
+            denom: row.get(2)?,
+            address: row.get(3)?,
+            time: row.get(4)?,
+            tx_hash: row.get(5)?,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    pub schema: Schema<Query, Mutation, EmptySubscription>,
+    pub protection: Arc<ProtectionConfig>,
+}
+
+pub async fn graphql(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    request: GraphQLRequest,
+) -> GraphQLResponse {
+    let client_ip = ClientIp(state.protection.client_ip(&headers, peer));
+    state
+        .schema
+        .execute(request.into_inner().data(client_ip))
+        .await
+        .into()
+}
+
+pub async fn graphiql() -> impl IntoResponse {
+    response::Html(GraphiQLSource::build().endpoint("/").finish())
+}
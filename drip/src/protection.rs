@@ -0,0 +1,440 @@
+//! Protections of the `send` mutation against abuse, on top of the captcha and the per-address
+//! cooldown of every chain.
+
+use std::{
+    collections::HashSet,
+    net::{IpAddr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use async_graphql::{ErrorExtensions, InputObject, SimpleObject};
+use axum::http::HeaderMap;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Maximum number of outstanding proof of work challenges, bounding the memory used by clients
+/// requesting challenges without solving them.
+const MAX_CHALLENGES: usize = 100_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtectionConfig {
+    /// Header containing the address of the client, set by the reverse proxy in front of drip.
+    /// If unset, the peer address of the connection is used.
+    #[serde(default)]
+    pub forwarded_header: Option<String>,
+    /// The number of trusted proxies appending to `forwarded_header`. Clients can send the header
+    /// themselves, only the entries appended by the trusted proxies are read: the client address is
+    /// the `trusted_hops`th entry from the right.
+    #[serde(default = "default_trusted_hops")]
+    pub trusted_hops: usize,
+    /// Limit on the requests made from a single IP address, across all chains.
+    #[serde(default)]
+    pub ip_ratelimit: Option<RateLimit>,
+    /// Limit on the requests made from a single /24 (IPv4) or /64 (IPv6) subnet, across all chains.
+    #[serde(default)]
+    pub subnet_ratelimit: Option<RateLimit>,
+    /// Addresses exempt from all rate limits. Daily budgets still apply.
+    #[serde(default)]
+    pub allowlist: HashSet<String>,
+    /// Addresses which are never sent funds.
+    #[serde(default)]
+    pub denylist: HashSet<String>,
+    /// Allow solving a proof of work challenge instead of a captcha, for headless clients.
+    #[serde(default)]
+    pub proof_of_work: Option<ProofOfWorkConfig>,
+}
+
+impl Default for ProtectionConfig {
+    fn default() -> Self {
+        Self {
+            forwarded_header: None,
+            trusted_hops: default_trusted_hops(),
+            ip_ratelimit: None,
+            subnet_ratelimit: None,
+            allowlist: HashSet::new(),
+            denylist: HashSet::new(),
+            proof_of_work: None,
+        }
+    }
+}
+
+impl ProtectionConfig {
+    pub fn is_allowlisted(&self, address: &str) -> bool {
+        self.allowlist
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(address))
+    }
+
+    pub fn is_denylisted(&self, address: &str) -> bool {
+        self.denylist
+            .iter()
+            .any(|denied| denied.eq_ignore_ascii_case(address))
+    }
+
+    /// The address of the client making the request.
+    pub fn client_ip(&self, headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+        self.forwarded_header
+            .as_ref()
+            .and_then(|header| headers.get(header))
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').nth(self.trusted_hops.checked_sub(1)?))
+            .and_then(|ip| ip.trim().parse().ok())
+            .unwrap_or(peer.ip())
+    }
+}
+
+fn default_trusted_hops() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub max_requests: u32,
+    pub window_seconds: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProofOfWorkConfig {
+    /// The number of leading zero bits required in the hash of a solution.
+    pub difficulty: u32,
+    #[serde(default = "default_challenge_ttl_seconds")]
+    pub challenge_ttl_seconds: u64,
+}
+
+fn default_challenge_ttl_seconds() -> u64 {
+    300
+}
+
+/// The address of the client, as seen by the server.
+pub struct ClientIp(pub IpAddr);
+
+impl ClientIp {
+    /// The /24 of IPv4 addresses, or the /64 of IPv6 addresses, which are usually assigned to a
+    /// single customer.
+    pub fn subnet(&self) -> String {
+        match self.0 {
+            IpAddr::V4(ip) => {
+                let [a, b, c, _] = ip.octets();
+                format!("{a}.{b}.{c}.0/24")
+            }
+            IpAddr::V6(ip) => {
+                let [a, b, c, d, ..] = ip.segments();
+                format!("{a:x}:{b:x}:{c:x}:{d:x}::/64")
+            }
+        }
+    }
+}
+
+/// Issues and verifies proof of work challenges. A challenge can only be used once, and a solution
+/// is bound to the address receiving the funds.
+pub struct ProofOfWork {
+    config: ProofOfWorkConfig,
+    challenges: DashMap<String, Instant>,
+}
+
+#[derive(SimpleObject)]
+pub struct Challenge {
+    pub challenge: String,
+    /// The number of leading zero bits required in `sha256("{challenge}:{toAddress}:{nonce}")`.
+    pub difficulty: u32,
+    pub expires_in_seconds: u64,
+}
+
+#[derive(InputObject)]
+pub struct ProofOfWorkSolution {
+    pub challenge: String,
+    pub nonce: String,
+}
+
+impl ProofOfWork {
+    pub fn new(config: ProofOfWorkConfig) -> Self {
+        Self {
+            config,
+            challenges: DashMap::new(),
+        }
+    }
+
+    fn ttl(&self) -> Duration {
+        Duration::from_secs(self.config.challenge_ttl_seconds)
+    }
+
+    pub fn challenge(&self) -> Result<Challenge, SendError> {
+        let ttl = self.ttl();
+        self.challenges.retain(|_, issued| issued.elapsed() < ttl);
+        if self.challenges.len() >= MAX_CHALLENGES {
+            return Err(SendError::TooManyChallenges);
+        }
+
+        let challenge = hex::encode(rand::random::<[u8; 16]>());
+        self.challenges.insert(challenge.clone(), Instant::now());
+
+        Ok(Challenge {
+            challenge,
+            difficulty: self.config.difficulty,
+            expires_in_seconds: self.config.challenge_ttl_seconds,
+        })
+    }
+
+    /// Verify and consume the challenge of `solution`.
+    pub fn verify(&self, solution: &ProofOfWorkSolution, to_address: &str) -> bool {
+        let Some((_, issued)) = self.challenges.remove(&solution.challenge) else {
+            return false;
+        };
+        if issued.elapsed() >= self.ttl() {
+            return false;
+        }
+
+        let hash = Sha256::digest(format!(
+            "{}:{to_address}:{}",
+            solution.challenge, solution.nonce
+        ));
+        leading_zero_bits(&hash) >= self.config.difficulty
+    }
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in bytes {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// The reasons a request to the faucet is rejected. Exposed to clients as the `code` extension
+/// of the GraphQL error.
+#[derive(Debug, thiserror::Error)]
+pub enum SendError {
+    #[error("unknown chain `{0}`")]
+    UnknownChain(String),
+    #[error("unknown denom `{denom}` for chain `{chain_id}`")]
+    UnknownDenom { chain_id: String, denom: String },
+    #[error("invalid address: {0}")]
+    InvalidAddress(String),
+    #[error("address is denylisted")]
+    Denylisted,
+    #[error("a captcha token or a proof of work is required")]
+    ChallengeRequired,
+    #[error("failed to verify captcha: {0}")]
+    InvalidCaptcha(String),
+    #[error("invalid or expired proof of work")]
+    InvalidProofOfWork,
+    #[error("too many outstanding proof of work challenges, try again later")]
+    TooManyChallenges,
+    #[error("proof of work is not enabled")]
+    ProofOfWorkDisabled,
+    #[error("ratelimited by {scope}, retry after {retry_after_seconds} seconds")]
+    Ratelimited {
+        scope: &'static str,
+        retry_after_seconds: i64,
+    },
+    #[error("daily budget of `{denom}` is exhausted")]
+    BudgetExhausted { denom: String },
+    #[error("request {id} is still queued")]
+    Queued { id: i64 },
+    #[error("transfer failed: {0}")]
+    Failed(String),
+}
+
+impl SendError {
+    fn code(&self) -> &'static str {
+        match self {
+            SendError::UnknownChain(_) => "UNKNOWN_CHAIN",
+            SendError::UnknownDenom { .. } => "UNKNOWN_DENOM",
+            SendError::InvalidAddress(_) => "INVALID_ADDRESS",
+            SendError::Denylisted => "DENYLISTED",
+            SendError::ChallengeRequired => "CHALLENGE_REQUIRED",
+            SendError::InvalidCaptcha(_) => "INVALID_CAPTCHA",
+            SendError::InvalidProofOfWork => "INVALID_PROOF_OF_WORK",
+            SendError::TooManyChallenges => "TOO_MANY_CHALLENGES",
+            SendError::ProofOfWorkDisabled => "PROOF_OF_WORK_DISABLED",
+            SendError::Ratelimited { .. } => "RATELIMITED",
+            SendError::BudgetExhausted { .. } => "BUDGET_EXHAUSTED",
+            SendError::Queued { .. } => "QUEUED",
+            SendError::Failed(_) => "FAILED",
+        }
+    }
+}
+
+impl ErrorExtensions for SendError {
+    fn extend(&self) -> async_graphql::Error {
+        async_graphql::Error::new(self.to_string()).extend_with(|_, e| {
+            e.set("code", self.code());
+            match self {
+                SendError::Ratelimited {
+                    scope,
+                    retry_after_seconds,
+                } => {
+                    e.set("scope", *scope);
+                    e.set("retryAfterSeconds", *retry_after_seconds);
+                }
+                SendError::BudgetExhausted { denom } => e.set("denom", denom.as_str()),
+                SendError::Queued { id } => e.set("requestId", *id),
+                _ => {}
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TO_ADDRESS: &str = "union1abc";
+
+    fn pow(challenge_ttl_seconds: u64) -> ProofOfWork {
+        ProofOfWork::new(ProofOfWorkConfig {
+            difficulty: 8,
+            challenge_ttl_seconds,
+        })
+    }
+
+    fn solve(challenge: &Challenge, to_address: &str) -> ProofOfWorkSolution {
+        let nonce = (0u64..)
+            .find(|nonce| {
+                let hash = Sha256::digest(format!("{}:{to_address}:{nonce}", challenge.challenge));
+                leading_zero_bits(&hash) >= challenge.difficulty
+            })
+            .unwrap();
+
+        ProofOfWorkSolution {
+            challenge: challenge.challenge.clone(),
+            nonce: nonce.to_string(),
+        }
+    }
+
+    #[test]
+    fn verify_consumes_challenge() {
+        let pow = pow(300);
+        let solution = solve(&pow.challenge().unwrap(), TO_ADDRESS);
+
+        assert!(pow.verify(&solution, TO_ADDRESS));
+        assert!(!pow.verify(&solution, TO_ADDRESS));
+    }
+
+    #[test]
+    fn verify_rejects_other_address() {
+        let pow = pow(300);
+        let challenge = pow.challenge().unwrap();
+        // a solution which doesn't hold for `TO_ADDRESS` as well
+        let solution = (0..)
+            .map(|i| solve(&challenge, &format!("union1other{i}")))
+            .find(|solution| {
+                let hash = Sha256::digest(format!(
+                    "{}:{TO_ADDRESS}:{}",
+                    solution.challenge, solution.nonce
+                ));
+                leading_zero_bits(&hash) < challenge.difficulty
+            })
+            .unwrap();
+
+        assert!(!pow.verify(&solution, TO_ADDRESS));
+    }
+
+    #[test]
+    fn verify_rejects_expired_challenge() {
+        let pow = pow(0);
+        let solution = solve(&pow.challenge().unwrap(), TO_ADDRESS);
+
+        assert!(!pow.verify(&solution, TO_ADDRESS));
+    }
+
+    #[test]
+    fn verify_rejects_unknown_challenge() {
+        let pow = pow(300);
+        let solution = solve(
+            &Challenge {
+                challenge: "00".repeat(16),
+                difficulty: 8,
+                expires_in_seconds: 300,
+            },
+            TO_ADDRESS,
+        );
+
+        assert!(!pow.verify(&solution, TO_ADDRESS));
+    }
+
+    #[test]
+    fn leading_zero_bits_counts_across_bytes() {
+        assert_eq!(leading_zero_bits(&[]), 0);
+        assert_eq!(leading_zero_bits(&[0x80, 0x00]), 0);
+        assert_eq!(leading_zero_bits(&[0x01, 0x00]), 7);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00, 0x10]), 19);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+    }
+
+    #[test]
+    fn subnet() {
+        assert_eq!(
+            ClientIp("203.0.113.7".parse().unwrap()).subnet(),
+            "203.0.113.0/24"
+        );
+        assert_eq!(
+            ClientIp("2001:db8:85a3:8d3:1319:8a2e:370:7348".parse().unwrap()).subnet(),
+            "2001:db8:85a3:8d3::/64"
+        );
+        assert_eq!(ClientIp("::1".parse().unwrap()).subnet(), "0:0:0:0::/64");
+    }
+
+    #[test]
+    fn client_ip_from_forwarded_header() {
+        let peer: SocketAddr = "10.0.0.1:1234".parse().unwrap();
+        let config = ProtectionConfig {
+            forwarded_header: Some("x-forwarded-for".to_owned()),
+            ..Default::default()
+        };
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(config.client_ip(&headers, peer), peer.ip());
+
+        headers.insert("x-forwarded-for", "203.0.113.7".parse().unwrap());
+        assert_eq!(
+            config.client_ip(&headers, peer),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+
+        // the header is ignored unless configured
+        assert_eq!(
+            ProtectionConfig::default().client_ip(&headers, peer),
+            peer.ip()
+        );
+
+        headers.insert("x-forwarded-for", "not an ip".parse().unwrap());
+        assert_eq!(config.client_ip(&headers, peer), peer.ip());
+    }
+
+    #[test]
+    fn client_ip_ignores_spoofed_entries() {
+        let peer: SocketAddr = "10.0.0.1:1234".parse().unwrap();
+        let config = |trusted_hops| ProtectionConfig {
+            forwarded_header: Some("x-forwarded-for".to_owned()),
+            trusted_hops,
+            ..Default::default()
+        };
+
+        // the client sent its own header, the proxy appended the address it saw
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "1.2.3.4, 203.0.113.7".parse().unwrap());
+        assert_eq!(
+            config(1).client_ip(&headers, peer),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+
+        // behind a CDN and a load balancer, both appending to the header
+        headers.insert(
+            "x-forwarded-for",
+            "1.2.3.4, 203.0.113.7, 198.51.100.1".parse().unwrap(),
+        );
+        assert_eq!(
+            config(2).client_ip(&headers, peer),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+
+        // fewer entries than trusted proxies, the header wasn't set by them
+        assert_eq!(config(4).client_ip(&headers, peer), peer.ip());
+        assert_eq!(config(0).client_ip(&headers, peer), peer.ip());
+    }
+}
@@ -0,0 +1,769 @@
+//! The workers fulfilling the queued requests of each chain.
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_sqlite::{rusqlite::params, Pool};
+use chain_utils::cosmos_sdk::{BroadcastTxCommitError, CosmosSdkChainExt};
+use ethers::types::U256;
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, warn, Instrument};
+use unionlabs::ErrorReporter;
+
+use crate::{
+    config::{ChainConfig, ChainType, CoinConfig},
+    cosmos::Chain,
+    ethereum::{EthereumChain, EthereumSendError},
+};
+
+/// Number of times a request is sent before giving up on it.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Time after which a batch stops waiting for its transaction to be included. Requests are only requeued once the
+/// transaction provably can no longer be included.
+const SEND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Interval at which the transactions of interrupted batches are checked for inclusion.
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Interval at which the receipts of the transfers of an EVM batch are polled.
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The maximum number of requests in an EVM batch. Each of them is a transaction of the signer, and geth only
+/// guarantees 16 pending transactions per account in its mempool.
+const MAX_EVM_BATCH_SIZE: usize = 16;
+
+/// Interval at which the balances of the signers are compared to the refill thresholds.
+const BALANCE_CHECK_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Poll the queued requests of `chain` and fulfill them, submitting one batch per signer
+/// concurrently. Panics on database errors, after which the worker is restarted.
+pub async fn worker(chain: ChainConfig, pool: Pool, batch_size: usize) {
+    // make sure to panic *here* so that the tokio task will catch the panic!
+    info!("creating drip client");
+    let drip = Arc::new(DripClient::new(chain.clone()).await);
+    let concurrency = drip.signers();
+    let batch_size = match chain.ty {
+        ChainType::Cosmos { .. } => batch_size,
+        ChainType::Ethereum { .. } => batch_size.min(MAX_EVM_BATCH_SIZE),
+    };
+
+    // nothing is in flight when the worker starts, requeue the batches of a previous worker which were never broadcast.
+    // the others may still be included, they are left to `reconcile`.
+    let chain_id = chain.id.clone();
+    pool.conn(move |conn| {
+        let rows_modified = conn.execute(
+            "UPDATE requests SET claimed_at = NULL
+            WHERE chain_id = ?1 AND tx_hash IS NULL AND claimed_at IS NOT NULL AND pending_tx_hash IS NULL",
+            [&chain_id],
+        )?;
+        if rows_modified > 0 {
+            info!(rows_modified, "requeued in-flight requests");
+        }
+        Ok(())
+    })
+    .await
+    .expect("pool error");
+
+    let mut batches = JoinSet::new();
+    let mut last_balance_check: Option<Instant> = None;
+    let mut last_reconcile: Option<Instant> = None;
+
+    info!(concurrency, "entering worker poll loop");
+    loop {
+        if last_balance_check.map_or(true, |time| time.elapsed() >= BALANCE_CHECK_INTERVAL) {
+            drip.check_balances().await;
+            last_balance_check = Some(Instant::now());
+        }
+
+        if last_reconcile.map_or(true, |time| time.elapsed() >= RECONCILE_INTERVAL) {
+            reconcile(&drip, &pool, chain.id.clone()).await;
+            last_reconcile = Some(Instant::now());
+        }
+
+        // every signer handles at most one batch at a time
+        while batches.len() >= concurrency {
+            batches
+                .join_next()
+                .await
+                .expect("set is not empty")
+                .expect("batch panicked");
+        }
+
+        let requests = pool
+            .conn({
+                let chain_id = chain.id.clone();
+                move |conn| {
+                    let mut stmt = conn
+                        .prepare_cached(
+                            "UPDATE requests SET claimed_at = datetime('now')
+                            WHERE id IN (
+                                SELECT id FROM requests
+                                WHERE chain_id = ?1 AND tx_hash IS NULL AND claimed_at IS NULL
+                                ORDER BY id
+                                LIMIT ?2
+                            )
+                            RETURNING id, address, denom, attempts",
+                        )
+                        .expect("???");
+
+                    let mut rows = stmt
+                        .query(params![chain_id, batch_size as i64])
+                        .expect("can't query rows");
+
+                    let mut requests = vec![];
+                    while let Some(row) = rows.next().expect("could not read row") {
+                        requests.push(SendRequest {
+                            id: row.get(0).expect("could not read id"),
+                            address: row.get(1).expect("could not read address"),
+                            denom: row.get(2).expect("could not read denom"),
+                            attempts: row.get(3).expect("could not read attempts"),
+                        });
+                    }
+
+                    Ok(requests)
+                }
+            })
+            .await
+            .expect("pool error");
+
+        if requests.is_empty() {
+            debug!("no requests in queue");
+            tokio::time::sleep(Duration::from_millis(1000)).await;
+            continue;
+        }
+
+        debug!(requests = requests.len(), "submitting batch");
+        let drip = drip.clone();
+        let pool = pool.clone();
+        batches.spawn(
+            async move {
+                let attempts = requests
+                    .iter()
+                    .map(|request| (request.id, request.attempts))
+                    .collect::<HashMap<_, _>>();
+                let results = drip.send(&pool, requests).await;
+                store_results(&pool, results, attempts).await;
+            }
+            .in_current_span(),
+        );
+    }
+}
+
+/// Store the transaction hashes of the sent requests. Failed requests are requeued, until they
+/// failed `MAX_ATTEMPTS` times, after which the error is stored in place of their hash. Pending
+/// requests stay claimed until `reconcile` resolves them.
+async fn store_results(pool: &Pool, results: Vec<SendOutcome>, attempts: HashMap<i64, u32>) {
+    pool.conn(move |conn| {
+        let tx = conn.unchecked_transaction()?;
+        let (mut sent, mut requeued, mut failed, mut pending) = (0, 0, 0, 0);
+        {
+            // the transaction is matched so that a request resolved twice, by its batch and by `reconcile`, is only
+            // updated once.
+            let mut set_tx_hash = tx
+                .prepare_cached(
+                    "UPDATE requests
+                    SET tx_hash = ?1, claimed_at = NULL, pending_tx_hash = NULL, pending_signer = NULL, pending_nonce = NULL
+                    WHERE id = ?2 AND pending_tx_hash IS ?3",
+                )
+                .expect("???");
+            let mut requeue = tx
+                .prepare_cached(
+                    "UPDATE requests
+                    SET attempts = attempts + 1, claimed_at = NULL, pending_tx_hash = NULL, pending_signer = NULL, pending_nonce = NULL
+                    WHERE id = ?1 AND pending_tx_hash IS ?2",
+                )
+                .expect("???");
+
+            for SendOutcome {
+                id,
+                pending_tx_hash,
+                result,
+            } in &results
+            {
+                match result {
+                    SendResult::Sent(tx_hash) => {
+                        sent += set_tx_hash
+                            .execute(params![tx_hash, id, pending_tx_hash])
+                            .expect("can't query rows");
+                    }
+                    SendResult::Failed(err) if attempts[id] + 1 >= MAX_ATTEMPTS => {
+                        failed += set_tx_hash
+                            .execute(params![format!("ERROR: {err}"), id, pending_tx_hash])
+                            .expect("can't query rows");
+                    }
+                    SendResult::Failed(_) => {
+                        requeued += requeue
+                            .execute(params![id, pending_tx_hash])
+                            .expect("can't query rows");
+                    }
+                    SendResult::Pending => pending += 1,
+                }
+            }
+        }
+        tx.commit()?;
+
+        info!(sent, requeued, failed, pending, "updated requests");
+
+        Ok(())
+    })
+    .await
+    .expect("pool error");
+}
+
+/// Store the transaction fulfilling each request before it is broadcast, so that its inclusion can be checked if the
+/// batch is interrupted.
+async fn mark_pending(pool: &Pool, pending: Vec<(i64, PendingTx)>) {
+    pool.conn(move |conn| {
+        let tx = conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "UPDATE requests SET pending_tx_hash = ?1, pending_signer = ?2, pending_nonce = ?3 WHERE id = ?4",
+            )?;
+            for (id, pending) in &pending {
+                stmt.execute(params![pending.tx_hash, pending.signer, pending.nonce, id])?;
+            }
+        }
+        tx.commit()
+    })
+    .await
+    .expect("pool error");
+}
+
+/// Resolve the requests whose transaction was broadcast by a batch which did not see it included, either because it
+/// timed out or because a previous worker was interrupted.
+async fn reconcile(drip: &DripClient, pool: &Pool, chain_id: String) {
+    let rows = pool
+        .conn(move |conn| {
+            // leave enough time for the batch itself to resolve its transaction.
+            let mut stmt = conn.prepare_cached(
+                "SELECT id, attempts, pending_tx_hash, pending_signer, pending_nonce FROM requests
+                WHERE chain_id = ?1 AND tx_hash IS NULL AND pending_tx_hash IS NOT NULL AND claimed_at < datetime('now', ?2)",
+            )?;
+            let rows = stmt.query_map(
+                params![chain_id, format!("-{} seconds", 2 * SEND_TIMEOUT.as_secs())],
+                |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, u32>(1)?,
+                        PendingTx {
+                            tx_hash: row.get(2)?,
+                            signer: row.get(3)?,
+                            nonce: row.get(4)?,
+                        },
+                    ))
+                },
+            )?;
+            rows.collect::<Result<Vec<_>, _>>()
+        })
+        .await
+        .expect("pool error");
+
+    if rows.is_empty() {
+        return;
+    }
+
+    // the requests of a cosmos batch share a single transaction
+    let mut attempts = HashMap::new();
+    let mut txs = HashMap::<String, (PendingTx, Vec<i64>)>::new();
+    for (id, attempt, pending) in rows {
+        attempts.insert(id, attempt);
+        txs.entry(pending.tx_hash.clone())
+            .or_insert_with(|| (pending, vec![]))
+            .1
+            .push(id);
+    }
+
+    info!(transactions = txs.len(), "checking pending transactions");
+    let mut results = vec![];
+    for (pending, ids) in txs.into_values() {
+        let result = drip.resolve(&pending).await;
+        results.extend(ids.into_iter().map(|id| SendOutcome {
+            id,
+            pending_tx_hash: Some(pending.tx_hash.clone()),
+            result: result.clone(),
+        }));
+    }
+
+    store_results(pool, results, attempts).await;
+}
+
+/// The outcome of sending a request.
+#[derive(Debug, Clone)]
+pub enum SendResult {
+    /// The transaction fulfilling the request was included, with this hash.
+    Sent(String),
+    /// The request was not fulfilled, and can safely be sent again.
+    Failed(String),
+    /// The transaction was broadcast, but it is not known yet whether it will be included.
+    Pending,
+}
+
+/// The outcome of sending the request `id`, along with the transaction stored for it by `mark_pending`, if any.
+pub struct SendOutcome {
+    id: i64,
+    pending_tx_hash: Option<String>,
+    result: SendResult,
+}
+
+/// A transaction which is (about to be) broadcast, as stored with the requests it fulfills.
+#[derive(Debug, Clone)]
+pub struct PendingTx {
+    pub tx_hash: String,
+    /// The address of the signer of the transaction.
+    pub signer: String,
+    /// The account sequence (cosmos) or nonce (EVM) the transaction was signed with.
+    pub nonce: u64,
+}
+
+/// A queued request, as stored in the database.
+pub struct SendRequest {
+    id: i64,
+    address: String,
+    denom: String,
+    /// How many times sending this request failed before.
+    attempts: u32,
+}
+
+struct DripClient {
+    chain: DripChain,
+    coins: HashMap<String, CoinConfig>,
+}
+
+enum DripChain {
+    Cosmos { chain: Chain, memo: String },
+    Ethereum(EthereumChain),
+}
+
+impl DripClient {
+    async fn new(config: ChainConfig) -> Self {
+        let chain = match config.ty {
+            ChainType::Cosmos {
+                ws_url,
+                grpc_url,
+                gas_config,
+                memo,
+            } => DripChain::Cosmos {
+                chain: Chain::new(grpc_url, ws_url, gas_config, config.keyring).await,
+                memo,
+            },
+            ChainType::Ethereum { rpc_url } => {
+                DripChain::Ethereum(EthereumChain::new(rpc_url, config.keyring).await)
+            }
+        };
+
+        Self {
+            chain,
+            coins: config
+                .coins
+                .into_iter()
+                .map(|coin| (coin.denom.clone(), coin))
+                .collect(),
+        }
+    }
+
+    /// The number of signers, which is also the number of batches that can be sent concurrently.
+    fn signers(&self) -> usize {
+        match &self.chain {
+            DripChain::Cosmos { chain, .. } => chain.keyring.keys().count(),
+            DripChain::Ethereum(chain) => chain.keyring.keys().count(),
+        }
+    }
+
+    /// Fulfill the requests with one of the signers, returning the outcome of each request. Transactions are stored
+    /// with their requests before being broadcast, see `mark_pending`.
+    async fn send(&self, pool: &Pool, requests: Vec<SendRequest>) -> Vec<SendOutcome> {
+        let (requests, unknown): (Vec<_>, Vec<_>) = requests
+            .into_iter()
+            .partition(|request| self.coins.contains_key(&request.denom));
+        let mut results = unknown
+            .into_iter()
+            .map(|request| {
+                warn!(denom = request.denom, "request for unknown denom");
+                SendOutcome {
+                    id: request.id,
+                    pending_tx_hash: None,
+                    result: SendResult::Failed("unknown denom".to_string()),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let ids = requests
+            .iter()
+            .map(|request| request.id)
+            .collect::<Vec<_>>();
+
+        let sent = match &self.chain {
+            DripChain::Cosmos { chain, memo } => {
+                let to_send = requests
+                    .iter()
+                    .map(|request| {
+                        (
+                            request.address.clone(),
+                            request.denom.clone(),
+                            self.coins[&request.denom].amount,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                chain
+                    .keyring
+                    .with(|signer| async move {
+                        let outcomes = |pending_tx_hash: Option<String>, result: SendResult| {
+                            requests
+                                .iter()
+                                .map(|request| SendOutcome {
+                                    id: request.id,
+                                    pending_tx_hash: pending_tx_hash.clone(),
+                                    result: result.clone(),
+                                })
+                                .collect::<Vec<_>>()
+                        };
+
+                        let tx = match chain.sign(signer, to_send, memo.clone()).await {
+                            Ok(tx) => tx,
+                            Err(err) => {
+                                warn!(err = %ErrorReporter(&err), "unable to sign transaction");
+                                return outcomes(
+                                    None,
+                                    SendResult::Failed(ErrorReporter(err).to_string()),
+                                );
+                            }
+                        };
+
+                        let pending = PendingTx {
+                            // this will be displayed to users, print the hash in the same way that cosmos sdk does
+                            tx_hash: tx.tx_hash.to_string_unprefixed().to_uppercase(),
+                            signer: signer.to_string(),
+                            nonce: tx.sequence,
+                        };
+                        mark_pending(
+                            pool,
+                            requests
+                                .iter()
+                                .map(|request| (request.id, pending.clone()))
+                                .collect(),
+                        )
+                        .await;
+
+                        let result = match tokio::time::timeout(
+                            SEND_TIMEOUT,
+                            chain.broadcast_signed_tx(tx),
+                        )
+                        .await
+                        {
+                            Ok(Ok((_, gas_used))) => {
+                                info!(tx_hash = %pending.tx_hash, %gas_used, "submitted multisend");
+                                SendResult::Sent(pending.tx_hash.clone())
+                            }
+                            // the transaction was either rejected, or included and failed: nothing was sent
+                            Ok(Err(err @ BroadcastTxCommitError::Tx(_))) => {
+                                warn!(err = %ErrorReporter(&err), "unable to submit transaction");
+                                SendResult::Failed(ErrorReporter(err).to_string())
+                            }
+                            Ok(Err(err)) => {
+                                warn!(err = %ErrorReporter(&err), "unable to confirm transaction");
+                                chain.resolve(&pending).await
+                            }
+                            Err(_) => {
+                                warn!("timed out submitting transaction");
+                                chain.resolve(&pending).await
+                            }
+                        };
+
+                        outcomes(Some(pending.tx_hash), result)
+                    })
+                    .await
+            }
+            DripChain::Ethereum(chain) => {
+                chain
+                    .keyring
+                    .with(|signer| async move {
+                        // each request gets its own transaction. they are signed with consecutive nonces, so that they
+                        // are all broadcast at once and their receipts awaited together.
+                        let mut nonce = match chain.next_nonce(signer).await {
+                            Ok(nonce) => nonce,
+                            Err(err) => {
+                                warn!(err = %ErrorReporter(&err), "unable to fetch the signer nonce");
+                                return requests
+                                    .iter()
+                                    .map(|request| SendOutcome {
+                                        id: request.id,
+                                        pending_tx_hash: None,
+                                        result: SendResult::Failed(
+                                            ErrorReporter(&err).to_string(),
+                                        ),
+                                    })
+                                    .collect();
+                            }
+                        };
+
+                        let mut results = vec![];
+                        let mut signed = vec![];
+                        for request in &requests {
+                            let to = request
+                                .address
+                                .parse()
+                                .expect("addresses are validated before being queued");
+
+                            match chain
+                                .sign(
+                                    signer,
+                                    to,
+                                    &request.denom,
+                                    self.coins[&request.denom].amount,
+                                    nonce,
+                                )
+                                .await
+                            {
+                                Ok((raw, pending)) => {
+                                    signed.push((request, raw, pending));
+                                    nonce += U256::one();
+                                }
+                                Err(err) => {
+                                    warn!(err = %ErrorReporter(&err), "unable to sign transaction");
+                                    results.push(SendOutcome {
+                                        id: request.id,
+                                        pending_tx_hash: None,
+                                        result: SendResult::Failed(ErrorReporter(err).to_string()),
+                                    });
+                                }
+                            }
+                        }
+
+                        mark_pending(
+                            pool,
+                            signed
+                                .iter()
+                                .map(|(request, _, pending)| (request.id, pending.clone()))
+                                .collect(),
+                        )
+                        .await;
+
+                        let mut broadcast = vec![];
+                        let mut interrupted = None;
+                        for (request, raw, pending) in signed {
+                            // the transactions after a failed one are not broadcast, as they could not be included
+                            // before it anyway
+                            if let Some(err) = &interrupted {
+                                results.push(SendOutcome {
+                                    id: request.id,
+                                    pending_tx_hash: Some(pending.tx_hash),
+                                    result: SendResult::Failed(format!("not broadcast: {err}")),
+                                });
+                                continue;
+                            }
+
+                            match chain.broadcast(raw).await {
+                                Ok(()) => broadcast.push((request, pending)),
+                                Err(err @ EthereumSendError::Rejected(_)) => {
+                                    warn!(err = %ErrorReporter(&err), "unable to submit transaction");
+                                    let err = ErrorReporter(err).to_string();
+                                    results.push(SendOutcome {
+                                        id: request.id,
+                                        pending_tx_hash: Some(pending.tx_hash),
+                                        result: SendResult::Failed(err.clone()),
+                                    });
+                                    interrupted = Some(err);
+                                }
+                                // the transaction may or may not have entered the mempool
+                                Err(err) => {
+                                    warn!(err = %ErrorReporter(&err), "unable to confirm transaction");
+                                    interrupted = Some(ErrorReporter(err).to_string());
+                                    broadcast.push((request, pending));
+                                }
+                            }
+                        }
+
+                        let deadline = Instant::now() + SEND_TIMEOUT;
+                        let mut receipts = HashMap::new();
+                        while receipts.len() < broadcast.len() && Instant::now() < deadline {
+                            tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+                            for (request, pending) in &broadcast {
+                                if receipts.contains_key(&request.id) {
+                                    continue;
+                                }
+                                match chain.receipt(&pending.tx_hash).await {
+                                    Ok(Some(receipt)) => {
+                                        receipts.insert(request.id, receipt);
+                                    }
+                                    Ok(None) => {}
+                                    Err(err) => {
+                                        warn!(err = %ErrorReporter(&err), "unable to fetch the transaction receipt");
+                                    }
+                                }
+                            }
+                        }
+
+                        for (request, pending) in broadcast {
+                            let result = match receipts.remove(&request.id) {
+                                Some(receipt) => chain.receipt_result(&request.denom, receipt),
+                                None => {
+                                    warn!(tx_hash = %pending.tx_hash, "timed out waiting for the transaction receipt");
+                                    chain.resolve(&pending).await
+                                }
+                            };
+
+                            results.push(SendOutcome {
+                                id: request.id,
+                                pending_tx_hash: Some(pending.tx_hash),
+                                result,
+                            });
+                        }
+                        results
+                    })
+                    .await
+            }
+        };
+
+        match sent {
+            Some(sent) => results.extend(sent),
+            // all signers are busy, the requests are requeued
+            None => results.extend(ids.into_iter().map(|id| SendOutcome {
+                id,
+                pending_tx_hash: None,
+                result: SendResult::Failed("no signer available".to_string()),
+            })),
+        }
+
+        results
+    }
+
+    /// Check whether the transaction of an interrupted batch was included.
+    async fn resolve(&self, pending: &PendingTx) -> SendResult {
+        match &self.chain {
+            DripChain::Cosmos { chain, .. } => chain.resolve(pending).await,
+            DripChain::Ethereum(chain) => chain.resolve(pending).await,
+        }
+    }
+
+    /// Raise an alarm for every signer whose balance of a coin dropped below its `refill_threshold`.
+    async fn check_balances(&self) {
+        for coin in self.coins.values() {
+            let Some(threshold) = coin.refill_threshold else {
+                continue;
+            };
+
+            let balances = match &self.chain {
+                DripChain::Cosmos { chain, .. } => chain.balances(&coin.denom).await,
+                DripChain::Ethereum(chain) => chain.balances(&coin.denom).await,
+            };
+
+            for (address, balance) in balances {
+                match balance {
+                    Ok(balance) if balance < threshold => {
+                        error!(
+                            %address,
+                            denom = coin.denom,
+                            balance,
+                            threshold,
+                            "signer balance is below the refill threshold"
+                        );
+                    }
+                    Ok(balance) => {
+                        debug!(%address, denom = coin.denom, balance, "signer balance");
+                    }
+                    Err(err) => {
+                        warn!(%address, denom = coin.denom, %err, "unable to fetch signer balance");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_sqlite::PoolBuilder;
+
+    use super::*;
+    use crate::db::migrate;
+
+    /// A database in a temporary file, as the connections of a pool don't share in-memory databases.
+    async fn pool() -> Pool {
+        let pool = PoolBuilder::new()
+            .path(std::env::temp_dir().join(format!("drip-{}.sqlite3", rand::random::<u64>())))
+            .open()
+            .await
+            .unwrap();
+        pool.conn(|conn| migrate(conn, None)).await.unwrap();
+        pool
+    }
+
+    #[tokio::test]
+    async fn store_results_requeues_until_max_attempts() {
+        let pool = pool().await;
+        pool.conn(|conn| {
+            let mut stmt = conn.prepare(
+                "INSERT INTO requests (id, chain_id, denom, address, time, claimed_at, attempts, pending_tx_hash)
+                VALUES (?1, 'union-testnet-8', 'muno', 'union1abc', datetime('now'), datetime('now'), ?2, ?3)",
+            )?;
+            stmt.execute(params![1, 0, None::<String>])?;
+            stmt.execute(params![2, 0, None::<String>])?;
+            stmt.execute(params![3, MAX_ATTEMPTS - 1, None::<String>])?;
+            stmt.execute(params![4, 0, "PENDING"])?;
+            // resolved and claimed again since the outcome was computed
+            stmt.execute(params![5, 0, "OTHER"])?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let outcome = |id, pending_tx_hash: Option<&str>, result| SendOutcome {
+            id,
+            pending_tx_hash: pending_tx_hash.map(ToOwned::to_owned),
+            result,
+        };
+        store_results(
+            &pool,
+            vec![
+                outcome(1, None, SendResult::Sent("SENT".to_owned())),
+                outcome(2, None, SendResult::Failed("error".to_owned())),
+                outcome(3, None, SendResult::Failed("error".to_owned())),
+                outcome(4, Some("PENDING"), SendResult::Pending),
+                outcome(5, Some("PENDING"), SendResult::Sent("PENDING".to_owned())),
+            ],
+            [(1, 0), (2, 0), (3, MAX_ATTEMPTS - 1), (4, 0), (5, 0)].into(),
+        )
+        .await;
+
+        let requests = pool
+            .conn(|conn| {
+                conn.prepare(
+                    "SELECT id, tx_hash, claimed_at IS NOT NULL, attempts, pending_tx_hash FROM requests ORDER BY id",
+                )?
+                .query_map((), |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, bool>(2)?,
+                        row.get::<_, u32>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            requests,
+            vec![
+                (1, Some("SENT".to_owned()), false, 0, None),
+                (2, None, false, 1, None),
+                (
+                    3,
+                    Some("ERROR: error".to_owned()),
+                    false,
+                    MAX_ATTEMPTS - 1,
+                    None
+                ),
+                (4, None, true, 0, Some("PENDING".to_owned())),
+                (5, None, true, 0, Some("OTHER".to_owned())),
+            ]
+        );
+    }
+}
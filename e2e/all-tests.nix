@@ -10,9 +10,7 @@
         };
       in
       {
-        # Disabled
-        # TODO: Fix Ensure Blocks Workflow unionlabs/union#2067
-        # ensure-blocks = import ./ensure-blocks/ensure-blocks.nix { inherit e2e networks pkgs nixpkgs crane; };
+        ensure-blocks = import ./ensure-blocks/ensure-blocks.nix { inherit e2e networks pkgs nixpkgs crane inputs self'; };
 
         # Tests from ./epoch-staking.nix
         epoch-completes = epoch-staking.epoch-completes;
@@ -1,17 +1,137 @@
-{ e2e, pkgs, crane, ... }:
+{ e2e, pkgs, crane, inputs, self', ... }:
 let
   ensure-blocks = pkgs.lib.meta.getExe (crane.buildWorkspaceMember {
     crateDirFromRoot = "e2e/ensure-blocks";
   }).packages.ensure-blocks;
+
+  voyager = pkgs.lib.meta.getExe self'.packages.voyager;
+  jq = pkgs.lib.meta.getExe pkgs.jq;
+
+  # the devnet union home, with the addresses of the instantiated contracts and the checksums of the light clients
+  unionHome = self'.packages.devnet-union-home;
+
+  # the address of the UCS01 relay deployed by forge on the devnet, see evm/README.md
+  ethereumRelay = "0xa9d03ba6e27b43c69a64c87f845485b73a8e5d46";
+
+  chains = {
+    ethereum-devnet = {
+      enabled = true;
+      chain_type = "ethereum";
+      preset_base = "minimal";
+      ibc_commitment_slot = "0";
+      ibc_handler_address = "0xed2af2ad7fe0d92011b26a2e5d1b4dc7d12a47c5";
+      multicall_address = "0x9fd9D9528c8373D990a1380B9414bDE179007A35";
+      # dev-key0 signs the transfers of the scenarios, the relayer uses its own key to not race them on nonces
+      keyring = {
+        name = "ethereum-devnet";
+        keys = [{
+          type = "raw";
+          name = "dev-key1";
+          key = "0xd9c5dc47ed678fc3e63249953866d79e5cf48418e79d8eec1a985be7393ef3b9";
+        }];
+      };
+      eth_rpc_api = "ws://devnetEth:8546";
+      eth_beacon_rpc_api = "http://devnetEth:9596";
+    };
+    union-devnet = {
+      enabled = true;
+      chain_type = "union";
+      keyring = {
+        name = "union-devnet";
+        keys = [{
+          type = "raw";
+          name = "alice";
+          key = "0xaa820fa947beb242032a41b6dc9a8b9c37d8f5fbcda0966b1ec80335b10a7d6f";
+        }];
+      };
+      ws_url = "ws://union:26657/websocket";
+      prover_endpoints = [ "http://localhost:9999" ];
+      grpc_url = "http://union:9090";
+      gas_config = {
+        gas_price = "1.0";
+        gas_denom = "muno";
+        gas_multiplier = "1.1";
+        max_gas = 40000000;
+      };
+    };
+  };
+
+  voyagerExtra = {
+    laddr = "0.0.0.0:65534";
+    max_batch_size = 20;
+    tx_batch = {
+      retry_count = 3;
+      min_batch_size = 1;
+      max_batch_size = 20;
+    };
+    optimizer_delay_milliseconds = 0;
+  };
+
+  dbUrl = "postgres://voyager@localhost/voyager";
+
+  voyagerConfig = pkgs.writeText "voyager-config.json" (builtins.toJSON {
+    chain = chains;
+    voyager = voyagerExtra // {
+      num_workers = 20;
+      queue = {
+        type = "pg-queue";
+        database_url = dbUrl;
+        min_connections = 20;
+        max_connections = 20;
+        idle_timeout = null;
+        max_lifetime = null;
+      };
+    };
+  });
+
+  unionRelay = "$(cat ${unionHome}/addresses/ucs01_relay_0)";
+
+  # opens the UCS01 channel between the relays on union and ethereum, and has voyager follow both chains to relay its
+  # packets
+  open-channel = pkgs.writeShellApplication {
+    name = "open-ucs01-channel";
+    runtimeInputs = [ pkgs.curl ];
+    text = ''
+      send() {
+        curl localhost:65534/msg --fail -H "content-type: application/json" \
+          -d "$(${voyager} --config-file-path ${voyagerConfig} "$@")"
+      }
+
+      send init-fetch --on union-devnet
+      send init-fetch --on ethereum-devnet
+      send handshake ethereum-devnet union-devnet \
+        --create-clients \
+        --client-a-config '{"client_type":"cometbls"}' \
+        --client-b-config "{\"checksum\":\"0x$(cat ${unionHome}/code-ids/ethereum_light_client_minimal)\"}" \
+        --open-connection \
+        --connection-ordering unordered \
+        --open-channel \
+        --port-a ${ethereumRelay} \
+        --port-b "wasm.${unionRelay}" \
+        --channel-version ucs01-relay-1 \
+        --channel-ordering unordered
+    '';
+  };
 in
 
 e2e.mkTestWithDevnetSetup {
   name = "ensure-blocks";
 
   testScript = ''
-    client.wait_until_succeeds('[[ $(curl http://devnetEth:9596/eth/v2/beacon/blocks/head --fail --silent | ${pkgs.lib.meta.getExe pkgs.jq} \'.data.message.slot | tonumber > 0\') == "true" ]]')
+    client.wait_until_succeeds('[[ $(curl http://devnetEth:9596/eth/v2/beacon/blocks/head --fail --silent | ${jq} \'.data.message.slot | tonumber > 0\') == "true" ]]')
+
+    client.succeed("RUST_LOG=info ${ensure-blocks} ${./scenarios/blocks.json} |& tee output.txt")
 
-    client.succeed("RUST_LOG=info ${ensure-blocks} ws://union:26657/websocket ws://devnetEth:8546 |& tee output.txt")
+    relayer.wait_for_unit("postgresql.service")
+    relayer.succeed("${voyager} --config-file-path ${voyagerConfig} run-migrations")
+    relayer.wait_for_open_port(65534)
+    relayer.succeed("${pkgs.lib.meta.getExe open-channel}")
+
+    union.wait_until_succeeds('[[ $(curl http://localhost:1317/ibc/core/channel/v1/channels --fail --silent | ${jq} -r ".channels[] | select(.port_id == \\"wasm.${unionRelay}\\") | .state") == "STATE_OPEN" ]]', timeout=1800)
+
+    # the scenario refers to the relay on union, whose address is only known once the devnet is built
+    client.succeed("sed \"s/@UNION_RELAY@/${unionRelay}/g\" ${./scenarios/transfer.json} > transfer.json")
+    client.succeed("RUST_LOG=info ${ensure-blocks} transfer.json |& tee -a output.txt")
 
     client.copy_from_vm("output.txt", "")
   '';
@@ -19,6 +139,38 @@ e2e.mkTestWithDevnetSetup {
   nodes = {
     # empty node used to communicate with the other nodes
     client = _: { };
+
+    # proves union blocks for the cometbls client on ethereum and relays the packets of the scenarios
+    relayer = _: {
+      imports = [
+        inputs.self.nixosModules.galoisd
+        inputs.self.nixosModules.voyager
+      ];
+      virtualisation = {
+        diskSize = 16 * 1024;
+        memorySize = 16 * 1024;
+      };
+      services.postgresql = {
+        enable = true;
+        ensureDatabases = [ "voyager" ];
+        ensureUsers = [{ name = "voyager"; ensureDBOwnership = true; }];
+        authentication = ''
+          host voyager voyager 127.0.0.1/32 trust
+          host voyager voyager ::1/128 trust
+        '';
+      };
+      services.galoisd = {
+        enable = true;
+        host = "localhost:9999";
+      };
+      services.voyager = {
+        enable = true;
+        inherit chains;
+        db-url = dbUrl;
+        voyager-extra = voyagerExtra;
+        log-format = "text";
+      };
+      environment.systemPackages = [ pkgs.curl ];
+    };
   };
 }
-
@@ -0,0 +1,583 @@
+use std::sync::Arc;
+
+use chain_utils::cosmos_sdk::{CosmosSdkChainExt, CosmosSdkChainRpcs, GasConfig};
+use contracts::{
+    erc20,
+    ibc_packet::{IBCPacket, SendPacketFilter},
+    ucs01_relay::UCS01Relay,
+};
+use ethers::{
+    contract::parse_log,
+    middleware::SignerMiddleware,
+    providers::{Middleware, Provider, Ws},
+    signers::{LocalWallet, Signer},
+    types::Address,
+    utils::secret_key_to_address,
+};
+use futures::{Stream, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+use tendermint_rpc::{
+    client::CompatMode,
+    endpoint::tx::Response as TxResponse,
+    query::{EventType, Query},
+    Client, Order, SubscriptionClient, WebSocketClient,
+};
+use tracing::info;
+use ucs01_evm_client::resolve_denom;
+use ucs01_relay::msg::{ExecuteMsg, ListDenomsResponse, QueryMsg, TransferMsg};
+use ucs01_relay_api::{
+    protocol::{ATTR_ERROR, ATTR_SUCCESS, PACKET_EVENT},
+    types::Ucs01Ack,
+};
+use unionlabs::{
+    cosmos::base::coin::Coin,
+    encoding::{DecodeAs, EthAbi},
+    google::protobuf::any::mk_any,
+    signer::CosmosSigner,
+    ErrorReporter,
+};
+
+use crate::scenario::{BalanceQuery, ChainConfig, EthereumConfig, Transfer, UnionConfig};
+
+pub enum Chain {
+    Union(Union),
+    Ethereum(Ethereum),
+}
+
+pub struct Union {
+    chain_id: String,
+    signer: CosmosSigner,
+    tm_client: WebSocketClient,
+    grpc_url: String,
+    gas_config: GasConfig,
+}
+
+pub struct Ethereum {
+    provider: Provider<Ws>,
+    wallet: LocalWallet,
+    ibc_handler_address: Address,
+}
+
+/// A packet sent by a transfer, identified by its channels and sequence.
+#[derive(Debug)]
+pub struct SentPacket {
+    pub tx_hash: String,
+    pub source_channel: String,
+    pub destination_channel: String,
+    pub sequence: u64,
+    /// The height of the source chain the packet was sent at.
+    pub source_height: u64,
+}
+
+impl Chain {
+    pub async fn connect(config: ChainConfig) -> Result<Self, String> {
+        match config {
+            ChainConfig::Union(config) => Union::connect(config).await.map(Chain::Union),
+            ChainConfig::Ethereum(config) => Ethereum::connect(config).await.map(Chain::Ethereum),
+        }
+    }
+
+    pub async fn latest_height(&self) -> Result<u64, String> {
+        match self {
+            Chain::Union(union) => union
+                .tm_client
+                .latest_block()
+                .await
+                .map(|block| block.block.header.height.value())
+                .map_err(|err| format!("cannot fetch latest block: {err}")),
+            Chain::Ethereum(ethereum) => ethereum
+                .provider
+                .get_block_number()
+                .await
+                .map(|number| number.as_u64())
+                .map_err(|err| format!("cannot fetch latest block: {err}")),
+        }
+    }
+
+    /// Waits for `blocks` new blocks, printing each one as it arrives.
+    pub async fn wait_for_blocks(&self, name: &str, blocks: usize) -> Result<(), String> {
+        let seen = match self {
+            Chain::Union(union) => {
+                let subscription = union
+                    .tm_client
+                    .subscribe(EventType::NewBlock.into())
+                    .await
+                    .map_err(|err| format!("{name}: cannot subscribe to blocks: {err}"))?;
+                count_blocks(name, subscription, blocks).await
+            }
+            Chain::Ethereum(ethereum) => {
+                let subscription = ethereum
+                    .provider
+                    .subscribe_blocks()
+                    .await
+                    .map_err(|err| format!("{name}: cannot subscribe to blocks: {err}"))?;
+                count_blocks(name, subscription, blocks).await
+            }
+        };
+
+        if seen == blocks {
+            Ok(())
+        } else {
+            Err(format!(
+                "{name}: block subscription ended after {seen} of {blocks} blocks"
+            ))
+        }
+    }
+
+    pub async fn balance(&self, query: &BalanceQuery) -> Result<u128, String> {
+        match self {
+            Chain::Union(union) => union.balance(query).await,
+            Chain::Ethereum(ethereum) => ethereum.balance(query).await,
+        }
+    }
+
+    pub async fn transfer(&self, transfer: &Transfer) -> Result<SentPacket, String> {
+        match self {
+            Chain::Union(union) => union.transfer(transfer).await,
+            Chain::Ethereum(ethereum) => ethereum.transfer(transfer).await,
+        }
+    }
+
+    /// Whether `packet` has been received on this chain, looking at blocks from `from_height` on.
+    pub async fn received(&self, packet: &SentPacket, from_height: u64) -> Result<bool, String> {
+        match self {
+            Chain::Union(union) => Ok(union
+                .packet_event_tx("recv_packet", packet)
+                .await?
+                .is_some()),
+            Chain::Ethereum(ethereum) => {
+                let events = ethereum
+                    .ibc_packet()
+                    .recv_packet_filter()
+                    .from_block(from_height)
+                    .query()
+                    .await
+                    .map_err(|err| format!("cannot query RecvPacket events: {err}"))?;
+                Ok(events.iter().any(|event| {
+                    event.packet.sequence == packet.sequence
+                        && event.packet.source_channel == packet.source_channel
+                        && event.packet.destination_channel == packet.destination_channel
+                }))
+            }
+        }
+    }
+
+    /// Whether `packet`, sent from this chain, has been acknowledged. An error acknowledgement is an error.
+    pub async fn acknowledged(&self, packet: &SentPacket) -> Result<bool, String> {
+        match self {
+            Chain::Union(union) => union.acknowledged(packet).await,
+            Chain::Ethereum(ethereum) => {
+                let events = ethereum
+                    .ibc_packet()
+                    .acknowledge_packet_filter()
+                    .from_block(packet.source_height)
+                    .query()
+                    .await
+                    .map_err(|err| format!("cannot query AcknowledgePacket events: {err}"))?;
+                let Some(ack) = events.iter().find(|event| {
+                    event.packet.sequence == packet.sequence
+                        && event.packet.source_channel == packet.source_channel
+                        && event.packet.destination_channel == packet.destination_channel
+                }) else {
+                    return Ok(false);
+                };
+                info!(acknowledgement = %ack.acknowledgement, "packet acknowledged");
+                // the relay on EVM chains only speaks UCS01
+                match Ucs01Ack::decode_as::<EthAbi>(ack.acknowledgement.as_ref()) {
+                    Ok(Ucs01Ack::Success) => Ok(true),
+                    _ => Err(format!(
+                        "packet was acknowledged with an error: {}",
+                        ack.acknowledgement
+                    )),
+                }
+            }
+        }
+    }
+}
+
+async fn count_blocks<T>(chain: &str, blocks: impl Stream<Item = T>, n: usize) -> usize {
+    blocks
+        .take(n)
+        .enumerate()
+        .map(|(i, _)| println!("{chain}: block {}", i + 1))
+        .count()
+        .await
+}
+
+impl Union {
+    async fn connect(config: UnionConfig) -> Result<Self, String> {
+        let (tm_client, driver) = WebSocketClient::builder(config.ws_url.clone())
+            .compat_mode(CompatMode::V0_37)
+            .build()
+            .await
+            .map_err(|err| format!("cannot connect to {}: {err}", config.ws_url))?;
+        tokio::spawn(async move { driver.run().await });
+
+        let chain_id = tm_client
+            .status()
+            .await
+            .map_err(|err| format!("cannot fetch status: {err}"))?
+            .node_info
+            .network
+            .to_string();
+
+        Ok(Self {
+            chain_id,
+            signer: CosmosSigner::new(config.signer.value(), "union".to_owned()),
+            tm_client,
+            grpc_url: config.grpc_url,
+            gas_config: config.gas_config,
+        })
+    }
+
+    async fn balance(&self, query: &BalanceQuery) -> Result<u128, String> {
+        let denom = match (&query.relay, &query.channel) {
+            (Some(relay), Some(channel)) => {
+                match self.local_denom(relay, channel, &query.denom).await? {
+                    Some(denom) => denom,
+                    None => return Ok(0),
+                }
+            }
+            _ => query.denom.clone(),
+        };
+
+        let balance = protos::cosmos::bank::v1beta1::query_client::QueryClient::connect(
+            self.grpc_url.clone(),
+        )
+        .await
+        .map_err(|err| format!("cannot connect to {}: {err}", self.grpc_url))?
+        .balance(protos::cosmos::bank::v1beta1::QueryBalanceRequest {
+            address: query.address.clone(),
+            denom: denom.clone(),
+        })
+        .await
+        .map_err(|err| format!("cannot query balance of {}: {err}", query.address))?
+        .into_inner()
+        .balance;
+
+        balance.map_or(Ok(0), |coin| {
+            coin.amount
+                .parse()
+                .map_err(|err| format!("invalid amount {} of {denom}: {err}", coin.amount))
+        })
+    }
+
+    /// The token factory denom `relay` mints for `foreign_denom` received over `channel`, if any was received yet.
+    async fn local_denom(
+        &self,
+        relay: &str,
+        channel: &str,
+        foreign_denom: &str,
+    ) -> Result<Option<String>, String> {
+        let mut start_after = None;
+        loop {
+            let ListDenomsResponse { mut denoms } = self
+                .query_contract(
+                    relay,
+                    &QueryMsg::ListDenoms {
+                        channel: channel.to_owned(),
+                        start_after: start_after.take(),
+                        limit: None,
+                    },
+                )
+                .await?;
+            if let Some(mapping) = denoms.iter().find(|m| m.foreign_denom == foreign_denom) {
+                return Ok(Some(mapping.local_denom.clone()));
+            }
+            match denoms.pop() {
+                Some(last) => start_after = Some(last.foreign_denom),
+                None => return Ok(None),
+            }
+        }
+    }
+
+    async fn query_contract<Q: Serialize, R: DeserializeOwned>(
+        &self,
+        contract: &str,
+        query: &Q,
+    ) -> Result<R, String> {
+        let response =
+            protos::cosmwasm::wasm::v1::query_client::QueryClient::connect(self.grpc_url.clone())
+                .await
+                .map_err(|err| format!("cannot connect to {}: {err}", self.grpc_url))?
+                .smart_contract_state(protos::cosmwasm::wasm::v1::QuerySmartContractStateRequest {
+                    address: contract.to_owned(),
+                    query_data: serde_json::to_vec(query).unwrap(),
+                })
+                .await
+                .map_err(|err| format!("query to {contract} failed: {err}"))?
+                .into_inner();
+
+        serde_json::from_slice(&response.data)
+            .map_err(|err| format!("invalid response from {contract}: {err}"))
+    }
+
+    async fn transfer(&self, transfer: &Transfer) -> Result<SentPacket, String> {
+        let msg = protos::cosmwasm::wasm::v1::MsgExecuteContract {
+            sender: self.signer.to_string(),
+            contract: transfer.relay.clone(),
+            msg: serde_json::to_vec(&ExecuteMsg::Transfer(TransferMsg {
+                channel: transfer.channel.clone(),
+                receiver: transfer.receiver.clone(),
+                timeout: None,
+                memo: transfer.memo.clone(),
+                fees: None,
+                relayer_fee: None,
+            }))
+            .unwrap(),
+            funds: transfer
+                .tokens
+                .iter()
+                .map(|token| {
+                    Coin {
+                        denom: token.denom.clone(),
+                        amount: token.amount,
+                    }
+                    .into()
+                })
+                .collect(),
+        };
+
+        let (tx_hash, _) = self
+            .broadcast_tx_commit(&self.signer, [mk_any(&msg)], String::new())
+            .await
+            .map_err(|err| format!("transfer failed: {err}"))?;
+        let tx = self
+            .tm_client
+            .tx(hex::encode_upper(tx_hash.0).parse().unwrap(), false)
+            .await
+            .map_err(|err| format!("cannot fetch transfer {tx_hash}: {err}"))?;
+
+        let send_packet = tx
+            .tx_result
+            .events
+            .iter()
+            .find(|event| event.kind == "send_packet")
+            .ok_or_else(|| format!("transfer {tx_hash} did not send a packet"))?;
+        let attribute = |key: &str| {
+            send_packet
+                .attributes
+                .iter()
+                .find(|attr| attr.key == key)
+                .map(|attr| attr.value.clone())
+                .ok_or_else(|| format!("send_packet event of {tx_hash} has no {key}"))
+        };
+
+        Ok(SentPacket {
+            tx_hash: tx_hash.to_string(),
+            source_channel: attribute("packet_src_channel")?,
+            destination_channel: attribute("packet_dst_channel")?,
+            sequence: attribute("packet_sequence")?
+                .parse()
+                .map_err(|err| format!("invalid packet sequence in {tx_hash}: {err}"))?,
+            source_height: tx.height.value(),
+        })
+    }
+
+    /// The transaction which emitted an event of type `kind` for `packet`, as indexed by the node.
+    async fn packet_event_tx(
+        &self,
+        kind: &str,
+        packet: &SentPacket,
+    ) -> Result<Option<TxResponse>, String> {
+        let response = self
+            .tm_client
+            .tx_search(
+                Query::eq(
+                    format!("{kind}.packet_sequence"),
+                    packet.sequence.to_string(),
+                )
+                .and_eq(
+                    format!("{kind}.packet_src_channel"),
+                    packet.source_channel.clone(),
+                )
+                .and_eq(
+                    format!("{kind}.packet_dst_channel"),
+                    packet.destination_channel.clone(),
+                ),
+                false,
+                1,
+                1,
+                Order::Ascending,
+            )
+            .await
+            .map_err(|err| format!("cannot search for {kind} events: {err}"))?;
+        Ok(response.txs.into_iter().next())
+    }
+
+    async fn acknowledged(&self, packet: &SentPacket) -> Result<bool, String> {
+        let Some(tx) = self.packet_event_tx("acknowledge_packet", packet).await? else {
+            return Ok(false);
+        };
+
+        // the events of the relay handling the acknowledgement follow the acknowledge_packet event of its message,
+        // and report the result of the packet as either a success or an error attribute.
+        let events = &tx.tx_result.events;
+        let attribute = |event: &tendermint::abci::Event, key: &str| {
+            event
+                .attributes
+                .iter()
+                .find(|attr| attr.key == key)
+                .map(|attr| attr.value.clone())
+        };
+        let Some(start) = events.iter().position(|event| {
+            event.kind == "acknowledge_packet"
+                && attribute(event, "packet_sequence") == Some(packet.sequence.to_string())
+                && attribute(event, "packet_src_channel") == Some(packet.source_channel.clone())
+                && attribute(event, "packet_dst_channel")
+                    == Some(packet.destination_channel.clone())
+        }) else {
+            return Err(format!(
+                "{} has no acknowledge_packet event for the packet",
+                tx.hash
+            ));
+        };
+        let mut results = events[start + 1..]
+            .iter()
+            .take_while(|event| event.kind != "acknowledge_packet")
+            .filter(|event| event.kind == format!("wasm-{PACKET_EVENT}"));
+        if results
+            .clone()
+            .any(|event| attribute(event, ATTR_SUCCESS).is_some())
+        {
+            return Ok(true);
+        }
+
+        let error = results
+            .find_map(|event| attribute(event, ATTR_ERROR))
+            .unwrap_or_default();
+        Err(format!(
+            "packet was acknowledged with an error in {}: {error}",
+            tx.hash
+        ))
+    }
+}
+
+impl CosmosSdkChainRpcs for Union {
+    fn tm_chain_id(&self) -> String {
+        self.chain_id.clone()
+    }
+
+    fn grpc_url(&self) -> String {
+        self.grpc_url.clone()
+    }
+
+    fn tm_client(&self) -> &WebSocketClient {
+        &self.tm_client
+    }
+
+    fn gas_config(&self) -> &GasConfig {
+        &self.gas_config
+    }
+}
+
+impl Ethereum {
+    async fn connect(config: EthereumConfig) -> Result<Self, String> {
+        let provider = Provider::new(
+            Ws::connect(config.ws_url.as_str())
+                .await
+                .map_err(|err| format!("cannot connect to {}: {err}", config.ws_url))?,
+        );
+
+        let chain_id = provider
+            .get_chainid()
+            .await
+            .map_err(|err| format!("cannot fetch chain id: {err}"))?;
+
+        let signer = config.signer.value();
+        let address = secret_key_to_address(&signer);
+        let wallet = LocalWallet::new_with_signer(signer, address, chain_id.as_u64());
+
+        Ok(Self {
+            provider,
+            wallet,
+            ibc_handler_address: config.ibc_handler_address,
+        })
+    }
+
+    fn ibc_packet(&self) -> IBCPacket<Provider<Ws>> {
+        IBCPacket::new(self.ibc_handler_address, Arc::new(self.provider.clone()))
+    }
+
+    async fn balance(&self, query: &BalanceQuery) -> Result<u128, String> {
+        let token = match (&query.relay, &query.channel) {
+            (Some(relay), Some(channel)) => {
+                let relay = UCS01Relay::new(parse_address(relay)?, Arc::new(self.provider.clone()));
+                match resolve_denom(&relay, channel, &query.denom)
+                    .await
+                    .map_err(|err| format!("cannot resolve {}: {err}", query.denom))?
+                {
+                    Some(token) => token,
+                    None => return Ok(0),
+                }
+            }
+            _ => parse_address(&query.denom)?,
+        };
+
+        let balance = erc20::ERC20::new(token, Arc::new(self.provider.clone()))
+            .balance_of(parse_address(&query.address)?)
+            .await
+            .map_err(|err| format!("cannot query balance of {}: {err}", query.address))?;
+        u128::try_from(balance)
+            .map_err(|_| format!("balance {balance} of {} overflows u128", query.address))
+    }
+
+    async fn transfer(&self, transfer: &Transfer) -> Result<SentPacket, String> {
+        let signer_middleware = Arc::new(SignerMiddleware::new(
+            self.provider.clone(),
+            self.wallet.clone(),
+        ));
+        let relay = UCS01Relay::new(parse_address(&transfer.relay)?, signer_middleware);
+
+        let receiver = hex::decode(transfer.receiver.trim_start_matches("0x"))
+            .map_err(|err| format!("receiver {} is not hex: {err}", transfer.receiver))?;
+        let tokens = transfer
+            .tokens
+            .iter()
+            .map(|token| (token.denom.clone(), token.amount))
+            .collect::<Vec<_>>();
+
+        let sent = ucs01_evm_client::send(
+            &relay,
+            self.wallet.address(),
+            &transfer.channel,
+            receiver.into(),
+            &tokens,
+            transfer.memo.clone(),
+            true,
+        )
+        .await
+        .map_err(|err| ErrorReporter(err).to_string())?;
+
+        let tx_hash = format!("{:?}", sent.receipt.transaction_hash);
+        let send_packet = sent
+            .receipt
+            .logs
+            .iter()
+            .find_map(|log| parse_log::<SendPacketFilter>(log.clone()).ok())
+            .ok_or_else(|| format!("transfer {tx_hash} did not emit a SendPacket event"))?;
+        let (_, _, counterparty, _) = self
+            .ibc_packet()
+            .channels(send_packet.source_port, send_packet.source_channel.clone())
+            .call()
+            .await
+            .map_err(|err| format!("cannot query channel {}: {err}", send_packet.source_channel))?;
+
+        Ok(SentPacket {
+            tx_hash,
+            source_channel: sent.event.channel_id,
+            destination_channel: counterparty.channel_id,
+            sequence: sent.event.packet_sequence,
+            source_height: sent
+                .receipt
+                .block_number
+                .map_or(0, |number| number.as_u64()),
+        })
+    }
+}
+
+fn parse_address(address: &str) -> Result<Address, String> {
+    address
+        .parse()
+        .map_err(|err| format!("invalid address {address}: {err}"))
+}
@@ -1,60 +1,248 @@
-use std::fmt::Debug;
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    future::Future,
+    path::PathBuf,
+    process::ExitCode,
+    time::{Duration, Instant},
+};
 
+use chain::{Chain, SentPacket};
 use clap::Parser;
-use ethers::providers::{Middleware, Provider, StreamExt, Ws};
-use tendermint_rpc::{query::EventType, SubscriptionClient, WebSocketClient};
-use tokio::join;
+use futures::future::try_join_all;
+use scenario::{BalanceQuery, Scenario, Step, Transfer};
+use tokio::time::{sleep, timeout};
 
-const BLOCKS_TO_WAIT_FOR: usize = 10;
+mod chain;
+mod scenario;
+
+/// How often to check whether a packet was received or acknowledged.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long to spend collecting the state of the chains after a step failed.
+const DIAGNOSTICS_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Parser)]
 struct Args {
-    union: String,
-    /// Websocket URL for connection to sepolia
-    sepolia: String,
+    /// The scenario to run, as JSON.
+    scenario: PathBuf,
 }
 
 #[tokio::main(flavor = "multi_thread")]
-async fn main() {
+async fn main() -> ExitCode {
     tracing_subscriber::fmt::init();
 
     let args = Args::parse();
 
-    do_main(args).await;
+    let scenario = match Scenario::read(&args.scenario) {
+        Ok(scenario) => scenario,
+        Err(err) => {
+            eprintln!("invalid scenario {}: {err}", args.scenario.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(errors) = scenario.validate() {
+        for err in errors {
+            eprintln!("{err}");
+        }
+        return ExitCode::FAILURE;
+    }
+
+    let mut runner = match Runner::connect(scenario.chains).await {
+        Ok(runner) => runner,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    for (i, step) in scenario.steps.iter().enumerate() {
+        println!("step {i}: {step}");
+        let started = Instant::now();
+        if let Err(err) = runner.run(step).await {
+            eprintln!(
+                "step {i} ({step}) failed after {:.1?}: {err}",
+                started.elapsed()
+            );
+            if timeout(DIAGNOSTICS_TIMEOUT, runner.diagnose())
+                .await
+                .is_err()
+            {
+                eprintln!("timed out collecting diagnostics");
+            }
+            return ExitCode::FAILURE;
+        }
+        println!("step {i} passed in {:.1?}", started.elapsed());
+    }
+
+    ExitCode::SUCCESS
 }
 
-async fn do_main(args: Args) {
-    let provider = Provider::new(Ws::connect(args.sepolia).await.unwrap());
+/// The state of a scenario between its steps.
+struct Runner<'a> {
+    chains: BTreeMap<String, Chain>,
+    transfers: BTreeMap<&'a str, SentTransfer<'a>>,
+    balances: BTreeMap<&'a str, (&'a BalanceQuery, u128)>,
+}
 
-    let (tm_client, driver) = WebSocketClient::builder(args.union.parse().unwrap())
-        .compat_mode(tendermint_rpc::client::CompatMode::V0_37)
-        .build()
-        .await
-        .unwrap();
-    tokio::spawn(async move { driver.run().await });
-
-    let sepolia_blocks = fetch_blocks("sepolia", provider.subscribe_blocks().await.unwrap());
-    let union_blocks = fetch_blocks(
-        "union",
-        tm_client
-            .subscribe(EventType::NewBlock.into())
-            .await
-            .unwrap(),
-    );
-
-    join!(union_blocks, sepolia_blocks);
+struct SentTransfer<'a> {
+    transfer: &'a Transfer,
+    packet: SentPacket,
+    /// The height of the destination chain before the packet was sent.
+    destination_height: u64,
 }
 
-async fn fetch_blocks<T: Debug>(chain: &'static str, blocks: impl StreamExt<Item = T>) {
-    let blocks = blocks
-        .take(BLOCKS_TO_WAIT_FOR)
-        .enumerate()
-        .map(|(n, block)| {
-            println!("{chain}: block {}", n + 1);
-            block
+impl<'a> Runner<'a> {
+    async fn connect(
+        configs: BTreeMap<String, scenario::ChainConfig>,
+    ) -> Result<Runner<'a>, String> {
+        let mut chains = BTreeMap::new();
+        for (name, config) in configs {
+            let chain = Chain::connect(config)
+                .await
+                .map_err(|err| format!("cannot connect to {name}: {err}"))?;
+            chains.insert(name, chain);
+        }
+
+        Ok(Self {
+            chains,
+            transfers: BTreeMap::new(),
+            balances: BTreeMap::new(),
         })
-        .collect::<Vec<_>>()
-        .await;
+    }
+
+    async fn run(&mut self, step: &'a Step) -> Result<(), String> {
+        match step {
+            Step::WaitForBlocks {
+                chains,
+                blocks,
+                timeout,
+            } => {
+                let waits = chains
+                    .iter()
+                    .map(|name| self.chains[name].wait_for_blocks(name, *blocks));
+                within(*timeout, "the blocks", try_join_all(waits)).await?;
+            }
+            Step::Transfer(transfer) => {
+                let destination_height = self.chains[&transfer.to]
+                    .latest_height()
+                    .await
+                    .map_err(|err| format!("{}: {err}", transfer.to))?;
+                let packet = self.chains[&transfer.from]
+                    .transfer(transfer)
+                    .await
+                    .map_err(|err| format!("{}: {err}", transfer.from))?;
+                println!(
+                    "sent packet {} from {} to {} in {}",
+                    packet.sequence,
+                    packet.source_channel,
+                    packet.destination_channel,
+                    packet.tx_hash
+                );
+                self.transfers.insert(
+                    &transfer.name,
+                    SentTransfer {
+                        transfer,
+                        packet,
+                        destination_height,
+                    },
+                );
+            }
+            Step::PacketReceived { transfer, timeout } => {
+                let sent = &self.transfers[transfer.as_str()];
+                let destination = &self.chains[&sent.transfer.to];
+                within(*timeout, "the packet to be received", async {
+                    while !destination
+                        .received(&sent.packet, sent.destination_height)
+                        .await?
+                    {
+                        sleep(POLL_INTERVAL).await;
+                    }
+                    Ok(())
+                })
+                .await?;
+            }
+            Step::PacketAcknowledged { transfer, timeout } => {
+                let sent = &self.transfers[transfer.as_str()];
+                let source = &self.chains[&sent.transfer.from];
+                within(*timeout, "the packet to be acknowledged", async {
+                    while !source.acknowledged(&sent.packet).await? {
+                        sleep(POLL_INTERVAL).await;
+                    }
+                    Ok(())
+                })
+                .await?;
+            }
+            Step::RecordBalance(query) => {
+                let balance = self.chains[&query.chain].balance(query).await?;
+                println!("balance `{}` is {balance}", query.name);
+                self.balances.insert(&query.name, (query, balance));
+            }
+            Step::BalanceChanged { name, by } => {
+                let (query, before) = self.balances[name.as_str()];
+                let after = self.chains[&query.chain].balance(query).await?;
+                let change = if after >= before {
+                    i128::try_from(after - before)
+                } else {
+                    i128::try_from(before - after).map(|change| -change)
+                }
+                .map_err(|_| format!("balance `{name}` changed from {before} to {after}"))?;
+                if change != *by {
+                    return Err(format!(
+                        "balance `{name}` of {} in {} on {} changed by {change} ({before} -> {after}), expected {by}",
+                        query.address, query.denom, query.chain
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prints the latest height of every chain and how far each packet sent so far got.
+    async fn diagnose(&self) {
+        eprintln!("diagnostics:");
+        for (name, chain) in &self.chains {
+            match chain.latest_height().await {
+                Ok(height) => eprintln!("  {name}: latest height {height}"),
+                Err(err) => eprintln!("  {name}: {err}"),
+            }
+        }
+        for (name, sent) in &self.transfers {
+            let received = self.chains[&sent.transfer.to]
+                .received(&sent.packet, sent.destination_height)
+                .await;
+            let acknowledged = self.chains[&sent.transfer.from]
+                .acknowledged(&sent.packet)
+                .await;
+            eprintln!(
+                "  transfer `{name}`: packet {} from {} to {} sent in {} at height {}, received: {}, acknowledged: {}",
+                sent.packet.sequence,
+                sent.packet.source_channel,
+                sent.packet.destination_channel,
+                sent.packet.tx_hash,
+                sent.packet.source_height,
+                status(received),
+                status(acknowledged),
+            );
+        }
+    }
+}
+
+async fn within<T>(
+    secs: u64,
+    what: impl Display,
+    fut: impl Future<Output = Result<T, String>>,
+) -> Result<T, String> {
+    timeout(Duration::from_secs(secs), fut)
+        .await
+        .map_err(|_| format!("timed out after {secs}s waiting for {what}"))?
+}
 
-    assert_eq!(blocks.len(), BLOCKS_TO_WAIT_FOR, "{chain}");
+fn status(result: Result<bool, String>) -> String {
+    match result {
+        Ok(true) => "yes".to_owned(),
+        Ok(false) => "no".to_owned(),
+        Err(err) => err,
+    }
 }
@@ -0,0 +1,404 @@
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use chain_utils::{cosmos_sdk::GasConfig, private_key::PrivateKey};
+use ethers::{prelude::k256::ecdsa, types::Address};
+use serde::Deserialize;
+use tendermint_rpc::WebSocketClientUrl;
+
+/// The chains to connect to and the steps to run against them, in order. Steps refer to chains by their key in
+/// `chains`.
+///
+/// ```json
+/// {
+///   "chains": {
+///     "union": {
+///       "type": "union",
+///       "ws_url": "ws://union:26657/websocket",
+///       "grpc_url": "http://union:9090",
+///       "signer": { "raw": "0xaa82..." },
+///       "gas_config": { "gas_price": "1.0", "gas_denom": "muno", "gas_multiplier": "1.1", "max_gas": 10000000 }
+///     },
+///     "sepolia": {
+///       "type": "ethereum",
+///       "ws_url": "ws://devnetEth:8546",
+///       "ibc_handler_address": "0xed2a...",
+///       "signer": { "raw": "0x4e94..." }
+///     }
+///   },
+///   "steps": [
+///     { "wait_for_blocks": { "chains": ["union", "sepolia"], "blocks": 10 } },
+///     { "record_balance": { "name": "receiver", "chain": "sepolia", "address": "0xbe68...", "denom": "0xa9d0.../channel-0/muno", "relay": "0xa9d0...", "channel": "channel-0" } },
+///     { "transfer": { "name": "muno", "from": "union", "to": "sepolia", "relay": "union1...", "channel": "channel-0", "receiver": "be68...", "tokens": [{ "denom": "muno", "amount": 100 }] } },
+///     { "packet_received": { "transfer": "muno" } },
+///     { "packet_acknowledged": { "transfer": "muno" } },
+///     { "balance_changed": { "name": "receiver", "by": 100 } }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub chains: BTreeMap<String, ChainConfig>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChainConfig {
+    Union(UnionConfig),
+    Ethereum(EthereumConfig),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnionConfig {
+    pub ws_url: WebSocketClientUrl,
+    pub grpc_url: String,
+    /// Signs the transfers sent from this chain.
+    pub signer: PrivateKey<ecdsa::SigningKey>,
+    pub gas_config: GasConfig,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EthereumConfig {
+    pub ws_url: String,
+    /// The IBC handler, which emits the events of the packets sent to and from this chain.
+    pub ibc_handler_address: Address,
+    /// Signs the transfers sent from this chain, approving the relay to spend the transferred tokens if needed.
+    pub signer: PrivateKey<ecdsa::SigningKey>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    /// Waits for `blocks` new blocks on each of `chains`, concurrently.
+    WaitForBlocks {
+        chains: Vec<String>,
+        #[serde(default = "default_blocks")]
+        blocks: usize,
+        /// Seconds to wait for the blocks.
+        #[serde(default = "default_blocks_timeout")]
+        timeout: u64,
+    },
+    /// Sends a UCS01 transfer, remembering the packet it sent as `name`.
+    Transfer(Transfer),
+    /// Waits for the packet sent by the transfer `transfer` to be received on its destination.
+    PacketReceived {
+        transfer: String,
+        /// Seconds to wait for the packet, counted from the start of this step.
+        #[serde(default = "default_packet_timeout")]
+        timeout: u64,
+    },
+    /// Waits for the packet sent by the transfer `transfer` to be acknowledged on its source.
+    PacketAcknowledged {
+        transfer: String,
+        /// Seconds to wait for the acknowledgement, counted from the start of this step.
+        #[serde(default = "default_packet_timeout")]
+        timeout: u64,
+    },
+    /// Records a balance as `name`, to be compared by `balance_changed` later on.
+    RecordBalance(BalanceQuery),
+    /// Checks that the balance recorded as `name` changed by exactly `by`.
+    BalanceChanged { name: String, by: i128 },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transfer {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    /// The UCS01 relay on `from`.
+    pub relay: String,
+    /// The channel of the relay to send the packet on.
+    pub channel: String,
+    /// The receiver on `to`. Transfers from Ethereum take it hex encoded.
+    pub receiver: String,
+    pub tokens: Vec<Token>,
+    #[serde(default)]
+    pub memo: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Token {
+    /// A bank denom on Union, or either the address of an ERC20 or a denom received over `channel` on Ethereum.
+    pub denom: String,
+    pub amount: u128,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BalanceQuery {
+    pub name: String,
+    pub chain: String,
+    pub address: String,
+    pub denom: String,
+    /// The UCS01 relay on `chain`. If given along with `channel`, `denom` is resolved to the token the relay mints
+    /// for it when received over `channel`. Such a balance is zero until the first transfer of the denom arrives.
+    pub relay: Option<String>,
+    pub channel: Option<String>,
+}
+
+fn default_blocks() -> usize {
+    10
+}
+
+fn default_blocks_timeout() -> u64 {
+    300
+}
+
+fn default_packet_timeout() -> u64 {
+    600
+}
+
+impl Scenario {
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("could not read {}: {err}", path.display()))?;
+        serde_json::from_str(&contents).map_err(|err| err.to_string())
+    }
+
+    /// Checks that every step only refers to chains, transfers and balances defined before it, so that a typo in
+    /// the scenario does not surface only after waiting on the devnet.
+    pub fn validate(&self) -> Result<(), Vec<ScenarioError>> {
+        let mut errors = Vec::new();
+        let mut transfers: Vec<&str> = Vec::new();
+        let mut balances: Vec<&str> = Vec::new();
+
+        let check_chain = |step: usize, chain: &str, errors: &mut Vec<ScenarioError>| {
+            if !self.chains.contains_key(chain) {
+                errors.push(ScenarioError::UnknownChain {
+                    step,
+                    chain: chain.to_owned(),
+                });
+            }
+        };
+
+        for (step, s) in self.steps.iter().enumerate() {
+            match s {
+                Step::WaitForBlocks { chains, .. } => {
+                    for chain in chains {
+                        check_chain(step, chain, &mut errors);
+                    }
+                }
+                Step::Transfer(transfer) => {
+                    check_chain(step, &transfer.from, &mut errors);
+                    check_chain(step, &transfer.to, &mut errors);
+                    if transfer.from == transfer.to {
+                        errors.push(ScenarioError::TransferToSelf { step });
+                    }
+                    if transfer.tokens.is_empty() {
+                        errors.push(ScenarioError::NoTokens { step });
+                    }
+                    if transfers.contains(&transfer.name.as_str()) {
+                        errors.push(ScenarioError::DuplicateName {
+                            step,
+                            name: transfer.name.clone(),
+                        });
+                    }
+                    transfers.push(&transfer.name);
+                }
+                Step::PacketReceived { transfer, .. }
+                | Step::PacketAcknowledged { transfer, .. } => {
+                    if !transfers.contains(&transfer.as_str()) {
+                        errors.push(ScenarioError::UnknownTransfer {
+                            step,
+                            transfer: transfer.clone(),
+                        });
+                    }
+                }
+                Step::RecordBalance(query) => {
+                    check_chain(step, &query.chain, &mut errors);
+                    if query.relay.is_some() != query.channel.is_some() {
+                        errors.push(ScenarioError::IncompleteDenom { step });
+                    }
+                    if balances.contains(&query.name.as_str()) {
+                        errors.push(ScenarioError::DuplicateName {
+                            step,
+                            name: query.name.clone(),
+                        });
+                    }
+                    balances.push(&query.name);
+                }
+                Step::BalanceChanged { name, .. } => {
+                    if !balances.contains(&name.as_str()) {
+                        errors.push(ScenarioError::UnknownBalance {
+                            step,
+                            name: name.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::WaitForBlocks { chains, blocks, .. } => {
+                write!(f, "wait for {blocks} blocks on {}", chains.join(", "))
+            }
+            Step::Transfer(transfer) => write!(
+                f,
+                "transfer `{}` from {} to {} over {}",
+                transfer.name, transfer.from, transfer.to, transfer.channel
+            ),
+            Step::PacketReceived { transfer, .. } => {
+                write!(f, "packet of transfer `{transfer}` is received")
+            }
+            Step::PacketAcknowledged { transfer, .. } => {
+                write!(f, "packet of transfer `{transfer}` is acknowledged")
+            }
+            Step::RecordBalance(query) => write!(
+                f,
+                "record balance `{}` of {} in {} on {}",
+                query.name, query.address, query.denom, query.chain
+            ),
+            Step::BalanceChanged { name, by } => write!(f, "balance `{name}` changed by {by}"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    UnknownChain { step: usize, chain: String },
+    UnknownTransfer { step: usize, transfer: String },
+    UnknownBalance { step: usize, name: String },
+    DuplicateName { step: usize, name: String },
+    TransferToSelf { step: usize },
+    NoTokens { step: usize },
+    IncompleteDenom { step: usize },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::UnknownChain { step, chain } => {
+                write!(
+                    f,
+                    "step {step} uses chain {chain}, which is not in the chains"
+                )
+            }
+            ScenarioError::UnknownTransfer { step, transfer } => write!(
+                f,
+                "step {step} waits on transfer `{transfer}`, which is not sent by an earlier step"
+            ),
+            ScenarioError::UnknownBalance { step, name } => write!(
+                f,
+                "step {step} checks balance `{name}`, which is not recorded by an earlier step"
+            ),
+            ScenarioError::DuplicateName { step, name } => {
+                write!(f, "step {step} reuses the name `{name}`")
+            }
+            ScenarioError::TransferToSelf { step } => {
+                write!(f, "step {step} transfers from a chain to itself")
+            }
+            ScenarioError::NoTokens { step } => write!(f, "step {step} transfers no tokens"),
+            ScenarioError::IncompleteDenom { step } => write!(
+                f,
+                "step {step} needs both `relay` and `channel` to resolve its denom, or neither"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAINS: &str = r#"{
+        "union": {
+            "type": "union",
+            "ws_url": "ws://localhost:26657/websocket",
+            "grpc_url": "http://localhost:9090",
+            "signer": { "raw": "0xaa820fa947beb242032a41b6dc9a8b9c37d8f5fbcda0966b1ec80335b10a7d6f" },
+            "gas_config": { "gas_price": "1.0", "gas_denom": "muno", "gas_multiplier": "1.1", "max_gas": 10000000 }
+        },
+        "sepolia": {
+            "type": "ethereum",
+            "ws_url": "ws://localhost:8546",
+            "ibc_handler_address": "0xeda338e4dc46038493b885327842fd3e301cab39",
+            "signer": { "raw": "0x4e9444a6efd6d42725a250b650a781da2737ea308c839eaccb0f7f3dbd2fea77" }
+        }
+    }"#;
+
+    fn scenario(steps: &str) -> Scenario {
+        serde_json::from_str(&format!(r#"{{ "chains": {CHAINS}, "steps": {steps} }}"#)).unwrap()
+    }
+
+    #[test]
+    fn reads_transfer_scenario() {
+        let scenario = scenario(
+            r#"[
+                { "wait_for_blocks": { "chains": ["union", "sepolia"] } },
+                { "record_balance": { "name": "sender", "chain": "union", "address": "union1", "denom": "muno" } },
+                { "transfer": { "name": "muno", "from": "union", "to": "sepolia", "relay": "union1relay", "channel": "channel-0", "receiver": "0x0", "tokens": [{ "denom": "muno", "amount": 100 }] } },
+                { "packet_received": { "transfer": "muno", "timeout": 60 } },
+                { "packet_acknowledged": { "transfer": "muno" } },
+                { "balance_changed": { "name": "sender", "by": -100 } }
+            ]"#,
+        );
+
+        assert!(scenario.validate().is_ok());
+        assert!(matches!(
+            scenario.steps[0],
+            Step::WaitForBlocks {
+                blocks: 10,
+                timeout: 300,
+                ..
+            }
+        ));
+        assert!(matches!(
+            scenario.steps[3],
+            Step::PacketReceived { timeout: 60, .. }
+        ));
+        assert!(matches!(
+            scenario.steps[5],
+            Step::BalanceChanged { by: -100, .. }
+        ));
+    }
+
+    #[test]
+    fn shipped_scenarios_are_valid() {
+        for contents in [
+            include_str!("../scenarios/blocks.json"),
+            include_str!("../scenarios/transfer.json"),
+        ] {
+            let scenario: Scenario = serde_json::from_str(contents).unwrap();
+            assert!(scenario.validate().is_ok());
+        }
+    }
+
+    #[test]
+    fn reports_all_errors() {
+        let scenario = scenario(
+            r#"[
+                { "wait_for_blocks": { "chains": ["union", "osmosis"] } },
+                { "packet_received": { "transfer": "muno" } },
+                { "transfer": { "name": "muno", "from": "union", "to": "union", "relay": "union1relay", "channel": "channel-0", "receiver": "union1", "tokens": [] } },
+                { "record_balance": { "name": "sender", "chain": "union", "address": "union1", "denom": "muno", "channel": "channel-0" } },
+                { "balance_changed": { "name": "receiver", "by": 100 } }
+            ]"#,
+        );
+
+        let errors = scenario.validate().unwrap_err();
+        assert!(matches!(
+            errors.as_slice(),
+            [
+                ScenarioError::UnknownChain { step: 0, .. },
+                ScenarioError::UnknownTransfer { step: 1, .. },
+                ScenarioError::TransferToSelf { step: 2 },
+                ScenarioError::NoTokens { step: 2 },
+                ScenarioError::IncompleteDenom { step: 3 },
+                ScenarioError::UnknownBalance { step: 4, .. },
+            ]
+        ));
+    }
+}
@@ -32,6 +32,9 @@ struct NFTPacket {
     string sender;
     string receiver;
     string memo;
+    string classUri;
+    bytes classData;
+    bytes[] tokenData;
 }
 
 library NFTPacketLib {
@@ -40,17 +43,16 @@ library NFTPacketLib {
         pure
         returns (bytes memory)
     {
-        return abi.encode(
-            packet.classOwner,
-            packet.classId,
-            packet.className,
-            packet.classSymbol,
-            packet.tokenIds,
-            packet.tokenUris,
-            packet.sender,
-            packet.receiver,
-            packet.memo
-        );
+        // Encoding the fields one by one exceeds the stack, we encode the
+        // struct instead and drop the leading offset of the dynamic tuple,
+        // which yields the same flat encoding expected by `decode`.
+        bytes memory encoded = abi.encode(packet);
+        assembly {
+            let length := mload(encoded)
+            encoded := add(encoded, 0x20)
+            mstore(encoded, sub(length, 0x20))
+        }
+        return encoded;
     }
 
     function decode(bytes calldata stream)
@@ -58,6 +60,9 @@ library NFTPacketLib {
         pure
         returns (NFTPacket calldata)
     {
+        // Packets sent before classUri, classData and tokenData were added
+        // lack these fields. Only their leading fields are read when
+        // refunding them, which are laid out identically.
         NFTPacket calldata packet;
         assembly {
             packet := stream.offset
@@ -291,7 +296,10 @@ contract UCS02NFT is
             tokenUris: tokenUris,
             sender: sender,
             receiver: receiver,
-            memo: ""
+            memo: "",
+            classUri: "",
+            classData: "",
+            tokenData: new bytes[](0)
         }).encode();
     }
 
@@ -163,6 +163,13 @@
           rpc-url = "http://localhost:8545";
           private-key = "0xfffdbb37105441e14b0ee6330d855d8504ff39e705c3afa8f859ac9865f99306";
         }
+        {
+          # for use with the anvil node of devnet-compose
+          network = "anvil-devnet";
+          rpc-url = "http://localhost:8645";
+          # first of anvil's default accounts
+          private-key = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+        }
         {
           # for use with the local arbitrum devnet from offchainlabs/nitro-testnode
           network = "arbitrum-devnet";
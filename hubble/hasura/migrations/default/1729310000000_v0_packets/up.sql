@@ -0,0 +1,139 @@
+CREATE SCHEMA v0_packets;
+
+-- Last height processed per chain and per source (`events` for v0.events, `logs` for v0.logs).
+CREATE TABLE v0_packets.cursors (
+    chain_id integer NOT NULL REFERENCES v0.chains(id),
+    source text NOT NULL,
+    height integer NOT NULL,
+    PRIMARY KEY (chain_id, source)
+);
+
+-- Decoded packet events, located on the chain that emitted them.
+CREATE TABLE v0_packets.packet_events (
+    chain_id integer NOT NULL REFERENCES v0.chains(id),
+    height integer NOT NULL,
+    time timestamptz NOT NULL,
+    transaction_hash text,
+    -- Index of the event (Cosmos) or log (EVM) in the block.
+    index integer NOT NULL,
+    stage text NOT NULL,
+    sequence bigint NOT NULL,
+    source_port text NOT NULL,
+    source_channel text NOT NULL,
+    -- Not part of the EVM SendPacket event.
+    destination_port text,
+    destination_channel text,
+    data bytea,
+    acknowledgement bytea,
+    timeout_revision_number bigint,
+    timeout_revision_height bigint,
+    timeout_timestamp numeric,
+    PRIMARY KEY (chain_id, height, index)
+);
+
+CREATE INDEX packet_events_packet_idx
+    ON v0_packets.packet_events (source_port, source_channel, sequence, stage);
+
+-- Channels opened on each chain (`channel_open_init` and `channel_open_try`), with their connection.
+CREATE TABLE v0_packets.channels (
+    chain_id integer NOT NULL REFERENCES v0.chains(id),
+    height integer NOT NULL,
+    port_id text NOT NULL,
+    channel_id text NOT NULL,
+    connection_id text NOT NULL,
+    PRIMARY KEY (chain_id, port_id, channel_id)
+);
+
+CREATE INDEX channels_height_idx ON v0_packets.channels (chain_id, height);
+
+-- Connections opened on each chain (`connection_open_init` and `connection_open_try`), with their client.
+CREATE TABLE v0_packets.connections (
+    chain_id integer NOT NULL REFERENCES v0.chains(id),
+    height integer NOT NULL,
+    connection_id text NOT NULL,
+    client_id text NOT NULL,
+    PRIMARY KEY (chain_id, connection_id)
+);
+
+CREATE INDEX connections_height_idx ON v0_packets.connections (chain_id, height);
+
+-- One row per sent packet, keyed by (source_chain_id, source_port, source_channel, sequence).
+--
+-- The destination chain is the counterparty chain of the client underlying the source channel,
+-- resolved through v0_packets.channels, v0_packets.connections and v0.clients. Received packets
+-- are only matched once it is known. The destination endpoint of packets sent from EVM chains is
+-- only known once acknowledged or timed out.
+CREATE VIEW v0_packets.packets AS
+SELECT
+    send.chain_id AS source_chain_id,
+    send.source_port,
+    send.source_channel,
+    send.sequence,
+    counterparty.id AS destination_chain_id,
+    COALESCE(send.destination_port, ack.destination_port, timeout.destination_port, recv.destination_port) AS destination_port,
+    COALESCE(send.destination_channel, ack.destination_channel, timeout.destination_channel, recv.destination_channel) AS destination_channel,
+    send.data,
+    COALESCE(write_ack.acknowledgement, ack.acknowledgement) AS acknowledgement,
+    send.timeout_revision_number,
+    send.timeout_revision_height,
+    send.timeout_timestamp,
+    send.height AS send_height,
+    send.time AS send_time,
+    send.transaction_hash AS send_transaction_hash,
+    recv.height AS recv_height,
+    recv.time AS recv_time,
+    recv.transaction_hash AS recv_transaction_hash,
+    write_ack.height AS write_ack_height,
+    write_ack.time AS write_ack_time,
+    write_ack.transaction_hash AS write_ack_transaction_hash,
+    ack.height AS ack_height,
+    ack.time AS ack_time,
+    ack.transaction_hash AS ack_transaction_hash,
+    timeout.height AS timeout_height,
+    timeout.time AS timeout_time,
+    timeout.transaction_hash AS timeout_transaction_hash
+FROM v0_packets.packet_events send
+LEFT JOIN v0_packets.channels chan
+    ON chan.chain_id = send.chain_id AND (chan.port_id, chan.channel_id) = (send.source_port, send.source_channel)
+LEFT JOIN v0_packets.connections conn
+    ON conn.chain_id = send.chain_id AND conn.connection_id = chan.connection_id
+LEFT JOIN v0.clients cl
+    ON cl.chain_id = send.chain_id AND cl.client_id = conn.client_id
+LEFT JOIN v0.chains counterparty
+    ON counterparty.chain_id = cl.counterparty_chain_id
+LEFT JOIN LATERAL (
+    SELECT * FROM v0_packets.packet_events e
+    WHERE e.stage = 'acknowledge_packet'
+        AND e.chain_id = send.chain_id
+        AND (e.source_port, e.source_channel, e.sequence) = (send.source_port, send.source_channel, send.sequence)
+    ORDER BY e.height
+    LIMIT 1
+) ack ON true
+LEFT JOIN LATERAL (
+    SELECT * FROM v0_packets.packet_events e
+    WHERE e.stage = 'timeout_packet'
+        AND e.chain_id = send.chain_id
+        AND (e.source_port, e.source_channel, e.sequence) = (send.source_port, send.source_channel, send.sequence)
+    ORDER BY e.height
+    LIMIT 1
+) timeout ON true
+LEFT JOIN LATERAL (
+    SELECT * FROM v0_packets.packet_events e
+    WHERE e.stage = 'recv_packet'
+        AND e.chain_id = counterparty.id
+        AND (e.source_port, e.source_channel, e.sequence) = (send.source_port, send.source_channel, send.sequence)
+        AND e.destination_port = COALESCE(send.destination_port, ack.destination_port, timeout.destination_port, e.destination_port)
+        AND e.destination_channel = COALESCE(send.destination_channel, ack.destination_channel, timeout.destination_channel, e.destination_channel)
+    ORDER BY e.time
+    LIMIT 1
+) recv ON true
+LEFT JOIN LATERAL (
+    SELECT * FROM v0_packets.packet_events e
+    WHERE e.stage = 'write_acknowledgement'
+        AND e.chain_id = recv.chain_id
+        AND (e.source_port, e.source_channel, e.sequence) = (send.source_port, send.source_channel, send.sequence)
+        AND (e.destination_port, e.destination_channel) = (recv.destination_port, recv.destination_channel)
+    ORDER BY e.height
+    LIMIT 1
+) write_ack ON true
+WHERE send.stage = 'send_packet';
@@ -0,0 +1,2 @@
+DROP TABLE v0_evm.events;
+DROP TABLE v0_evm.cursors;
@@ -0,0 +1,31 @@
+-- The v0_evm schema already holds the views over the raw logs, such as v0_evm.client_created.
+CREATE SCHEMA IF NOT EXISTS v0_evm;
+
+-- Last height of v0.logs decoded per chain. Rewound by the EVM indexers when they rewrite logs.
+CREATE TABLE v0_evm.cursors (
+    chain_id integer NOT NULL REFERENCES v0.chains(id),
+    height integer NOT NULL,
+    PRIMARY KEY (chain_id)
+);
+
+-- Logs of the IBC contracts, decoded using their ABI.
+CREATE TABLE v0_evm.events (
+    chain_id integer NOT NULL REFERENCES v0.chains(id),
+    block_hash text NOT NULL,
+    height integer NOT NULL,
+    time timestamptz NOT NULL,
+    transaction_hash text NOT NULL,
+    transaction_index integer NOT NULL,
+    log_index integer NOT NULL,
+    -- Address of the contract emitting the event.
+    address text NOT NULL,
+    -- Name of the ABI the event was decoded with (IBCHandler, UCS01Relay or UCS02NFT).
+    contract text NOT NULL,
+    name text NOT NULL,
+    -- Decoded arguments, keyed by parameter name. Integers are encoded as decimal strings.
+    args jsonb NOT NULL,
+    PRIMARY KEY (chain_id, height, log_index)
+);
+
+CREATE INDEX events_contract_name_idx ON v0_evm.events (contract, name);
+CREATE INDEX events_address_idx ON v0_evm.events (address);
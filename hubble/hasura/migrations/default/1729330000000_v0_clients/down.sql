@@ -0,0 +1 @@
+DROP SCHEMA v0_clients CASCADE;
@@ -0,0 +1,74 @@
+CREATE SCHEMA v0_clients;
+
+-- Trusting periods read from the client states.
+CREATE TABLE v0_clients.trusting_periods (
+    chain_id integer NOT NULL REFERENCES v0.chains(id),
+    client_id text NOT NULL,
+    -- NULL for clients without a trusting period, such as the Ethereum light clients.
+    trusting_period interval,
+    PRIMARY KEY (chain_id, client_id)
+);
+
+-- Last height processed per chain and per source (`events` for v0.events, `logs` for v0.logs).
+CREATE TABLE v0_clients.cursors (
+    chain_id integer NOT NULL REFERENCES v0.chains(id),
+    source text NOT NULL,
+    height integer NOT NULL,
+    PRIMARY KEY (chain_id, source)
+);
+
+-- Client updates, decoded from the `update_client` events of CosmosSDK chains and the
+-- `ClientUpdated` logs of EVM chains.
+CREATE TABLE v0_clients.updates (
+    chain_id integer NOT NULL REFERENCES v0.chains(id),
+    height integer NOT NULL,
+    time timestamptz NOT NULL,
+    -- Index of the event (Cosmos) or log (EVM) in the block.
+    index integer NOT NULL,
+    client_id text NOT NULL,
+    consensus_height bigint NOT NULL,
+    PRIMARY KEY (chain_id, height, index)
+);
+
+CREATE INDEX updates_client_idx
+    ON v0_clients.updates (chain_id, client_id, height DESC, index DESC);
+
+-- The health of every client known to hubble (see v0.clients), based on its latest update in
+-- v0_clients.updates. The counterparty height is the highest indexed block or consensus height of
+-- the counterparty chain.
+CREATE VIEW v0_clients.client_status AS
+SELECT
+    cl.chain_id,
+    host.chain_id AS host_chain_id,
+    cl.client_id,
+    cl.counterparty_chain_id,
+    upd.consensus_height,
+    upd.height AS update_height,
+    upd.time AS update_time,
+    head.height AS counterparty_height,
+    head.height - upd.consensus_height AS height_lag,
+    now() - upd.time AS since_update,
+    tp.trusting_period,
+    -- The trusting period runs from the timestamp of the consensus state, approximated by the
+    -- update time when the counterparty block is not indexed.
+    tp.trusting_period - (now() - COALESCE(consensus_block.time, upd.time)) AS trusting_period_remaining
+FROM v0.clients cl
+JOIN v0.chains host ON host.id = cl.chain_id
+LEFT JOIN v0.chains counterparty ON counterparty.chain_id = cl.counterparty_chain_id
+LEFT JOIN v0_clients.trusting_periods tp ON tp.chain_id = cl.chain_id AND tp.client_id = cl.client_id
+LEFT JOIN LATERAL (
+    SELECT u.height, u.time, u.consensus_height FROM v0_clients.updates u
+    WHERE u.chain_id = cl.chain_id AND u.client_id = cl.client_id
+    ORDER BY u.height DESC, u.index DESC
+    LIMIT 1
+) upd ON true
+LEFT JOIN LATERAL (
+    SELECT GREATEST(
+        (SELECT MAX(consensus_height) FROM v0.consensus_heights WHERE chain_id = counterparty.id),
+        (SELECT MAX(height) FROM v0.blocks WHERE chain_id = counterparty.id)
+    ) AS height
+) head ON true
+LEFT JOIN LATERAL (
+    SELECT b.time FROM v0.blocks b
+    WHERE b.chain_id = counterparty.id AND b.height = upd.consensus_height
+) consensus_block ON true;
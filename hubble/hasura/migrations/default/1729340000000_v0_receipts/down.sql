@@ -0,0 +1 @@
+DROP TABLE v0.receipts;
@@ -0,0 +1,17 @@
+-- Receipts are the unit of execution on NEAR. A transaction is converted into a receipt, which
+-- may spawn more receipts, possibly executed in later blocks and on other shards.
+CREATE TABLE v0.receipts (
+    chain_id integer NOT NULL REFERENCES v0.chains(id),
+    block_hash text NOT NULL,
+    height integer NOT NULL,
+    transaction_hash text NOT NULL,
+    receipt_id text NOT NULL,
+    index integer NOT NULL,
+    executor_id text NOT NULL,
+    data jsonb NOT NULL,
+    time timestamptz NOT NULL,
+    PRIMARY KEY (chain_id, receipt_id)
+);
+
+CREATE INDEX receipts_height ON v0.receipts (chain_id, height);
+CREATE INDEX receipts_transaction_hash ON v0.receipts (chain_id, transaction_hash);
@@ -1,6 +1,14 @@
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc};
 
-use ethers::providers::{Http, Middleware, Provider};
+use color_eyre::eyre::{eyre, Report};
+use contracts::{
+    i_light_client::ILightClient, ibc_client::ClientCreatedFilter, ibc_handler::IBCHandler,
+};
+use ethers::{
+    contract::EthEvent,
+    providers::{Http, Middleware, Provider},
+    types::H256,
+};
 use prost::Message;
 use protos::ibc::{
     core::client::v1::QueryClientStateRequest, lightclients::wasm::v1::QueryCodeRequest,
@@ -29,11 +37,24 @@ pub async fn tx(db: PgPool, indexers: Indexers) {
 
     for indexer in indexers {
         match indexer {
-            IndexerConfig::Scroll(_) => {}
-            IndexerConfig::Arb(_) => {}
+            IndexerConfig::Scroll(scroll_config) => {
+                let provider = Provider::<Http>::try_from(scroll_config.l2_url.as_str()).unwrap();
+                evm_clients(&db, provider, &mut datas).await;
+            }
+            IndexerConfig::Arb(arb_config) => {
+                let provider = Provider::<Http>::try_from(arb_config.l2_url.as_str()).unwrap();
+                evm_clients(&db, provider, &mut datas).await;
+            }
+            // These only map consensus heights of chains indexed by another indexer.
             IndexerConfig::Beacon(_) => {}
             IndexerConfig::Bera(_) => {}
-            IndexerConfig::EthFork(_) => {}
+            // The counterparty chain ids of clients hosted on NEAR are not resolved yet.
+            IndexerConfig::Near(_) => {}
+            IndexerConfig::EthFork(fork_config) => {
+                let provider =
+                    Provider::<Http>::try_from(fork_config.urls[0].clone().as_str()).unwrap();
+                evm_clients(&db, provider, &mut datas).await;
+            }
             IndexerConfig::Tm(tm_config) => {
                 let client = HttpClient::new(tm_config.urls[0].as_str()).unwrap();
 
@@ -207,69 +228,7 @@ pub async fn tx(db: PgPool, indexers: Indexers) {
             IndexerConfig::Eth(eth_config) => {
                 let provider =
                     Provider::<Http>::try_from(eth_config.urls[0].clone().as_str()).unwrap();
-
-                let chain_id = provider.get_chainid().await.unwrap().as_u64().to_string();
-                dbg!("hi");
-
-                let eth_clients = sqlx::query!(
-                    r#"
-                    SELECT
-                        cl.transaction_hash, cl.client_id, ch.id
-                    FROM
-                        v0_evm.client_created cl
-                    JOIN
-                        v0.chains ch
-                    ON
-                        cl.chain_id = ch.id
-                    WHERE
-                        ch.chain_id = $1
-                    "#,
-                    chain_id
-                )
-                .fetch_all(&db)
-                .await
-                .unwrap();
-
-                for record in eth_clients {
-                    let Some(client_id) = record.client_id else {
-                        tracing::info!(
-                            internal_db_chain_id = record.id,
-                            %chain_id,
-                            "skipping record"
-                        );
-                        continue;
-                    };
-
-                    let tx = provider
-                        .get_transaction(
-                            ethers::types::H256::from_str(&record.transaction_hash.unwrap())
-                                .unwrap(),
-                        )
-                        .await
-                        .unwrap()
-                        .unwrap();
-
-                    let msg = match <contracts::ibc_handler::CreateClientCall as ethers::abi::AbiDecode>::decode(&tx.input) {
-                        Ok(msg) => msg,
-                        Err(err) => {
-                            warn!("could not decode CreateClientCall, most likely due to ABI change: {}", err);
-                            continue
-                        }
-                    };
-
-                    match &*msg.0.client_type {
-                        "cometbls" => {
-                            let cs = unionlabs::ibc::lightclients::cometbls::client_state::ClientState::decode_as::<EthAbi>(&msg.0.client_state_bytes).unwrap();
-
-                            datas.push(Data {
-                                chain_id: record.id,
-                                client_id,
-                                counterparty_chain_id: cs.chain_id.to_string(),
-                            })
-                        }
-                        ty => panic!("unknown evm client type `{ty}`"),
-                    }
-                }
+                evm_clients(&db, provider, &mut datas).await;
             }
         }
     }
@@ -298,3 +257,281 @@ pub async fn tx(db: PgPool, indexers: Indexers) {
     .await
     .unwrap();
 }
+
+/// Resolves the counterparty chain ids of the clients hosted on the EVM chain served by `provider`
+/// which are not yet known. Failures are logged, as they are retried on the next run.
+async fn evm_clients(db: &PgPool, provider: Provider<Http>, datas: &mut Vec<Data>) {
+    let chain_id = match provider.get_chainid().await {
+        Ok(chain_id) => chain_id.as_u64().to_string(),
+        Err(err) => {
+            warn!("could not fetch the chain id: {:?}", err);
+            return;
+        }
+    };
+
+    let records = sqlx::query!(
+        r#"
+        SELECT
+            ch.id, cc.client_id AS "client_id!", cc.transaction_hash
+        FROM
+            v0_evm.client_created cc
+        JOIN
+            v0.chains ch
+        ON
+            cc.chain_id = ch.id
+        LEFT JOIN
+            v0.clients cl
+        ON
+            cl.chain_id = ch.id AND
+            cl.client_id = cc.client_id
+        WHERE
+            ch.chain_id = $1 AND
+            cc.client_id IS NOT NULL AND
+            cl.chain_id IS NULL
+        "#,
+        chain_id
+    )
+    .fetch_all(db)
+    .await
+    .unwrap();
+
+    let provider = Arc::new(provider);
+    for record in records {
+        let (id, client_id) = (record.id, record.client_id);
+        let Some(transaction_hash) = record.transaction_hash else {
+            tracing::info!(internal_db_chain_id = id, %chain_id, client_id, "skipping record");
+            continue;
+        };
+
+        match evm_counterparty_chain_id(provider.clone(), &transaction_hash, &client_id).await {
+            Ok(Some(counterparty_chain_id)) => datas.push(Data {
+                chain_id: id,
+                client_id,
+                counterparty_chain_id,
+            }),
+            Ok(None) => {}
+            Err(err) => warn!(
+                "could not resolve the counterparty of {client_id}: {:?}",
+                err
+            ),
+        }
+    }
+}
+
+/// Reads the client state from the light client registered for `client_id` in the IBCHandler
+/// which emitted the `ClientCreated` event of `transaction_hash`.
+async fn evm_counterparty_chain_id(
+    provider: Arc<Provider<Http>>,
+    transaction_hash: &str,
+    client_id: &str,
+) -> Result<Option<String>, Report> {
+    let receipt = provider
+        .get_transaction_receipt(H256::from_str(transaction_hash)?)
+        .await?
+        .ok_or_else(|| eyre!("transaction {transaction_hash} not found"))?;
+
+    let handler = receipt
+        .logs
+        .iter()
+        .find(|log| log.topics.first() == Some(&ClientCreatedFilter::signature()))
+        .map(|log| log.address)
+        .ok_or_else(|| eyre!("transaction {transaction_hash} did not create a client"))?;
+
+    let handler = IBCHandler::new(handler, provider.clone());
+    let client_type = handler.client_types(client_id.to_owned()).call().await?;
+    let light_client = handler.client_impls(client_id.to_owned()).call().await?;
+    let client_state = ILightClient::new(light_client, provider)
+        .get_client_state(client_id.to_owned())
+        .call()
+        .await?;
+
+    evm_client_state_chain_id(&client_type, &client_state)
+}
+
+/// Decodes the chain id of the counterparty from a client state stored on an EVM chain.
+fn evm_client_state_chain_id(
+    client_type: &str,
+    client_state: &[u8],
+) -> Result<Option<String>, Report> {
+    match client_type {
+        "cometbls" => {
+            let cs =
+                unionlabs::ibc::lightclients::cometbls::client_state::ClientState::decode_as::<
+                    EthAbi,
+                >(client_state)
+                .map_err(|err| eyre!("could not decode cometbls client state: {:?}", err))?;
+            Ok(Some(cs.chain_id))
+        }
+        ty => {
+            warn!("unknown evm client type `{ty}`");
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, sync::Arc};
+
+    use axum::{routing::post, Router};
+    use contracts::{
+        i_light_client::{GetClientStateCall, GetClientStateReturn},
+        ibc_client::ClientCreatedFilter,
+        ibc_handler::{
+            ClientImplsCall, ClientImplsReturn, ClientTypesCall, ClientTypesReturn, IBCHandlerCalls,
+        },
+    };
+    use ethers::{
+        abi::{AbiDecode, AbiEncode, Token},
+        contract::EthEvent,
+        providers::{Http, Provider},
+        types::{Address, Bloom, Bytes, H256},
+    };
+    use serde_json::{json, Value};
+    use unionlabs::{
+        encoding::{EncodeAs, EthAbi},
+        ibc::{core::client::height::Height, lightclients::cometbls::client_state::ClientState},
+    };
+
+    use super::{evm_client_state_chain_id, evm_counterparty_chain_id};
+
+    const CREATE_CLIENT_TX: &str =
+        "0x1111111111111111111111111111111111111111111111111111111111111111";
+    const HANDLER: &str = "0xed2af2ad7fe0d92011b26a2e5d1b4dc7d12a47c5";
+    const LIGHT_CLIENT: &str = "0x2222222222222222222222222222222222222222";
+
+    fn cometbls_client_state() -> ClientState {
+        ClientState {
+            chain_id: "union-devnet-1".to_owned(),
+            trusting_period: 1_814_400_000_000_000,
+            unbonding_period: 1_814_400_000_000_000,
+            max_clock_drift: 40_000_000_000,
+            frozen_height: Height {
+                revision_number: 0,
+                revision_height: 0,
+            },
+            latest_height: Height {
+                revision_number: 1,
+                revision_height: 42,
+            },
+        }
+    }
+
+    #[test]
+    fn decode_cometbls_client_state() {
+        assert_eq!(
+            evm_client_state_chain_id("cometbls", &cometbls_client_state().encode_as::<EthAbi>())
+                .unwrap(),
+            Some("union-devnet-1".to_owned())
+        );
+    }
+
+    #[test]
+    fn decode_invalid_client_state() {
+        assert!(evm_client_state_chain_id("cometbls", &[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn decode_unknown_client_type() {
+        assert_eq!(
+            evm_client_state_chain_id("unknown", &cometbls_client_state().encode_as::<EthAbi>())
+                .unwrap(),
+            None
+        );
+    }
+
+    /// Answers as an EVM node on which `CREATE_CLIENT_TX` created `cometbls-0` in the IBCHandler at
+    /// `HANDLER`, backed by the light client at `LIGHT_CLIENT`.
+    fn respond(request: &Value) -> Value {
+        let params = &request["params"];
+        let result = match request["method"].as_str().unwrap() {
+            "eth_getTransactionReceipt" if params[0] == CREATE_CLIENT_TX => json!({
+                "transactionHash": CREATE_CLIENT_TX,
+                "transactionIndex": "0x0",
+                "blockHash": H256::repeat_byte(0xbb),
+                "blockNumber": "0x2a",
+                "from": Address::repeat_byte(0xff),
+                "to": HANDLER,
+                "cumulativeGasUsed": "0x1",
+                "gasUsed": "0x1",
+                "status": "0x1",
+                "logsBloom": Bloom::zero(),
+                "logs": [{
+                    "address": HANDLER,
+                    "topics": [ClientCreatedFilter::signature()],
+                    "data": Bytes::from(ethers::abi::encode(&[Token::String("cometbls-0".into())])),
+                }],
+            }),
+            "eth_getTransactionReceipt" => Value::Null,
+            "eth_call" => {
+                let call = &params[0];
+                let to = serde_json::from_value::<Address>(call["to"].clone()).unwrap();
+                let data = serde_json::from_value::<Bytes>(
+                    call.get("input").unwrap_or(&call["data"]).clone(),
+                )
+                .unwrap();
+                let result = if to == HANDLER.parse().unwrap() {
+                    match IBCHandlerCalls::decode(&data).unwrap() {
+                        IBCHandlerCalls::ClientTypes(ClientTypesCall(client_id))
+                            if client_id == "cometbls-0" =>
+                        {
+                            ClientTypesReturn("cometbls".into()).encode()
+                        }
+                        IBCHandlerCalls::ClientImpls(ClientImplsCall(client_id))
+                            if client_id == "cometbls-0" =>
+                        {
+                            ClientImplsReturn(LIGHT_CLIENT.parse().unwrap()).encode()
+                        }
+                        call => panic!("unexpected call to the handler {call:?}"),
+                    }
+                } else if to == LIGHT_CLIENT.parse().unwrap() {
+                    let call = GetClientStateCall::decode(&data).unwrap();
+                    assert_eq!(call.client_id, "cometbls-0");
+                    GetClientStateReturn(cometbls_client_state().encode_as::<EthAbi>().into())
+                        .encode()
+                } else {
+                    panic!("unexpected call to {to:?}")
+                };
+                json!(Bytes::from(result))
+            }
+            method => panic!("unexpected call to {method} with {params}"),
+        };
+        json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+    }
+
+    fn mock_provider() -> Provider<Http> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().route(
+            "/",
+            post(|body: String| async move {
+                respond(&serde_json::from_str(&body).unwrap()).to_string()
+            }),
+        );
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+        Provider::<Http>::try_from(url).unwrap()
+    }
+
+    #[tokio::test]
+    async fn resolve_evm_client() {
+        let provider = Arc::new(mock_provider());
+
+        assert_eq!(
+            evm_counterparty_chain_id(provider.clone(), CREATE_CLIENT_TX, "cometbls-0")
+                .await
+                .unwrap(),
+            Some("union-devnet-1".to_owned())
+        );
+        assert!(evm_counterparty_chain_id(
+            provider,
+            "0x0000000000000000000000000000000000000000000000000000000000000001",
+            "cometbls-0"
+        )
+        .await
+        .is_err());
+    }
+}
@@ -36,6 +36,18 @@ pub struct Args {
     #[arg(long)]
     pub fetch_client_chain_ids: bool,
 
+    /// Decode the IBC packet events of all indexed chains into `v0_packets`.
+    #[arg(long, env = "HUBBLE_INDEX_PACKETS")]
+    pub index_packets: bool,
+
+    /// Decode the logs of the IBCHandler, UCS01 and UCS02 contracts into `v0_evm.events`.
+    #[arg(long, env = "HUBBLE_DECODE_EVM_EVENTS")]
+    pub decode_evm_events: bool,
+
+    /// Export the health of all clients known to hubble as Prometheus gauges.
+    #[arg(long, env = "HUBBLE_MONITOR_CLIENTS")]
+    pub monitor_clients: bool,
+
     /// The log format for Hubble.
     #[arg(
         global = true,
@@ -45,6 +57,15 @@ pub struct Args {
         default_value = "json"
     )]
     pub log_format: LogFormat,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub enum Command {
+    /// Scan an indexed chain for missing or inconsistent heights and refetch them.
+    Repair(crate::repair::RepairArgs),
 }
 
 #[derive(Clone, Debug, serde::Deserialize)]
@@ -78,6 +99,8 @@ pub enum IndexerConfig {
     Arb(crate::arb::Config),
     #[serde(rename = "scroll")]
     Scroll(crate::scroll::Config),
+    #[serde(rename = "near")]
+    Near(crate::near::Config),
 }
 
 impl IndexerConfig {
@@ -90,6 +113,7 @@ impl IndexerConfig {
             Self::EthFork(cfg) => &cfg.label,
             Self::Arb(cfg) => &cfg.label,
             Self::Scroll(cfg) => &cfg.label,
+            Self::Near(cfg) => &cfg.label,
         }
     }
 }
@@ -151,6 +175,7 @@ impl IndexerConfig {
                     .instrument(indexer_span)
                     .await
             }
+            Self::Near(cfg) => cfg.index(db).instrument(indexer_span).await,
         }
     }
 }
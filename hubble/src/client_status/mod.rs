@@ -0,0 +1,132 @@
+//! Monitoring stage exporting the health of every client known to hubble, as computed by the
+//! `v0_clients.client_status` view, as Prometheus gauges.
+
+use std::time::Duration;
+
+use color_eyre::eyre::Report;
+use sqlx::PgPool;
+use tracing::{debug, info_span, warn, Instrument};
+
+use crate::{
+    cli::Indexers,
+    decoder::{self, Source},
+    metrics::{
+        CLIENT_CONSENSUS_HEIGHT, CLIENT_COUNTERPARTY_HEIGHT, CLIENT_HEIGHT_LAG,
+        CLIENT_SECONDS_SINCE_UPDATE, CLIENT_TRUSTING_PERIOD_REMAINING,
+    },
+};
+
+mod trusting_period;
+mod updates;
+
+/// Interval at which the gauges are refreshed.
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// DTO corresponding to the v0_clients.client_status view.
+struct ClientStatus {
+    host_chain_id: String,
+    client_id: String,
+    counterparty_chain_id: String,
+    consensus_height: Option<i64>,
+    counterparty_height: Option<i64>,
+    height_lag: Option<i64>,
+    seconds_since_update: Option<f64>,
+    trusting_period_remaining: Option<f64>,
+}
+
+/// Continuously decode the client updates and export the client status gauges.
+pub async fn monitor(db: PgPool, indexers: Indexers) -> Result<(), Report> {
+    tokio::try_join!(
+        decoder::index(
+            db.clone(),
+            &[
+                updates::ClientUpdatesDecoder(Source::Events),
+                updates::ClientUpdatesDecoder(Source::Logs),
+            ],
+        ),
+        export_periodically(&db, indexers),
+    )?;
+
+    Ok(())
+}
+
+async fn export_periodically(db: &PgPool, indexers: Indexers) -> Result<(), Report> {
+    let mut interval = tokio::time::interval(INTERVAL);
+    loop {
+        interval.tick().await;
+
+        for indexer in indexers.clone() {
+            let label = indexer.label().to_owned();
+            // Client states are read from the RPCs, which may be temporarily unavailable.
+            if let Err(err) = trusting_period::update(db, indexer)
+                .instrument(info_span!("trusting_period", label))
+                .await
+            {
+                warn!("could not fetch trusting periods: {:?}", err);
+            }
+        }
+
+        export(db).await?;
+    }
+}
+
+async fn export(db: &PgPool) -> Result<(), Report> {
+    let statuses = sqlx::query_as!(
+        ClientStatus,
+        r#"
+        SELECT
+            host_chain_id AS "host_chain_id!",
+            client_id AS "client_id!",
+            counterparty_chain_id AS "counterparty_chain_id!",
+            consensus_height,
+            counterparty_height,
+            height_lag,
+            EXTRACT(EPOCH FROM since_update)::float8 AS seconds_since_update,
+            EXTRACT(EPOCH FROM trusting_period_remaining)::float8 AS trusting_period_remaining
+        FROM v0_clients.client_status
+        "#
+    )
+    .fetch_all(db)
+    .await?;
+
+    // Clients may have been removed, or their values may have become unknown.
+    CLIENT_CONSENSUS_HEIGHT.reset();
+    CLIENT_COUNTERPARTY_HEIGHT.reset();
+    CLIENT_HEIGHT_LAG.reset();
+    CLIENT_SECONDS_SINCE_UPDATE.reset();
+    CLIENT_TRUSTING_PERIOD_REMAINING.reset();
+
+    debug!("exporting the status of {} clients", statuses.len());
+    for status in statuses {
+        let labels = [
+            status.host_chain_id.as_str(),
+            status.client_id.as_str(),
+            status.counterparty_chain_id.as_str(),
+        ];
+        if let Some(height) = status.consensus_height {
+            CLIENT_CONSENSUS_HEIGHT
+                .with_label_values(&labels)
+                .set(height);
+        }
+        if let Some(height) = status.counterparty_height {
+            CLIENT_COUNTERPARTY_HEIGHT
+                .with_label_values(&labels)
+                .set(height);
+        }
+        if let Some(lag) = status.height_lag {
+            CLIENT_HEIGHT_LAG.with_label_values(&labels).set(lag);
+        }
+        if let Some(seconds) = status.seconds_since_update {
+            CLIENT_SECONDS_SINCE_UPDATE
+                .with_label_values(&labels)
+                .set(seconds);
+        }
+        if let Some(seconds) = status.trusting_period_remaining {
+            CLIENT_TRUSTING_PERIOD_REMAINING
+                .with_label_values(&labels)
+                .set(seconds);
+        }
+    }
+
+    Ok(())
+}
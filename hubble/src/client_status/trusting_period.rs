@@ -0,0 +1,209 @@
+use std::str::FromStr;
+
+use color_eyre::eyre::{eyre, Report};
+use ethers::providers::{Http, Middleware, Provider};
+use prost::Message;
+use protos::ibc::{
+    core::client::v1::QueryClientStateRequest, lightclients::wasm::v1::QueryCodeRequest,
+};
+use sqlx::PgPool;
+use tendermint_rpc::{Client, HttpClient};
+use tracing::{info, warn};
+use unionlabs::{
+    encoding::{DecodeAs, EthAbi, Proto},
+    parse_wasm_client_type, WasmClientType,
+};
+
+use crate::cli::IndexerConfig;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Read the trusting period of the clients hosted on the chain of `indexer` which are not known
+/// yet. Only clients hosted on tendermint and ethereum indexed chains are supported. Clients whose
+/// state cannot be read are retried on the next call.
+pub async fn update(db: &PgPool, indexer: IndexerConfig) -> Result<(), Report> {
+    match indexer {
+        IndexerConfig::Tm(cfg) => {
+            let Some(grpc_url) = cfg.grpc_url else {
+                return Ok(());
+            };
+            let client = HttpClient::new(cfg.urls[0].as_str())?;
+            let chain_id = client.status().await?.node_info.network.to_string();
+
+            for (id, client_id) in unknown_clients(db, &chain_id).await? {
+                let trusting_period = match tm_trusting_period(&grpc_url, &client_id).await {
+                    Ok(trusting_period) => trusting_period,
+                    Err(err) => {
+                        warn!("could not read the client state of {client_id}: {:?}", err);
+                        continue;
+                    }
+                };
+                insert(db, id, &client_id, trusting_period).await?;
+            }
+        }
+        IndexerConfig::Eth(cfg) => {
+            let provider = Provider::<Http>::try_from(cfg.urls[0].as_str())?;
+            let chain_id = provider.get_chainid().await?.as_u64().to_string();
+
+            for (id, client_id) in unknown_clients(db, &chain_id).await? {
+                let trusting_period = match evm_trusting_period(db, &provider, id, &client_id).await
+                {
+                    Ok(trusting_period) => trusting_period,
+                    Err(err) => {
+                        warn!("could not read the client state of {client_id}: {:?}", err);
+                        continue;
+                    }
+                };
+                insert(db, id, &client_id, trusting_period).await?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The clients hosted on `chain_id` without a known trusting period.
+async fn unknown_clients(db: &PgPool, chain_id: &str) -> sqlx::Result<Vec<(i32, String)>> {
+    Ok(sqlx::query!(
+        "
+        SELECT cl.chain_id, cl.client_id FROM v0.clients cl
+        JOIN v0.chains ch ON ch.id = cl.chain_id
+        LEFT JOIN v0_clients.trusting_periods tp
+            ON tp.chain_id = cl.chain_id AND tp.client_id = cl.client_id
+        WHERE ch.chain_id = $1 AND tp.client_id IS NULL
+        ",
+        chain_id
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|record| (record.chain_id, record.client_id))
+    .collect())
+}
+
+async fn insert(
+    db: &PgPool,
+    chain_id: i32,
+    client_id: &str,
+    trusting_period: Option<u64>,
+) -> sqlx::Result<()> {
+    info!(
+        client_id,
+        ?trusting_period,
+        "storing trusting period (in seconds)"
+    );
+    sqlx::query!(
+        "
+        INSERT INTO v0_clients.trusting_periods (chain_id, client_id, trusting_period)
+        VALUES ($1, $2, $3::bigint * interval '1 second')
+        ON CONFLICT DO NOTHING
+        ",
+        chain_id,
+        client_id,
+        trusting_period.map(|seconds| seconds as i64)
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+/// The trusting period, in seconds, of a client hosted on a CosmosSDK chain.
+async fn tm_trusting_period(grpc_url: &str, client_id: &str) -> Result<Option<u64>, Report> {
+    let mut grpc_client =
+        protos::ibc::core::client::v1::query_client::QueryClient::connect(grpc_url.to_owned())
+            .await?;
+
+    let client_state = grpc_client
+        .client_state(QueryClientStateRequest {
+            client_id: client_id.to_owned(),
+        })
+        .await?
+        .into_inner()
+        .client_state
+        .ok_or_else(|| eyre!("client {client_id} not found"))?;
+
+    Ok(match &*client_state.type_url {
+        "/ibc.lightclients.tendermint.v1.ClientState" => {
+            protos::ibc::lightclients::tendermint::v1::ClientState::decode(&*client_state.value)?
+                .trusting_period
+                .map(|duration| duration.seconds as u64)
+        }
+        "/ibc.lightclients.wasm.v1.ClientState" => {
+            let cs =
+                protos::ibc::lightclients::wasm::v1::ClientState::decode(&*client_state.value)?;
+
+            let mut wasm_client =
+                protos::ibc::lightclients::wasm::v1::query_client::QueryClient::connect(
+                    grpc_url.to_owned(),
+                )
+                .await?;
+            let wasm_blob = wasm_client
+                .code(QueryCodeRequest {
+                    checksum: hex::encode(&*cs.checksum),
+                })
+                .await?
+                .into_inner()
+                .data;
+
+            match parse_wasm_client_type(wasm_blob)? {
+                Some(WasmClientType::Cometbls) => Some(
+                    unionlabs::ibc::lightclients::cometbls::client_state::ClientState::decode_as::<
+                        Proto,
+                    >(&cs.data)
+                    .map_err(|err| eyre!("{err:?}"))?
+                    .trusting_period
+                        / NANOS_PER_SECOND,
+                ),
+                Some(WasmClientType::Tendermint) => Some(
+                    unionlabs::ibc::lightclients::tendermint::client_state::ClientState::decode_as::<
+                        Proto,
+                    >(&cs.data)
+                    .map_err(|err| eyre!("{err:?}"))?
+                    .trusting_period
+                    .seconds()
+                    .inner() as u64,
+                ),
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// The trusting period, in seconds, of a client hosted on an EVM chain, read from the transaction
+/// which created it.
+async fn evm_trusting_period(
+    db: &PgPool,
+    provider: &Provider<Http>,
+    chain_id: i32,
+    client_id: &str,
+) -> Result<Option<u64>, Report> {
+    let transaction_hash = sqlx::query_scalar!(
+        "SELECT transaction_hash FROM v0_evm.client_created WHERE chain_id = $1 AND client_id = $2",
+        chain_id,
+        client_id
+    )
+    .fetch_one(db)
+    .await?
+    .ok_or_else(|| eyre!("client {client_id} was created without a transaction"))?;
+
+    let tx = provider
+        .get_transaction(ethers::types::H256::from_str(&transaction_hash)?)
+        .await?
+        .ok_or_else(|| eyre!("transaction {transaction_hash} not found"))?;
+
+    let msg =
+        <contracts::ibc_handler::CreateClientCall as ethers::abi::AbiDecode>::decode(&tx.input)?;
+
+    Ok(match &*msg.0.client_type {
+        "cometbls" => Some(
+            unionlabs::ibc::lightclients::cometbls::client_state::ClientState::decode_as::<EthAbi>(
+                &msg.0.client_state_bytes,
+            )
+            .map_err(|err| eyre!("{err:?}"))?
+            .trusting_period
+                / NANOS_PER_SECOND,
+        ),
+        _ => None,
+    })
+}
@@ -0,0 +1,232 @@
+use color_eyre::eyre::{eyre, Report};
+use contracts::ibc_client::IBCClientEvents;
+use ethers::{abi::RawLog, contract::EthLogDecode, types::Log};
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres};
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::decoder::{fetch_logs, Decoder, Source};
+
+/// DTO corresponding to the v0_clients.updates table.
+pub struct PgClientUpdate {
+    pub height: i32,
+    pub time: OffsetDateTime,
+    pub index: i32,
+    pub client_id: String,
+    pub consensus_height: i64,
+}
+
+/// A tendermint event as stored in v0.events by the tendermint indexer.
+#[derive(Deserialize)]
+struct StoredEvent {
+    attributes: Vec<StoredEventAttribute>,
+}
+
+#[derive(Deserialize)]
+struct StoredEventAttribute {
+    key: String,
+    value: String,
+}
+
+/// Decodes the `update_client` events of CosmosSDK chains and the `ClientUpdated` logs of EVM
+/// chains into v0_clients.updates.
+pub struct ClientUpdatesDecoder(pub Source);
+
+impl Decoder for ClientUpdatesDecoder {
+    type Output = Vec<PgClientUpdate>;
+
+    fn name(&self) -> &'static str {
+        "client_updates"
+    }
+
+    fn source(&self) -> Source {
+        self.0
+    }
+
+    async fn cursor(&self, db: &PgPool, chain_id: i32) -> sqlx::Result<Option<i32>> {
+        sqlx::query_scalar!(
+            "SELECT height FROM v0_clients.cursors WHERE chain_id = $1 AND source = $2",
+            chain_id,
+            self.0.as_str()
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    async fn decode(
+        &self,
+        db: &PgPool,
+        chain_id: i32,
+        from: i32,
+        to: i32,
+    ) -> Result<Vec<PgClientUpdate>, Report> {
+        match self.0 {
+            Source::Events => fetch_events(db, chain_id, from, to).await,
+            Source::Logs => Ok(fetch_logs(db, chain_id, from, to)
+                .await?
+                .into_iter()
+                .filter_map(|indexed| {
+                    let (client_id, consensus_height) = decode_log(indexed.log)?;
+                    Some(PgClientUpdate {
+                        height: indexed.height,
+                        time: indexed.time,
+                        index: indexed.log_index,
+                        client_id,
+                        consensus_height,
+                    })
+                })
+                .collect()),
+        }
+    }
+
+    async fn store(
+        &self,
+        tx: &mut sqlx::Transaction<'_, Postgres>,
+        chain_id: i32,
+        from: i32,
+        to: i32,
+        updates: Vec<PgClientUpdate>,
+    ) -> sqlx::Result<usize> {
+        // Blocks are processed again after a reorg, which rewinds the cursor.
+        sqlx::query!(
+            "DELETE FROM v0_clients.updates WHERE chain_id = $1 AND height > $2 AND height <= $3",
+            chain_id,
+            from,
+            to
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        let count = updates.len();
+        if count > 0 {
+            let mut heights = Vec::with_capacity(count);
+            let mut times = Vec::with_capacity(count);
+            let mut indexes = Vec::with_capacity(count);
+            let mut client_ids = Vec::with_capacity(count);
+            let mut consensus_heights = Vec::with_capacity(count);
+            for update in updates {
+                heights.push(update.height);
+                times.push(update.time);
+                indexes.push(update.index);
+                client_ids.push(update.client_id);
+                consensus_heights.push(update.consensus_height);
+            }
+
+            sqlx::query!("
+                INSERT INTO v0_clients.updates (chain_id, height, time, index, client_id, consensus_height)
+                SELECT $1, unnest($2::int[]), unnest($3::timestamptz[]), unnest($4::int[]), unnest($5::text[]), unnest($6::bigint[])
+                ",
+                chain_id,
+                &heights,
+                &times,
+                &indexes,
+                &client_ids,
+                &consensus_heights,
+            )
+            .execute(tx.as_mut())
+            .await?;
+        }
+
+        sqlx::query!(
+            "
+            INSERT INTO v0_clients.cursors (chain_id, source, height) VALUES ($1, $2, $3)
+            ON CONFLICT (chain_id, source) DO UPDATE SET height = excluded.height
+            ",
+            chain_id,
+            self.0.as_str(),
+            to
+        )
+        .execute(tx.as_mut())
+        .await?;
+
+        Ok(count)
+    }
+}
+
+async fn fetch_events(
+    db: &PgPool,
+    chain_id: i32,
+    from: i32,
+    to: i32,
+) -> Result<Vec<PgClientUpdate>, Report> {
+    let rows = sqlx::query!(
+        "
+        SELECT height, time, index, data FROM v0.events
+        WHERE chain_id = $1 AND height > $2 AND height <= $3 AND data->>'type' = 'update_client'
+        ",
+        chain_id,
+        from,
+        to
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| match decode_event(row.data) {
+            Ok((client_id, consensus_height)) => Some(PgClientUpdate {
+                height: row.height,
+                time: row.time,
+                index: row.index,
+                client_id,
+                consensus_height,
+            }),
+            Err(err) => {
+                warn!(
+                    height = row.height,
+                    index = row.index,
+                    "could not decode client update: {:?}",
+                    err
+                );
+                None
+            }
+        })
+        .collect())
+}
+
+/// Read the client id and the (first) updated consensus height of an `update_client` event. Older
+/// ibc-go versions emit a single `consensus_height` rather than `consensus_heights`.
+fn decode_event(data: serde_json::Value) -> Result<(String, i64), Report> {
+    let event: StoredEvent = serde_json::from_value(data)?;
+    let attribute = |keys: &[&str]| {
+        event
+            .attributes
+            .iter()
+            .find(|attribute| keys.contains(&attribute.key.as_str()))
+            .map(|attribute| attribute.value.as_str())
+            .ok_or_else(|| eyre!("missing attribute {}", keys[0]))
+    };
+
+    let client_id = attribute(&["client_id"])?.to_owned();
+    // Heights are formatted as `{revision_number}-{revision_height}`.
+    let consensus_height = attribute(&["consensus_heights", "consensus_height"])?
+        .split(',')
+        .next()
+        .and_then(|height| height.split_once('-'))
+        .ok_or_else(|| eyre!("invalid consensus height"))?
+        .1
+        .parse()?;
+
+    Ok((client_id, consensus_height))
+}
+
+/// Decode a `ClientUpdated` log, any other log is ignored.
+fn decode_log(log: Log) -> Option<(String, i64)> {
+    match IBCClientEvents::decode_log(&RawLog {
+        topics: log.topics,
+        data: log.data.to_vec(),
+    })
+    .ok()?
+    {
+        IBCClientEvents::ClientUpdatedFilter(event) => Some((
+            event.client_id,
+            event
+                .height
+                .revision_height
+                .try_into()
+                .expect("revision height should fit in an i64"),
+        )),
+        _ => None,
+    }
+}
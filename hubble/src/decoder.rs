@@ -0,0 +1,214 @@
+//! Post-processing stages decoding the raw data stored by the indexers (`v0.events` and
+//! `v0.logs`) into tables of their own, in batches of blocks. The progress of a stage is tracked
+//! per chain by a cursor, which the indexers rewind when they rewrite blocks (see
+//! [`crate::postgres::rewind_log_decoders`]).
+
+use std::time::Duration;
+
+use color_eyre::eyre::Report;
+use ethers::types::Log;
+use sqlx::{PgPool, Postgres};
+use time::OffsetDateTime;
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use crate::eth::TransactionInsert;
+
+/// The maximum number of blocks processed in a single database transaction.
+const BATCH_SIZE: i32 = 1000;
+
+/// Interval at which we check for newly indexed blocks once caught up.
+const INTERVAL: Duration = Duration::from_secs(10);
+
+/// The raw data a stage decodes.
+#[derive(Debug, Clone, Copy)]
+pub enum Source {
+    /// Tendermint events stored in v0.events.
+    Events,
+    /// EVM logs stored in v0.logs.
+    Logs,
+}
+
+impl Source {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Source::Events => "events",
+            Source::Logs => "logs",
+        }
+    }
+
+    /// The highest height indexed for this chain, if any.
+    async fn head(&self, db: &PgPool, chain_id: i32) -> sqlx::Result<Option<i32>> {
+        match self {
+            Source::Events => {
+                sqlx::query_scalar!(
+                    "SELECT MAX(height) FROM v0.blocks WHERE chain_id = $1",
+                    chain_id
+                )
+                .fetch_one(db)
+                .await
+            }
+            Source::Logs => {
+                sqlx::query_scalar!(
+                    "SELECT MAX(height) FROM v0.logs WHERE chain_id = $1",
+                    chain_id
+                )
+                .fetch_one(db)
+                .await
+            }
+        }
+    }
+}
+
+/// A post-processing stage.
+pub trait Decoder {
+    /// The rows decoded from a batch of blocks.
+    type Output: Send;
+
+    /// Name of the stage, used in logs.
+    fn name(&self) -> &'static str;
+
+    fn source(&self) -> Source;
+
+    /// The last height processed for this chain, if any.
+    async fn cursor(&self, db: &PgPool, chain_id: i32) -> sqlx::Result<Option<i32>>;
+
+    /// Decode the blocks in `from + 1..=to`.
+    async fn decode(
+        &self,
+        db: &PgPool,
+        chain_id: i32,
+        from: i32,
+        to: i32,
+    ) -> Result<Self::Output, Report>;
+
+    /// Replace the rows previously decoded from the blocks in `from + 1..=to` by `output` and
+    /// move the cursor to `to`, returning the number of rows stored.
+    async fn store(
+        &self,
+        tx: &mut sqlx::Transaction<'_, Postgres>,
+        chain_id: i32,
+        from: i32,
+        to: i32,
+        output: Self::Output,
+    ) -> sqlx::Result<usize>;
+}
+
+/// Continuously run the `decoders` over every chain known to hubble.
+pub async fn index<D: Decoder + Sync>(db: PgPool, decoders: &[D]) -> Result<(), Report> {
+    let mut interval = tokio::time::interval(INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let chains = sqlx::query!("SELECT id, chain_id FROM v0.chains")
+            .fetch_all(&db)
+            .await?;
+
+        for chain in chains {
+            for decoder in decoders {
+                async {
+                    while process_batch(&db, decoder, chain.id).await? {}
+                    Ok::<_, Report>(())
+                }
+                .instrument(info_span!(
+                    "decoder",
+                    name = decoder.name(),
+                    chain_id = chain.chain_id,
+                    source = decoder.source().as_str()
+                ))
+                .await?;
+            }
+        }
+    }
+}
+
+/// Decode the next batch of blocks, returning whether more blocks are left to process.
+async fn process_batch<D: Decoder>(
+    db: &PgPool,
+    decoder: &D,
+    chain_id: i32,
+) -> Result<bool, Report> {
+    let Some(head) = decoder.source().head(db, chain_id).await? else {
+        return Ok(false);
+    };
+
+    let from = decoder.cursor(db, chain_id).await?.unwrap_or_default();
+    let to = head.min(from.saturating_add(BATCH_SIZE));
+    if to <= from {
+        return Ok(false);
+    }
+
+    debug!("decoding {}..={}", from + 1, to);
+    let output = decoder.decode(db, chain_id, from, to).await?;
+
+    let mut tx = db.begin().await?;
+    let count = decoder.store(&mut tx, chain_id, from, to, output).await?;
+    tx.commit().await?;
+
+    if count > 0 {
+        info!("decoded {} rows up to height {}", count, to);
+    }
+
+    Ok(to < head)
+}
+
+/// An EVM log, located in the block and transaction it was emitted in.
+pub struct IndexedLog {
+    pub block_hash: String,
+    pub height: i32,
+    pub time: OffsetDateTime,
+    pub transaction_hash: String,
+    pub transaction_index: i32,
+    pub log_index: i32,
+    pub log: Log,
+}
+
+/// The logs stored in v0.logs for the blocks in `from + 1..=to`. Logs which cannot be parsed are
+/// logged and skipped.
+pub async fn fetch_logs(
+    db: &PgPool,
+    chain_id: i32,
+    from: i32,
+    to: i32,
+) -> Result<Vec<IndexedLog>, Report> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT block_hash, height, time, data->'transactions' AS "transactions!"
+        FROM v0.logs
+        WHERE chain_id = $1 AND height > $2 AND height <= $3
+        "#,
+        chain_id,
+        from,
+        to
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut logs = vec![];
+    for row in rows {
+        let transactions: Vec<TransactionInsert> = serde_json::from_value(row.transactions)?;
+        for transaction in transactions {
+            for event in transaction.events {
+                let log: Log = match serde_json::from_value(event.data) {
+                    Ok(log) => log,
+                    Err(err) => {
+                        warn!(height = row.height, "could not decode log: {:?}", err);
+                        continue;
+                    }
+                };
+                logs.push(IndexedLog {
+                    block_hash: row.block_hash.clone(),
+                    height: row.height,
+                    time: row.time,
+                    transaction_hash: transaction.hash.clone(),
+                    transaction_index: transaction.index,
+                    log_index: event
+                        .log_index
+                        .try_into()
+                        .expect("log index should fit in an i32"),
+                    log,
+                });
+            }
+        }
+    }
+    Ok(logs)
+}
@@ -1,5 +1,6 @@
 mod client;
 pub mod fork;
 pub mod indexer;
+pub mod repair;
 
 pub use indexer::*;
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+
+use backon::Retryable;
+use color_eyre::eyre::{eyre, Report};
+use ethers::{
+    abi::ethereum_types::Address,
+    providers::{Http, Provider},
+    types::BlockId,
+};
+use futures::{StreamExt, TryStreamExt};
+use sqlx::PgPool;
+use tracing::info;
+
+use crate::{
+    eth::{BlockInsert, Config, LogData, PgLog, ToLowerHex},
+    postgres::{self, InsertMode},
+    race_client::RaceClient,
+    repair::{Issue, RepairArgs},
+};
+
+/// How many blocks are compared against the RPC at the same time.
+const CONCURRENCY: usize = 32;
+
+/// Find the blocks in v0.logs whose hash differs from the canonical chain and, unless running
+/// dry, refetch them. Blocks which no longer contain logs of the tracked contracts are removed.
+///
+/// v0.logs only contains the blocks with logs of the tracked contracts, hence its heights are not
+/// expected to be contiguous.
+pub async fn repair(config: Config, db: &PgPool, args: &RepairArgs) -> Result<Vec<Issue>, Report> {
+    let provider = RaceClient::new(
+        config
+            .urls
+            .iter()
+            .map(|url| Provider::<Http>::try_from(url.as_str()).unwrap())
+            .collect(),
+    );
+
+    let canonical = (|| provider.get_chainid())
+        .retry(&crate::expo_backoff())
+        .await?
+        .as_u64()
+        .to_string();
+    let chain_id = postgres::get_chain_id(db, canonical.clone())
+        .await?
+        .ok_or_else(|| eyre!("chain {canonical} has not been indexed yet"))?;
+
+    let contracts = sqlx::query_scalar!(
+        "SELECT address FROM v0.contracts WHERE chain_id = $1",
+        chain_id.db
+    )
+    .fetch_all(db)
+    .await?;
+    let filter: Arc<Vec<Address>> = Arc::new(
+        contracts
+            .iter()
+            .map(|address| {
+                address
+                    .parse()
+                    .expect("database should contain valid addresses")
+            })
+            .collect(),
+    );
+
+    let logs = sqlx::query!(
+        "
+        SELECT block_hash, height FROM v0.logs
+        WHERE chain_id = $1 AND height >= COALESCE($2, 0) AND height <= COALESCE($3, 2147483647)
+        ORDER BY height
+        ",
+        chain_id.db,
+        args.from,
+        args.to
+    )
+    .fetch_all(db)
+    .await?;
+
+    let issues: Vec<Issue> = futures::stream::iter(logs)
+        .map(|record| {
+            let (stored, height) = (record.block_hash, record.height);
+            let provider = provider.clone();
+            async move {
+                let block = (|| provider.get_block(BlockId::Number((height as u64).into())))
+                    .retry(&crate::expo_backoff())
+                    .await?
+                    .ok_or_else(|| eyre!("node did not return block {height}"))?;
+                let canonical = block
+                    .hash
+                    .expect("blocks should have a hash")
+                    .to_lower_hex();
+                Ok::<_, Report>((canonical != stored).then_some(Issue::ForkedBlock {
+                    height,
+                    stored,
+                    canonical,
+                }))
+            }
+        })
+        .buffered(CONCURRENCY)
+        .try_filter_map(|issue| async move { Ok(issue) })
+        .try_collect()
+        .await?;
+
+    if args.dry_run {
+        return Ok(issues);
+    }
+
+    for issue in &issues {
+        let Issue::ForkedBlock { height, .. } = *issue else {
+            continue;
+        };
+
+        let block = BlockInsert::from_provider_retried_filtered(
+            chain_id,
+            height as u64,
+            provider.clone(),
+            filter.clone(),
+        )
+        .await
+        .map_err(|err| eyre!("could not fetch block {height}: {err:?}"))?;
+
+        let mut tx = db.begin().await?;
+        match block {
+            Some(block) => {
+                postgres::insert_batch_logs(
+                    &mut tx,
+                    [PgLog::<LogData>::from(block)],
+                    InsertMode::Upsert,
+                )
+                .await?
+            }
+            None => postgres::delete_logs(&mut tx, chain_id, height).await?,
+        }
+        tx.commit().await?;
+
+        info!("repaired block {}", height);
+    }
+
+    Ok(issues)
+}
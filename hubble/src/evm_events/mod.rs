@@ -0,0 +1,267 @@
+//! Post-processing stage decoding the logs of the IBC contracts stored in `v0.logs` into typed
+//! rows in `v0_evm.events`.
+
+use std::collections::HashMap;
+
+use color_eyre::eyre::Report;
+use contracts::{
+    ibc_channel_handshake::IBCCHANNELHANDSHAKE_ABI, ibc_client::IBCCLIENT_ABI,
+    ibc_connection::IBCCONNECTION_ABI, ibc_handler::IBCHANDLER_ABI, ibc_packet::IBCPACKET_ABI,
+    ucs01_relay::UCS01RELAY_ABI,
+};
+use ethers::{
+    abi::{Abi, Event, RawLog, Token},
+    types::{Log, H256, I256},
+};
+use lazy_static::lazy_static;
+use sqlx::{PgPool, Postgres};
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::decoder::{self, fetch_logs, Decoder, Source};
+
+/// Events of the UCS02 NFT app. No bindings are generated for it yet, hence they are declared
+/// here, matching `NFTLib` in `evm/contracts/apps/ucs/02-nft/NFT.sol`.
+const UCS02_EVENTS: [&str; 4] = [
+    "event ClassCreated(uint64 packetSequence, string channelId, address indexed nftClass)",
+    "event Received(uint64 packetSequence, string channelId, string sender, address receiver, address indexed nftClass, uint256[] tokenIds)",
+    "event Sent(uint64 packetSequence, string channelId, address sender, string receiver, address indexed nftClass, uint256[] tokenIds)",
+    "event Refunded(uint64 packetSequence, string channelId, address sender, string receiver, address indexed nftClass, uint256[] tokenIds)",
+];
+
+lazy_static! {
+    /// The decodable events, by signature (topic 0). The IBCHandler emits the events of all its
+    /// components. Events shared by several contracts, such as the proxy `Upgraded`, are
+    /// attributed to the first one declaring them.
+    static ref EVENTS: HashMap<H256, (&'static str, Event)> = {
+        let ucs02 = ethers::abi::parse_abi(&UCS02_EVENTS).expect("UCS02 events are valid");
+        let abis: [(&'static str, &Abi); 7] = [
+            ("IBCHandler", &IBCHANDLER_ABI),
+            ("IBCHandler", &IBCCLIENT_ABI),
+            ("IBCHandler", &IBCCONNECTION_ABI),
+            ("IBCHandler", &IBCCHANNELHANDSHAKE_ABI),
+            ("IBCHandler", &IBCPACKET_ABI),
+            ("UCS01Relay", &UCS01RELAY_ABI),
+            ("UCS02NFT", &ucs02),
+        ];
+
+        let mut events = HashMap::new();
+        for (contract, abi) in abis {
+            for event in abi.events().filter(|event| !event.anonymous) {
+                events
+                    .entry(event.signature())
+                    .or_insert_with(|| (contract, event.clone()));
+            }
+        }
+        events
+    };
+}
+
+/// DTO corresponding to the v0_evm.events table.
+pub struct PgEvmEvent {
+    pub block_hash: String,
+    pub height: i32,
+    pub time: OffsetDateTime,
+    pub transaction_hash: String,
+    pub transaction_index: i32,
+    pub log_index: i32,
+    pub address: String,
+    pub contract: &'static str,
+    pub name: String,
+    pub args: serde_json::Value,
+}
+
+/// Decodes the logs of the IBC contracts into v0_evm.events.
+pub struct EvmEventsDecoder;
+
+impl Decoder for EvmEventsDecoder {
+    type Output = Vec<PgEvmEvent>;
+
+    fn name(&self) -> &'static str {
+        "evm_events"
+    }
+
+    fn source(&self) -> Source {
+        Source::Logs
+    }
+
+    async fn cursor(&self, db: &PgPool, chain_id: i32) -> sqlx::Result<Option<i32>> {
+        sqlx::query_scalar!(
+            "SELECT height FROM v0_evm.cursors WHERE chain_id = $1",
+            chain_id
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    async fn decode(
+        &self,
+        db: &PgPool,
+        chain_id: i32,
+        from: i32,
+        to: i32,
+    ) -> Result<Vec<PgEvmEvent>, Report> {
+        Ok(fetch_logs(db, chain_id, from, to)
+            .await?
+            .into_iter()
+            .filter_map(|indexed| {
+                let address = format!("{:#x}", indexed.log.address);
+                let (contract, name, args) = decode(indexed.log)?;
+                Some(PgEvmEvent {
+                    block_hash: indexed.block_hash,
+                    height: indexed.height,
+                    time: indexed.time,
+                    transaction_hash: indexed.transaction_hash,
+                    transaction_index: indexed.transaction_index,
+                    log_index: indexed.log_index,
+                    address,
+                    contract,
+                    name,
+                    args,
+                })
+            })
+            .collect())
+    }
+
+    async fn store(
+        &self,
+        tx: &mut sqlx::Transaction<'_, Postgres>,
+        chain_id: i32,
+        from: i32,
+        to: i32,
+        events: Vec<PgEvmEvent>,
+    ) -> sqlx::Result<usize> {
+        // Blocks are processed again after a reorg, which rewinds the cursor.
+        sqlx::query!(
+            "DELETE FROM v0_evm.events WHERE chain_id = $1 AND height > $2 AND height <= $3",
+            chain_id,
+            from,
+            to
+        )
+        .execute(tx.as_mut())
+        .await?;
+        let count = events.len();
+        insert_batch_evm_events(tx, chain_id, events).await?;
+        sqlx::query!(
+            "
+            INSERT INTO v0_evm.cursors (chain_id, height) VALUES ($1, $2)
+            ON CONFLICT (chain_id) DO UPDATE SET height = excluded.height
+            ",
+            chain_id,
+            to
+        )
+        .execute(tx.as_mut())
+        .await?;
+        Ok(count)
+    }
+}
+
+/// Continuously decode the logs of every chain known to hubble.
+pub async fn index(db: PgPool) -> Result<(), Report> {
+    decoder::index(db, &[EvmEventsDecoder]).await
+}
+
+/// Decode a log of one of the IBC contracts, any other log is ignored.
+fn decode(log: Log) -> Option<(&'static str, String, serde_json::Value)> {
+    let (contract, event) = EVENTS.get(log.topics.first()?)?;
+
+    let decoded = match event.parse_log(RawLog {
+        topics: log.topics,
+        data: log.data.to_vec(),
+    }) {
+        Ok(decoded) => decoded,
+        Err(err) => {
+            warn!(event = event.name, "could not decode event: {:?}", err);
+            return None;
+        }
+    };
+
+    let args = decoded
+        .params
+        .into_iter()
+        .enumerate()
+        .map(|(i, param)| {
+            let name = if param.name.is_empty() {
+                i.to_string()
+            } else {
+                param.name
+            };
+            (name, token_to_json(param.value))
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    Some((contract, event.name.clone(), args.into()))
+}
+
+/// Integers are encoded as strings as they may not fit in a JSON number, tuples as arrays since
+/// the ABI does not carry the names of their components.
+fn token_to_json(token: Token) -> serde_json::Value {
+    match token {
+        Token::Address(address) => format!("{:#x}", address).into(),
+        Token::FixedBytes(bytes) | Token::Bytes(bytes) => {
+            format!("0x{}", hex::encode(bytes)).into()
+        }
+        Token::Int(int) => I256::from_raw(int).to_string().into(),
+        Token::Uint(uint) => uint.to_string().into(),
+        Token::Bool(bool) => bool.into(),
+        Token::String(string) => string.into(),
+        Token::FixedArray(tokens) | Token::Array(tokens) | Token::Tuple(tokens) => {
+            tokens.into_iter().map(token_to_json).collect()
+        }
+    }
+}
+
+async fn insert_batch_evm_events(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    chain_id: i32,
+    events: Vec<PgEvmEvent>,
+) -> sqlx::Result<()> {
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let mut block_hashes = Vec::with_capacity(events.len());
+    let mut heights = Vec::with_capacity(events.len());
+    let mut times = Vec::with_capacity(events.len());
+    let mut transaction_hashes = Vec::with_capacity(events.len());
+    let mut transaction_indexes = Vec::with_capacity(events.len());
+    let mut log_indexes = Vec::with_capacity(events.len());
+    let mut addresses = Vec::with_capacity(events.len());
+    let mut contracts = Vec::with_capacity(events.len());
+    let mut names = Vec::with_capacity(events.len());
+    let mut args = Vec::with_capacity(events.len());
+
+    for event in events {
+        block_hashes.push(event.block_hash);
+        heights.push(event.height);
+        times.push(event.time);
+        transaction_hashes.push(event.transaction_hash);
+        transaction_indexes.push(event.transaction_index);
+        log_indexes.push(event.log_index);
+        addresses.push(event.address);
+        contracts.push(event.contract);
+        names.push(event.name);
+        args.push(event.args);
+    }
+
+    sqlx::query!("
+        INSERT INTO v0_evm.events (chain_id, block_hash, height, time, transaction_hash, transaction_index, log_index, address, contract, name, args)
+        SELECT $1, unnest($2::text[]), unnest($3::int[]), unnest($4::timestamptz[]), unnest($5::text[]), unnest($6::int[]), unnest($7::int[]), unnest($8::text[]), unnest($9::text[]), unnest($10::text[]), unnest($11::jsonb[])
+        ",
+        chain_id,
+        &block_hashes,
+        &heights,
+        &times,
+        &transaction_hashes,
+        &transaction_indexes,
+        &log_indexes,
+        &addresses,
+        &contracts as _,
+        &names,
+        &args,
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    Ok(())
+}
@@ -17,13 +17,19 @@ mod beacon;
 mod bera;
 mod chain_id_query;
 mod cli;
+mod client_status;
 mod consensus;
+mod decoder;
 mod eth;
+mod evm_events;
 mod healthz;
 mod logging;
 mod metrics;
+mod near;
+mod packets;
 mod postgres;
 mod race_client;
+mod repair;
 mod scroll;
 mod tm;
 
@@ -47,6 +53,10 @@ async fn main() -> color_eyre::eyre::Result<()> {
         .connect(&args.database_url.unwrap())
         .await?;
 
+    if let Some(cli::Command::Repair(repair)) = args.command {
+        return repair::repair(db, args.indexers, repair).await;
+    }
+
     let mut set = JoinSet::new();
 
     if let Some(addr) = args.metrics_addr {
@@ -72,6 +82,39 @@ async fn main() -> color_eyre::eyre::Result<()> {
         });
     });
 
+    if args.index_packets {
+        let db = db.clone();
+        set.spawn(async move {
+            info!("starting packet indexer");
+            packets::index(db).await.inspect_err(|err| {
+                warn!("packet indexer exited with: {:?}", err);
+            })
+        });
+    }
+
+    if args.decode_evm_events {
+        let db = db.clone();
+        set.spawn(async move {
+            info!("starting evm event decoder");
+            evm_events::index(db).await.inspect_err(|err| {
+                warn!("evm event decoder exited with: {:?}", err);
+            })
+        });
+    }
+
+    if args.monitor_clients {
+        let db = db.clone();
+        let indexers = args.indexers.clone();
+        set.spawn(async move {
+            info!("starting client monitor");
+            client_status::monitor(db, indexers)
+                .await
+                .inspect_err(|err| {
+                    warn!("client monitor exited with: {:?}", err);
+                })
+        });
+    }
+
     let indexers = args.indexers.clone();
 
     let client_updates = async move {
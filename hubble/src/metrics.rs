@@ -1,5 +1,5 @@
 use lazy_static::lazy_static;
-use prometheus::{IntCounterVec, Opts, Registry};
+use prometheus::{GaugeVec, IntCounterVec, IntGaugeVec, Opts, Registry};
 use reqwest::StatusCode;
 
 lazy_static! {
@@ -25,6 +25,53 @@ lazy_static! {
         &["chain_id"]
     )
     .expect("register TRANSACTION_COLLECTOR");
+    pub static ref CLIENT_CONSENSUS_HEIGHT: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("consensus_height", "Latest consensus height of the client")
+            .namespace("hubble")
+            .subsystem("client"),
+        &["chain_id", "client_id", "counterparty_chain_id"]
+    )
+    .expect("register CLIENT_CONSENSUS_HEIGHT");
+    pub static ref CLIENT_COUNTERPARTY_HEIGHT: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "counterparty_height",
+            "Latest indexed height of the counterparty chain"
+        )
+        .namespace("hubble")
+        .subsystem("client"),
+        &["chain_id", "client_id", "counterparty_chain_id"]
+    )
+    .expect("register CLIENT_COUNTERPARTY_HEIGHT");
+    pub static ref CLIENT_HEIGHT_LAG: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "height_lag",
+            "Number of counterparty blocks the client is behind"
+        )
+        .namespace("hubble")
+        .subsystem("client"),
+        &["chain_id", "client_id", "counterparty_chain_id"]
+    )
+    .expect("register CLIENT_HEIGHT_LAG");
+    pub static ref CLIENT_SECONDS_SINCE_UPDATE: GaugeVec = GaugeVec::new(
+        Opts::new(
+            "seconds_since_update",
+            "Seconds since the client was last updated"
+        )
+        .namespace("hubble")
+        .subsystem("client"),
+        &["chain_id", "client_id", "counterparty_chain_id"]
+    )
+    .expect("register CLIENT_SECONDS_SINCE_UPDATE");
+    pub static ref CLIENT_TRUSTING_PERIOD_REMAINING: GaugeVec = GaugeVec::new(
+        Opts::new(
+            "trusting_period_remaining_seconds",
+            "Seconds left before the client expires"
+        )
+        .namespace("hubble")
+        .subsystem("client"),
+        &["chain_id", "client_id", "counterparty_chain_id"]
+    )
+    .expect("register CLIENT_TRUSTING_PERIOD_REMAINING");
 }
 
 pub fn register_custom_metrics() {
@@ -37,6 +84,21 @@ pub fn register_custom_metrics() {
     REGISTRY
         .register(Box::new(TRANSACTION_COLLECTOR.clone()))
         .expect("TRANSACTION_COLLECTOR can be registered");
+    REGISTRY
+        .register(Box::new(CLIENT_CONSENSUS_HEIGHT.clone()))
+        .expect("CLIENT_CONSENSUS_HEIGHT can be registered");
+    REGISTRY
+        .register(Box::new(CLIENT_COUNTERPARTY_HEIGHT.clone()))
+        .expect("CLIENT_COUNTERPARTY_HEIGHT can be registered");
+    REGISTRY
+        .register(Box::new(CLIENT_HEIGHT_LAG.clone()))
+        .expect("CLIENT_HEIGHT_LAG can be registered");
+    REGISTRY
+        .register(Box::new(CLIENT_SECONDS_SINCE_UPDATE.clone()))
+        .expect("CLIENT_SECONDS_SINCE_UPDATE can be registered");
+    REGISTRY
+        .register(Box::new(CLIENT_TRUSTING_PERIOD_REMAINING.clone()))
+        .expect("CLIENT_TRUSTING_PERIOD_REMAINING can be registered");
 }
 
 #[axum::debug_handler]
@@ -0,0 +1,212 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use url::Url;
+
+use crate::race_client::RaceClient;
+
+/// A minimal NEAR JSON-RPC client, covering the methods used by the indexer.
+#[derive(Clone, Debug)]
+pub struct JsonRpcClient {
+    client: reqwest::Client,
+    url: Url,
+    id: Arc<AtomicU64>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RpcError {
+    #[error("transport error: {0}")]
+    Transport(#[from] reqwest::Error),
+    #[error("rpc error {name}: {cause:?}")]
+    Rpc { name: String, cause: Option<String> },
+    #[error("invalid response: {0}")]
+    Decode(#[from] serde_json::Error),
+}
+
+impl RpcError {
+    /// The node does not know the block, either because it was skipped or is not produced yet.
+    pub fn is_unknown_block(&self) -> bool {
+        matches!(self, RpcError::Rpc { cause: Some(cause), .. } if cause == "UNKNOWN_BLOCK")
+    }
+}
+
+#[derive(Deserialize)]
+struct Response {
+    result: Option<Value>,
+    error: Option<ResponseError>,
+}
+
+#[derive(Deserialize)]
+struct ResponseError {
+    name: Option<String>,
+    cause: Option<ResponseErrorCause>,
+    message: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ResponseErrorCause {
+    name: String,
+}
+
+impl JsonRpcClient {
+    pub fn new(url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+            id: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub async fn call<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> Result<R, RpcError> {
+        let response: Response = self
+            .client
+            .post(self.url.clone())
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": self.id.fetch_add(1, Ordering::Relaxed).to_string(),
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(RpcError::Rpc {
+                name: error
+                    .name
+                    .or(error.message)
+                    .unwrap_or_else(|| "UNKNOWN".to_owned()),
+                cause: error.cause.map(|cause| cause.name),
+            }),
+            (Some(result), None) => Ok(serde_json::from_value(result)?),
+            (None, None) => Ok(serde_json::from_value(Value::Null)?),
+        }
+    }
+
+    pub async fn block(&self, height: u64) -> Result<Option<BlockView>, RpcError> {
+        match self.call("block", json!({ "block_id": height })).await {
+            Ok(block) => Ok(Some(block)),
+            Err(err) if err.is_unknown_block() => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn chunk(&self, chunk_hash: &str) -> Result<ChunkView, RpcError> {
+        self.call("chunk", json!({ "chunk_id": chunk_hash })).await
+    }
+
+    /// The outcome of a transaction and all the receipts it produced, waiting for their execution.
+    pub async fn tx_status(
+        &self,
+        transaction_hash: &str,
+        signer_id: &str,
+    ) -> Result<FinalExecutionOutcomeView, RpcError> {
+        self.call("tx", json!([transaction_hash, signer_id])).await
+    }
+}
+
+impl RaceClient<JsonRpcClient> {
+    pub async fn status(&self) -> Result<StatusView, RpcError> {
+        self.race(|c| c.call("status", json!([]))).await
+    }
+
+    pub async fn final_block(&self) -> Result<BlockView, RpcError> {
+        self.race(|c| c.call("block", json!({ "finality": "final" })))
+            .await
+    }
+
+    /// Returns None if the block at `height` was skipped or is not produced yet.
+    pub async fn block(&self, height: u64) -> Result<Option<BlockView>, RpcError> {
+        self.race_some(|c| c.block(height)).await
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusView {
+    pub chain_id: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockView {
+    pub header: BlockHeaderView,
+    pub chunks: Vec<ChunkHeaderView>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockHeaderView {
+    pub height: u64,
+    pub hash: String,
+    /// Nanoseconds since the unix epoch, as a string since it does not fit in a JSON number.
+    pub timestamp_nanosec: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkHeaderView {
+    pub chunk_hash: String,
+    pub shard_id: u64,
+    /// The height of the block the chunk was produced for. Shards without a new chunk repeat
+    /// the header of their previous chunk.
+    pub height_included: u64,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChunkView {
+    pub header: ChunkHeaderView,
+    pub transactions: Vec<SignedTransactionView>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedTransactionView {
+    pub hash: String,
+    pub signer_id: String,
+    pub receiver_id: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FinalExecutionOutcomeView {
+    pub transaction_outcome: ExecutionOutcomeWithIdView,
+    pub receipts_outcome: Vec<ExecutionOutcomeWithIdView>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionOutcomeWithIdView {
+    pub id: String,
+    /// The block in which the receipt or transaction was executed.
+    pub block_hash: String,
+    pub outcome: ExecutionOutcomeView,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionOutcomeView {
+    pub logs: Vec<String>,
+    pub executor_id: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, Value>,
+}
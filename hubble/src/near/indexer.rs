@@ -0,0 +1,637 @@
+use std::ops::Range;
+
+use backon::Retryable;
+use color_eyre::eyre::{bail, Report};
+use futures::{
+    stream::{FuturesOrdered, TryStreamExt},
+    TryFutureExt,
+};
+use itertools::Itertools;
+use serde::{de::Error as _, Deserialize, Deserializer};
+use serde_json::{json, Value};
+use sqlx::{Acquire, Postgres};
+use time::OffsetDateTime;
+use tokio::time::{sleep, Duration};
+use tracing::{debug, info, info_span, Instrument};
+use url::Url;
+
+use crate::{
+    near::client::{ChunkView, JsonRpcClient},
+    postgres::{self, ChainId},
+    race_client::RaceClient,
+    tm::SerdeValueExt,
+};
+
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct Config {
+    pub label: String,
+    pub urls: Vec<Url>,
+
+    /// The height from which we start indexing
+    pub start_height: Option<u32>,
+
+    /// The accounts of the near-ibc contracts. Their logs are stored as events.
+    #[serde(default)]
+    pub contracts: Vec<String>,
+
+    /// Only [`postgres::InsertMode::Insert`] is supported, blocks, transactions and events can't be
+    /// upserted.
+    #[serde(default, deserialize_with = "deserialize_insert_mode")]
+    pub mode: postgres::InsertMode,
+}
+
+fn deserialize_insert_mode<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<postgres::InsertMode, D::Error> {
+    match postgres::InsertMode::deserialize(deserializer)? {
+        postgres::InsertMode::Upsert => {
+            Err(D::Error::custom("upsert is not supported for near chains"))
+        }
+        mode => Ok(mode),
+    }
+}
+
+/// Unit struct describing parametrization of associated types for NEAR chains.
+pub struct Near;
+
+impl postgres::ChainType for Near {
+    type BlockHash = String;
+    type BlockHeight = i32;
+    type TransactionHash = String;
+}
+
+pub type PgBlock = postgres::Block<Near>;
+pub type PgTransaction = postgres::Transaction<Near>;
+pub type PgEvent = postgres::Event<Near>;
+
+/// DTO corresponding to the v0.receipts table.
+pub struct PgReceipt {
+    pub chain_id: ChainId,
+    /// The block including the transaction. The receipt itself may be executed in a later block.
+    pub block_hash: String,
+    pub height: i32,
+    pub transaction_hash: String,
+    pub receipt_id: String,
+    /// The position of the receipt in the outcome of the transaction.
+    pub index: i32,
+    pub executor_id: String,
+    pub data: Value,
+    pub time: OffsetDateTime,
+}
+
+/// Everything stored for a single block.
+pub struct BlockInsert {
+    pub block: PgBlock,
+    pub transactions: Vec<PgTransaction>,
+    pub receipts: Vec<PgReceipt>,
+    pub events: Vec<PgEvent>,
+}
+
+impl Config {
+    /// The batch size for the fast sync protocol. Blocks of a batch are fetched concurrently.
+    pub const BATCH_SIZE: u32 = 20;
+
+    pub async fn index<DB>(self, pool: DB) -> Result<(), Report>
+    where
+        for<'a> &'a DB:
+            sqlx::Acquire<'a, Database = Postgres> + sqlx::Executor<'a, Database = Postgres>,
+    {
+        let client = RaceClient::new(self.urls.into_iter().map(JsonRpcClient::new).collect());
+
+        let mode = self.mode;
+        let contracts = self.contracts;
+        let (chain_id, height) = fetch_meta(&client, &pool).await?;
+        let indexing_span = info_span!("indexer", chain_id = chain_id.canonical);
+
+        async move {
+            // Determine from which height we should start indexing if we haven't
+            // indexed any blocks yet. If start_height > current_height, we jump to the new start height
+            let mut height = height
+                .unwrap_or_default()
+                .max(self.start_height.unwrap_or_default().into());
+
+            let mut retry_count = 0;
+            loop {
+                let head = (|| {
+                    client
+                        .final_block()
+                        .inspect_err(|e| debug!(?e, "error fetching final block"))
+                })
+                .retry(&crate::expo_backoff())
+                .await?
+                .header
+                .height;
+
+                if height > head {
+                    if retry_count > 30 {
+                        bail!("node {chain_id} has stopped providing new blocks");
+                    }
+                    retry_count += 1;
+                    debug!("caught up indexing, sleeping for 1 second");
+                    sleep(Duration::from_millis(1000)).await;
+                    continue;
+                }
+                retry_count = 0;
+
+                // Fast sync protocol. While we are more than a batch behind the final head, whole
+                // batches are fetched, after which we continue block by block.
+                let batch_end = std::cmp::min(head + 1, height + Self::BATCH_SIZE as u64);
+                let mut tx = pool.begin().await?;
+                fetch_and_insert_blocks(
+                    &client,
+                    &mut tx,
+                    chain_id,
+                    height..batch_end,
+                    &contracts,
+                    mode,
+                )
+                .await?;
+                tx.commit().await?;
+                if batch_end - height > 1 {
+                    info!("indexed blocks {}..{}", height, batch_end);
+                } else {
+                    info!("indexed block {}", height);
+                }
+                height = batch_end;
+            }
+        }
+        .instrument(indexing_span)
+        .await
+    }
+}
+
+/// fetches the ChainId for a given `JsonRpcClient`, among with the height up until we have indexed that chain in the DB.
+/// If we have not yet indexed any block for that chain, then height = None.
+async fn fetch_meta<DB>(
+    client: &RaceClient<JsonRpcClient>,
+    pool: &DB,
+) -> Result<(ChainId, Option<u64>), Report>
+where
+    for<'a> &'a DB:
+        sqlx::Acquire<'a, Database = Postgres> + sqlx::Executor<'a, Database = Postgres>,
+{
+    info!(?client, "fetching chain-id from node");
+    let chain_id = (|| {
+        client
+            .status()
+            .inspect_err(|e| debug!(?e, "error fetching chain-id"))
+    })
+    .retry(&crate::expo_backoff())
+    .await?
+    .chain_id;
+    info!(?client, "chain-id is {}", &chain_id);
+
+    let chain_id = postgres::fetch_or_insert_chain_id(pool, chain_id)
+        .await?
+        .get_inner_logged();
+    let height = sqlx::query!(
+        r#"SELECT MAX(height) height FROM "v0".blocks WHERE chain_id = $1"#,
+        chain_id.db
+    )
+    .fetch_one(pool)
+    .await?
+    .height;
+    Ok((chain_id, height.map(|height| height as u64 + 1)))
+}
+
+/// Fetches and inserts the blocks in `heights`, which should not exceed the final head. Skipped
+/// heights are ignored.
+pub(super) async fn fetch_and_insert_blocks(
+    client: &RaceClient<JsonRpcClient>,
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    chain_id: ChainId,
+    heights: Range<u64>,
+    contracts: &[String],
+    mode: postgres::InsertMode,
+) -> Result<(), Report> {
+    debug!("fetching blocks {}..{}", heights.start, heights.end);
+    let inserts: Vec<Option<BlockInsert>> = FuturesOrdered::from_iter(heights.map(|height| {
+        (move || {
+            fetch_block(client, chain_id, height, contracts)
+                .inspect_err(move |e| debug!(?e, height, "error fetching block"))
+        })
+        .retry(&crate::expo_backoff())
+    }))
+    .try_collect()
+    .await?;
+
+    let (blocks, transactions, receipts, events): (Vec<_>, Vec<_>, Vec<_>, Vec<_>) = inserts
+        .into_iter()
+        .flatten()
+        .map(|insert| {
+            (
+                insert.block,
+                insert.transactions,
+                insert.receipts,
+                insert.events,
+            )
+        })
+        .multiunzip();
+
+    postgres::insert_batch_blocks(tx, blocks, mode).await?;
+    postgres::insert_batch_transactions(tx, transactions.into_iter().flatten(), mode).await?;
+    insert_batch_receipts(tx, receipts.into_iter().flatten(), mode).await?;
+    postgres::insert_batch_events(tx, events.into_iter().flatten(), mode).await?;
+    Ok(())
+}
+
+/// Fetches a block with its transactions, their receipts and the logs of the `contracts`.
+///
+/// Returns None if the block at `height` was skipped.
+pub async fn fetch_block(
+    client: &RaceClient<JsonRpcClient>,
+    chain_id: ChainId,
+    height: u64,
+    contracts: &[String],
+) -> Result<Option<BlockInsert>, Report> {
+    let Some(block) = client.block(height).await? else {
+        debug!(height, "block was skipped");
+        return Ok(None);
+    };
+
+    // From this moment on we use the client which provided us with the block, since we know the
+    // data will be consistent with that node.
+    let client = client.fastest();
+
+    let block_hash = block.header.hash.clone();
+    let block_height: i32 = height.try_into()?;
+    let time = OffsetDateTime::from_unix_timestamp_nanos(block.header.timestamp_nanosec.parse()?)?;
+
+    // Shards without a new chunk repeat their previous chunk, which was already indexed.
+    let chunks: Vec<ChunkView> = FuturesOrdered::from_iter(
+        block
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.height_included == height)
+            .map(|chunk| client.chunk(&chunk.chunk_hash)),
+    )
+    .try_collect()
+    .await?;
+
+    let signed_transactions = chunks
+        .into_iter()
+        .flat_map(|chunk| chunk.transactions)
+        .collect::<Vec<_>>();
+    let outcomes: Vec<_> = FuturesOrdered::from_iter(
+        signed_transactions
+            .iter()
+            .map(|transaction| client.tx_status(&transaction.hash, &transaction.signer_id)),
+    )
+    .try_collect()
+    .await?;
+
+    let mut transactions = Vec::with_capacity(signed_transactions.len());
+    let mut receipts = vec![];
+    let mut events = vec![];
+    let mut block_index = 0;
+
+    for (index, (transaction, outcome)) in signed_transactions.into_iter().zip(outcomes).enumerate()
+    {
+        let transaction_hash = transaction.hash.clone();
+        let mut transaction_index = 0;
+
+        for (i, receipt) in outcome.receipts_outcome.iter().enumerate() {
+            if contracts.contains(&receipt.outcome.executor_id) {
+                for log in &receipt.outcome.logs {
+                    // near-ibc logs its events as JSON, other logs are kept as is.
+                    let event: Value = serde_json::from_str(log).unwrap_or_else(|_| json!(log));
+                    events.push(PgEvent {
+                        chain_id,
+                        block_hash: block_hash.clone(),
+                        block_height,
+                        time,
+                        data: json!({
+                            "receipt_id": receipt.id,
+                            "executor_id": receipt.outcome.executor_id,
+                            "block_hash": receipt.block_hash,
+                            "event": event,
+                        })
+                        .replace_escape_chars(),
+                        transaction_hash: Some(transaction_hash.clone()),
+                        transaction_index: Some(transaction_index),
+                        block_index,
+                    });
+                    transaction_index += 1;
+                    block_index += 1;
+                }
+            }
+
+            receipts.push(PgReceipt {
+                chain_id,
+                block_hash: block_hash.clone(),
+                height: block_height,
+                transaction_hash: transaction_hash.clone(),
+                receipt_id: receipt.id.clone(),
+                index: i.try_into().unwrap(),
+                executor_id: receipt.outcome.executor_id.clone(),
+                data: serde_json::to_value(receipt)
+                    .unwrap()
+                    .replace_escape_chars(),
+                time,
+            });
+        }
+
+        transactions.push(PgTransaction {
+            chain_id,
+            block_hash: block_hash.clone(),
+            block_height,
+            time,
+            data: json!({
+                "transaction": transaction,
+                "outcome": outcome.transaction_outcome,
+            })
+            .replace_escape_chars(),
+            hash: transaction_hash,
+            index: index.try_into().unwrap(),
+        });
+    }
+
+    Ok(Some(BlockInsert {
+        block: PgBlock {
+            chain_id,
+            hash: block_hash,
+            height: block_height,
+            time,
+            data: serde_json::to_value(&block).unwrap().replace_escape_chars(),
+        },
+        transactions,
+        receipts,
+        events,
+    }))
+}
+
+pub async fn insert_batch_receipts(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    receipts: impl IntoIterator<Item = PgReceipt>,
+    mode: postgres::InsertMode,
+) -> sqlx::Result<()> {
+    #![allow(clippy::type_complexity)]
+    let (
+        chain_ids,
+        block_hashes,
+        heights,
+        transaction_hashes,
+        receipt_ids,
+        indexes,
+        executor_ids,
+        data,
+        times,
+    ): (
+        Vec<i32>,
+        Vec<String>,
+        Vec<i32>,
+        Vec<String>,
+        Vec<String>,
+        Vec<i32>,
+        Vec<String>,
+        Vec<Value>,
+        Vec<OffsetDateTime>,
+    ) = receipts
+        .into_iter()
+        .map(|r| {
+            (
+                r.chain_id.db,
+                r.block_hash,
+                r.height,
+                r.transaction_hash,
+                r.receipt_id,
+                r.index,
+                r.executor_id,
+                r.data,
+                r.time,
+            )
+        })
+        .multiunzip();
+
+    match mode {
+        postgres::InsertMode::Insert => {
+            sqlx::query!("
+                INSERT INTO v0.receipts (chain_id, block_hash, height, transaction_hash, receipt_id, index, executor_id, data, time)
+                SELECT unnest($1::int[]), unnest($2::text[]), unnest($3::int[]), unnest($4::text[]), unnest($5::text[]), unnest($6::int[]), unnest($7::text[]), unnest($8::jsonb[]), unnest($9::timestamptz[])
+                ",
+                &chain_ids,
+                &block_hashes,
+                &heights,
+                &transaction_hashes,
+                &receipt_ids,
+                &indexes,
+                &executor_ids,
+                &data,
+                &times,
+            )
+            .execute(tx.as_mut())
+            .await?;
+        }
+        postgres::InsertMode::Upsert => unreachable!("upsert is rejected by the config"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use axum::{routing::post, Router};
+    use serde_json::{json, Value};
+
+    use super::{fetch_block, Config};
+    use crate::{
+        near::client::JsonRpcClient,
+        postgres::{ChainId, InsertMode},
+        race_client::RaceClient,
+    };
+
+    const CONTRACT: &str = "ibc.union.near";
+
+    fn block(height: u64, chunks: Value) -> Value {
+        json!({
+            "author": "validator.near",
+            "header": {
+                "height": height,
+                "hash": format!("block-{height}"),
+                "timestamp_nanosec": "1700000000123456789",
+            },
+            "chunks": chunks,
+        })
+    }
+
+    fn chunk_header(chunk_hash: &str, shard_id: u64, height_included: u64) -> Value {
+        json!({
+            "chunk_hash": chunk_hash,
+            "shard_id": shard_id,
+            "height_included": height_included,
+        })
+    }
+
+    fn outcome(id: &str, executor_id: &str, logs: Value) -> Value {
+        json!({
+            "id": id,
+            "block_hash": "block-11",
+            "proof": [],
+            "outcome": {
+                "logs": logs,
+                "executor_id": executor_id,
+                "receipt_ids": [],
+                "gas_burnt": 1,
+                "status": { "SuccessValue": "" },
+            },
+        })
+    }
+
+    /// Answers as a NEAR node whose block 10 contains a single transaction calling the IBC
+    /// contract, and which skipped block 11.
+    fn respond(request: &Value) -> Value {
+        let params = &request["params"];
+        let result = match request["method"].as_str().unwrap() {
+            "status" => json!({ "chain_id": "near-mock" }),
+            "block" if params["block_id"] == 10 => block(
+                10,
+                json!([
+                    chunk_header("chunk-0", 0, 10),
+                    chunk_header("chunk-1", 1, 9)
+                ]),
+            ),
+            "block" => {
+                return json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "error": {
+                        "name": "HANDLER_ERROR",
+                        "cause": { "name": "UNKNOWN_BLOCK", "info": {} },
+                        "message": "DB Not Found Error",
+                    },
+                })
+            }
+            "chunk" if params["chunk_id"] == "chunk-0" => json!({
+                "author": "validator.near",
+                "header": chunk_header("chunk-0", 0, 10),
+                "transactions": [{
+                    "hash": "tx-0",
+                    "signer_id": "relayer.near",
+                    "receiver_id": CONTRACT,
+                    "actions": [],
+                }],
+                "receipts": [],
+            }),
+            "tx" if params[0] == "tx-0" && params[1] == "relayer.near" => json!({
+                "status": { "SuccessValue": "" },
+                "transaction": {},
+                "transaction_outcome": outcome("tx-0", "relayer.near", json!([])),
+                "receipts_outcome": [
+                    outcome(
+                        "receipt-0",
+                        CONTRACT,
+                        json!([r#"{"ClientCreated":{"client_id":"cometbls-1"}}"#, "not json"]),
+                    ),
+                    outcome("receipt-1", "relayer.near", json!(["refund"])),
+                ],
+            }),
+            method => panic!("unexpected call to {method} with {params}"),
+        };
+        json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+    }
+
+    fn mock_client() -> RaceClient<JsonRpcClient> {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().route(
+            "/",
+            post(|body: String| async move {
+                respond(&serde_json::from_str(&body).unwrap()).to_string()
+            }),
+        );
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+        RaceClient::new(vec![JsonRpcClient::new(url.parse().unwrap())])
+    }
+
+    #[tokio::test]
+    async fn fetch_block_with_ibc_logs() {
+        let client = mock_client();
+        let chain_id = ChainId::new(1, "near-mock");
+
+        assert_eq!(client.status().await.unwrap().chain_id, "near-mock");
+
+        let insert = fetch_block(&client, chain_id, 10, &[CONTRACT.to_owned()])
+            .await
+            .unwrap()
+            .expect("block 10 was produced");
+
+        assert_eq!(insert.block.hash, "block-10");
+        assert_eq!(insert.block.height, 10);
+        assert_eq!(
+            insert.block.time.unix_timestamp_nanos(),
+            1_700_000_000_123_456_789
+        );
+
+        // Only the chunk produced at height 10 is fetched.
+        assert_eq!(insert.transactions.len(), 1);
+        assert_eq!(insert.transactions[0].hash, "tx-0");
+        assert_eq!(insert.transactions[0].index, 0);
+        assert_eq!(
+            insert.transactions[0].data["transaction"]["receiver_id"],
+            CONTRACT
+        );
+
+        let receipts: Vec<_> = insert
+            .receipts
+            .iter()
+            .map(|r| (r.receipt_id.as_str(), r.index, r.executor_id.as_str()))
+            .collect();
+        assert_eq!(
+            receipts,
+            [("receipt-0", 0, CONTRACT), ("receipt-1", 1, "relayer.near")]
+        );
+
+        // Logs of other accounts are not events.
+        let events: Vec<_> = insert
+            .events
+            .iter()
+            .map(|e| (e.block_index, e.transaction_index, e.data["event"].clone()))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (
+                    0,
+                    Some(0),
+                    json!({ "ClientCreated": { "client_id": "cometbls-1" } })
+                ),
+                (1, Some(1), json!("not json")),
+            ]
+        );
+        assert_eq!(insert.events[0].data["receipt_id"], "receipt-0");
+        assert_eq!(insert.events[0].data["block_hash"], "block-11");
+    }
+
+    #[test]
+    fn config_rejects_upsert() {
+        let config = |mode: &str| {
+            serde_json::from_value::<Config>(json!({
+                "label": "near",
+                "urls": ["http://localhost:3030"],
+                "mode": mode,
+            }))
+        };
+
+        assert!(matches!(config("Insert").unwrap().mode, InsertMode::Insert));
+        assert!(config("Upsert")
+            .unwrap_err()
+            .to_string()
+            .contains("upsert is not supported"));
+    }
+
+    #[tokio::test]
+    async fn fetch_skipped_block() {
+        let client = mock_client();
+        let chain_id = ChainId::new(1, "near-mock");
+
+        assert!(fetch_block(&client, chain_id, 11, &[CONTRACT.to_owned()])
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,4 @@
+pub mod client;
+mod indexer;
+
+pub use indexer::*;
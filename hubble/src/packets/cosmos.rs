@@ -0,0 +1,215 @@
+use color_eyre::eyre::{eyre, Report};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::warn;
+use unionlabs::{
+    events::IbcEvent,
+    ibc::core::client::height::Height,
+    tendermint::abci::{event::Event, event_attribute::EventAttribute},
+};
+
+use crate::packets::{DecodedEvent, HandshakeEvent, PacketEvent, PgDecodedEvent, Stage};
+
+/// The handshake events resolving the counterparty chain of a channel.
+const HANDSHAKE_EVENTS: [&str; 4] = [
+    "channel_open_init",
+    "channel_open_try",
+    "connection_open_init",
+    "connection_open_try",
+];
+
+/// A tendermint event as stored in v0.events by the tendermint indexer.
+#[derive(Deserialize)]
+struct StoredEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    attributes: Vec<StoredEventAttribute>,
+}
+
+#[derive(Deserialize)]
+struct StoredEventAttribute {
+    key: String,
+    value: String,
+    #[serde(default)]
+    index: bool,
+}
+
+pub async fn fetch(
+    db: &PgPool,
+    chain_id: i32,
+    from: i32,
+    to: i32,
+) -> Result<Vec<PgDecodedEvent>, Report> {
+    let types = Stage::ALL
+        .map(|stage| stage.as_str())
+        .into_iter()
+        .chain(HANDSHAKE_EVENTS)
+        .collect::<Vec<_>>();
+    let rows = sqlx::query!(
+        "
+        SELECT height, time, transaction_hash, index, data FROM v0.events
+        WHERE chain_id = $1 AND height > $2 AND height <= $3 AND data->>'type' = ANY($4)
+        ",
+        chain_id,
+        from,
+        to,
+        &types as _
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| match decode(row.data) {
+            Ok(event) => event.map(|event| PgDecodedEvent {
+                height: row.height,
+                time: row.time,
+                transaction_hash: row.transaction_hash,
+                index: row.index,
+                event,
+            }),
+            Err(err) => {
+                warn!(
+                    height = row.height,
+                    index = row.index,
+                    "could not decode packet event: {:?}",
+                    err
+                );
+                None
+            }
+        })
+        .collect())
+}
+
+fn decode(data: serde_json::Value) -> Result<Option<DecodedEvent>, Report> {
+    let event: StoredEvent = serde_json::from_value(data)?;
+    let event = Event {
+        ty: event.kind,
+        attributes: event
+            .attributes
+            .into_iter()
+            .map(|attribute| EventAttribute {
+                key: attribute.key,
+                value: attribute.value,
+                index: attribute.index,
+            })
+            .collect(),
+    };
+
+    let Some(event) = IbcEvent::<String, String, String>::try_from_tendermint_event(event) else {
+        return Ok(None);
+    };
+
+    let timeout_height = |height: Height| Some((height.revision_number, height.revision_height));
+
+    Ok(
+        match event.map_err(|err| eyre!("invalid ibc event: {:?}", err))? {
+            IbcEvent::ChannelOpenInit(event) => Some(
+                HandshakeEvent::ChannelOpen {
+                    port_id: event.port_id.to_string(),
+                    channel_id: event.channel_id.to_string(),
+                    connection_id: event.connection_id.to_string(),
+                }
+                .into(),
+            ),
+            IbcEvent::ChannelOpenTry(event) => Some(
+                HandshakeEvent::ChannelOpen {
+                    port_id: event.port_id.to_string(),
+                    channel_id: event.channel_id.to_string(),
+                    connection_id: event.connection_id.to_string(),
+                }
+                .into(),
+            ),
+            IbcEvent::ConnectionOpenInit(event) => Some(
+                HandshakeEvent::ConnectionOpen {
+                    connection_id: event.connection_id.to_string(),
+                    client_id: event.client_id,
+                }
+                .into(),
+            ),
+            IbcEvent::ConnectionOpenTry(event) => Some(
+                HandshakeEvent::ConnectionOpen {
+                    connection_id: event.connection_id.to_string(),
+                    client_id: event.client_id,
+                }
+                .into(),
+            ),
+            IbcEvent::SendPacket(event) => Some(
+                PacketEvent {
+                    stage: Stage::Send,
+                    sequence: event.packet_sequence.get(),
+                    source_port: event.packet_src_port.to_string(),
+                    source_channel: event.packet_src_channel.to_string(),
+                    destination_port: Some(event.packet_dst_port.to_string()),
+                    destination_channel: Some(event.packet_dst_channel.to_string()),
+                    data: Some(event.packet_data_hex),
+                    acknowledgement: None,
+                    timeout_height: timeout_height(event.packet_timeout_height),
+                    timeout_timestamp: Some(event.packet_timeout_timestamp),
+                }
+                .into(),
+            ),
+            IbcEvent::RecvPacket(event) => Some(
+                PacketEvent {
+                    stage: Stage::Recv,
+                    sequence: event.packet_sequence.get(),
+                    source_port: event.packet_src_port.to_string(),
+                    source_channel: event.packet_src_channel.to_string(),
+                    destination_port: Some(event.packet_dst_port.to_string()),
+                    destination_channel: Some(event.packet_dst_channel.to_string()),
+                    data: Some(event.packet_data_hex),
+                    acknowledgement: None,
+                    timeout_height: timeout_height(event.packet_timeout_height),
+                    timeout_timestamp: Some(event.packet_timeout_timestamp),
+                }
+                .into(),
+            ),
+            IbcEvent::WriteAcknowledgement(event) => Some(
+                PacketEvent {
+                    stage: Stage::WriteAck,
+                    sequence: event.packet_sequence.get(),
+                    source_port: event.packet_src_port.to_string(),
+                    source_channel: event.packet_src_channel.to_string(),
+                    destination_port: Some(event.packet_dst_port.to_string()),
+                    destination_channel: Some(event.packet_dst_channel.to_string()),
+                    data: Some(event.packet_data_hex),
+                    acknowledgement: Some(event.packet_ack_hex),
+                    timeout_height: timeout_height(event.packet_timeout_height),
+                    timeout_timestamp: Some(event.packet_timeout_timestamp),
+                }
+                .into(),
+            ),
+            IbcEvent::AcknowledgePacket(event) => Some(
+                PacketEvent {
+                    stage: Stage::Ack,
+                    sequence: event.packet_sequence.get(),
+                    source_port: event.packet_src_port.to_string(),
+                    source_channel: event.packet_src_channel.to_string(),
+                    destination_port: Some(event.packet_dst_port.to_string()),
+                    destination_channel: Some(event.packet_dst_channel.to_string()),
+                    data: None,
+                    acknowledgement: None,
+                    timeout_height: timeout_height(event.packet_timeout_height),
+                    timeout_timestamp: Some(event.packet_timeout_timestamp),
+                }
+                .into(),
+            ),
+            IbcEvent::TimeoutPacket(event) => Some(
+                PacketEvent {
+                    stage: Stage::Timeout,
+                    sequence: event.packet_sequence.get(),
+                    source_port: event.packet_src_port.to_string(),
+                    source_channel: event.packet_src_channel.to_string(),
+                    destination_port: Some(event.packet_dst_port.to_string()),
+                    destination_channel: Some(event.packet_dst_channel.to_string()),
+                    data: None,
+                    acknowledgement: None,
+                    timeout_height: timeout_height(event.packet_timeout_height),
+                    timeout_timestamp: Some(event.packet_timeout_timestamp),
+                }
+                .into(),
+            ),
+            _ => None,
+        },
+    )
+}
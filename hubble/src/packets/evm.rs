@@ -0,0 +1,142 @@
+use color_eyre::eyre::Report;
+use contracts::{
+    ibc_channel_handshake::IBCChannelHandshakeEvents,
+    ibc_connection::IBCConnectionEvents,
+    ibc_packet::IBCPacketEvents,
+    shared_types::{IbcCoreChannelV1PacketData, IbcCoreClientV1HeightData},
+};
+use ethers::{
+    abi::RawLog,
+    contract::EthLogDecode,
+    types::{Bytes, Log},
+};
+use sqlx::PgPool;
+
+use crate::{
+    decoder::fetch_logs,
+    packets::{DecodedEvent, HandshakeEvent, PacketEvent, PgDecodedEvent, Stage},
+};
+
+pub async fn fetch(
+    db: &PgPool,
+    chain_id: i32,
+    from: i32,
+    to: i32,
+) -> Result<Vec<PgDecodedEvent>, Report> {
+    Ok(fetch_logs(db, chain_id, from, to)
+        .await?
+        .into_iter()
+        .filter_map(|indexed| {
+            Some(PgDecodedEvent {
+                height: indexed.height,
+                time: indexed.time,
+                transaction_hash: Some(indexed.transaction_hash),
+                index: indexed.log_index,
+                event: decode(indexed.log)?,
+            })
+        })
+        .collect())
+}
+
+/// Decode an IBCPacket event or a channel or connection opening, any other log is ignored.
+fn decode(log: Log) -> Option<DecodedEvent> {
+    let log = RawLog {
+        topics: log.topics,
+        data: log.data.to_vec(),
+    };
+
+    if let Ok(event) = IBCChannelHandshakeEvents::decode_log(&log) {
+        return match event {
+            IBCChannelHandshakeEvents::ChannelOpenInitFilter(event) => {
+                Some(HandshakeEvent::ChannelOpen {
+                    port_id: event.port_id,
+                    channel_id: event.channel_id,
+                    connection_id: event.connection_id,
+                })
+            }
+            IBCChannelHandshakeEvents::ChannelOpenTryFilter(event) => {
+                Some(HandshakeEvent::ChannelOpen {
+                    port_id: event.port_id,
+                    channel_id: event.channel_id,
+                    connection_id: event.connection_id,
+                })
+            }
+            _ => None,
+        }
+        .map(Into::into);
+    }
+
+    if let Ok(event) = IBCConnectionEvents::decode_log(&log) {
+        return match event {
+            IBCConnectionEvents::ConnectionOpenInitFilter(event) => {
+                Some(HandshakeEvent::ConnectionOpen {
+                    connection_id: event.connection_id,
+                    client_id: event.client_id,
+                })
+            }
+            IBCConnectionEvents::ConnectionOpenTryFilter(event) => {
+                Some(HandshakeEvent::ConnectionOpen {
+                    connection_id: event.connection_id,
+                    client_id: event.client_id,
+                })
+            }
+            _ => None,
+        }
+        .map(Into::into);
+    }
+
+    let event = IBCPacketEvents::decode_log(&log).ok()?;
+
+    Some(
+        match event {
+            IBCPacketEvents::SendPacketFilter(event) => PacketEvent {
+                stage: Stage::Send,
+                sequence: event.sequence,
+                source_port: event.source_port,
+                source_channel: event.source_channel,
+                destination_port: None,
+                destination_channel: None,
+                data: Some(event.data.to_vec()),
+                acknowledgement: None,
+                timeout_height: Some(timeout_height(event.timeout_height)),
+                timeout_timestamp: Some(event.timeout_timestamp),
+            },
+            IBCPacketEvents::RecvPacketFilter(event) => {
+                from_packet(Stage::Recv, event.packet, None)
+            }
+            IBCPacketEvents::WriteAcknowledgementFilter(event) => {
+                from_packet(Stage::WriteAck, event.packet, Some(event.acknowledgement))
+            }
+            IBCPacketEvents::AcknowledgePacketFilter(event) => {
+                from_packet(Stage::Ack, event.packet, None)
+            }
+            IBCPacketEvents::TimeoutPacketFilter(event) => {
+                from_packet(Stage::Timeout, event.packet, None)
+            }
+        }
+        .into(),
+    )
+}
+
+fn from_packet(
+    stage: Stage,
+    packet: IbcCoreChannelV1PacketData,
+    acknowledgement: Option<Bytes>,
+) -> PacketEvent {
+    PacketEvent {
+        stage,
+        sequence: packet.sequence,
+        source_port: packet.source_port,
+        source_channel: packet.source_channel,
+        destination_port: Some(packet.destination_port),
+        destination_channel: Some(packet.destination_channel),
+        data: Some(packet.data.to_vec()),
+        acknowledgement: acknowledgement.map(|ack| ack.to_vec()),
+        timeout_height: Some(timeout_height(packet.timeout_height)),
+        timeout_timestamp: Some(packet.timeout_timestamp),
+    }
+}
+
+fn timeout_height(height: IbcCoreClientV1HeightData) -> (u64, u64) {
+    (height.revision_number, height.revision_height)
+}
@@ -0,0 +1,337 @@
+//! Post-processing stage decoding the IBC packet events of all indexed chains, which are joined
+//! into one row per packet by the `v0_packets.packets` view.
+
+use color_eyre::eyre::Report;
+use sqlx::{types::BigDecimal, PgPool, Postgres};
+use time::OffsetDateTime;
+
+use crate::decoder::{self, Decoder, Source};
+
+mod cosmos;
+mod evm;
+
+/// The stages of a packet lifecycle, named after the ibc-go events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Send,
+    Recv,
+    WriteAck,
+    Ack,
+    Timeout,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Send,
+        Stage::Recv,
+        Stage::WriteAck,
+        Stage::Ack,
+        Stage::Timeout,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Send => "send_packet",
+            Stage::Recv => "recv_packet",
+            Stage::WriteAck => "write_acknowledgement",
+            Stage::Ack => "acknowledge_packet",
+            Stage::Timeout => "timeout_packet",
+        }
+    }
+}
+
+/// A packet event, decoded from either a Cosmos event or an EVM log.
+#[derive(Debug, Clone)]
+pub struct PacketEvent {
+    pub stage: Stage,
+    pub sequence: u64,
+    pub source_port: String,
+    pub source_channel: String,
+    pub destination_port: Option<String>,
+    pub destination_channel: Option<String>,
+    pub data: Option<Vec<u8>>,
+    pub acknowledgement: Option<Vec<u8>>,
+    /// (revision_number, revision_height)
+    pub timeout_height: Option<(u64, u64)>,
+    pub timeout_timestamp: Option<u64>,
+}
+
+/// A handshake event, linking a channel to the client tracking the counterparty chain.
+#[derive(Debug, Clone)]
+pub enum HandshakeEvent {
+    /// `channel_open_init` or `channel_open_try`, stored in v0_packets.channels.
+    ChannelOpen {
+        port_id: String,
+        channel_id: String,
+        connection_id: String,
+    },
+    /// `connection_open_init` or `connection_open_try`, stored in v0_packets.connections.
+    ConnectionOpen {
+        connection_id: String,
+        client_id: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum DecodedEvent {
+    Packet(PacketEvent),
+    Handshake(HandshakeEvent),
+}
+
+impl From<PacketEvent> for DecodedEvent {
+    fn from(event: PacketEvent) -> Self {
+        DecodedEvent::Packet(event)
+    }
+}
+
+impl From<HandshakeEvent> for DecodedEvent {
+    fn from(event: HandshakeEvent) -> Self {
+        DecodedEvent::Handshake(event)
+    }
+}
+
+/// A decoded event, located in the block it was emitted in.
+pub struct PgDecodedEvent {
+    pub height: i32,
+    pub time: OffsetDateTime,
+    pub transaction_hash: Option<String>,
+    pub index: i32,
+    pub event: DecodedEvent,
+}
+
+/// Decodes the packet and handshake events of the given source.
+pub struct PacketsDecoder(pub Source);
+
+impl Decoder for PacketsDecoder {
+    type Output = Vec<PgDecodedEvent>;
+
+    fn name(&self) -> &'static str {
+        "packets"
+    }
+
+    fn source(&self) -> Source {
+        self.0
+    }
+
+    async fn cursor(&self, db: &PgPool, chain_id: i32) -> sqlx::Result<Option<i32>> {
+        sqlx::query_scalar!(
+            "SELECT height FROM v0_packets.cursors WHERE chain_id = $1 AND source = $2",
+            chain_id,
+            self.0.as_str()
+        )
+        .fetch_optional(db)
+        .await
+    }
+
+    async fn decode(
+        &self,
+        db: &PgPool,
+        chain_id: i32,
+        from: i32,
+        to: i32,
+    ) -> Result<Vec<PgDecodedEvent>, Report> {
+        match self.0 {
+            Source::Events => cosmos::fetch(db, chain_id, from, to).await,
+            Source::Logs => evm::fetch(db, chain_id, from, to).await,
+        }
+    }
+
+    async fn store(
+        &self,
+        tx: &mut sqlx::Transaction<'_, Postgres>,
+        chain_id: i32,
+        from: i32,
+        to: i32,
+        events: Vec<PgDecodedEvent>,
+    ) -> sqlx::Result<usize> {
+        let (packets, handshakes): (Vec<_>, Vec<_>) = events
+            .into_iter()
+            .partition(|event| matches!(event.event, DecodedEvent::Packet(_)));
+
+        // Blocks may be processed again, for instance after resetting the cursor.
+        sqlx::query!(
+            "DELETE FROM v0_packets.packet_events WHERE chain_id = $1 AND height > $2 AND height <= $3",
+            chain_id,
+            from,
+            to
+        )
+        .execute(tx.as_mut())
+        .await?;
+        sqlx::query!(
+            "DELETE FROM v0_packets.channels WHERE chain_id = $1 AND height > $2 AND height <= $3",
+            chain_id,
+            from,
+            to
+        )
+        .execute(tx.as_mut())
+        .await?;
+        sqlx::query!(
+            "DELETE FROM v0_packets.connections WHERE chain_id = $1 AND height > $2 AND height <= $3",
+            chain_id,
+            from,
+            to
+        )
+        .execute(tx.as_mut())
+        .await?;
+        let count = packets.len() + handshakes.len();
+        insert_batch_packet_events(tx, chain_id, packets).await?;
+        insert_batch_handshake_events(tx, chain_id, handshakes).await?;
+        sqlx::query!(
+            "
+            INSERT INTO v0_packets.cursors (chain_id, source, height) VALUES ($1, $2, $3)
+            ON CONFLICT (chain_id, source) DO UPDATE SET height = excluded.height
+            ",
+            chain_id,
+            self.0.as_str(),
+            to
+        )
+        .execute(tx.as_mut())
+        .await?;
+        Ok(count)
+    }
+}
+
+/// Continuously decode the packet events of every chain known to hubble.
+pub async fn index(db: PgPool) -> Result<(), Report> {
+    decoder::index(
+        db,
+        &[PacketsDecoder(Source::Events), PacketsDecoder(Source::Logs)],
+    )
+    .await
+}
+
+async fn insert_batch_packet_events(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    chain_id: i32,
+    events: Vec<PgDecodedEvent>,
+) -> sqlx::Result<()> {
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let mut heights = Vec::with_capacity(events.len());
+    let mut times = Vec::with_capacity(events.len());
+    let mut transaction_hashes = Vec::with_capacity(events.len());
+    let mut indexes = Vec::with_capacity(events.len());
+    let mut stages = Vec::with_capacity(events.len());
+    let mut sequences = Vec::with_capacity(events.len());
+    let mut source_ports = Vec::with_capacity(events.len());
+    let mut source_channels = Vec::with_capacity(events.len());
+    let mut destination_ports = Vec::with_capacity(events.len());
+    let mut destination_channels = Vec::with_capacity(events.len());
+    let mut datas = Vec::with_capacity(events.len());
+    let mut acknowledgements = Vec::with_capacity(events.len());
+    let mut timeout_revision_numbers = Vec::with_capacity(events.len());
+    let mut timeout_revision_heights = Vec::with_capacity(events.len());
+    let mut timeout_timestamps = Vec::with_capacity(events.len());
+
+    for PgDecodedEvent {
+        height,
+        time,
+        transaction_hash,
+        index,
+        event,
+    } in events
+    {
+        let DecodedEvent::Packet(event) = event else {
+            continue;
+        };
+        heights.push(height);
+        times.push(time);
+        transaction_hashes.push(transaction_hash);
+        indexes.push(index);
+        stages.push(event.stage.as_str());
+        sequences.push(event.sequence as i64);
+        source_ports.push(event.source_port);
+        source_channels.push(event.source_channel);
+        destination_ports.push(event.destination_port);
+        destination_channels.push(event.destination_channel);
+        datas.push(event.data);
+        acknowledgements.push(event.acknowledgement);
+        timeout_revision_numbers.push(event.timeout_height.map(|(number, _)| number as i64));
+        timeout_revision_heights.push(event.timeout_height.map(|(_, height)| height as i64));
+        timeout_timestamps.push(event.timeout_timestamp.map(BigDecimal::from));
+    }
+
+    sqlx::query!("
+        INSERT INTO v0_packets.packet_events (chain_id, height, time, transaction_hash, index, stage, sequence, source_port, source_channel, destination_port, destination_channel, data, acknowledgement, timeout_revision_number, timeout_revision_height, timeout_timestamp)
+        SELECT $1, unnest($2::int[]), unnest($3::timestamptz[]), unnest($4::text[]), unnest($5::int[]), unnest($6::text[]), unnest($7::bigint[]), unnest($8::text[]), unnest($9::text[]), unnest($10::text[]), unnest($11::text[]), unnest($12::bytea[]), unnest($13::bytea[]), unnest($14::bigint[]), unnest($15::bigint[]), unnest($16::numeric[])
+        ",
+        chain_id,
+        &heights,
+        &times,
+        &transaction_hashes as _,
+        &indexes,
+        &stages as _,
+        &sequences,
+        &source_ports,
+        &source_channels,
+        &destination_ports as _,
+        &destination_channels as _,
+        &datas as _,
+        &acknowledgements as _,
+        &timeout_revision_numbers as _,
+        &timeout_revision_heights as _,
+        &timeout_timestamps as _,
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    Ok(())
+}
+
+/// Store the channels and connections opened in the batch. Later handshakes of the same channel
+/// or connection identifier replace earlier ones.
+async fn insert_batch_handshake_events(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    chain_id: i32,
+    events: Vec<PgDecodedEvent>,
+) -> sqlx::Result<()> {
+    for PgDecodedEvent { height, event, .. } in events {
+        match event {
+            DecodedEvent::Handshake(HandshakeEvent::ChannelOpen {
+                port_id,
+                channel_id,
+                connection_id,
+            }) => {
+                sqlx::query!(
+                    "
+                    INSERT INTO v0_packets.channels (chain_id, height, port_id, channel_id, connection_id)
+                    VALUES ($1, $2, $3, $4, $5)
+                    ON CONFLICT (chain_id, port_id, channel_id) DO UPDATE
+                    SET height = excluded.height, connection_id = excluded.connection_id
+                    ",
+                    chain_id,
+                    height,
+                    port_id,
+                    channel_id,
+                    connection_id
+                )
+                .execute(tx.as_mut())
+                .await?;
+            }
+            DecodedEvent::Handshake(HandshakeEvent::ConnectionOpen {
+                connection_id,
+                client_id,
+            }) => {
+                sqlx::query!(
+                    "
+                    INSERT INTO v0_packets.connections (chain_id, height, connection_id, client_id)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (chain_id, connection_id) DO UPDATE
+                    SET height = excluded.height, client_id = excluded.client_id
+                    ",
+                    chain_id,
+                    height,
+                    connection_id,
+                    client_id
+                )
+                .execute(tx.as_mut())
+                .await?;
+            }
+            DecodedEvent::Packet(_) => {}
+        }
+    }
+
+    Ok(())
+}
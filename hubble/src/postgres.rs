@@ -174,6 +174,7 @@ where
         let min_height = height.iter().min().expect("at least one height");
 
         schedule_replication_reset(&mut tx, *chain_id, (*min_height).into(), "block reorg").await?;
+        rewind_log_decoders(&mut tx, *chain_id, *min_height).await?;
     }
 
     tx.commit().await?;
@@ -236,6 +237,7 @@ where
             let min_height = height.iter().min().expect("at least one height");
 
             schedule_replication_reset(tx, *chain_id, (*min_height).into(), "block reorg").await?;
+            rewind_log_decoders(tx, *chain_id, *min_height).await?;
         }
     }
     Ok(())
@@ -522,6 +524,144 @@ pub async fn schedule_replication_reset(
     Ok(())
 }
 
+/// Rewind the stages decoding v0.logs, so that the logs from `height` onwards are decoded again.
+pub async fn rewind_log_decoders(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    chain_id: i32,
+    height: i32,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "UPDATE v0_evm.cursors SET height = $2 WHERE chain_id = $1 AND height > $2",
+        chain_id,
+        height - 1
+    )
+    .execute(tx.as_mut())
+    .await?;
+    sqlx::query!(
+        "UPDATE v0_packets.cursors SET height = $2 WHERE chain_id = $1 AND source = 'logs' AND height > $2",
+        chain_id,
+        height - 1
+    )
+    .execute(tx.as_mut())
+    .await?;
+    sqlx::query!(
+        "UPDATE v0_clients.cursors SET height = $2 WHERE chain_id = $1 AND source = 'logs' AND height > $2",
+        chain_id,
+        height - 1
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    Ok(())
+}
+
+/// Rewind the stages decoding v0.events, so that the events from `height` onwards are decoded again.
+pub async fn rewind_event_decoders(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    chain_id: i32,
+    height: i32,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "UPDATE v0_packets.cursors SET height = $2 WHERE chain_id = $1 AND source = 'events' AND height > $2",
+        chain_id,
+        height - 1
+    )
+    .execute(tx.as_mut())
+    .await?;
+    sqlx::query!(
+        "UPDATE v0_clients.cursors SET height = $2 WHERE chain_id = $1 AND source = 'events' AND height > $2",
+        chain_id,
+        height - 1
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the inclusive ranges of heights missing from v0.blocks between the lowest and highest
+/// indexed height, optionally bounded by `from` and `to`.
+pub async fn get_block_gaps(
+    db: &PgPool,
+    chain_id: ChainId,
+    from: Option<i32>,
+    to: Option<i32>,
+) -> sqlx::Result<Vec<(i32, i32)>> {
+    Ok(sqlx::query!(
+        r#"
+        SELECT height + 1 AS "from!", next_height - 1 AS "to!" FROM (
+            SELECT height, LEAD(height) OVER (ORDER BY height) AS next_height
+            FROM v0.blocks
+            WHERE chain_id = $1 AND height >= COALESCE($2, 0) AND height <= COALESCE($3, 2147483647)
+        ) heights
+        WHERE next_height > height + 1
+        ORDER BY height
+        "#,
+        chain_id.db,
+        from,
+        to
+    )
+    .fetch_all(db)
+    .await?
+    .into_iter()
+    .map(|gap| (gap.from, gap.to))
+    .collect())
+}
+
+/// Removes the blocks, transactions and events of a CosmosSDK chain in the inclusive range.
+pub async fn delete_blocks(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    chain_id: ChainId,
+    from: i32,
+    to: i32,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "DELETE FROM v0.events WHERE chain_id = $1 AND height >= $2 AND height <= $3",
+        chain_id.db,
+        from,
+        to
+    )
+    .execute(tx.as_mut())
+    .await?;
+    sqlx::query!(
+        "DELETE FROM v0.transactions WHERE chain_id = $1 AND height >= $2 AND height <= $3",
+        chain_id.db,
+        from,
+        to
+    )
+    .execute(tx.as_mut())
+    .await?;
+    sqlx::query!(
+        "DELETE FROM v0.blocks WHERE chain_id = $1 AND height >= $2 AND height <= $3",
+        chain_id.db,
+        from,
+        to
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    Ok(())
+}
+
+/// Removes the logs of an EVM chain at `height`, for instance when the canonical block no longer
+/// contains logs of the tracked contracts.
+pub async fn delete_logs(
+    tx: &mut sqlx::Transaction<'_, Postgres>,
+    chain_id: ChainId,
+    height: i32,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        "DELETE FROM v0.logs WHERE chain_id = $1 AND height = $2",
+        chain_id.db,
+        height
+    )
+    .execute(tx.as_mut())
+    .await?;
+
+    schedule_replication_reset(tx, chain_id.db, height.into(), "block reorg").await?;
+    rewind_log_decoders(tx, chain_id.db, height).await
+}
+
 pub async fn get_max_consensus_height<'a, A: Acquire<'a, Database = Postgres>>(
     db: A,
     chain_id: ChainId,
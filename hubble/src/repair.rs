@@ -0,0 +1,85 @@
+use std::fmt;
+
+use color_eyre::eyre::{bail, eyre, Report};
+use sqlx::PgPool;
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::cli::{IndexerConfig, Indexers};
+
+/// Arguments of `hubble repair`.
+#[derive(clap::Args, Debug, Clone)]
+pub struct RepairArgs {
+    /// The label of the indexer, as configured in `--indexers`, to repair.
+    #[arg(long)]
+    pub label: String,
+
+    /// The lowest height to scan.
+    #[arg(long)]
+    pub from: Option<i32>,
+
+    /// The highest height to scan.
+    #[arg(long)]
+    pub to: Option<i32>,
+
+    /// Only report the issues found, without refetching anything.
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// An inconsistency found in the indexed data of a chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// The inclusive range of heights is missing from v0.blocks.
+    MissingBlocks { from: i32, to: i32 },
+    /// The block hash stored in v0.logs differs from the canonical block hash served by the RPC.
+    ForkedBlock {
+        height: i32,
+        stored: String,
+        canonical: String,
+    },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::MissingBlocks { from, to } => write!(f, "missing blocks {from}..={to}"),
+            Issue::ForkedBlock {
+                height,
+                stored,
+                canonical,
+            } => write!(
+                f,
+                "block {height} is {stored}, but canonical is {canonical}"
+            ),
+        }
+    }
+}
+
+pub async fn repair(db: PgPool, indexers: Indexers, args: RepairArgs) -> Result<(), Report> {
+    let indexer = indexers
+        .into_iter()
+        .find(|indexer| indexer.label() == args.label)
+        .ok_or_else(|| eyre!("no indexer configured with label {}", args.label))?;
+
+    let span = info_span!("repair", label = args.label);
+    async move {
+        let issues = match indexer {
+            IndexerConfig::Tm(cfg) => crate::tm::repair::repair(cfg, &db, &args).await?,
+            IndexerConfig::Eth(cfg) => crate::eth::repair::repair(cfg, &db, &args).await?,
+            _ => bail!("repair is only supported for tendermint and ethereum indexers"),
+        };
+
+        for issue in &issues {
+            warn!("{}", issue);
+        }
+
+        if args.dry_run {
+            info!("found {} issues, nothing repaired (dry run)", issues.len());
+        } else {
+            info!("repaired {} issues", issues.len());
+        }
+        Ok(())
+    }
+    .instrument(span)
+    .await
+}
@@ -243,7 +243,7 @@ async fn should_fast_sync_up_to(
 /// if the batch size > 1.
 ///
 /// Will return None if the node had no new blocks and no inserts were made, otherwise it will returns the last height inserted.
-async fn fetch_and_insert_blocks(
+pub(super) async fn fetch_and_insert_blocks(
     client: &RaceClient<HttpClient>,
     tx: &mut sqlx::Transaction<'_, Postgres>,
     chain_id: ChainId,
@@ -599,7 +599,7 @@ impl<I> WithType<I> {
     }
 }
 
-trait SerdeValueExt {
+pub(crate) trait SerdeValueExt {
     fn replace_escape_chars(self) -> Self;
 }
 
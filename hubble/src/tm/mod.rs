@@ -1,4 +1,5 @@
 pub mod client;
 mod indexer;
+pub mod repair;
 
 pub use indexer::*;
@@ -0,0 +1,86 @@
+use backon::Retryable;
+use color_eyre::eyre::{eyre, Report};
+use regex::Regex;
+use sqlx::PgPool;
+use tendermint_rpc::HttpClient;
+use tracing::info;
+
+use super::{indexer::fetch_and_insert_blocks, Config};
+use crate::{
+    postgres::{self, InsertMode},
+    race_client::RaceClient,
+    repair::{Issue, RepairArgs},
+};
+
+/// Find the heights missing from v0.blocks and, unless running dry, refetch them. Partially
+/// indexed data in the missing ranges is removed first, so repairing is idempotent.
+pub async fn repair(config: Config, db: &PgPool, args: &RepairArgs) -> Result<Vec<Issue>, Report> {
+    let client = RaceClient::new(
+        config
+            .urls
+            .iter()
+            .map(|url| HttpClient::new(url.as_str()).unwrap())
+            .collect(),
+    );
+
+    let filter = config
+        .filter
+        .as_ref()
+        .map(|filter| Regex::new(filter).expect("should get valid regex"));
+
+    let canonical = (|| client.status())
+        .retry(&crate::expo_backoff())
+        .await?
+        .node_info
+        .network
+        .as_str()
+        .to_owned();
+    let chain_id = postgres::get_chain_id(db, canonical.clone())
+        .await?
+        .ok_or_else(|| eyre!("chain {canonical} has not been indexed yet"))?;
+
+    let gaps = postgres::get_block_gaps(db, chain_id, args.from, args.to).await?;
+    let issues = gaps
+        .iter()
+        .map(|&(from, to)| Issue::MissingBlocks { from, to })
+        .collect();
+
+    if args.dry_run {
+        return Ok(issues);
+    }
+
+    for (from, to) in gaps {
+        let mut height = from;
+        while height <= to {
+            let batch_size = (to - height + 1).min(Config::BATCH_SIZE as i32);
+            let mut tx = db.begin().await?;
+            postgres::delete_blocks(&mut tx, chain_id, height, height + batch_size - 1).await?;
+            let next = fetch_and_insert_blocks(
+                &client,
+                &mut tx,
+                chain_id,
+                batch_size as u32,
+                (height as u32).into(),
+                InsertMode::Insert,
+                filter.as_ref(),
+            )
+            .await?
+            .ok_or_else(|| eyre!("node did not return block {height}"))?;
+            // The node may return no headers at all, which would otherwise repair the same
+            // heights forever.
+            let next = next.value() as i32;
+            if next <= height {
+                return Err(eyre!("node did not return block {height}"));
+            }
+            postgres::schedule_replication_reset(&mut tx, chain_id.db, height.into(), "repair")
+                .await?;
+            postgres::rewind_event_decoders(&mut tx, chain_id.db, height).await?;
+            tx.commit().await?;
+
+            info!("repaired blocks {}..{}", height, next);
+            height = next;
+        }
+    }
+
+    Ok(issues)
+}
@@ -195,6 +195,18 @@ pub trait CosmosSdkChainExt: CosmosSdkChainRpcs {
         messages: impl IntoIterator<Item = protos::google::protobuf::Any> + Clone,
         memo: String,
     ) -> Result<(H256, u64), BroadcastTxCommitError> {
+        let tx = self.sign_tx(signer, messages, memo).await?;
+        self.broadcast_signed_tx(tx).await
+    }
+
+    /// - simulate tx
+    /// - sign tx with the simulated gas
+    async fn sign_tx(
+        &self,
+        signer: &CosmosSigner,
+        messages: impl IntoIterator<Item = protos::google::protobuf::Any> + Clone,
+        memo: String,
+    ) -> Result<SignedTx, BroadcastTxCommitError> {
         let account = self.account_info(&signer.to_string()).await;
 
         let (tx_body, mut auth_info, simulation_gas_info) = self
@@ -243,6 +255,26 @@ pub trait CosmosSdkChainExt: CosmosSdkChainRpcs {
             .chain_update(&tx_raw_bytes)
             .finalize()
             .into();
+
+        Ok(SignedTx {
+            tx_raw_bytes,
+            tx_hash: tx_hash_normalized,
+            sequence: auth_info.signer_infos[0].sequence,
+        })
+    }
+
+    /// - submit tx
+    /// - wait for inclusion
+    /// - return (tx_hash, gas_used)
+    async fn broadcast_signed_tx(
+        &self,
+        tx: SignedTx,
+    ) -> Result<(H256, u64), BroadcastTxCommitError> {
+        let SignedTx {
+            tx_raw_bytes,
+            tx_hash: tx_hash_normalized,
+            ..
+        } = tx;
         let tx_hash = hex::encode_upper(tx_hash_normalized);
 
         if let Ok(tx) = self.tm_client().tx(tx_hash.parse().unwrap(), false).await {
@@ -515,6 +547,16 @@ impl<T: CosmosSdkChain + CosmosSdkChainRpcs + Chain<Error = tendermint_rpc::Erro
 
 impl<T: CosmosSdkChainRpcs> CosmosSdkChainExt for T {}
 
+/// A transaction signed by [`CosmosSdkChainExt::sign_tx`], which is not broadcast yet.
+#[derive(Debug, Clone)]
+pub struct SignedTx {
+    pub tx_raw_bytes: Vec<u8>,
+    pub tx_hash: H256,
+    /// The account sequence the transaction was signed with. Once the sequence of the signer is
+    /// past it, the transaction can no longer be included.
+    pub sequence: u64,
+}
+
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum BroadcastTxCommitError {
     #[error("error querying latest height")]
@@ -0,0 +1,205 @@
+//! Transfers through the UCS01 relay deployed on EVM chains, shared by `ucli` and the e2e checks.
+
+use std::collections::BTreeMap;
+
+use contracts::{
+    erc20::ERC20,
+    ucs01_relay::{LocalToken, SentFilter, UCS01Relay},
+};
+use ethers::{
+    abi::Detokenize,
+    contract::{parse_log, ContractCall, ContractError},
+    providers::{Middleware, ProviderError},
+    types::{Address, Bytes, TransactionReceipt, H256, U256},
+};
+use thiserror::Error;
+use tracing::info;
+use unionlabs::ibc::core::client::height::Height;
+
+/// A transfer sent with [`send`].
+#[derive(Debug)]
+pub struct Sent {
+    pub receipt: TransactionReceipt,
+    pub event: SentFilter,
+}
+
+#[derive(Debug, Error)]
+pub enum TransferError<M: Middleware + 'static> {
+    #[error("cannot resolve {denom}")]
+    ResolveDenom {
+        denom: String,
+        source: ContractError<M>,
+    },
+    #[error("{denom} is neither an ERC20 address nor a denom registered on {channel}")]
+    UnknownDenom { denom: String, channel: String },
+    #[error("the total amount of {0} transferred overflows")]
+    AmountOverflow(String),
+    #[error("cannot query the allowance of the relay for {denom}")]
+    Allowance {
+        denom: String,
+        source: ContractError<M>,
+    },
+    #[error("allowance of the relay for {denom} is {allowance}, but {amount} is transferred")]
+    InsufficientAllowance {
+        denom: String,
+        allowance: U256,
+        amount: u128,
+    },
+    #[error("cannot send {what}")]
+    Send {
+        what: String,
+        source: ContractError<M>,
+    },
+    #[error("cannot wait for {what} ({tx_hash:?}) to be included")]
+    Wait {
+        what: String,
+        tx_hash: H256,
+        source: ProviderError,
+    },
+    #[error("{what} ({tx_hash:?}) was dropped")]
+    Dropped { what: String, tx_hash: H256 },
+    #[error("{what} ({tx_hash:?}) reverted")]
+    Reverted { what: String, tx_hash: H256 },
+    #[error("the transfer ({0:?}) did not emit a Sent event")]
+    MissingSentEvent(H256),
+}
+
+/// Resolves `denom` to the ERC20 it is transferred as: either the token the relay created for a denom received over
+/// `channel`, or a local ERC20 given by its address. `None` if it is neither, which for a denom received over
+/// `channel` means none of it was received yet.
+pub async fn resolve_denom<M: Middleware + 'static>(
+    relay: &UCS01Relay<M>,
+    channel: &str,
+    denom: &str,
+) -> Result<Option<Address>, ContractError<M>> {
+    let address = relay
+        .get_denom_address(channel.to_owned(), denom.to_owned())
+        .await?;
+    if !address.is_zero() {
+        return Ok(Some(address));
+    }
+    Ok(denom.parse().ok())
+}
+
+/// Sends `tokens`, given as `(denom, amount)` with the denoms resolved by [`resolve_denom`], from `sender` to
+/// `receiver` over `channel`.
+///
+/// The relay pulls the tokens from `sender`, which must have allowed it to spend the total amount of each token. If
+/// `approve` is set, a lower allowance is raised to that amount rather than failing the transfer.
+pub async fn send<M: Middleware + 'static>(
+    relay: &UCS01Relay<M>,
+    sender: Address,
+    channel: &str,
+    receiver: Bytes,
+    tokens: &[(String, u128)],
+    memo: String,
+    approve: bool,
+) -> Result<Sent, TransferError<M>> {
+    let mut local_tokens = Vec::with_capacity(tokens.len());
+    // the same token can be listed several times, the relay pulls the sum of the amounts.
+    let mut totals = BTreeMap::<Address, (&str, u128)>::new();
+    for (denom, amount) in tokens {
+        let address = resolve_denom(relay, channel, denom)
+            .await
+            .map_err(|source| TransferError::ResolveDenom {
+                denom: denom.clone(),
+                source,
+            })?
+            .ok_or_else(|| TransferError::UnknownDenom {
+                denom: denom.clone(),
+                channel: channel.to_owned(),
+            })?;
+        info!(%denom, ?address, "resolved denom");
+
+        let (_, total) = totals.entry(address).or_insert((denom.as_str(), 0));
+        *total = total
+            .checked_add(*amount)
+            .ok_or_else(|| TransferError::AmountOverflow(denom.clone()))?;
+        local_tokens.push(LocalToken {
+            denom: address,
+            amount: *amount,
+        });
+    }
+
+    for (address, (denom, amount)) in totals {
+        let erc20 = ERC20::new(address, relay.client());
+        let allowance = erc20
+            .allowance(sender, relay.address())
+            .await
+            .map_err(|source| TransferError::Allowance {
+                denom: denom.to_owned(),
+                source,
+            })?;
+        if allowance >= amount.into() {
+            continue;
+        }
+        if !approve {
+            return Err(TransferError::InsufficientAllowance {
+                denom: denom.to_owned(),
+                allowance,
+                amount,
+            });
+        }
+
+        info!(%denom, amount, "approving the relay");
+        execute(
+            erc20.approve(relay.address(), amount.into()),
+            format!("the approval of {denom}"),
+        )
+        .await?;
+    }
+
+    let receipt = execute(
+        relay.send(
+            channel.to_owned(),
+            receiver,
+            local_tokens,
+            memo,
+            Height {
+                revision_number: 0,
+                revision_height: 0,
+            }
+            .into(),
+            u64::MAX,
+        ),
+        "the transfer".to_owned(),
+    )
+    .await?;
+
+    let event = receipt
+        .logs
+        .iter()
+        .find_map(|log| parse_log::<SentFilter>(log.clone()).ok())
+        .ok_or(TransferError::MissingSentEvent(receipt.transaction_hash))?;
+
+    Ok(Sent { receipt, event })
+}
+
+/// Sends `call` and waits for it to be included, failing if it reverted.
+async fn execute<M: Middleware + 'static, D: Detokenize>(
+    call: ContractCall<M, D>,
+    what: String,
+) -> Result<TransactionReceipt, TransferError<M>> {
+    let pending = call.send().await.map_err(|source| TransferError::Send {
+        what: what.clone(),
+        source,
+    })?;
+    let tx_hash = *pending;
+
+    let receipt = pending
+        .await
+        .map_err(|source| TransferError::Wait {
+            what: what.clone(),
+            tx_hash,
+            source,
+        })?
+        .ok_or_else(|| TransferError::Dropped {
+            what: what.clone(),
+            tx_hash,
+        })?;
+    if receipt.status != Some(1.into()) {
+        return Err(TransferError::Reverted { what, tx_hash });
+    }
+
+    Ok(receipt)
+}
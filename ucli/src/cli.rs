@@ -1,6 +1,9 @@
 use std::{ffi::OsString, marker::PhantomData};
 
-use chain_utils::{cosmos_sdk::GasConfig, private_key::PrivateKey};
+use chain_utils::{
+    cosmos_sdk::{CosmosSdkChainRpcs, GasConfig},
+    private_key::PrivateKey,
+};
 use clap::{Parser, Subcommand};
 use ethers::{
     prelude::k256::ecdsa,
@@ -8,11 +11,15 @@ use ethers::{
     signers::LocalWallet,
     utils::secret_key_to_address,
 };
-use serde::{Deserialize, Serialize};
-use tendermint_rpc::WebSocketClientUrl;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tendermint_rpc::{client::CompatMode, Client, WebSocketClient, WebSocketClientUrl};
+use ucs01_relay_api::types::FeePerU128;
 use unionlabs::{
+    bounded::BoundedU128,
+    cosmos::base::coin::Coin,
     ethereum::config::ChainSpec,
     hash::{H160, H256},
+    signer::CosmosSigner,
 };
 
 #[derive(Debug, Parser)]
@@ -56,6 +63,8 @@ pub enum ComputeCmd {
 pub enum TxCmd {
     #[command(subcommand)]
     Ethereum(EthereumTx),
+    #[command(subcommand)]
+    Union(UnionTx),
 }
 
 #[derive(Debug, Subcommand)]
@@ -67,15 +76,87 @@ pub enum EthereumTx {
         channel_id: String,
         #[arg(long)]
         receiver: String,
+        /// The tokens to transfer, as `<denom>:<amount>`. The denom is either the address of a local ERC20 or a
+        /// denom received over `channel_id`. Can be passed multiple times.
+        #[arg(long = "token", required = true, value_parser = parse_token)]
+        tokens: Vec<(String, u128)>,
         #[arg(long)]
-        amount: u64,
+        memo: String,
+        /// Approve the relay to spend the tokens if the current allowance is insufficient.
         #[arg(long)]
-        denom: String,
+        approve: bool,
+    },
+}
+
+fn parse_token(s: &str) -> Result<(String, u128), String> {
+    let (denom, amount) = s
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected `<denom>:<amount>`, found `{s}`"))?;
+    let amount = amount
+        .parse()
+        .map_err(|err| format!("invalid amount in `{s}`: {err}"))?;
+    Ok((denom.to_owned(), amount))
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UnionTx {
+    /// Transfer native tokens through the UCS01 relay contract.
+    Transfer {
+        #[arg(long)]
+        contract_address: String,
+        #[arg(long)]
+        channel_id: String,
         #[arg(long)]
+        receiver: String,
+        /// The coins to transfer, i.e. `100muno`. Can be passed multiple times.
+        #[arg(long = "funds", required = true, value_parser = parse_coin)]
+        funds: Vec<Coin>,
+        /// Fee taken from a transferred denom, as a percentage of the amount, i.e. `muno=1`.
+        #[arg(long = "fee", value_parser = parse_fee)]
+        fees: Vec<(String, FeePerU128)>,
+        /// Timeout of the packet in seconds, defaults to the timeout configured in the contract.
+        #[arg(long)]
+        timeout: Option<u64>,
+        #[arg(long, default_value = "")]
         memo: String,
+        /// Forward the tokens from the receiving chain to `forward_receiver` through this channel. Overrides `memo`.
+        #[arg(long, requires_all = ["forward_port", "forward_receiver"])]
+        forward_channel: Option<String>,
+        #[arg(long, requires = "forward_channel")]
+        forward_port: Option<String>,
+        #[arg(long, requires = "forward_channel")]
+        forward_receiver: Option<String>,
+        /// Index of the signer in the `union.signers` of the config.
+        #[arg(long, default_value_t = 0)]
+        signer: usize,
     },
 }
 
+fn parse_coin(s: &str) -> Result<Coin, String> {
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format!("missing denom in `{s}`"))?;
+    let (amount, denom) = s.split_at(split);
+    Ok(Coin {
+        denom: denom.to_owned(),
+        amount: amount
+            .parse()
+            .map_err(|err| format!("invalid amount in `{s}`: {err}"))?,
+    })
+}
+
+fn parse_fee(s: &str) -> Result<(String, FeePerU128), String> {
+    let (denom, percent) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `<denom>=<percent>`, found `{s}`"))?;
+    let percent = percent
+        .parse()
+        .map_err(|err| format!("invalid percentage in `{s}`: {err}"))
+        .and_then(|percent| BoundedU128::new(percent).map_err(|err| format!("{err:?}")))?;
+    let fee = FeePerU128::percent(percent).map_err(|err| err.to_string())?;
+    Ok((denom.to_owned(), fee))
+}
+
 #[derive(Debug, Subcommand)]
 pub enum QueryCmd {
     #[command(subcommand)]
@@ -86,7 +167,7 @@ pub enum QueryCmd {
 
 #[derive(Debug, Subcommand)]
 pub enum EthereumQuery {
-    // TODO(aeryz): Check if native denoms present in the `denomToAddress` mapping.
+    /// The balance of a denom, either the address of a local ERC20 or a denom received over `channel_id`.
     Ucs01Balance {
         #[arg(long)]
         contract_address: H160,
@@ -103,6 +184,24 @@ pub enum EthereumQuery {
         #[arg(long)]
         address: H160,
     },
+    /// The amount of a local token sent over a channel and not yet returned.
+    Outstanding {
+        #[arg(long)]
+        contract_address: H160,
+        #[arg(long)]
+        channel_id: String,
+        #[arg(long)]
+        token: H160,
+    },
+    /// The ERC20 token the relay mints for a denom received over a channel.
+    DenomAddress {
+        #[arg(long)]
+        contract_address: H160,
+        #[arg(long)]
+        channel_id: String,
+        #[arg(long)]
+        denom: String,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -111,6 +210,28 @@ pub enum UnionQuery {
         #[arg(long)]
         address: String,
     },
+    /// The UCS01 relay channel and the outstanding balances sent over it.
+    Channel {
+        #[arg(long)]
+        contract_address: String,
+        #[arg(long)]
+        channel_id: String,
+    },
+    ListChannels {
+        #[arg(long)]
+        contract_address: String,
+    },
+    /// The token factory denoms the UCS01 relay minted for denoms received over a channel.
+    ListDenoms {
+        #[arg(long)]
+        contract_address: String,
+        #[arg(long)]
+        channel_id: String,
+        #[arg(long)]
+        start_after: Option<String>,
+        #[arg(long)]
+        limit: Option<u32>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -160,6 +281,86 @@ pub struct Ethereum<C: ChainSpec> {
     __marker: PhantomData<fn() -> C>,
 }
 
+#[derive(Debug, Clone)]
+pub struct Union {
+    pub chain_id: String,
+    pub signers: Vec<CosmosSigner>,
+    pub tm_client: WebSocketClient,
+    pub grpc_url: String,
+    pub gas_config: GasConfig,
+}
+
+impl Union {
+    pub async fn new(config: UnionChainConfig) -> Result<Self, ()> {
+        let (tm_client, driver) = WebSocketClient::builder(config.ws_url)
+            .compat_mode(CompatMode::V0_37)
+            .build()
+            .await
+            .unwrap();
+
+        tokio::spawn(async move { driver.run().await });
+
+        let chain_id = tm_client
+            .status()
+            .await
+            .unwrap()
+            .node_info
+            .network
+            .to_string();
+
+        Ok(Self {
+            chain_id,
+            signers: config
+                .signers
+                .into_iter()
+                .map(|signer| CosmosSigner::new(signer.value(), "union".to_owned()))
+                .collect(),
+            tm_client,
+            grpc_url: config.grpc_url,
+            gas_config: config.gas_config,
+        })
+    }
+
+    /// Executes a smart query against `contract` and deserializes the response.
+    pub async fn query_contract<Q: Serialize, R: DeserializeOwned>(
+        &self,
+        contract: String,
+        query: &Q,
+    ) -> R {
+        let response =
+            protos::cosmwasm::wasm::v1::query_client::QueryClient::connect(self.grpc_url.clone())
+                .await
+                .unwrap()
+                .smart_contract_state(protos::cosmwasm::wasm::v1::QuerySmartContractStateRequest {
+                    address: contract,
+                    query_data: serde_json::to_vec(query).unwrap(),
+                })
+                .await
+                .unwrap()
+                .into_inner();
+
+        serde_json::from_slice(&response.data).unwrap()
+    }
+}
+
+impl CosmosSdkChainRpcs for Union {
+    fn tm_chain_id(&self) -> String {
+        self.chain_id.clone()
+    }
+
+    fn grpc_url(&self) -> String {
+        self.grpc_url.clone()
+    }
+
+    fn tm_client(&self) -> &WebSocketClient {
+        &self.tm_client
+    }
+
+    fn gas_config(&self) -> &GasConfig {
+        &self.gas_config
+    }
+}
+
 impl<C: ChainSpec> Ethereum<C> {
     pub async fn new(config: EthereumChainConfigFields) -> Result<Self, ()> {
         let provider = Provider::new(Ws::connect(config.eth_rpc_api).await.unwrap());
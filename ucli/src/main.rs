@@ -1,16 +1,18 @@
 use std::{fs::read_to_string, sync::Arc};
 
+use chain_utils::cosmos_sdk::CosmosSdkChainExt;
 use clap::Parser;
-use cli::Ethereum;
-use contracts::{
-    erc20,
-    ucs01_relay::{LocalToken, UCS01Relay},
-};
+use cli::{Ethereum, Union};
+use contracts::{erc20, ucs01_relay::UCS01Relay};
 use ethers::{middleware::SignerMiddleware, signers::Signer, types::Address};
+use ucs01_evm_client::{resolve_denom, TransferError};
+use ucs01_relay::msg::{ChannelResponse, ListChannelsResponse, ListDenomsResponse, TransferMsg};
+use ucs01_relay_api::middleware::Memo;
 use unionlabs::{
+    cosmos::base::coin::Coin,
     ethereum::config::{ChainSpec, Mainnet, Minimal},
-    ibc::core::client::height::Height,
-    uint::U256,
+    google::protobuf::any::mk_any,
+    ErrorReporter,
 };
 
 use crate::cli::{AppArgs, Config};
@@ -55,9 +57,9 @@ async fn main() {
                             relay_address,
                             channel_id,
                             receiver,
-                            amount,
-                            denom,
+                            tokens,
                             memo,
+                            approve,
                         } => match config.ethereum {
                             cli::EthereumChainConfig::Mainnet(config) => {
                                 handle_transfer::<Mainnet>(
@@ -65,9 +67,9 @@ async fn main() {
                                     relay_address.into(),
                                     channel_id,
                                     receiver,
-                                    amount,
-                                    denom,
+                                    tokens,
                                     memo,
+                                    approve,
                                 )
                                 .await
                             }
@@ -77,15 +79,52 @@ async fn main() {
                                     relay_address.into(),
                                     channel_id,
                                     receiver,
-                                    amount,
-                                    denom,
+                                    tokens,
                                     memo,
+                                    approve,
                                 )
                                 .await
                             }
                         },
                     };
                 }
+                cli::TxCmd::Union(union_tx) => match union_tx {
+                    cli::UnionTx::Transfer {
+                        contract_address,
+                        channel_id,
+                        receiver,
+                        funds,
+                        fees,
+                        timeout,
+                        memo,
+                        forward_channel,
+                        forward_port,
+                        forward_receiver,
+                        signer,
+                    } => {
+                        let memo = match (forward_channel, forward_port, forward_receiver) {
+                            (Some(channel), Some(port), Some(receiver)) => {
+                                pfm_memo(channel, port, receiver)
+                            }
+                            _ => memo,
+                        };
+                        handle_union_transfer(
+                            Union::new(config.union).await.unwrap(),
+                            signer,
+                            contract_address,
+                            TransferMsg {
+                                channel: channel_id,
+                                receiver,
+                                timeout,
+                                memo,
+                                fees: (!fees.is_empty()).then(|| fees.into_iter().collect()),
+                                relayer_fee: None,
+                            },
+                            funds,
+                        )
+                        .await
+                    }
+                },
             }
         }
         cli::Command::Query(query) => {
@@ -140,24 +179,103 @@ async fn main() {
                             .await
                         }
                     },
+                    cli::EthereumQuery::Outstanding {
+                        contract_address,
+                        channel_id,
+                        token,
+                    } => match config.ethereum {
+                        cli::EthereumChainConfig::Mainnet(config) => {
+                            handle_outstanding::<Mainnet>(
+                                Ethereum::new(config).await.unwrap(),
+                                contract_address.into(),
+                                channel_id,
+                                token.into(),
+                            )
+                            .await
+                        }
+                        cli::EthereumChainConfig::Minimal(config) => {
+                            handle_outstanding::<Minimal>(
+                                Ethereum::new(config).await.unwrap(),
+                                contract_address.into(),
+                                channel_id,
+                                token.into(),
+                            )
+                            .await
+                        }
+                    },
+                    cli::EthereumQuery::DenomAddress {
+                        contract_address,
+                        channel_id,
+                        denom,
+                    } => match config.ethereum {
+                        cli::EthereumChainConfig::Mainnet(config) => {
+                            handle_denom_address::<Mainnet>(
+                                Ethereum::new(config).await.unwrap(),
+                                contract_address.into(),
+                                channel_id,
+                                denom,
+                            )
+                            .await
+                        }
+                        cli::EthereumChainConfig::Minimal(config) => {
+                            handle_denom_address::<Minimal>(
+                                Ethereum::new(config).await.unwrap(),
+                                contract_address.into(),
+                                channel_id,
+                                denom,
+                            )
+                            .await
+                        }
+                    },
                 },
-                // cli::QueryCmd::Union(union_query) => match union_query {
-                //     cli::UnionQuery::AccountInfo { address } => {
-                //         let info = Union::new(chain_utils::union::Config {
-                //             keyring: config.union.signers,
-                //             ws_url: config.union.ws_url,
-                //             prover_endpoint: config.union.prover_endpoint,
-                //             grpc_url: config.union.grpc_url,
-                //             gas_config: config.union.gas_config,
-                //         })
-                //         .await
-                //         .unwrap()
-                //         .account_info(&address)
-                //         .await;
-                //         println!("{info:#?}");
-                //     }
-                // },
-                _ => unimplemented!(),
+                cli::QueryCmd::Union(union_query) => {
+                    let union = Union::new(config.union).await.unwrap();
+                    match union_query {
+                        cli::UnionQuery::AccountInfo { address } => {
+                            let info = union.account_info(&address).await;
+                            println!("{info:#?}");
+                        }
+                        cli::UnionQuery::Channel {
+                            contract_address,
+                            channel_id,
+                        } => {
+                            let channel: ChannelResponse = union
+                                .query_contract(
+                                    contract_address,
+                                    &ucs01_relay::msg::QueryMsg::Channel { id: channel_id },
+                                )
+                                .await;
+                            println!("{}", serde_json::to_string_pretty(&channel).unwrap());
+                        }
+                        cli::UnionQuery::ListChannels { contract_address } => {
+                            let channels: ListChannelsResponse = union
+                                .query_contract(
+                                    contract_address,
+                                    &ucs01_relay::msg::QueryMsg::ListChannels {},
+                                )
+                                .await;
+                            println!("{}", serde_json::to_string_pretty(&channels).unwrap());
+                        }
+                        cli::UnionQuery::ListDenoms {
+                            contract_address,
+                            channel_id,
+                            start_after,
+                            limit,
+                        } => {
+                            let denoms: ListDenomsResponse = union
+                                .query_contract(
+                                    contract_address,
+                                    &ucs01_relay::msg::QueryMsg::ListDenoms {
+                                        channel: channel_id,
+                                        start_after,
+                                        limit,
+                                    },
+                                )
+                                .await;
+                            println!("{}", serde_json::to_string_pretty(&denoms).unwrap());
+                        }
+                    }
+                }
             }
         }
     }
@@ -176,8 +294,13 @@ async fn handle_ucs_balance<C: ChainSpec>(
     ));
     let relay = UCS01Relay::new(contract_address, signer_middleware.clone());
 
-    let denom = relay.get_denom_address(channel_id, denom).await.unwrap();
-    println!("Corresponding ERC20 address: {}", denom);
+    let denom = resolve_denom(&relay, &channel_id, &denom)
+        .await
+        .unwrap()
+        .unwrap_or_else(|| {
+            panic!("{denom} is neither an ERC20 address nor a denom registered on {channel_id}")
+        });
+    println!("Corresponding ERC20 address: {:?}", denom);
 
     let erc_contract = erc20::ERC20::new(denom, signer_middleware.clone());
 
@@ -185,6 +308,37 @@ async fn handle_ucs_balance<C: ChainSpec>(
     println!("Balance is: {}", balance);
 }
 
+async fn handle_outstanding<C: ChainSpec>(
+    ethereum: Ethereum<C>,
+    contract_address: Address,
+    channel_id: String,
+    token: Address,
+) {
+    let relay = UCS01Relay::new(contract_address, Arc::new(ethereum.provider));
+
+    let outstanding = relay.get_outstanding(channel_id, token).await.unwrap();
+    println!("Outstanding is: {}", outstanding);
+}
+
+async fn handle_denom_address<C: ChainSpec>(
+    ethereum: Ethereum<C>,
+    contract_address: Address,
+    channel_id: String,
+    denom: String,
+) {
+    let relay = UCS01Relay::new(contract_address, Arc::new(ethereum.provider));
+
+    let address = relay
+        .get_denom_address(channel_id.clone(), denom.clone())
+        .await
+        .unwrap();
+    if address.is_zero() {
+        println!("Denom {denom} is not registered on {channel_id}");
+    } else {
+        println!("Corresponding ERC20 address: {:?}", address);
+    }
+}
+
 async fn handle_erc_balance<C: ChainSpec>(
     ethereum: Ethereum<C>,
     contract_address: Address,
@@ -205,62 +359,78 @@ async fn handle_transfer<C: ChainSpec>(
     relay_address: Address,
     channel_id: String,
     receiver: String,
-    amount: u64,
-    denom: String,
+    tokens: Vec<(String, u128)>,
     memo: String,
+    approve: bool,
 ) {
     let signer_middleware = Arc::new(SignerMiddleware::new(
         ethereum.provider.clone(),
         ethereum.wallet.clone(),
     ));
-    let relay = UCS01Relay::new(relay_address, signer_middleware.clone());
+    let relay = UCS01Relay::new(relay_address, signer_middleware);
 
-    let denom = relay
-        .get_denom_address(channel_id.clone(), denom)
-        .await
-        .unwrap();
-    println!("Address is: {:?}", denom);
+    let sent = match ucs01_evm_client::send(
+        &relay,
+        ethereum.wallet.address(),
+        &channel_id,
+        hex::decode(receiver).unwrap().into(),
+        &tokens,
+        memo,
+        approve,
+    )
+    .await
+    {
+        Ok(sent) => sent,
+        Err(err @ TransferError::InsufficientAllowance { .. }) => {
+            panic!("{err}. Pass --approve to approve it.")
+        }
+        Err(err) => panic!("{}", ErrorReporter(err)),
+    };
 
-    let erc_contract = erc20::ERC20::new(denom, signer_middleware.clone());
+    println!("Transaction hash: {:?}", sent.receipt.transaction_hash);
+    println!("Packet sequence: {}", sent.event.packet_sequence);
+}
 
-    let balance = erc_contract
-        .balance_of(ethereum.wallet.address())
-        .await
-        .unwrap();
-    println!("Balance is: {}", balance);
+/// A packet forward memo, forwarding the tokens from the receiving chain to `receiver` on `port`/`channel`.
+fn pfm_memo(channel: String, port: String, receiver: String) -> String {
+    let memo = serde_json::json!({
+        "forward": {
+            "receiver": receiver,
+            "port": port,
+            "channel": channel,
+        }
+    })
+    .to_string();
+    // make sure the relay contracts on the way will parse it as a forward, rather than ignoring it
+    assert!(
+        matches!(
+            serde_json::from_str::<Memo>(&memo).unwrap(),
+            Memo::Forward { .. }
+        ),
+        "{memo} is not a valid packet forward memo"
+    );
+    memo
+}
 
-    erc_contract
-        .approve(relay_address, (U256::MAX / U256::from(2)).into())
-        .send()
-        .await
-        .unwrap()
-        .await
-        .unwrap()
-        .unwrap();
+async fn handle_union_transfer(
+    union: Union,
+    signer: usize,
+    contract_address: String,
+    transfer: TransferMsg,
+    funds: Vec<Coin>,
+) {
+    let signer = &union.signers[signer];
 
-    let tx_rcp = relay
-        .send(
-            channel_id,
-            hex::decode(receiver).unwrap().into(),
-            [LocalToken {
-                denom,
-                amount: amount.into(),
-            }]
-            .into(),
-            memo,
-            Height {
-                revision_number: 0,
-                revision_height: 0,
-            }
-            .into(),
-            u64::MAX,
-        )
-        .send()
-        .await
-        .unwrap()
+    let msg = protos::cosmwasm::wasm::v1::MsgExecuteContract {
+        sender: signer.to_string(),
+        contract: contract_address,
+        msg: serde_json::to_vec(&ucs01_relay::msg::ExecuteMsg::Transfer(transfer)).unwrap(),
+        funds: funds.into_iter().map(Into::into).collect(),
+    };
+
+    let (tx_hash, gas_used) = union
+        .broadcast_tx_commit(signer, [mk_any(&msg)], String::new())
         .await
-        .unwrap()
         .unwrap();
-
-    dbg!(tx_rcp);
+    println!("Transaction {tx_hash} included, gas used: {gas_used}");
 }
@@ -0,0 +1,525 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use chrono::Utc;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::{field::display as as_display, info, warn};
+use walkdir::WalkDir;
+
+const MANIFEST: &str = "manifest.json";
+const HOME: &str = "home";
+const ARCHIVE: &str = "home.tar.gz";
+
+/// How snapshots of the home directory are taken and retained.
+#[derive(Clone, Copy, Debug)]
+pub struct BackupConfig {
+    /// The number of snapshots to keep. Older snapshots are removed after a new one was taken.
+    pub retention: usize,
+    /// Store snapshots as a `.tar.gz` archive. Compressed snapshots cannot share files with earlier snapshots.
+    pub compress: bool,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            retention: 3,
+            compress: false,
+        }
+    }
+}
+
+/// Timestamped snapshots of the uniond home directory, stored in `root/backups/{id}`. Each snapshot contains a
+/// [`Manifest`] of the hashes of its files, used to verify the snapshot before restoring it.
+///
+/// Uncompressed snapshots are incremental: files which are unchanged since the previous snapshot are hard linked
+/// rather than copied.
+#[derive(Clone, Debug)]
+pub struct Backups {
+    dir: PathBuf,
+    config: BackupConfig,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub id: String,
+    /// RFC 3339 timestamp of when the snapshot was taken.
+    pub created_at: String,
+    /// The uniond version running when the snapshot was taken.
+    pub version: Option<String>,
+    pub compressed: bool,
+    /// All directories, relative to the home directory, including empty ones.
+    pub directories: Vec<PathBuf>,
+    pub files: Vec<FileEntry>,
+}
+
+impl Manifest {
+    /// The total size of the files in the snapshot, in bytes.
+    pub fn size(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEntry {
+    /// Relative to the home directory.
+    pub path: PathBuf,
+    pub size: u64,
+    /// Modification time in nanoseconds since the unix epoch, used to skip hashing unchanged files.
+    pub modified: u64,
+    pub sha256: String,
+}
+
+impl Backups {
+    pub fn new(root: impl AsRef<Path>, config: BackupConfig) -> Self {
+        Self {
+            dir: root.as_ref().join("backups"),
+            config,
+        }
+    }
+
+    fn snapshot_dir(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    /// Takes a snapshot of `home`, then removes the snapshots exceeding the retention.
+    pub fn create(&self, home: &Path, version: Option<String>) -> Result<Manifest, BackupError> {
+        fs::create_dir_all(&self.dir).map_err(|err| BackupError::Io(self.dir.clone(), err))?;
+
+        let now = Utc::now();
+        let mut id = now.format("%Y%m%dT%H%M%S%.3fZ").to_string();
+        let mut suffix = 0;
+        while self.snapshot_dir(&id).exists() {
+            suffix += 1;
+            id = format!("{}-{suffix}", now.format("%Y%m%dT%H%M%S%.3fZ"));
+        }
+
+        // Files which are unchanged since the latest uncompressed snapshot are not hashed again, but linked.
+        let previous = self
+            .list()?
+            .into_iter()
+            .rev()
+            .find(|manifest| !manifest.compressed);
+        let previous_files = previous
+            .iter()
+            .flat_map(|manifest| manifest.files.iter().map(|file| (&file.path, file)))
+            .collect::<HashMap<_, _>>();
+
+        info!(target: "unionvisor", "hashing {}", as_display(home.display()));
+        let mut directories = vec![];
+        let mut files = vec![];
+        let mut linked = HashSet::new();
+        for entry in WalkDir::new(home).min_depth(1).sort_by_file_name() {
+            let entry = entry.map_err(|err| BackupError::Walk(home.to_owned(), err))?;
+            let path = entry
+                .path()
+                .strip_prefix(home)
+                .expect("walked paths are in home; qed;")
+                .to_owned();
+            let metadata = entry
+                .metadata()
+                .map_err(|err| BackupError::Walk(home.to_owned(), err))?;
+
+            if metadata.is_dir() {
+                directories.push(path);
+                continue;
+            }
+            if !metadata.is_file() {
+                warn!(target: "unionvisor", "skipping {}, which is not a regular file", as_display(entry.path().display()));
+                continue;
+            }
+
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |modified| {
+                    u64::try_from(modified.as_nanos()).unwrap_or(u64::MAX)
+                });
+            let unchanged = previous_files
+                .get(&path)
+                .filter(|file| file.size == metadata.len() && file.modified == modified);
+            let file = match unchanged {
+                Some(file) => {
+                    linked.insert(path.clone());
+                    (*file).clone()
+                }
+                None => FileEntry {
+                    sha256: sha256(entry.path())?,
+                    path,
+                    size: metadata.len(),
+                    modified,
+                },
+            };
+            files.push(file);
+        }
+
+        let manifest = Manifest {
+            id: id.clone(),
+            created_at: now.to_rfc3339(),
+            version,
+            compressed: self.config.compress,
+            directories,
+            files,
+        };
+
+        let required = if self.config.compress {
+            manifest.size()
+        } else {
+            manifest
+                .files
+                .iter()
+                .filter(|file| !linked.contains(&file.path))
+                .map(|file| file.size)
+                .sum()
+        };
+        let available = fs2::available_space(&self.dir)
+            .map_err(|err| BackupError::Io(self.dir.clone(), err))?;
+        // Leave some headroom, as the node needs to write to disk after the upgrade as well.
+        if required.saturating_add(required / 10) > available {
+            return Err(BackupError::InsufficientSpace {
+                required,
+                available,
+            });
+        }
+
+        // The snapshot is written to a temporary directory first, so that interrupted backups are never listed.
+        let tmp = self.dir.join(format!(".{id}.tmp"));
+        fs::create_dir_all(&tmp).map_err(|err| BackupError::Io(tmp.clone(), err))?;
+        info!(target: "unionvisor", id = id.as_str(), required, "backing up {} to {}. This might take a while", as_display(home.display()), as_display(self.snapshot_dir(&id).display()));
+
+        if self.config.compress {
+            let archive = tmp.join(ARCHIVE);
+            let file =
+                File::create(&archive).map_err(|err| BackupError::Io(archive.clone(), err))?;
+            let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+            for file in &manifest.files {
+                builder
+                    .append_path_with_name(home.join(&file.path), &file.path)
+                    .map_err(|err| BackupError::Io(home.join(&file.path), err))?;
+            }
+            builder
+                .into_inner()
+                .and_then(GzEncoder::finish)
+                .map_err(|err| BackupError::Io(archive.clone(), err))?;
+        } else {
+            let snapshot_home = tmp.join(HOME);
+            for dir in &manifest.directories {
+                let dir = snapshot_home.join(dir);
+                fs::create_dir_all(&dir).map_err(|err| BackupError::Io(dir, err))?;
+            }
+            for file in &manifest.files {
+                let to = snapshot_home.join(&file.path);
+                if let Some(parent) = to.parent() {
+                    fs::create_dir_all(parent)
+                        .map_err(|err| BackupError::Io(parent.to_owned(), err))?;
+                }
+                let from = match &previous {
+                    Some(previous) if linked.contains(&file.path) => {
+                        let from = self.snapshot_dir(&previous.id).join(HOME).join(&file.path);
+                        if fs::hard_link(&from, &to).is_ok() {
+                            continue;
+                        }
+                        from
+                    }
+                    _ => home.join(&file.path),
+                };
+                fs::copy(&from, &to).map_err(|err| BackupError::Io(from, err))?;
+            }
+        }
+
+        write_manifest(&tmp, &manifest)?;
+        let snapshot = self.snapshot_dir(&id);
+        fs::rename(&tmp, &snapshot).map_err(|err| BackupError::Io(snapshot, err))?;
+        info!(target: "unionvisor", id = id.as_str(), files = manifest.files.len(), linked = linked.len(), "completed backup");
+
+        self.prune()?;
+        Ok(manifest)
+    }
+
+    /// All snapshots, oldest first.
+    pub fn list(&self) -> Result<Vec<Manifest>, BackupError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(BackupError::Io(self.dir.clone(), err)),
+        };
+
+        let mut manifests = vec![];
+        for entry in entries {
+            let entry = entry.map_err(|err| BackupError::Io(self.dir.clone(), err))?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            match read_manifest(&entry.path()) {
+                Ok(manifest) => manifests.push(manifest),
+                Err(err) => {
+                    warn!(target: "unionvisor", err = err.to_string().as_str(), "ignoring {}", as_display(entry.path().display()));
+                }
+            }
+        }
+        manifests.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(manifests)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Manifest, BackupError> {
+        let dir = self.snapshot_dir(id);
+        if !dir.exists() {
+            return Err(BackupError::NotFound(id.to_owned()));
+        }
+        read_manifest(&dir)
+    }
+
+    /// Checks that every file in snapshot `id` matches the hash in its manifest.
+    pub fn verify(&self, id: &str) -> Result<Manifest, BackupError> {
+        let manifest = self.get(id)?;
+        let dir = self.snapshot_dir(id);
+        let corrupted = |path: &Path| BackupError::Corrupted {
+            id: id.to_owned(),
+            path: path.to_owned(),
+        };
+
+        if manifest.compressed {
+            let mut expected = manifest
+                .files
+                .iter()
+                .map(|file| (file.path.clone(), file))
+                .collect::<BTreeMap<_, _>>();
+            let archive = dir.join(ARCHIVE);
+            let mut archive = tar::Archive::new(GzDecoder::new(
+                File::open(&archive).map_err(|err| BackupError::Io(archive.clone(), err))?,
+            ));
+            let entries = archive
+                .entries()
+                .map_err(|err| BackupError::Io(dir.clone(), err))?;
+            for entry in entries {
+                let mut entry = entry.map_err(|err| BackupError::Io(dir.clone(), err))?;
+                let path = entry
+                    .path()
+                    .map_err(|err| BackupError::Io(dir.clone(), err))?
+                    .into_owned();
+                let file = expected.remove(&path).ok_or_else(|| corrupted(&path))?;
+                let mut hasher = Sha256::new();
+                io::copy(&mut entry, &mut hasher)
+                    .map_err(|err| BackupError::Io(dir.clone(), err))?;
+                if hex::encode(hasher.finalize()) != file.sha256 {
+                    return Err(corrupted(&path));
+                }
+            }
+            if let Some(path) = expected.into_keys().next() {
+                return Err(corrupted(&path));
+            }
+        } else {
+            for file in &manifest.files {
+                let path = dir.join(HOME).join(&file.path);
+                if !path.is_file() || sha256(&path)? != file.sha256 {
+                    return Err(corrupted(&file.path));
+                }
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// Verifies snapshot `id`, then replaces `home` with it.
+    pub fn restore(&self, id: &str, home: &Path) -> Result<Manifest, BackupError> {
+        let manifest = self.verify(id)?;
+        let dir = self.snapshot_dir(id);
+        info!(target: "unionvisor", id, "restoring {} from {}", as_display(home.display()), as_display(dir.display()));
+
+        if home.exists() {
+            fs::remove_dir_all(home).map_err(|err| BackupError::Io(home.to_owned(), err))?;
+        }
+        fs::create_dir_all(home).map_err(|err| BackupError::Io(home.to_owned(), err))?;
+        for directory in &manifest.directories {
+            let directory = home.join(directory);
+            fs::create_dir_all(&directory).map_err(|err| BackupError::Io(directory, err))?;
+        }
+
+        if manifest.compressed {
+            let archive = dir.join(ARCHIVE);
+            tar::Archive::new(GzDecoder::new(
+                File::open(&archive).map_err(|err| BackupError::Io(archive.clone(), err))?,
+            ))
+            .unpack(home)
+            .map_err(|err| BackupError::Io(archive, err))?;
+        } else {
+            // Files are copied rather than linked, as uniond would otherwise modify the snapshot.
+            for file in &manifest.files {
+                let from = dir.join(HOME).join(&file.path);
+                fs::copy(&from, home.join(&file.path)).map_err(|err| BackupError::Io(from, err))?;
+            }
+        }
+
+        info!(target: "unionvisor", id, "completed restore");
+        Ok(manifest)
+    }
+
+    /// Removes the oldest snapshots exceeding the retention.
+    fn prune(&self) -> Result<(), BackupError> {
+        let manifests = self.list()?;
+        let excess = manifests.len().saturating_sub(self.config.retention.max(1));
+        for manifest in &manifests[..excess] {
+            info!(target: "unionvisor", id = manifest.id.as_str(), "removing backup exceeding retention");
+            let dir = self.snapshot_dir(&manifest.id);
+            fs::remove_dir_all(&dir).map_err(|err| BackupError::Io(dir, err))?;
+        }
+        Ok(())
+    }
+}
+
+fn sha256(path: &Path) -> Result<String, BackupError> {
+    let mut file =
+        BufReader::new(File::open(path).map_err(|err| BackupError::Io(path.to_owned(), err))?);
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).map_err(|err| BackupError::Io(path.to_owned(), err))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn read_manifest(dir: &Path) -> Result<Manifest, BackupError> {
+    let path = dir.join(MANIFEST);
+    let contents = fs::read_to_string(&path).map_err(|err| BackupError::Io(path, err))?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+fn write_manifest(dir: &Path, manifest: &Manifest) -> Result<(), BackupError> {
+    let path = dir.join(MANIFEST);
+    let contents = serde_json::to_string_pretty(manifest)?;
+    fs::write(&path, contents).map_err(|err| BackupError::Io(path, err))
+}
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error("io error at {0}")]
+    Io(PathBuf, #[source] io::Error),
+    #[error("cannot walk {0}")]
+    Walk(PathBuf, #[source] walkdir::Error),
+    #[error("cannot (de)serialize manifest")]
+    Manifest(#[from] serde_json::Error),
+    #[error("backup requires {required} bytes, but only {available} bytes are available")]
+    InsufficientSpace { required: u64, available: u64 },
+    #[error("backup {0} not found")]
+    NotFound(String),
+    #[error("backup {id} is corrupted: {} does not match the manifest", path.display())]
+    Corrupted { id: String, path: PathBuf },
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::MetadataExt;
+
+    use super::*;
+    use crate::testdata;
+
+    fn assert_file_contains(file: impl AsRef<Path>, want: &str) {
+        let contents = fs::read_to_string(file.as_ref()).unwrap();
+        assert_eq!(contents, want);
+    }
+
+    fn backups(compress: bool) -> (PathBuf, Backups) {
+        let tmp = testdata::temp_dir_with(&["test_backup"]);
+        let root = tmp.into_path().join("test_backup");
+        let backups = Backups::new(
+            &root,
+            BackupConfig {
+                retention: 2,
+                compress,
+            },
+        );
+        (root, backups)
+    }
+
+    #[test]
+    fn test_create_and_restore() {
+        for compress in [false, true] {
+            let (root, backups) = backups(compress);
+            let home = root.join("home");
+
+            let manifest = backups.create(&home, Some("v0.1.0".to_owned())).unwrap();
+            assert_eq!(manifest.files.len(), 2);
+            assert_eq!(backups.list().unwrap(), vec![manifest.clone()]);
+
+            fs::write(home.join("data/foo.db"), "corrupted").unwrap();
+            fs::write(home.join("data/baz.db"), "baz").unwrap();
+            backups.restore(&manifest.id, &home).unwrap();
+            assert_file_contains(home.join("data/foo.db"), "foo");
+            assert_file_contains(home.join("data/bar.db"), "bar");
+            assert!(!home.join("data/baz.db").exists());
+        }
+    }
+
+    #[test]
+    fn test_incremental() {
+        let (root, backups) = backups(false);
+        let home = root.join("home");
+
+        let first = backups.create(&home, None).unwrap();
+        fs::write(home.join("data/foo.db"), "foo2").unwrap();
+        let second = backups.create(&home, None).unwrap();
+
+        let snapshot = |id: &str, file: &str| {
+            fs::metadata(root.join("backups").join(id).join("home/data").join(file)).unwrap()
+        };
+        assert_eq!(
+            snapshot(&first.id, "bar.db").ino(),
+            snapshot(&second.id, "bar.db").ino()
+        );
+        assert_ne!(
+            snapshot(&first.id, "foo.db").ino(),
+            snapshot(&second.id, "foo.db").ino()
+        );
+        assert_file_contains(
+            root.join("backups")
+                .join(&first.id)
+                .join("home/data/foo.db"),
+            "foo",
+        );
+
+        backups.restore(&first.id, &home).unwrap();
+        assert_file_contains(home.join("data/foo.db"), "foo");
+    }
+
+    #[test]
+    fn test_verify_detects_corruption() {
+        let (root, backups) = backups(false);
+        let manifest = backups.create(&root.join("home"), None).unwrap();
+        backups.verify(&manifest.id).unwrap();
+
+        fs::write(
+            root.join("backups")
+                .join(&manifest.id)
+                .join("home/data/foo.db"),
+            "corrupted",
+        )
+        .unwrap();
+        assert!(matches!(
+            backups.restore(&manifest.id, &root.join("home")),
+            Err(BackupError::Corrupted { .. })
+        ));
+        assert_file_contains(root.join("home/data/foo.db"), "foo");
+    }
+
+    #[test]
+    fn test_retention() {
+        let (root, backups) = backups(false);
+        let ids = (0..3)
+            .map(|_| backups.create(&root.join("home"), None).unwrap().id)
+            .collect::<Vec<_>>();
+        let listed = backups
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|manifest| manifest.id)
+            .collect::<Vec<_>>();
+        assert_eq!(listed, ids[1..]);
+        // the remaining snapshots are intact, even though they were linked to the removed one.
+        backups.verify(&ids[1]).unwrap();
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
-    ffi::OsString,
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
     fs, io,
     path::PathBuf,
     process::{Command, Stdio},
@@ -115,6 +116,57 @@ pub struct BundleMeta {
     fallback_version: String,
     /// The directory containing a directory for each version
     versions_directory: PathBuf,
+    /// Overrides for specific versions, keyed by the version directory name.
+    #[serde(default)]
+    overrides: BTreeMap<String, VersionMeta>,
+}
+
+/// How a specific version in the bundle is run and upgraded to, found in `meta.overrides` in `bundle/meta.json`.
+///
+/// ```json
+/// "overrides": {
+///   "v0.9.0": {
+///     "extra_args": ["--api.enable"],
+///     "env": { "GOGC": "50" },
+///     "pre_upgrade": ["./hooks/migrate-config.sh"]
+///   }
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VersionMeta {
+    /// Replaces the arguments passed to `unionvisor run` for this version.
+    #[serde(default)]
+    pub args: Option<Vec<String>>,
+    /// Appended to the arguments this version is started with.
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    /// Environment variables set for this version.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Command run before upgrading to this version, after the home directory was backed up. A non-zero exit code
+    /// aborts the upgrade.
+    #[serde(default)]
+    pub pre_upgrade: Option<Vec<String>>,
+    /// Command run after this version was started by an upgrade. A non-zero exit code rolls the upgrade back.
+    #[serde(default)]
+    pub post_upgrade: Option<Vec<String>>,
+}
+
+impl VersionMeta {
+    /// The arguments this version is started with, given the arguments passed to `unionvisor run`.
+    pub fn args<S: AsRef<OsStr>, I: IntoIterator<Item = S>>(&self, args: I) -> Vec<OsString> {
+        match &self.args {
+            Some(replacement) => replacement.iter().map(OsString::from).collect(),
+            None => args
+                .into_iter()
+                .map(|arg| arg.as_ref().to_os_string())
+                .collect(),
+        }
+        .into_iter()
+        .chain(self.extra_args.iter().map(OsString::from))
+        .collect()
+    }
 }
 
 impl Bundle {
@@ -149,6 +201,21 @@ impl Bundle {
         )
     }
 
+    /// The overrides of `version`, or the defaults if `meta.json` has none.
+    pub fn version_meta(&self, version: impl AsRef<OsStr>) -> VersionMeta {
+        version
+            .as_ref()
+            .to_str()
+            .and_then(|version| self.meta.overrides.get(version))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The name of the binary within each version directory.
+    pub fn binary_name(&self) -> &str {
+        &self.meta.binary_name
+    }
+
     /// Provides the full path the the versions directory
     pub fn versions_path(&self) -> PathBuf {
         self.path.join(&self.meta.versions_directory)
@@ -1,23 +1,29 @@
 use core::time::Duration;
 use std::{
-    ffi::OsString,
+    ffi::{OsStr, OsString},
     fs,
     io::{self},
+    net::SocketAddr,
     path::PathBuf,
     process::Stdio,
 };
 
 use clap::Parser;
 use thiserror::Error;
-use tracing::{field::display as as_display, info};
+use tracing::{field::display as as_display, info, warn};
 use tracing_subscriber::filter::LevelFilter;
 
 use crate::{
+    backup::{BackupConfig, BackupError, Backups},
     bundle::{log_bundle, Bundle, NewBundleError, ValidateVersionPathError},
+    download::Prefetcher,
     init::{self, SetSeedsError},
     logging::LogFormat,
-    supervisor::{self, RuntimeError},
-    symlinker::{MakeFallbackLinkError, Symlinker},
+    metrics,
+    restart::RestartPolicy,
+    status::{self, ServeError, Status},
+    supervisor::{self, RollbackConfig, RollbackPolicy, RuntimeError},
+    symlinker::{MakeFallbackLinkError, Symlinker, SymlinkerError},
 };
 
 #[derive(Parser, Clone)]
@@ -64,6 +70,25 @@ pub enum Command {
 
     /// Initializes a local directory to join the union network.
     Init(InitCmd),
+
+    /// Manages the backups of the home directory taken before upgrades.
+    #[command(subcommand)]
+    Backups(BackupsCmd),
+}
+
+#[derive(Clone, Parser)]
+pub enum BackupsCmd {
+    /// Lists all backups, oldest first.
+    List,
+    /// Verifies backup `id` and replaces the home directory with it, swapping the `uniond` symlink to the version the
+    /// backup was taken with. unionvisor must not be running.
+    Restore {
+        id: String,
+
+        /// Path to where the binary bundle is stored.
+        #[arg(short, long, env = "UNIONVISOR_BUNDLE")]
+        bundle: PathBuf,
+    },
 }
 
 #[derive(Clone, Parser)]
@@ -111,6 +136,54 @@ pub struct RunCmd {
     /// Milliseconds in between each poll for an upgrade.
     #[arg(short, long, env = "UNIONVISOR_POLL_INTERVAL")]
     poll_interval: Option<u64>,
+
+    /// Seconds after an upgrade during which an exit of uniond is considered a failed upgrade, which is rolled back
+    /// by restoring the home directory from the backup taken before it and swapping back to the previous version.
+    #[arg(long, env = "UNIONVISOR_ROLLBACK_WINDOW", default_value = "60")]
+    rollback_window: u64,
+
+    /// What to do after rolling back a failed upgrade.
+    #[arg(
+        long,
+        env = "UNIONVISOR_ON_FAILED_UPGRADE",
+        value_enum,
+        default_value = "halt"
+    )]
+    on_failed_upgrade: RollbackPolicy,
+
+    /// The maximum number of times uniond is restarted after crashing within `--restart-window`, after which
+    /// unionvisor exits.
+    #[arg(long, env = "UNIONVISOR_MAX_RESTARTS", default_value = "5")]
+    max_restarts: u32,
+
+    /// Seconds in which crashes count towards `--max-restarts`.
+    #[arg(long, env = "UNIONVISOR_RESTART_WINDOW", default_value = "600")]
+    restart_window: u64,
+
+    /// Seconds to wait before restarting uniond after a crash, doubled for every further crash within the window.
+    #[arg(long, env = "UNIONVISOR_RESTART_BACKOFF", default_value = "1")]
+    restart_backoff: u64,
+
+    /// The maximum number of seconds to wait before restarting uniond.
+    #[arg(long, env = "UNIONVISOR_MAX_RESTART_BACKOFF", default_value = "60")]
+    max_restart_backoff: u64,
+
+    /// The number of backups of the home directory to keep.
+    #[arg(long, env = "UNIONVISOR_BACKUP_RETENTION", default_value = "3")]
+    backup_retention: usize,
+
+    /// Store backups as compressed archives. Uncompressed backups share unchanged files with the previous backup.
+    #[arg(long, env = "UNIONVISOR_BACKUP_COMPRESS")]
+    backup_compress: bool,
+
+    /// Address to serve `/status` and `/metrics` on. Disabled if unset.
+    #[arg(long, env = "UNIONVISOR_STATUS_ADDR")]
+    status_addr: Option<SocketAddr>,
+
+    /// Address of the REST API of uniond. If set, the binary of an upgrade is downloaded as soon as the upgrade is
+    /// planned on chain, rather than once uniond halts for it.
+    #[arg(long, env = "UNIONVISOR_UPGRADE_PLAN_API")]
+    upgrade_plan_api: Option<String>,
 }
 
 impl Cli {
@@ -127,6 +200,10 @@ impl Cli {
             Command::Init(cmd) => {
                 cmd.init(self.root)?;
                 Ok(())
+            }
+            Command::Backups(cmd) => {
+                cmd.run(self.root)?;
+                Ok(())
             } // Command::Merge(cmd) => cmd.merge(),
         }
     }
@@ -140,6 +217,8 @@ pub enum RunCliError {
     Run(#[from] RunError),
     #[error("init command error")]
     Init(#[from] InitError),
+    #[error("backups command error")]
+    Backups(#[from] BackupsError),
 }
 
 /// The state that the init command left the fs in.
@@ -210,12 +289,38 @@ impl RunCmd {
         let bundle = Bundle::new(self.bundle.clone())?;
         log_bundle(&bundle);
         let symlinker = Symlinker::new(root.clone(), bundle);
+        let status = Status::default();
+        if let Some(addr) = self.status_addr {
+            metrics::register_custom_metrics();
+            status::serve(addr, status.clone(), symlinker.clone())?;
+        }
         supervisor::run_and_upgrade(
             root,
             logformat,
             &symlinker,
             &self.args,
             Duration::from_millis(self.poll_interval.unwrap_or(6000)),
+            RollbackConfig {
+                window: Duration::from_secs(self.rollback_window),
+                policy: self.on_failed_upgrade,
+            },
+            RestartPolicy {
+                max_restarts: self.max_restarts,
+                window: Duration::from_secs(self.restart_window),
+                initial_backoff: Duration::from_secs(self.restart_backoff),
+                max_backoff: Duration::from_secs(self.max_restart_backoff),
+            },
+            &Backups::new(
+                &root,
+                BackupConfig {
+                    retention: self.backup_retention,
+                    compress: self.backup_compress,
+                },
+            ),
+            &status,
+            self.upgrade_plan_api
+                .as_ref()
+                .map(|api| Prefetcher::new(api.clone(), symlinker.bundle.clone())),
         )?;
         Ok(())
     }
@@ -227,6 +332,86 @@ pub enum RunError {
     NewBundle(#[from] NewBundleError),
     #[error("runtime error")]
     Runtime(#[from] RuntimeError),
+    #[error("status server error")]
+    Serve(#[from] ServeError),
+}
+
+impl BackupsCmd {
+    fn run(&self, root: impl Into<PathBuf>) -> Result<(), BackupsError> {
+        let root = root.into();
+        let backups = Backups::new(&root, BackupConfig::default());
+        match self {
+            BackupsCmd::List => {
+                for manifest in backups.list()? {
+                    println!(
+                        "{}\t{}\t{}\t{} files\t{} bytes{}",
+                        manifest.id,
+                        manifest.created_at,
+                        manifest.version.as_deref().unwrap_or("unknown"),
+                        manifest.files.len(),
+                        manifest.size(),
+                        if manifest.compressed {
+                            "\tcompressed"
+                        } else {
+                            ""
+                        },
+                    );
+                }
+            }
+            BackupsCmd::Restore { id, bundle } => {
+                let symlinker = Symlinker::new(root.clone(), Bundle::new(bundle.clone())?);
+                let manifest = backups.get(id)?;
+
+                // the home directory can only be run by the version that wrote it, so make sure that version is
+                // available before replacing the home directory.
+                if let Some(version) = &manifest.version {
+                    symlinker
+                        .bundle
+                        .path_to(version)
+                        .validate()
+                        .map_err(|source| BackupsError::VersionUnavailable {
+                            id: id.clone(),
+                            version: version.clone(),
+                            source,
+                        })?;
+                }
+
+                backups.restore(id, &root.join("home"))?;
+                info!(target: "unionvisor", "restored backup {}", id);
+
+                match &manifest.version {
+                    Some(version)
+                        if symlinker.current_version().ok().as_deref()
+                            != Some(OsStr::new(version)) =>
+                    {
+                        symlinker.swap(version)?;
+                        info!(target: "unionvisor", "swapped uniond to {}, the version backup {} was taken with", version, id);
+                    }
+                    Some(_) => {}
+                    None => {
+                        warn!(target: "unionvisor", "backup {} does not record the version it was taken with, make sure uniond is the version that wrote it", id);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum BackupsError {
+    #[error("backup error")]
+    Backup(#[from] BackupError),
+    #[error("cannot load bundle")]
+    NewBundle(#[from] NewBundleError),
+    #[error("backup {id} was taken with {version}, which is not in the bundle")]
+    VersionUnavailable {
+        id: String,
+        version: String,
+        source: ValidateVersionPathError,
+    },
+    #[error("cannot swap symlink")]
+    Symlinker(#[from] SymlinkerError),
 }
 
 impl CallCmd {
@@ -327,4 +512,62 @@ mod tests {
         .init(root)
         .expect_err("unionvisor should refuse to initialize if the home directory is populated");
     }
+
+    #[test]
+    fn test_restore_swaps_to_backup_version() {
+        let tmp = testdata::temp_dir_with(&["test_run"]);
+        let root = tmp.into_path().join("test_run");
+        let bundle = Bundle::new(root.join("bundle")).unwrap();
+        let symlinker = Symlinker::new(root.clone(), bundle);
+        let backups = Backups::new(&root, BackupConfig::default());
+
+        symlinker.swap("upgrade1").unwrap();
+        let manifest = backups
+            .create(&root.join("home"), Some("upgrade1".to_owned()))
+            .unwrap();
+        symlinker.swap("upgrade2").unwrap();
+        fs::write(root.join("home/data/foo.db"), "migrated").unwrap();
+
+        BackupsCmd::Restore {
+            id: manifest.id,
+            bundle: root.join("bundle"),
+        }
+        .run(&root)
+        .unwrap();
+
+        assert_eq!(symlinker.current_version().unwrap(), "upgrade1");
+        assert_ne!(
+            fs::read_to_string(root.join("home/data/foo.db")).unwrap(),
+            "migrated"
+        );
+    }
+
+    #[test]
+    fn test_restore_refuses_unavailable_version() {
+        let tmp = testdata::temp_dir_with(&["test_run"]);
+        let root = tmp.into_path().join("test_run");
+        let bundle = Bundle::new(root.join("bundle")).unwrap();
+        let symlinker = Symlinker::new(root.clone(), bundle);
+        let backups = Backups::new(&root, BackupConfig::default());
+
+        symlinker.swap("upgrade2").unwrap();
+        let manifest = backups
+            .create(&root.join("home"), Some("upgrade9".to_owned()))
+            .unwrap();
+        fs::write(root.join("home/data/foo.db"), "migrated").unwrap();
+
+        let err = BackupsCmd::Restore {
+            id: manifest.id,
+            bundle: root.join("bundle"),
+        }
+        .run(&root)
+        .unwrap_err();
+
+        assert!(matches!(err, BackupsError::VersionUnavailable { .. }));
+        assert_eq!(symlinker.current_version().unwrap(), "upgrade2");
+        assert_eq!(
+            fs::read_to_string(root.join("home/data/foo.db")).unwrap(),
+            "migrated"
+        );
+    }
 }
@@ -0,0 +1,501 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Read, Write},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::{field::display as as_display, info, warn};
+
+use crate::bundle::Bundle;
+
+/// Binaries are large and may be served slowly, so a request may take far longer than reqwest's default of 30 seconds.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Number of times a document or binary is fetched before giving up.
+const ATTEMPTS: u32 = 5;
+
+/// Delay before fetching again after a failure, multiplied by the number of failed attempts.
+const RETRY_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Interval at which the [`Prefetcher`] queries the upgrade plan.
+const PLAN_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The upgrade plan is queried on the supervisor loop, which must not be blocked by an unresponsive API.
+const PLAN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The `info` of an upgrade proposal, in the format used by cosmovisor:
+///
+/// ```json
+/// {
+///   "binaries": {
+///     "linux/amd64": "https://example.com/uniond?checksum=sha256:0123...",
+///     "linux/arm64": "https://example.com/uniond-arm64?checksum=sha256:4567..."
+///   }
+/// }
+/// ```
+///
+/// The `info` can also be a URL pointing to such a document.
+#[derive(Debug, Deserialize)]
+struct UpgradeBinaries {
+    binaries: BTreeMap<String, String>,
+}
+
+/// The response of the `/cosmos/upgrade/v1beta1/current_plan` endpoint of the REST API.
+#[derive(Debug, Deserialize)]
+struct CurrentPlan {
+    plan: Option<Plan>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Plan {
+    name: String,
+    #[serde(default)]
+    info: String,
+}
+
+/// Where to download the binary of an upgrade from.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BinarySource {
+    pub url: String,
+    /// Hex encoded sha256 of the binary, taken from the `checksum=sha256:...` query parameter of the url.
+    pub sha256: String,
+}
+
+/// The platform key of this machine in [`UpgradeBinaries`], such as `linux/amd64`.
+fn platform() -> String {
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        arch => arch,
+    };
+    format!("{}/{arch}", std::env::consts::OS)
+}
+
+/// Parses the binary for this platform from the `info` of an upgrade. Returns `None` if `info` does not list any
+/// binaries, which is the case for most upgrades shipped in a bundle.
+pub fn binary_source(info: &str) -> Result<Option<BinarySource>, DownloadError> {
+    let info = info.trim();
+    let document = if info.starts_with("https://") || info.starts_with("http://") {
+        info!(target: "unionvisor", "fetching upgrade info from {}", info);
+        let client = client(REQUEST_TIMEOUT)?;
+        with_retries(info, || fetch_text(&client, info))?
+    } else {
+        info.to_owned()
+    };
+
+    let Ok(UpgradeBinaries { binaries }) = serde_json::from_str(&document) else {
+        return Ok(None);
+    };
+
+    let platform = platform();
+    let Some(url) = binaries.get(&platform).or_else(|| binaries.get("any")) else {
+        return Err(DownloadError::UnsupportedPlatform(platform));
+    };
+
+    let parsed = reqwest::Url::parse(url).map_err(|_| DownloadError::InvalidUrl(url.clone()))?;
+    let sha256 = parsed
+        .query_pairs()
+        .find(|(key, _)| key == "checksum")
+        .and_then(|(_, checksum)| checksum.strip_prefix("sha256:").map(str::to_lowercase))
+        .ok_or_else(|| DownloadError::MissingChecksum(url.clone()))?;
+
+    Ok(Some(BinarySource {
+        url: url.clone(),
+        sha256,
+    }))
+}
+
+/// Downloads the binary of `version` into `versions/{version}/` of the bundle, verifying its checksum. The binary is
+/// only moved into place once verified.
+pub fn download(
+    bundle: &Bundle,
+    version: &str,
+    source: &BinarySource,
+) -> Result<PathBuf, DownloadError> {
+    let dir = bundle.versions_path().join(version);
+    fs::create_dir_all(&dir).map_err(|err| DownloadError::Io(dir.clone(), err))?;
+    let binary = dir.join(bundle.binary_name());
+    let tmp = dir.join(format!(".{}.download", bundle.binary_name()));
+
+    info!(target: "unionvisor", "downloading {} from {}", as_display(binary.display()), &source.url);
+    let result = client(REQUEST_TIMEOUT).and_then(|client| {
+        with_retries(&source.url, || {
+            let sha256 = fetch_to(&client, &source.url, &tmp)?;
+            if sha256 == source.sha256 {
+                Ok(())
+            } else {
+                Err(DownloadError::ChecksumMismatch {
+                    url: source.url.clone(),
+                    expected: source.sha256.clone(),
+                    actual: sha256,
+                })
+            }
+        })
+    });
+    if let Err(err) = result {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+
+    fs::set_permissions(&tmp, fs::Permissions::from_mode(0o755))
+        .map_err(|err| DownloadError::Io(tmp.clone(), err))?;
+    fs::rename(&tmp, &binary).map_err(|err| DownloadError::Io(binary.clone(), err))?;
+    info!(target: "unionvisor", "downloaded and verified {}", as_display(binary.display()));
+    Ok(binary)
+}
+
+fn client(timeout: Duration) -> Result<Client, DownloadError> {
+    Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT.min(timeout))
+        .timeout(timeout)
+        .build()
+        .map_err(DownloadError::Client)
+}
+
+/// Runs `fetch` until it succeeds, at most [`ATTEMPTS`] times. Only errors which may be transient are retried.
+fn with_retries<T>(
+    url: &str,
+    mut fetch: impl FnMut() -> Result<T, DownloadError>,
+) -> Result<T, DownloadError> {
+    let mut attempt = 1;
+    loop {
+        match fetch() {
+            Err(err) if attempt < ATTEMPTS && err.is_transient() => {
+                let backoff = RETRY_BACKOFF * attempt;
+                warn!(target: "unionvisor", err = err.to_string().as_str(), "cannot fetch {}, retrying in {} seconds", url, backoff.as_secs());
+                std::thread::sleep(backoff);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn fetch_text(client: &Client, url: &str) -> Result<String, DownloadError> {
+    client
+        .get(url)
+        .send()
+        .and_then(reqwest::blocking::Response::error_for_status)
+        .and_then(reqwest::blocking::Response::text)
+        .map_err(|source| DownloadError::Fetch {
+            url: url.to_owned(),
+            source,
+        })
+}
+
+/// Streams `url` to `path`, returning the hex encoded sha256 of the contents.
+fn fetch_to(client: &Client, url: &str, path: &Path) -> Result<String, DownloadError> {
+    let fetch_error = |source| DownloadError::Fetch {
+        url: url.to_owned(),
+        source,
+    };
+    let mut response = client
+        .get(url)
+        .send()
+        .and_then(reqwest::blocking::Response::error_for_status)
+        .map_err(fetch_error)?;
+
+    let mut file = File::create(path).map_err(|err| DownloadError::Io(path.to_owned(), err))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let read = response
+            .read(&mut buf)
+            .map_err(|err| DownloadError::Io(path.to_owned(), err))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+        file.write_all(&buf[..read])
+            .map_err(|err| DownloadError::Io(path.to_owned(), err))?;
+    }
+    file.sync_all()
+        .map_err(|err| DownloadError::Io(path.to_owned(), err))?;
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// The upgrade currently planned on chain, as `(name, info)`, queried from the REST API of uniond at `api`.
+fn current_plan(client: &Client, api: &str) -> Result<Option<(String, String)>, DownloadError> {
+    let url = format!(
+        "{}/cosmos/upgrade/v1beta1/current_plan",
+        api.trim_end_matches('/')
+    );
+    let CurrentPlan { plan } = serde_json::from_str(&fetch_text(client, &url)?)
+        .map_err(|source| DownloadError::InvalidPlan { url, source })?;
+    Ok(plan.map(|plan| (plan.name, plan.info)))
+}
+
+/// Downloads the binary of the upgrade planned on chain in the background, as soon as the plan is known, so that it is
+/// in the bundle by the time uniond halts for the upgrade.
+pub struct Prefetcher {
+    /// The REST API of uniond.
+    api: String,
+    bundle: Bundle,
+    last_check: Option<Instant>,
+    /// The upgrade whose binary is being downloaded, along with the download.
+    download: Option<(String, JoinHandle<Result<Option<PathBuf>, DownloadError>>)>,
+}
+
+impl Prefetcher {
+    pub fn new(api: impl Into<String>, bundle: Bundle) -> Self {
+        Self {
+            api: api.into(),
+            bundle,
+            last_check: None,
+            download: None,
+        }
+    }
+
+    /// Queries the upgrade plan, at most every [`PLAN_CHECK_INTERVAL`], and starts downloading the binary of a planned
+    /// upgrade missing from the bundle. The query times out after [`PLAN_TIMEOUT`], without being retried. Errors are only
+    /// logged, as the binary is downloaded again once uniond halts.
+    pub fn poll(&mut self) {
+        if self
+            .last_check
+            .is_some_and(|checked| checked.elapsed() < PLAN_CHECK_INTERVAL)
+        {
+            return;
+        }
+        self.last_check = Some(Instant::now());
+
+        let (name, info) = match client(PLAN_TIMEOUT)
+            .and_then(|client| current_plan(&client, &self.api))
+        {
+            Ok(Some(plan)) => plan,
+            Ok(None) => return,
+            Err(err) => {
+                warn!(target: "unionvisor", err = err.to_string().as_str(), "cannot query the upgrade plan");
+                return;
+            }
+        };
+
+        if self
+            .download
+            .as_ref()
+            .is_some_and(|(upgrade, _)| upgrade == &name)
+            || self.bundle.path_to(&name).validate().is_ok()
+        {
+            return;
+        }
+
+        info!(target: "unionvisor", "upgrade {} is planned, downloading its binary", &name);
+        let bundle = self.bundle.clone();
+        let upgrade = name.clone();
+        let handle = std::thread::spawn(move || {
+            binary_source(&info)?
+                .map(|source| download(&bundle, &upgrade, &source))
+                .transpose()
+        });
+        self.download = Some((name, handle));
+    }
+
+    /// Waits for the download of the binary of `name`, if it was started, returning whether the binary was downloaded.
+    pub fn wait(&mut self, name: &str) -> bool {
+        match self.download.take() {
+            Some((upgrade, handle)) if upgrade == name => {
+                info!(target: "unionvisor", "waiting for the download of the binary of {}", name);
+                match handle.join() {
+                    Ok(Ok(binary)) => binary.is_some(),
+                    Ok(Err(err)) => {
+                        warn!(target: "unionvisor", err = err.to_string().as_str(), "downloading the binary of {} failed", name);
+                        false
+                    }
+                    Err(_) => {
+                        warn!(target: "unionvisor", "downloading the binary of {} panicked", name);
+                        false
+                    }
+                }
+            }
+            download => {
+                self.download = download;
+                false
+            }
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum DownloadError {
+    #[error("cannot build http client")]
+    Client(#[source] reqwest::Error),
+    #[error("cannot fetch {url}")]
+    Fetch { url: String, source: reqwest::Error },
+    #[error("invalid upgrade plan at {url}")]
+    InvalidPlan {
+        url: String,
+        source: serde_json::Error,
+    },
+    #[error("upgrade info has no binary for {0}")]
+    UnsupportedPlatform(String),
+    #[error("invalid binary url {0}")]
+    InvalidUrl(String),
+    #[error("binary url {0} has no `checksum=sha256:...` parameter")]
+    MissingChecksum(String),
+    #[error("checksum of {url} is {actual}, expected {expected}")]
+    ChecksumMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("io error at {0}")]
+    Io(PathBuf, #[source] io::Error),
+}
+
+impl DownloadError {
+    /// Whether fetching again may succeed.
+    fn is_transient(&self) -> bool {
+        matches!(self, DownloadError::Fetch { .. } | DownloadError::Io(..))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use super::*;
+    use crate::testdata;
+
+    const BINARY: &str = "#!/usr/bin/env sh\n\necho $1 $2 $3 $4\n";
+
+    /// Serves [`BINARY`] on `/uniond`, an upgrade info document on `/info.json` and an upgrade plan for it.
+    fn serve() -> SocketAddr {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let response = match request.url() {
+                    "/uniond" => tiny_http::Response::from_string(BINARY),
+                    "/info.json" => tiny_http::Response::from_string(info(addr, &sha256())),
+                    "/cosmos/upgrade/v1beta1/current_plan" => {
+                        tiny_http::Response::from_string(format!(
+                            r#"{{"plan": {{"name": "upgrade3", "time": "0001-01-01T00:00:00Z", "height": "100", "info": "http://{addr}/info.json", "upgraded_client_state": null}}}}"#
+                        ))
+                    }
+                    _ => tiny_http::Response::from_string("not found").with_status_code(404),
+                };
+                let _ = request.respond(response);
+            }
+        });
+        addr
+    }
+
+    fn sha256() -> String {
+        hex::encode(Sha256::digest(BINARY))
+    }
+
+    fn info(addr: SocketAddr, sha256: &str) -> String {
+        format!(
+            r#"{{"binaries": {{"{}": "http://{addr}/uniond?checksum=sha256:{sha256}"}}}}"#,
+            platform()
+        )
+    }
+
+    #[test]
+    fn test_binary_source() {
+        let addr: SocketAddr = "127.0.0.1:1234".parse().unwrap();
+        assert_eq!(
+            binary_source(&info(addr, "ABCD")).unwrap(),
+            Some(BinarySource {
+                url: format!("http://{addr}/uniond?checksum=sha256:ABCD"),
+                sha256: "abcd".to_owned(),
+            })
+        );
+        assert_eq!(binary_source("some free form info").unwrap(), None);
+        assert!(matches!(
+            binary_source(r#"{"binaries": {"any": "http://127.0.0.1:1234/uniond"}}"#),
+            Err(DownloadError::MissingChecksum(_))
+        ));
+        assert!(matches!(
+            binary_source(r#"{"binaries": {"plan9/386": "http://127.0.0.1:1234/uniond"}}"#),
+            Err(DownloadError::UnsupportedPlatform(_))
+        ));
+    }
+
+    #[test]
+    fn test_download() {
+        let addr = serve();
+        let tmp = testdata::temp_dir_with(&["test_run"]);
+        let root = tmp.into_path().join("test_run");
+        let bundle = Bundle::new(root.join("bundle")).unwrap();
+
+        // the info can point to a document listing the binaries.
+        let source = binary_source(&format!("http://{addr}/info.json"))
+            .unwrap()
+            .unwrap();
+        let binary = download(&bundle, "upgrade3", &source).unwrap();
+
+        assert_eq!(binary, bundle.versions_path().join("upgrade3/uniond"));
+        assert_eq!(fs::read_to_string(&binary).unwrap(), BINARY);
+        assert_eq!(
+            fs::metadata(&binary).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+        bundle.path_to("upgrade3").validate().unwrap();
+    }
+
+    #[test]
+    fn test_download_checksum_mismatch() {
+        let addr = serve();
+        let tmp = testdata::temp_dir_with(&["test_run"]);
+        let root = tmp.into_path().join("test_run");
+        let bundle = Bundle::new(root.join("bundle")).unwrap();
+
+        let source = binary_source(&info(addr, &"0".repeat(64)))
+            .unwrap()
+            .unwrap();
+        let err = download(&bundle, "upgrade3", &source).unwrap_err();
+
+        assert!(matches!(err, DownloadError::ChecksumMismatch { .. }));
+        let dir = bundle.versions_path().join("upgrade3");
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let addr = serve();
+        let tmp = testdata::temp_dir_with(&["test_run"]);
+        let root = tmp.into_path().join("test_run");
+        let bundle = Bundle::new(root.join("bundle")).unwrap();
+
+        let mut prefetcher = Prefetcher::new(format!("http://{addr}/"), bundle.clone());
+        prefetcher.poll();
+
+        assert!(!prefetcher.wait("upgrade2"));
+        assert!(prefetcher.wait("upgrade3"));
+        assert_eq!(
+            fs::read_to_string(bundle.versions_path().join("upgrade3/uniond")).unwrap(),
+            BINARY
+        );
+    }
+
+    #[test]
+    fn test_prefetch_unresponsive_api() {
+        // accepts connections, but never responds.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let _connections = listener.incoming().collect::<Vec<_>>();
+        });
+        let tmp = testdata::temp_dir_with(&["test_run"]);
+        let root = tmp.into_path().join("test_run");
+        let bundle = Bundle::new(root.join("bundle")).unwrap();
+
+        let mut prefetcher = Prefetcher::new(format!("http://{addr}/"), bundle);
+        let started = Instant::now();
+        prefetcher.poll();
+
+        assert!(started.elapsed() < PLAN_TIMEOUT * 2);
+        assert!(!prefetcher.wait("upgrade3"));
+    }
+}
@@ -4,10 +4,15 @@
 use clap::Parser;
 use color_eyre::eyre;
 
+mod backup;
 mod bundle;
 mod cli;
+mod download;
 mod init;
 mod logging;
+mod metrics;
+mod restart;
+mod status;
 mod supervisor;
 mod symlinker;
 mod watcher;
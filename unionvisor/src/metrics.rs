@@ -0,0 +1,113 @@
+use lazy_static::lazy_static;
+use prometheus::{IntCounter, IntGauge, IntGaugeVec, Opts, Registry};
+
+lazy_static! {
+    pub static ref REGISTRY: Registry = Registry::new();
+    pub static ref UNIOND_UP: IntGauge = IntGauge::with_opts(
+        Opts::new("up", "Whether uniond is running")
+            .namespace("unionvisor")
+            .subsystem("uniond")
+    )
+    .expect("register UNIOND_UP");
+    pub static ref UNIOND_UPTIME: IntGauge = IntGauge::with_opts(
+        Opts::new("uptime_seconds", "Seconds since uniond was last started")
+            .namespace("unionvisor")
+            .subsystem("uniond")
+    )
+    .expect("register UNIOND_UPTIME");
+    pub static ref UNIOND_VERSION: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("version_info", "The version uniond is symlinked to")
+            .namespace("unionvisor")
+            .subsystem("uniond"),
+        &["version"]
+    )
+    .expect("register UNIOND_VERSION");
+    pub static ref RESTARTS: IntCounter = IntCounter::with_opts(
+        Opts::new("restarts_total", "Number of times uniond was restarted")
+            .namespace("unionvisor")
+            .subsystem("uniond")
+    )
+    .expect("register RESTARTS");
+    pub static ref CRASHES: IntCounter = IntCounter::with_opts(
+        Opts::new("crashes_total", "Number of times uniond crashed")
+            .namespace("unionvisor")
+            .subsystem("uniond")
+    )
+    .expect("register CRASHES");
+    pub static ref RECENT_CRASHES: IntGauge = IntGauge::with_opts(
+        Opts::new(
+            "recent_crashes",
+            "Number of times uniond crashed within the restart window"
+        )
+        .namespace("unionvisor")
+        .subsystem("uniond")
+    )
+    .expect("register RECENT_CRASHES");
+    pub static ref PENDING_UPGRADE_HEIGHT: IntGauge = IntGauge::with_opts(
+        Opts::new(
+            "pending_height",
+            "Height of the detected upgrade which has not been applied yet, 0 if none"
+        )
+        .namespace("unionvisor")
+        .subsystem("upgrade")
+    )
+    .expect("register PENDING_UPGRADE_HEIGHT");
+    pub static ref UPGRADES: IntCounter = IntCounter::with_opts(
+        Opts::new("applied_total", "Number of upgrades applied")
+            .namespace("unionvisor")
+            .subsystem("upgrade")
+    )
+    .expect("register UPGRADES");
+    pub static ref ROLLBACKS: IntCounter = IntCounter::with_opts(
+        Opts::new("rollbacks_total", "Number of failed upgrades rolled back")
+            .namespace("unionvisor")
+            .subsystem("upgrade")
+    )
+    .expect("register ROLLBACKS");
+    pub static ref LAST_BACKUP: IntGauge = IntGauge::with_opts(
+        Opts::new(
+            "last_timestamp_seconds",
+            "Unix timestamp of the last backup of the home directory"
+        )
+        .namespace("unionvisor")
+        .subsystem("backup")
+    )
+    .expect("register LAST_BACKUP");
+}
+
+pub fn register_custom_metrics() {
+    REGISTRY
+        .register(Box::new(UNIOND_UP.clone()))
+        .expect("UNIOND_UP can be registered");
+    REGISTRY
+        .register(Box::new(UNIOND_UPTIME.clone()))
+        .expect("UNIOND_UPTIME can be registered");
+    REGISTRY
+        .register(Box::new(UNIOND_VERSION.clone()))
+        .expect("UNIOND_VERSION can be registered");
+    REGISTRY
+        .register(Box::new(RESTARTS.clone()))
+        .expect("RESTARTS can be registered");
+    REGISTRY
+        .register(Box::new(CRASHES.clone()))
+        .expect("CRASHES can be registered");
+    REGISTRY
+        .register(Box::new(RECENT_CRASHES.clone()))
+        .expect("RECENT_CRASHES can be registered");
+    REGISTRY
+        .register(Box::new(PENDING_UPGRADE_HEIGHT.clone()))
+        .expect("PENDING_UPGRADE_HEIGHT can be registered");
+    REGISTRY
+        .register(Box::new(UPGRADES.clone()))
+        .expect("UPGRADES can be registered");
+    REGISTRY
+        .register(Box::new(ROLLBACKS.clone()))
+        .expect("ROLLBACKS can be registered");
+    REGISTRY
+        .register(Box::new(LAST_BACKUP.clone()))
+        .expect("LAST_BACKUP can be registered");
+}
+
+pub fn encode() -> Result<String, prometheus::Error> {
+    prometheus::TextEncoder::new().encode_to_string(&REGISTRY.gather())
+}
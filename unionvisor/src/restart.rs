@@ -0,0 +1,153 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Maximum number of crashes kept in the [`RestartHistory`], regardless of the window.
+const MAX_HISTORY: usize = 100;
+
+/// How unionvisor restarts uniond after it crashed.
+#[derive(Clone, Copy, Debug)]
+pub struct RestartPolicy {
+    /// The maximum number of restarts within `window`. Once exceeded, unionvisor exits.
+    pub max_restarts: u32,
+    pub window: Duration,
+    /// The delay before the first restart, doubled for every further crash within `window`.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RestartPolicy {
+    /// The delay before restarting after the `crashes`th crash within the window.
+    pub fn backoff(&self, crashes: usize) -> Duration {
+        let exponent = u32::try_from(crashes.saturating_sub(1))
+            .unwrap_or(u32::MAX)
+            .min(31);
+        self.initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff)
+    }
+}
+
+/// Why uniond exited.
+#[derive(Debug, PartialEq)]
+pub enum ExitKind {
+    /// uniond exited successfully, for example after being stopped by the operator.
+    Clean,
+    /// uniond halted at the height of an upgrade which has not been applied yet.
+    UpgradeHalt,
+    /// uniond halted at the height of an upgrade which was rolled back before.
+    RolledBackHalt,
+    Crash,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Crash {
+    /// Unix timestamp of the crash.
+    pub time: u64,
+    pub version: String,
+    /// `None` if uniond was terminated by a signal.
+    pub exit_code: Option<i32>,
+}
+
+impl Crash {
+    pub fn new(version: String, code: ExitStatus) -> Self {
+        Self {
+            time: unix_seconds(SystemTime::now()),
+            version,
+            exit_code: code.code(),
+        }
+    }
+}
+
+/// The recent crashes of uniond, persisted to `root/restarts.json` so that flapping is detected across restarts of
+/// unionvisor itself.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestartHistory {
+    pub crashes: Vec<Crash>,
+}
+
+impl RestartHistory {
+    pub fn path(root: impl AsRef<Path>) -> PathBuf {
+        root.as_ref().join("restarts.json")
+    }
+
+    pub fn load(root: impl AsRef<Path>) -> Result<Self, RestartHistoryError> {
+        let path = Self::path(root);
+        match fs::read_to_string(&path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(RestartHistoryError::Io(path, err)),
+        }
+    }
+
+    pub fn save(&self, root: impl AsRef<Path>) -> Result<(), RestartHistoryError> {
+        let path = Self::path(root);
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(&path, contents).map_err(|source| RestartHistoryError::Io(path, source))
+    }
+
+    /// Records `crash`, dropping crashes which are outside of `window`. Returns the number of crashes within `window`.
+    pub fn record(&mut self, crash: Crash, window: Duration) -> usize {
+        let now = crash.time;
+        self.crashes.push(crash);
+        self.crashes
+            .retain(|crash| now.saturating_sub(crash.time) < window.as_secs());
+        let excess = self.crashes.len().saturating_sub(MAX_HISTORY);
+        self.crashes.drain(..excess);
+        self.crashes.len()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RestartHistoryError {
+    #[error("cannot access restart history at {0}")]
+    Io(PathBuf, #[source] io::Error),
+    #[error("cannot (de)serialize restart history")]
+    Serde(#[from] serde_json::Error),
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RestartPolicy {
+            max_restarts: 5,
+            window: Duration::from_secs(600),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(10),
+        };
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(2), Duration::from_secs(2));
+        assert_eq!(policy.backoff(4), Duration::from_secs(8));
+        assert_eq!(policy.backoff(5), Duration::from_secs(10));
+        assert_eq!(policy.backoff(1000), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_record_drops_crashes_outside_window() {
+        let crash = |time| Crash {
+            time,
+            version: "genesis".to_owned(),
+            exit_code: Some(1),
+        };
+        let mut history = RestartHistory {
+            crashes: vec![crash(100), crash(500)],
+        };
+        assert_eq!(history.record(crash(700), Duration::from_secs(300)), 2);
+        assert_eq!(history.crashes, vec![crash(500), crash(700)]);
+    }
+}
@@ -0,0 +1,269 @@
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde::Serialize;
+use thiserror::Error;
+use tiny_http::{Header, Request, Response, Server};
+use tracing::{error, info, warn};
+
+use crate::{
+    metrics,
+    restart::{Crash, RestartHistory},
+    symlinker::Symlinker,
+    watcher::UpgradeInfo,
+};
+
+/// Shared state of the supervisor, updated by [`crate::supervisor::run_and_upgrade`] and exposed by [`serve`].
+/// Every update is mirrored to the prometheus metrics in [`crate::metrics`].
+#[derive(Clone, Default)]
+pub struct Status(Arc<Mutex<StatusInner>>);
+
+#[derive(Default)]
+struct StatusInner {
+    pending_upgrade: Option<UpgradeInfo>,
+    pid: Option<u32>,
+    started_at: Option<Instant>,
+    last_backup: Option<SystemTime>,
+    spawns: u64,
+    upgrades: u64,
+    rollbacks: u64,
+    crashes: Vec<Crash>,
+}
+
+/// The response of `/status`.
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    pub current_version: Option<String>,
+    pub pending_upgrade: Option<UpgradeInfo>,
+    pub uniond: Option<UniondStatus>,
+    /// Unix timestamp of the last backup of the home directory.
+    pub last_backup: Option<u64>,
+    pub restarts: u64,
+    pub upgrades: u64,
+    pub rollbacks: u64,
+    /// The crashes of uniond within the restart window.
+    pub recent_crashes: Vec<Crash>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UniondStatus {
+    pub pid: u32,
+    pub uptime_seconds: u64,
+}
+
+impl Status {
+    fn lock(&self) -> std::sync::MutexGuard<'_, StatusInner> {
+        self.0.lock().expect("status lock is poisoned")
+    }
+
+    /// uniond was (re)started with `pid`.
+    pub fn spawned(&self, pid: u32) {
+        let mut inner = self.lock();
+        inner.pid = Some(pid);
+        inner.started_at = Some(Instant::now());
+        inner.spawns += 1;
+        if inner.spawns > 1 {
+            metrics::RESTARTS.inc();
+        }
+        metrics::UNIOND_UP.set(1);
+    }
+
+    pub fn exited(&self) {
+        let mut inner = self.lock();
+        inner.pid = None;
+        inner.started_at = None;
+        metrics::UNIOND_UP.set(0);
+    }
+
+    /// An upgrade was detected by the watcher, but has not been applied yet.
+    pub fn upgrade_detected(&self, upgrade: &UpgradeInfo) {
+        self.lock().pending_upgrade = Some(upgrade.clone());
+        metrics::PENDING_UPGRADE_HEIGHT.set(i64::try_from(upgrade.height).unwrap_or(i64::MAX));
+    }
+
+    /// The pending upgrade was applied and its binary was started.
+    pub fn upgraded(&self) {
+        let mut inner = self.lock();
+        inner.pending_upgrade = None;
+        inner.upgrades += 1;
+        metrics::PENDING_UPGRADE_HEIGHT.set(0);
+        metrics::UPGRADES.inc();
+    }
+
+    /// The last applied upgrade failed and was rolled back. It is pending again.
+    pub fn rolled_back(&self, upgrade: &UpgradeInfo) {
+        self.upgrade_detected(upgrade);
+        self.lock().rollbacks += 1;
+        metrics::ROLLBACKS.inc();
+    }
+
+    /// The crashes loaded from the persisted [`RestartHistory`].
+    pub fn restart_history(&self, history: &RestartHistory) {
+        self.lock().crashes.clone_from(&history.crashes);
+        metrics::RECENT_CRASHES.set(i64::try_from(history.crashes.len()).unwrap_or(i64::MAX));
+    }
+
+    /// uniond crashed, which was recorded in `history`.
+    pub fn crashed(&self, history: &RestartHistory) {
+        self.restart_history(history);
+        metrics::CRASHES.inc();
+    }
+
+    pub fn backed_up(&self) {
+        let now = SystemTime::now();
+        self.lock().last_backup = Some(now);
+        metrics::LAST_BACKUP.set(i64::try_from(unix_seconds(now)).unwrap_or(i64::MAX));
+    }
+
+    pub fn report(&self, symlinker: &Symlinker) -> StatusReport {
+        let current_version = symlinker
+            .current_version()
+            .map(|version| version.to_string_lossy().into_owned())
+            .ok();
+        let inner = self.lock();
+        StatusReport {
+            current_version,
+            pending_upgrade: inner.pending_upgrade.clone(),
+            uniond: inner
+                .pid
+                .zip(inner.started_at)
+                .map(|(pid, started_at)| UniondStatus {
+                    pid,
+                    uptime_seconds: started_at.elapsed().as_secs(),
+                }),
+            last_backup: inner.last_backup.map(unix_seconds),
+            restarts: inner.spawns.saturating_sub(1),
+            upgrades: inner.upgrades,
+            rollbacks: inner.rollbacks,
+            recent_crashes: inner.crashes.clone(),
+        }
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[derive(Debug, Error)]
+#[error("cannot bind status server")]
+pub struct ServeError(#[source] Box<dyn std::error::Error + Send + Sync>);
+
+/// Serves `/status` as JSON and `/metrics` in the prometheus text format on a background thread. Returns the address
+/// the server is bound to.
+pub fn serve(
+    addr: SocketAddr,
+    status: Status,
+    symlinker: Symlinker,
+) -> Result<SocketAddr, ServeError> {
+    let server = Server::http(addr).map_err(ServeError)?;
+    let addr = server
+        .server_addr()
+        .to_ip()
+        .expect("server is bound to an ip address; qed;");
+    info!(target: "unionvisor", "serving status on {}", addr);
+
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle(request, &status, &symlinker);
+        }
+    });
+
+    Ok(addr)
+}
+
+fn handle(request: Request, status: &Status, symlinker: &Symlinker) {
+    let response = match request.url() {
+        "/status" => {
+            let report = status.report(symlinker);
+            Response::from_string(
+                serde_json::to_string(&report).expect("status report is serializable; qed;"),
+            )
+            .with_header(content_type("application/json"))
+        }
+        "/metrics" => {
+            let report = status.report(symlinker);
+            metrics::UNIOND_UPTIME.set(report.uniond.map_or(0, |uniond| {
+                i64::try_from(uniond.uptime_seconds).unwrap_or(i64::MAX)
+            }));
+            metrics::UNIOND_VERSION.reset();
+            if let Some(version) = &report.current_version {
+                metrics::UNIOND_VERSION.with_label_values(&[version]).set(1);
+            }
+
+            match metrics::encode() {
+                Ok(body) => Response::from_string(body)
+                    .with_header(content_type("text/plain; version=0.0.4")),
+                Err(err) => {
+                    error!(target: "unionvisor", "could not gather metrics: {}", err);
+                    Response::from_string("could not gather metrics").with_status_code(500)
+                }
+            }
+        }
+        _ => Response::from_string("not found").with_status_code(404),
+    };
+
+    if let Err(err) = request.respond(response) {
+        warn!(target: "unionvisor", "failed to respond to status request: {}", err);
+    }
+}
+
+fn content_type(value: &str) -> Header {
+    Header::from_bytes("Content-Type", value).expect("content type header is valid; qed;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{bundle::Bundle, testdata};
+
+    #[test]
+    fn test_serve_status_and_metrics() {
+        let tmp = testdata::temp_dir_with(&["test_swap"]);
+        let root = tmp.into_path().join("test_swap");
+        let bundle = Bundle::new(root.join("bundle")).unwrap();
+        let symlinker = Symlinker::new(root, bundle);
+        symlinker.swap("foo").unwrap();
+
+        metrics::register_custom_metrics();
+        let status = Status::default();
+        status.spawned(42);
+        status.backed_up();
+        status.upgrade_detected(&UpgradeInfo {
+            name: "bar".to_owned(),
+            height: 123,
+            info: None,
+        });
+        status.spawned(43);
+
+        let addr = serve("127.0.0.1:0".parse().unwrap(), status, symlinker).unwrap();
+
+        let report: serde_json::Value = serde_json::from_str(
+            &reqwest::blocking::get(format!("http://{addr}/status"))
+                .unwrap()
+                .text()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(report["current_version"], "foo");
+        assert_eq!(report["pending_upgrade"]["name"], "bar");
+        assert_eq!(report["uniond"]["pid"], 43);
+        assert_eq!(report["restarts"], 1);
+        assert!(report["last_backup"].is_u64());
+
+        let metrics = reqwest::blocking::get(format!("http://{addr}/metrics"))
+            .unwrap()
+            .text()
+            .unwrap();
+        assert!(metrics.contains("unionvisor_uniond_up 1"));
+        assert!(metrics.contains("unionvisor_upgrade_pending_height 123"));
+        assert!(metrics.contains(r#"unionvisor_uniond_version_info{version="foo"} 1"#));
+
+        let not_found = reqwest::blocking::get(format!("http://{addr}/foo")).unwrap();
+        assert_eq!(not_found.status(), 404);
+    }
+}
@@ -1,20 +1,24 @@
 use std::{
     ffi::{OsStr, OsString},
-    fs::{self, create_dir_all},
-    io,
+    fs, io,
     path::{Path, PathBuf},
     process::{Child, ExitStatus},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::{error, field::display as as_display, info, warn};
 
 use crate::{
+    backup::{BackupError, Backups, Manifest},
     bundle::ValidateVersionPathError,
+    download::{self, DownloadError},
     logging::LogFormat,
+    restart::{Crash, ExitKind, RestartHistory, RestartHistoryError, RestartPolicy},
+    status::Status,
     symlinker::{CurrentVersionError, Symlinker, SymlinkerError},
-    watcher::{FileReader, FileReaderError},
+    watcher::{FileReader, FileReaderError, UpgradeInfo},
 };
 
 /// A process supervisor for the uniond binary, which can start, gracefully exit and backup uniond data.
@@ -53,6 +57,10 @@ impl Supervisor {
         args: I,
     ) -> Result<(), SpawnError> {
         let program = self.symlinker.current_validated()?;
+        let version = self
+            .symlinker
+            .bundle
+            .version_meta(self.symlinker.current_version()?);
         info!(
             "running {:?} pointing to {:?}",
             program.0.clone().into_os_string(),
@@ -64,11 +72,12 @@ impl Supervisor {
         let command = command
             .args(vec!["--log_format", logformat.as_str()])
             .arg("start")
-            .args(args)
+            .args(version.args(args))
             .args(vec![
                 OsString::from("--home"),
                 self.home_dir().into_os_string(),
             ])
+            .envs(&version.env)
             .stderr(std::process::Stdio::inherit())
             .stdout(std::process::Stdio::inherit());
 
@@ -86,25 +95,26 @@ impl Supervisor {
         self.root.join("home")
     }
 
-    /// Backup the current uniond home directory to the provided path. The location will be "{dir}/data".
-    pub fn backup(&self, backup_dir: impl AsRef<Path>) -> Result<(), BackupError> {
-        use fs_extra::dir::{copy, CopyOptions};
-        let backup_dir = backup_dir.as_ref();
-        info!(target: "unionvisor", "creating backup dir at {}",  as_display(backup_dir.display()));
-        create_dir_all(backup_dir)
-            .map_err(|source| BackupError::CreateDir(backup_dir.to_owned(), source))?;
-        let home_dir = self.home_dir();
-        let options = CopyOptions::new().overwrite(true);
-        info!(target: "unionvisor", "backing up {} to {}. This might take a while",  as_display(home_dir.display()),  as_display(backup_dir.display()));
-        copy(&home_dir, backup_dir, &options).map_err(|source| BackupError::CopyDir {
-            home: home_dir.clone(),
-            backup: backup_dir.to_owned(),
-            source,
-        })?;
-        info!(target: "unionvisor", "completed backup");
+    /// Take a snapshot of the current uniond home directory.
+    pub fn backup(&self, backups: &Backups) -> Result<Manifest, BackupError> {
+        let version = self
+            .symlinker
+            .current_version()
+            .ok()
+            .map(|version| version.to_string_lossy().into_owned());
+        backups.create(&self.home_dir(), version)
+    }
+
+    /// Replace the current uniond home directory with the snapshot `id`, taken by [`Supervisor::backup`].
+    pub fn restore(&self, backups: &Backups, id: &str) -> Result<(), BackupError> {
+        backups.restore(id, &self.home_dir())?;
         Ok(())
     }
 
+    pub fn pid(&self) -> Option<u32> {
+        self.child.as_ref().map(Child::id)
+    }
+
     pub fn try_wait(&mut self) -> Result<Option<ExitStatus>, TryWaitError> {
         match &mut self.child {
             Some(child) => Ok(child.try_wait()?),
@@ -114,7 +124,10 @@ impl Supervisor {
 
     pub fn kill(&mut self) -> Result<(), KillError> {
         if let Some(ref mut child) = self.child.take() {
-            child.kill()?;
+            // The child might have exited already, for example when halting for an upgrade.
+            if child.try_wait()?.is_none() {
+                child.kill()?;
+            }
         } else {
             debug_assert!(false, "killing a child should only happen after spawn");
         }
@@ -134,20 +147,146 @@ pub struct KillError(#[from] io::Error);
 pub enum SpawnError {
     #[error("error validating version path")]
     ValidateVersionPath(#[from] ValidateVersionPathError),
+    #[error("cannot get current version")]
+    CurrentVersion(#[from] CurrentVersionError),
     #[error("error spawning child with command {command}")]
     SpawnChildError { source: io::Error, command: String },
 }
 
 #[derive(Debug, Error)]
-pub enum BackupError {
-    #[error("Cannot create backup dir {0}")]
-    CreateDir(PathBuf, #[source] io::Error),
-    #[error("Cannot copy home dir to backup dir")]
-    CopyDir {
-        home: PathBuf,
-        backup: PathBuf,
-        source: fs_extra::error::Error,
+pub enum HookError {
+    #[error("{hook} hook of {version} is empty")]
+    Empty { hook: &'static str, version: String },
+    #[error("cannot run {hook} hook of {version}")]
+    Spawn {
+        hook: &'static str,
+        version: String,
+        source: io::Error,
     },
+    #[error("{hook} hook of {version} exited with code: {code}")]
+    Exit {
+        hook: &'static str,
+        version: String,
+        code: ExitStatus,
+    },
+}
+
+/// Runs the `hook` command of an upgrade in the bundle directory, blocking until it exits.
+fn run_hook(
+    hook: &'static str,
+    command: &[String],
+    root: &Path,
+    symlinker: &Symlinker,
+    upgrade: &PendingUpgrade,
+) -> Result<(), HookError> {
+    let Some((program, args)) = command.split_first() else {
+        return Err(HookError::Empty {
+            hook,
+            version: upgrade.name.clone(),
+        });
+    };
+
+    info!(target: "unionvisor", "running {} hook of {}: {:?}", hook, &upgrade.name, command);
+    let code = std::process::Command::new(program)
+        .args(args)
+        .current_dir(&symlinker.bundle.path)
+        .env("UNIONVISOR_ROOT", root)
+        .env("UNIONVISOR_HOME", root.join("home"))
+        .env("UNIONVISOR_UPGRADE_NAME", &upgrade.name)
+        .env("UNIONVISOR_UPGRADE_HEIGHT", upgrade.height.to_string())
+        .env("UNIONVISOR_PREVIOUS_VERSION", &upgrade.previous_version)
+        .stderr(std::process::Stdio::inherit())
+        .stdout(std::process::Stdio::inherit())
+        .status()
+        .map_err(|source| HookError::Spawn {
+            hook,
+            version: upgrade.name.clone(),
+            source,
+        })?;
+
+    if !code.success() {
+        return Err(HookError::Exit {
+            hook,
+            version: upgrade.name.clone(),
+            code,
+        });
+    }
+    Ok(())
+}
+
+/// What unionvisor does after rolling back a failed upgrade.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum RollbackPolicy {
+    /// Restart the previous version, for example to keep serving queries. The failed upgrade is not attempted again
+    /// until the state file is removed. As the previous version halts again once it reaches the upgrade height,
+    /// unionvisor then exits the same way as with [`RollbackPolicy::Halt`].
+    Restart,
+    /// Exit unionvisor, leaving the state file for the operator to inspect.
+    Halt,
+}
+
+/// Configures the rollback of upgrades whose binary fails to spawn or exits shortly after being started.
+#[derive(Clone, Copy, Debug)]
+pub struct RollbackConfig {
+    /// An upgraded uniond exiting within this window is considered a failed upgrade.
+    pub window: Duration,
+    pub policy: RollbackPolicy,
+}
+
+/// Written to `root/rollback.json` when an upgrade is rolled back.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RollbackState {
+    /// The name of the upgrade that failed.
+    pub failed_upgrade: String,
+    /// The height of the failed upgrade.
+    pub height: u64,
+    /// The version that `uniond` was swapped back to.
+    pub restored_version: String,
+    /// The id of the backup the home directory was restored from.
+    #[serde(default)]
+    pub backup: String,
+    /// Why the upgrade was considered failed.
+    pub reason: String,
+}
+
+impl RollbackState {
+    pub fn path(root: impl AsRef<Path>) -> PathBuf {
+        root.as_ref().join("rollback.json")
+    }
+
+    /// Reads the state left by a previous rollback, if any.
+    pub fn read(root: impl AsRef<Path>) -> Result<Option<Self>, RollbackStateError> {
+        let path = Self::path(root);
+        match fs::read_to_string(&path) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(RollbackStateError::Io(path, err)),
+        }
+    }
+
+    pub fn write(&self, root: impl AsRef<Path>) -> Result<(), RollbackStateError> {
+        let path = Self::path(root);
+        let contents = serde_json::to_string_pretty(self)?;
+        fs::write(&path, contents).map_err(|source| RollbackStateError::Io(path, source))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RollbackStateError {
+    #[error("cannot access rollback state at {0}")]
+    Io(PathBuf, #[source] io::Error),
+    #[error("cannot (de)serialize rollback state")]
+    Serde(#[from] serde_json::Error),
+}
+
+/// An upgrade which has been started, but not yet been running for the rollback window.
+struct PendingUpgrade {
+    name: String,
+    height: u64,
+    previous_version: OsString,
+    /// The id of the backup taken before the upgrade.
+    backup: String,
+    started_at: Instant,
 }
 
 #[derive(Debug, Error)]
@@ -171,23 +310,122 @@ pub enum RuntimeError {
         name: String,
         source: ValidateVersionPathError,
     },
+    #[error("cannot download upgrade binary")]
+    Download(#[from] DownloadError),
     #[error("uniond exited with code: {code}")]
     UniondExit { code: ExitStatus },
     #[error("unknown FileReaderError while polling for upgrades")]
     FileReader(#[from] FileReaderError),
     #[error("cannot fixup legacy files")]
     Fixup(#[from] std::io::Error),
+    #[error("cannot access rollback state")]
+    RollbackState(#[from] RollbackStateError),
+    #[error("cannot access restart history")]
+    RestartHistory(#[from] RestartHistoryError),
+    #[error("pre-upgrade hook failed, the upgrade was aborted")]
+    PreUpgradeHook(#[source] HookError),
+    #[error("upgrade {name} failed and was rolled back, see {}", state_file.display())]
+    RolledBack { name: String, state_file: PathBuf },
+}
+
+/// Restores the home directory from the backup taken before the upgrade, swaps the symlink back to the version running before the upgrade
+/// and records the failure in the [`RollbackState`]. Depending on the [`RollbackPolicy`], the previous version is then
+/// restarted or [`RuntimeError::RolledBack`] is returned.
+#[allow(clippy::too_many_arguments)]
+fn roll_back<S: AsRef<OsStr>, I: IntoIterator<Item = S> + Clone>(
+    root: &Path,
+    logformat: LogFormat,
+    symlinker: &Symlinker,
+    args: &I,
+    policy: RollbackPolicy,
+    upgrade: &PendingUpgrade,
+    reason: String,
+    backups: &Backups,
+    status: &Status,
+) -> Result<Supervisor, RuntimeError> {
+    error!(target: "unionvisor", upgrade = upgrade.name.as_str(), reason = reason.as_str(), "upgrade failed, rolling back");
+    let mut supervisor = Supervisor::new(root, symlinker.clone());
+    supervisor.restore(backups, &upgrade.backup)?;
+
+    info!(target: "unionvisor", "restoring symlink to {:?}", &upgrade.previous_version);
+    symlinker.swap(&upgrade.previous_version)?;
+
+    let state = RollbackState {
+        failed_upgrade: upgrade.name.clone(),
+        height: upgrade.height,
+        restored_version: upgrade.previous_version.to_string_lossy().into_owned(),
+        backup: upgrade.backup.clone(),
+        reason,
+    };
+    state.write(root)?;
+    status.rolled_back(&UpgradeInfo {
+        name: upgrade.name.clone(),
+        height: upgrade.height,
+        info: None,
+    });
+
+    match policy {
+        RollbackPolicy::Halt => Err(RuntimeError::RolledBack {
+            name: upgrade.name.clone(),
+            state_file: RollbackState::path(root),
+        }),
+        RollbackPolicy::Restart => {
+            info!(target: "unionvisor", "restarting {:?}", &upgrade.previous_version);
+            supervisor.spawn(logformat, args.clone())?;
+            status.spawned(supervisor.pid().expect("uniond was spawned; qed;"));
+            Ok(supervisor)
+        }
+    }
+}
+
+/// Determines why uniond exited. Exiting while `upgrade-info.json` names a version we are not running means uniond
+/// halted at the upgrade height.
+fn classify_exit(
+    code: ExitStatus,
+    watcher: &FileReader,
+    symlinker: &Symlinker,
+    failed_upgrade: Option<&String>,
+) -> Result<ExitKind, RuntimeError> {
+    if let Some(upgrade) = watcher.peek()? {
+        if symlinker.current_version()? != OsString::from(&upgrade.name) {
+            if failed_upgrade == Some(&upgrade.name) {
+                return Ok(ExitKind::RolledBackHalt);
+            }
+            return Ok(ExitKind::UpgradeHalt);
+        }
+    }
+    if code.success() {
+        return Ok(ExitKind::Clean);
+    }
+    Ok(ExitKind::Crash)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn run_and_upgrade<S: AsRef<OsStr>, I: IntoIterator<Item = S> + Clone>(
     root: impl Into<PathBuf>,
     logformat: LogFormat,
     symlinker: &Symlinker,
     args: &I,
     pol_interval: Duration,
+    rollback: RollbackConfig,
+    restart: RestartPolicy,
+    backups: &Backups,
+    status: &Status,
+    mut prefetcher: Option<download::Prefetcher>,
 ) -> Result<(), RuntimeError> {
     let root = root.into();
     symlinker.fix_legacy_paths()?;
+
+    let mut failed_upgrade = RollbackState::read(&root)?.map(|state| {
+        warn!(target: "unionvisor", "upgrade {} was previously rolled back, it will not be attempted again until {} is removed", &state.failed_upgrade, RollbackState::path(&root).display());
+        state.failed_upgrade
+    });
+    let mut pending: Option<PendingUpgrade> = None;
+    let mut history = RestartHistory::load(&root)?;
+    status.restart_history(&history);
+    // Set once uniond halted at an upgrade height, until the upgrade is applied.
+    let mut halted = false;
+
     let mut supervisor = Supervisor::new(root.clone(), symlinker.clone());
     let home = supervisor.home_dir();
     let mut watcher = FileReader::new(home.join("data/upgrade-info.json"));
@@ -197,11 +435,82 @@ pub fn run_and_upgrade<S: AsRef<OsStr>, I: IntoIterator<Item = S> + Clone>(
         warn!(target: "supervisor", "failed to spawn initial binary call");
         err
     })?;
+    status.spawned(supervisor.pid().expect("uniond was spawned; qed;"));
     info!(target: "unionvisor", "spawned uniond, starting poll for upgrade signals");
     std::thread::sleep(Duration::from_millis(300));
     loop {
-        if let Some(code) = supervisor.try_wait()? {
-            return Err(RuntimeError::UniondExit { code });
+        if let Some(code) = supervisor.try_wait()?.filter(|_| !halted) {
+            status.exited();
+            if let Some(upgrade) = pending.take() {
+                if upgrade.started_at.elapsed() < rollback.window {
+                    supervisor = roll_back(
+                        &root,
+                        logformat,
+                        symlinker,
+                        args,
+                        rollback.policy,
+                        &upgrade,
+                        format!("uniond exited with code: {code}"),
+                        backups,
+                        status,
+                    )?;
+                    failed_upgrade = Some(upgrade.name);
+                    continue;
+                }
+            }
+
+            match classify_exit(code, &watcher, symlinker, failed_upgrade.as_ref())? {
+                ExitKind::Clean => {
+                    info!(target: "unionvisor", "uniond exited cleanly, stopping");
+                    return Ok(());
+                }
+                ExitKind::UpgradeHalt => {
+                    info!(target: "unionvisor", "uniond halted for an upgrade");
+                    halted = true;
+                }
+                ExitKind::RolledBackHalt => {
+                    let name = failed_upgrade.expect("only a failed upgrade is rolled back; qed;");
+                    error!(target: "unionvisor", "uniond halted for upgrade {}, which was rolled back before, stopping", &name);
+                    return Err(RuntimeError::RolledBack {
+                        name,
+                        state_file: RollbackState::path(&root),
+                    });
+                }
+                ExitKind::Crash => {
+                    let version = symlinker.current_version()?.to_string_lossy().into_owned();
+                    let crashes = history.record(Crash::new(version, code), restart.window);
+                    history.save(&root)?;
+                    status.crashed(&history);
+
+                    if crashes > restart.max_restarts as usize {
+                        error!(target: "unionvisor", "uniond crashed {} times within {} seconds, giving up", crashes, restart.window.as_secs());
+                        return Err(RuntimeError::UniondExit { code });
+                    }
+
+                    let backoff = restart.backoff(crashes);
+                    warn!(target: "unionvisor", "uniond crashed with code: {}, restarting in {} milliseconds", code, backoff.as_millis());
+                    std::thread::sleep(backoff);
+
+                    supervisor = Supervisor::new(root.clone(), symlinker.clone());
+                    supervisor.spawn(logformat, args.clone())?;
+                    status.spawned(supervisor.pid().expect("uniond was spawned; qed;"));
+                    continue;
+                }
+            }
+        }
+
+        if pending
+            .as_ref()
+            .is_some_and(|upgrade| upgrade.started_at.elapsed() >= rollback.window)
+        {
+            let upgrade = pending
+                .take()
+                .expect("pending upgrade was checked above; qed;");
+            info!(target: "unionvisor", "upgrade {} has been running for {} seconds, considering it successful", &upgrade.name, rollback.window.as_secs());
+        }
+
+        if let Some(prefetcher) = &mut prefetcher {
+            prefetcher.poll();
         }
 
         match watcher.poll() {
@@ -227,6 +536,13 @@ pub fn run_and_upgrade<S: AsRef<OsStr>, I: IntoIterator<Item = S> + Clone>(
                     continue;
                 }
 
+                status.upgrade_detected(&upgrade);
+                if failed_upgrade.as_ref() == Some(&upgrade.name) {
+                    warn!(target: "unionvisor", "detected upgrade {}, but it was rolled back before. remove {} to retry it.", &upgrade.name, RollbackState::path(&root).display());
+                    std::thread::sleep(pol_interval);
+                    continue;
+                }
+
                 info!(
                     target: "unionvisor",
                     name = upgrade.name.as_str(),
@@ -235,6 +551,26 @@ pub fn run_and_upgrade<S: AsRef<OsStr>, I: IntoIterator<Item = S> + Clone>(
                 );
                 info!(target: "unionvisor", "checking binary availability");
 
+                // the binary may already be downloading since the upgrade was planned.
+                if symlinker.bundle.path_to(&upgrade_name).validate().is_err() {
+                    if let Some(prefetcher) = &mut prefetcher {
+                        prefetcher.wait(&upgrade.name);
+                    }
+                }
+
+                if symlinker.bundle.path_to(&upgrade_name).validate().is_err() {
+                    if let Some(source) = upgrade
+                        .info
+                        .as_deref()
+                        .map(download::binary_source)
+                        .transpose()?
+                        .flatten()
+                    {
+                        info!(target: "unionvisor", "binary for upgrade {} not in bundle, downloading it", &upgrade.name);
+                        download::download(&symlinker.bundle, &upgrade.name, &source)?;
+                    }
+                }
+
                 symlinker
                     .bundle
                     .path_to(&upgrade_name)
@@ -246,27 +582,84 @@ pub fn run_and_upgrade<S: AsRef<OsStr>, I: IntoIterator<Item = S> + Clone>(
 
                 info!(target: "unionvisor", "killing supervisor process");
                 supervisor.kill()?;
-                let backup_dir = root.join("home_backup");
-
                 // If we fail to backup, the file system is incorrectly configured (permissions) or we are running
                 // out of disk space. Either way we exit the node as now the server itself has become unreliable.
                 info!(target: "unionvisor", "backing up current home");
-                supervisor.backup(&backup_dir)?;
+                let backup = supervisor.backup(backups)?;
+                status.backed_up();
+
+                let meta = symlinker.bundle.version_meta(&upgrade_name);
+                let upgrade = PendingUpgrade {
+                    name: upgrade.name,
+                    height: upgrade.height,
+                    previous_version: current_version,
+                    backup: backup.id,
+                    started_at: Instant::now(),
+                };
+
+                // The pre-upgrade hook gates the upgrade. As it might have partially migrated the home directory, we
+                // restore it before exiting.
+                if let Some(hook) = &meta.pre_upgrade {
+                    if let Err(err) = run_hook("pre-upgrade", hook, &root, symlinker, &upgrade) {
+                        error!(target: "unionvisor", err = err.to_string().as_str(), "aborting upgrade {}", &upgrade.name);
+                        supervisor.restore(backups, &upgrade.backup)?;
+                        return Err(RuntimeError::PreUpgradeHook(err));
+                    }
+                }
 
                 info!(target: "unionvisor", "creating new symlink for {}", &upgrade.name);
                 symlinker.swap(&upgrade_name)?;
 
                 supervisor = Supervisor::new(root.clone(), symlinker.clone());
 
-                // If this upgrade fails, we'll revert the local DB and exit the node, ensuring we keep the filesystem in
-                // the last correct state.
+                // If this upgrade fails to spawn or exits within the rollback window, we revert the local DB and the
+                // symlink, ensuring we keep the filesystem in the last correct state.
                 info!(target: "unionvisor", "spawning new supervisor process for {}", &upgrade.name);
-                supervisor.spawn(logformat, args.clone()).map_err(|err| {
-                    error!(target: "unionvisor", err = err.to_string().as_str(), "spawning new supervisor process for {} failed", &upgrade.name);
-                    // This error is most likely caused by incorrect args because of an upgrade. We can reduce the chance of that happening
-                    // by introducing a configuration file with name -> args mappings.
-                    err
-                })?;
+                if let Err(err) = supervisor.spawn(logformat, args.clone()) {
+                    // This error is most likely caused by incorrect args because of an upgrade, which can be overridden
+                    // per version in the bundle's meta.json.
+                    supervisor = roll_back(
+                        &root,
+                        logformat,
+                        symlinker,
+                        args,
+                        rollback.policy,
+                        &upgrade,
+                        format!("spawning failed: {err}"),
+                        backups,
+                        status,
+                    )?;
+                    failed_upgrade = Some(upgrade.name);
+                    continue;
+                }
+                status.spawned(supervisor.pid().expect("uniond was spawned; qed;"));
+
+                if let Some(hook) = &meta.post_upgrade {
+                    if let Err(err) = run_hook("post-upgrade", hook, &root, symlinker, &upgrade) {
+                        supervisor.kill()?;
+                        status.exited();
+                        supervisor = roll_back(
+                            &root,
+                            logformat,
+                            symlinker,
+                            args,
+                            rollback.policy,
+                            &upgrade,
+                            err.to_string(),
+                            backups,
+                            status,
+                        )?;
+                        failed_upgrade = Some(upgrade.name);
+                        continue;
+                    }
+                }
+
+                status.upgraded();
+                halted = false;
+                pending = Some(PendingUpgrade {
+                    started_at: Instant::now(),
+                    ..upgrade
+                });
             }
         }
         info!(target: "unionvisor", "no upgrade detected, sleeping for {} milliseconds.", &pol_interval.as_millis());
@@ -279,7 +672,19 @@ mod tests {
     use tracing_test::traced_test;
 
     use super::*;
-    use crate::{bundle::Bundle, testdata};
+    use crate::{backup::BackupConfig, bundle::Bundle, testdata};
+
+    const ROLLBACK: RollbackConfig = RollbackConfig {
+        window: Duration::from_secs(60),
+        policy: RollbackPolicy::Halt,
+    };
+
+    const RESTART: RestartPolicy = RestartPolicy {
+        max_restarts: 0,
+        window: Duration::from_secs(60),
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(100),
+    };
 
     #[test]
     #[traced_test]
@@ -302,6 +707,11 @@ mod tests {
             &symlinker,
             &vec![root.join("home/data").as_os_str()],
             Duration::from_secs(1),
+            ROLLBACK,
+            RESTART,
+            &Backups::new(&root, BackupConfig::default()),
+            &Status::default(),
+            None,
         )
         .unwrap_err();
 
@@ -331,6 +741,11 @@ mod tests {
             &symlinker,
             &vec![root.join("home/data").as_os_str()],
             Duration::from_secs(1),
+            ROLLBACK,
+            RESTART,
+            &Backups::new(&root, BackupConfig::default()),
+            &Status::default(),
+            None,
         )
         .unwrap_err();
 
@@ -341,6 +756,301 @@ mod tests {
         }
     }
 
+    #[test]
+    #[traced_test]
+    /// upgrade1 corrupts the database and crashes, which should restore the backup and halt.
+    fn test_rollback_halt() {
+        let tmp = testdata::temp_dir_with(&["test_rollback"]);
+        let root = tmp.into_path().join("test_rollback");
+        let bundle = Bundle::new(root.join("bundle")).unwrap();
+        let symlinker = Symlinker::new(root.clone(), bundle);
+
+        // Usually this is made as part of the init process, but we're not test that here.
+        symlinker
+            .make_fallback_link()
+            .expect("fallback link should be made");
+
+        let err = run_and_upgrade(
+            root.clone(),
+            LogFormat::Plain,
+            &symlinker,
+            &vec![root.join("home/data").as_os_str()],
+            Duration::from_secs(1),
+            ROLLBACK,
+            RESTART,
+            &Backups::new(&root, BackupConfig::default()),
+            &Status::default(),
+            None,
+        )
+        .unwrap_err();
+
+        if let RuntimeError::RolledBack { name, state_file } = err {
+            assert_eq!(name, "upgrade1");
+            assert_eq!(state_file, RollbackState::path(&root));
+        } else {
+            panic!("didn't receive expected error: {err:?}")
+        }
+
+        assert_eq!(symlinker.current_version().unwrap(), "genesis");
+        assert_file_contains(root.join("home/data/foo.db"), "foo");
+
+        let state = RollbackState::read(&root).unwrap().unwrap();
+        assert_eq!(state.failed_upgrade, "upgrade1");
+        assert_eq!(state.height, 123);
+        assert_eq!(state.restored_version, "genesis");
+    }
+
+    #[test]
+    #[traced_test]
+    /// After rolling back upgrade1, genesis is restarted and upgrade1 is not attempted again. genesis halts again at
+    /// the height of upgrade1, which should stop unionvisor instead of restarting genesis.
+    fn test_rollback_restart() {
+        let tmp = testdata::temp_dir_with(&["test_rollback"]);
+        let root = tmp.into_path().join("test_rollback");
+        let bundle = Bundle::new(root.join("bundle")).unwrap();
+        let symlinker = Symlinker::new(root.clone(), bundle);
+
+        // Usually this is made as part of the init process, but we're not test that here.
+        symlinker
+            .make_fallback_link()
+            .expect("fallback link should be made");
+
+        let err = run_and_upgrade(
+            root.clone(),
+            LogFormat::Plain,
+            &symlinker,
+            &vec![root.join("home/data").as_os_str()],
+            Duration::from_secs(1),
+            RollbackConfig {
+                policy: RollbackPolicy::Restart,
+                ..ROLLBACK
+            },
+            RestartPolicy {
+                max_restarts: 2,
+                ..RESTART
+            },
+            &Backups::new(&root, BackupConfig::default()),
+            &Status::default(),
+            None,
+        )
+        .unwrap_err();
+
+        if let RuntimeError::RolledBack { name, state_file } = err {
+            assert_eq!(name, "upgrade1");
+            assert_eq!(state_file, RollbackState::path(&root));
+        } else {
+            panic!("didn't receive expected error: {err:?}")
+        }
+        assert_eq!(symlinker.current_version().unwrap(), "genesis");
+        assert_file_contains(root.join("home/data/foo.db"), "foo");
+        assert!(RollbackState::read(&root).unwrap().is_some());
+        assert!(RestartHistory::load(&root).unwrap().crashes.is_empty());
+    }
+
+    #[test]
+    #[traced_test]
+    /// upgrade1 is run with the args and env of its overrides in meta.json, and its hooks are run around the upgrade.
+    fn test_upgrade_overrides_and_hooks() {
+        let tmp = testdata::temp_dir_with(&["test_hooks"]);
+        let root = tmp.into_path().join("test_hooks");
+        let bundle = Bundle::new(root.join("bundle")).unwrap();
+        let symlinker = Symlinker::new(root.clone(), bundle);
+
+        // Usually this is made as part of the init process, but we're not test that here.
+        symlinker
+            .make_fallback_link()
+            .expect("fallback link should be made");
+
+        let err = run_and_upgrade(
+            root.clone(),
+            LogFormat::Plain,
+            &symlinker,
+            &vec![root.join("home/data").as_os_str()],
+            Duration::from_secs(1),
+            ROLLBACK,
+            RESTART,
+            &Backups::new(&root, BackupConfig::default()),
+            &Status::default(),
+            None,
+        )
+        .unwrap_err();
+
+        if let RuntimeError::BinaryUnavailable { name, source: _ } = err {
+            assert_eq!(name, "upgrade2");
+        } else {
+            panic!("didn't receive expected error: {err:?}")
+        }
+
+        assert_file_contains(root.join("home/data/upgrade1.out"), "bar extra");
+        assert_file_contains(root.join("home/data/pre_upgrade"), "genesis");
+        assert_file_contains(root.join("home/data/post_upgrade"), "upgrade1 123");
+    }
+
+    #[test]
+    #[traced_test]
+    /// The pre-upgrade hook of upgrade1 fails after modifying the home directory, which should abort the upgrade.
+    fn test_failed_pre_upgrade_hook() {
+        let tmp = testdata::temp_dir_with(&["test_hooks_failed"]);
+        let root = tmp.into_path().join("test_hooks_failed");
+        let bundle = Bundle::new(root.join("bundle")).unwrap();
+        let symlinker = Symlinker::new(root.clone(), bundle);
+
+        // Usually this is made as part of the init process, but we're not test that here.
+        symlinker
+            .make_fallback_link()
+            .expect("fallback link should be made");
+
+        let err = run_and_upgrade(
+            root.clone(),
+            LogFormat::Plain,
+            &symlinker,
+            &vec![root.join("home/data").as_os_str()],
+            Duration::from_secs(1),
+            ROLLBACK,
+            RESTART,
+            &Backups::new(&root, BackupConfig::default()),
+            &Status::default(),
+            None,
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            err,
+            RuntimeError::PreUpgradeHook(HookError::Exit { .. })
+        ));
+        assert_eq!(symlinker.current_version().unwrap(), "genesis");
+        assert_file_contains(root.join("home/data/foo.db"), "foo");
+        assert!(!root.join("home/data/upgrade1.out").exists());
+    }
+
+    #[test]
+    #[traced_test]
+    /// uniond keeps crashing, which should be restarted until the restart limit is reached.
+    fn test_crash_restart_limit() {
+        let tmp_dir = testdata::temp_dir_with(&["test_early_exit"]);
+        let root = tmp_dir.into_path().join("test_early_exit");
+        let bundle = Bundle::new(root.join("bundle")).expect("should be able to create a bundle");
+        let symlinker = Symlinker::new(root.clone(), bundle);
+
+        // Usually this is made as part of the init process, but we're not test that here.
+        symlinker
+            .make_fallback_link()
+            .expect("fallback link should be made");
+
+        let status = Status::default();
+        let err = run_and_upgrade(
+            root.clone(),
+            LogFormat::Plain,
+            &symlinker,
+            &vec![root.join("data").as_os_str()],
+            Duration::from_secs(1),
+            ROLLBACK,
+            RestartPolicy {
+                max_restarts: 2,
+                ..RESTART
+            },
+            &Backups::new(&root, BackupConfig::default()),
+            &status,
+            None,
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, RuntimeError::UniondExit { .. }));
+        let history = RestartHistory::load(&root).unwrap();
+        assert_eq!(history.crashes.len(), 3);
+        assert_eq!(history.crashes[0].exit_code, Some(1));
+        assert_eq!(history.crashes[0].version, "genesis");
+
+        let report = status.report(&symlinker);
+        assert_eq!(report.restarts, 2);
+        assert_eq!(report.recent_crashes.len(), 3);
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_clean_exit() {
+        let tmp_dir = testdata::temp_dir_with(&["test_clean_exit"]);
+        let root = tmp_dir.into_path().join("test_clean_exit");
+        let bundle = Bundle::new(root.join("bundle")).expect("should be able to create a bundle");
+        let symlinker = Symlinker::new(root.clone(), bundle);
+
+        // Usually this is made as part of the init process, but we're not test that here.
+        symlinker
+            .make_fallback_link()
+            .expect("fallback link should be made");
+
+        run_and_upgrade(
+            root.clone(),
+            LogFormat::Plain,
+            &symlinker,
+            &vec![root.join("data").as_os_str()],
+            Duration::from_secs(1),
+            ROLLBACK,
+            RESTART,
+            &Backups::new(&root, BackupConfig::default()),
+            &Status::default(),
+            None,
+        )
+        .unwrap();
+
+        assert!(RestartHistory::load(&root).unwrap().crashes.is_empty());
+    }
+
+    #[test]
+    #[traced_test]
+    /// genesis exits after writing the upgrade info, which should be upgraded rather than treated as a crash.
+    fn test_upgrade_halt() {
+        let tmp = testdata::temp_dir_with(&["test_upgrade_halt"]);
+        let root = tmp.into_path().join("test_upgrade_halt");
+        let bundle = Bundle::new(root.join("bundle")).unwrap();
+        let symlinker = Symlinker::new(root.clone(), bundle);
+
+        // Usually this is made as part of the init process, but we're not test that here.
+        symlinker
+            .make_fallback_link()
+            .expect("fallback link should be made");
+
+        let err = run_and_upgrade(
+            root.clone(),
+            LogFormat::Plain,
+            &symlinker,
+            &vec![root.join("home/data").as_os_str()],
+            Duration::from_secs(1),
+            ROLLBACK,
+            RESTART,
+            &Backups::new(&root, BackupConfig::default()),
+            &Status::default(),
+            None,
+        )
+        .unwrap_err();
+
+        if let RuntimeError::BinaryUnavailable { name, source: _ } = err {
+            assert_eq!(name, "upgrade2");
+        } else {
+            panic!("didn't receive expected error: {err:?}")
+        }
+        assert!(RestartHistory::load(&root).unwrap().crashes.is_empty());
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_restore() {
+        let tmp = testdata::temp_dir_with(&["test_backup", "bundle"]);
+        let tmp = tmp.into_path();
+        let root = tmp.join("test_backup");
+        let bundle = Bundle::new(tmp.join("bundle")).unwrap();
+        let symlinker = Symlinker::new(root.clone(), bundle);
+        let supervisor: Supervisor = Supervisor::new(root.clone(), symlinker);
+        let backups = Backups::new(&root, BackupConfig::default());
+        let backup = supervisor.backup(&backups).unwrap();
+        fs::write(root.join("home/data/foo.db"), "corrupted").unwrap();
+        fs::write(root.join("home/data/baz.db"), "baz").unwrap();
+        supervisor.restore(&backups, &backup.id).unwrap();
+        assert_file_contains(root.join("home/data/foo.db"), "foo");
+        assert_file_contains(root.join("home/data/bar.db"), "bar");
+        assert!(!root.join("home/data/baz.db").exists());
+    }
+
     #[test]
     #[traced_test]
     fn test_backup() {
@@ -350,10 +1060,13 @@ mod tests {
         let bundle = Bundle::new(tmp.join("bundle")).unwrap();
         let symlinker = Symlinker::new(root.clone(), bundle);
         let supervisor: Supervisor = Supervisor::new(root.clone(), symlinker);
-        supervisor.backup(root.join("home_backup")).unwrap();
-        assert_file_contains(root.join("home_backup/home/data/foo.db"), "foo");
+        let backup = supervisor
+            .backup(&Backups::new(&root, BackupConfig::default()))
+            .unwrap();
+        let backup_dir = root.join("backups").join(&backup.id);
+        assert_file_contains(backup_dir.join("home/data/foo.db"), "foo");
         assert_file_contains(root.join("home/data/foo.db"), "foo");
-        assert_file_contains(root.join("home_backup/home/data/bar.db"), "bar");
+        assert_file_contains(backup_dir.join("home/data/bar.db"), "bar");
         assert_file_contains(root.join("home/data/bar.db"), "bar");
     }
 
@@ -381,6 +1094,11 @@ mod tests {
             &symlinker,
             &vec![root.join("data").as_os_str()],
             Duration::from_secs(1),
+            ROLLBACK,
+            RESTART,
+            &Backups::new(&root, BackupConfig::default()),
+            &Status::default(),
+            None,
         )
         .unwrap_err();
 
@@ -0,0 +1,3 @@
+#!/usr/bin/env sh
+
+exit 0
\ No newline at end of file
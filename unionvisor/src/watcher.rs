@@ -5,11 +5,11 @@ use std::{
     time::Duration,
 };
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tracing::error;
 
 /// `UpgradeInfo` is set by the node periodically when a chain upgrade is required.
-#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct UpgradeInfo {
     /// The name of the upgrade; which operators must match to a binary.
     pub name: String,
@@ -71,6 +71,15 @@ impl FileReader {
         Ok(info)
     }
 
+    /// Reads the current upgrade info, regardless of whether it was polled before.
+    pub fn peek(&self) -> Result<Option<UpgradeInfo>, FileReaderError> {
+        match self.read_upgrade_info() {
+            Err(FileReaderError::FileNotFound) => Ok(None),
+            Ok(info) => Ok(Some(info)),
+            Err(err) => Err(err),
+        }
+    }
+
     pub fn poll(&mut self) -> Result<Option<UpgradeInfo>, FileReaderError> {
         match self.read_upgrade_info() {
             Err(FileReaderError::FileNotFound) => Ok(None),